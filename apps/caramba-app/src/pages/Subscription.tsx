@@ -29,6 +29,7 @@ export default function Subscription() {
     const [copiedVless, setCopiedVless] = useState<number | null>(null);
     const [activatingId, setActivatingId] = useState<number | null>(null);
     const [giftingId, setGiftingId] = useState<number | null>(null);
+    const [rotatingId, setRotatingId] = useState<number | null>(null);
     const [message, setMessage] = useState<{ type: 'success' | 'error'; text: string } | null>(null);
 
     const handleCopy = (sub: UserSubscription) => {
@@ -115,6 +116,39 @@ export default function Subscription() {
         }
     };
 
+    const handleRotateLink = async (subId: number) => {
+        if (!token) return;
+        if (!window.confirm('Issue a new subscription link? Apps using the current link must re-import the new one.')) return;
+
+        setRotatingId(subId);
+        setMessage(null);
+        try {
+            const res = await fetch(`/api/client/subscription/${subId}/rotate-link`, {
+                method: 'POST',
+                headers: {
+                    Authorization: `Bearer ${token}`,
+                },
+            });
+
+            if (res.ok) {
+                const data = await res.json();
+                const grace = data?.grace_hours > 0
+                    ? ` The old link keeps working for ${data.grace_hours} h.`
+                    : ' The old link no longer works.';
+                setMessage({ type: 'success', text: `New link issued.${grace}` });
+                await refreshData();
+                setExpandedId(subId);
+            } else {
+                const err = await res.text();
+                setMessage({ type: 'error', text: err || 'Failed to rotate subscription link.' });
+            }
+        } catch {
+            setMessage({ type: 'error', text: 'Network error while rotating subscription link.' });
+        } finally {
+            setRotatingId(null);
+        }
+    };
+
     const toggleExpand = (id: number) => {
         setExpandedId(expandedId === id ? null : id);
     };
@@ -342,6 +376,14 @@ export default function Subscription() {
                                         )}
                                     </div>
 
+                                    <button
+                                        className="btn-text"
+                                        onClick={() => handleRotateLink(sub.id)}
+                                        disabled={rotatingId !== null}
+                                    >
+                                        {rotatingId === sub.id ? '⏳ Issuing new link...' : '🔄 New Subscription Link'}
+                                    </button>
+
                                     {sub.is_trial && (
                                         <div className="badge badge-warning trial-badge">Free Trial</div>
                                     )}
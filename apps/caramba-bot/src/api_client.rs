@@ -34,6 +34,23 @@ impl ApiClient {
         Ok(resp.json().await?)
     }
 
+    /// Raw body of a GET, for images the panel renders.
+    pub async fn get_bytes(&self, path: &str) -> Result<Vec<u8>> {
+        let url = format!("{}/api/v2/bot{}", self.base_url, path);
+        let resp = self
+            .client
+            .get(&url)
+            .header("X-Bot-Token", &self.token)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("Request failed: {}", resp.status()));
+        }
+
+        Ok(resp.bytes().await?.to_vec())
+    }
+
     pub async fn post<T: for<'de> Deserialize<'de>, B: Serialize>(
         &self,
         path: &str,
@@ -1,8 +1,10 @@
 use crate::bot::keyboards::make_amount_keyboard;
 use crate::bot::keyboards::{main_menu, terms_keyboard};
-use crate::bot::utils::escape_md;
+use crate::bot::utils::{escape_md, link_label};
 use crate::models::payment::PaymentType;
-use crate::models::store::{DetailedSubscription, GiftCode, Plan, SubscriptionIpTracking, User};
+use crate::models::store::{
+    DetailedSubscription, GiftCode, Plan, SubscriptionDevice, SubscriptionIpTracking, User,
+};
 use crate::AppState;
 use reqwest::Url;
 use teloxide::prelude::*;
@@ -476,10 +478,49 @@ pub async fn callback_handler(
                             for link in links {
                                 response.push_str(&format!("`{}`\n\n", escape_md(&link)));
                             }
+                            let mut rows = vec![
+                                vec![InlineKeyboardButton::callback(
+                                    "📷 QR Codes for Links",
+                                    format!("links_qr_{}", sub_id),
+                                )],
+                                vec![InlineKeyboardButton::callback(
+                                    "🔄 New Subscription Link",
+                                    format!("rotate_link_{}", sub_id),
+                                )],
+                            ];
+                            if state
+                                .store_service
+                                .get_wireguard_configs(sub_id, tg_id)
+                                .await
+                                .is_ok_and(|configs| !configs.is_empty())
+                            {
+                                rows.push(vec![InlineKeyboardButton::callback(
+                                    "🛡 WireGuard Config + QR",
+                                    format!("wg_conf_{}", sub_id),
+                                )]);
+                            }
                             let _ = bot
                                 .send_message(ChatId(tg_id), response)
                                 .parse_mode(ParseMode::MarkdownV2)
+                                .reply_markup(InlineKeyboardMarkup::new(rows))
                                 .await;
+
+                            // The subscription URL as a QR code, for importing on another screen.
+                            match state
+                                .store_service
+                                .get_subscription_qr(sub_id, None, tg_id)
+                                .await
+                            {
+                                Ok(png) => {
+                                    let photo = teloxide::types::InputFile::memory(png)
+                                        .file_name("subscription.png");
+                                    let _ = bot
+                                        .send_photo(ChatId(tg_id), photo)
+                                        .caption("📷 Scan to import your subscription on another device.")
+                                        .await;
+                                }
+                                Err(e) => error!("Subscription QR error: {}", e),
+                            }
                         }
                     }
                     Err(e) => {
@@ -491,6 +532,207 @@ pub async fn callback_handler(
                 }
             }
 
+            links_qr if links_qr.starts_with("links_qr_") => {
+                let sub_id = links_qr
+                    .strip_prefix("links_qr_")
+                    .unwrap_or("0")
+                    .parse::<i64>()
+                    .unwrap_or(0);
+                let _ = bot
+                    .answer_callback_query(callback_id)
+                    .text("Rendering QR codes...")
+                    .await;
+
+                let links = state
+                    .store_service
+                    .get_subscription_links(sub_id)
+                    .await
+                    .unwrap_or_default();
+                let mut qrs = Vec::new();
+                for (index, link) in links.iter().enumerate() {
+                    match state
+                        .store_service
+                        .get_subscription_qr(sub_id, Some(index), tg_id)
+                        .await
+                    {
+                        Ok(png) => qrs.push((
+                            teloxide::types::InputFile::memory(png)
+                                .file_name(format!("link_{}.png", index)),
+                            link_label(link),
+                        )),
+                        Err(e) => error!("Link QR error: {}", e),
+                    }
+                }
+                if qrs.is_empty() {
+                    let _ = bot
+                        .send_message(ChatId(tg_id), "❌ No connection links available.")
+                        .await;
+                }
+                // Telegram albums hold 2 to 10 photos.
+                for chunk in qrs.chunks(10) {
+                    if let [(photo, label)] = chunk {
+                        let _ = bot
+                            .send_photo(ChatId(tg_id), photo.clone())
+                            .caption(label.clone())
+                            .await;
+                    } else {
+                        let media = chunk
+                            .iter()
+                            .map(|(photo, label)| {
+                                teloxide::types::InputMedia::Photo(
+                                    teloxide::types::InputMediaPhoto::new(photo.clone())
+                                        .caption(label.clone()),
+                                )
+                            })
+                            .collect::<Vec<_>>();
+                        let _ = bot.send_media_group(ChatId(tg_id), media).await;
+                    }
+                }
+            }
+
+            rotate_ok if rotate_ok.starts_with("rotate_link_ok_") => {
+                let sub_id = rotate_ok
+                    .strip_prefix("rotate_link_ok_")
+                    .unwrap_or("0")
+                    .parse::<i64>()
+                    .unwrap_or(0);
+                let user_res: AnyhowResult<Option<User>> =
+                    state.store_service.get_user_by_tg_id(tg_id).await;
+                if let Ok(Some(u)) = user_res {
+                    match state
+                        .store_service
+                        .rotate_subscription_link(sub_id, u.id)
+                        .await
+                    {
+                        Ok(grace_hours) => {
+                            let _ = bot
+                                .answer_callback_query(callback_id)
+                                .text("✅ New link issued!")
+                                .await;
+                            let note = if grace_hours > 0 {
+                                format!("The old link keeps working for {} h\\.", grace_hours)
+                            } else {
+                                "The old link no longer works\\.".to_string()
+                            };
+                            if let Some(msg) = q.message {
+                                let kb = InlineKeyboardMarkup::new(vec![vec![
+                                    InlineKeyboardButton::callback(
+                                        "🔗 Get Links",
+                                        format!("get_links_{}", sub_id),
+                                    ),
+                                ]]);
+                                let _ = bot
+                                    .edit_message_text(
+                                        msg.chat().id,
+                                        msg.id(),
+                                        format!(
+                                            "✅ *Subscription Link Rotated*\n\n{}\nRe\\-import the new link in your apps\\.",
+                                            note
+                                        ),
+                                    )
+                                    .parse_mode(ParseMode::MarkdownV2)
+                                    .reply_markup(kb)
+                                    .await;
+                            }
+                        }
+                        Err(e) => {
+                            error!("Rotate link error: {}", e);
+                            let _ = bot
+                                .answer_callback_query(callback_id)
+                                .text("❌ Failed to rotate link.")
+                                .show_alert(true)
+                                .await;
+                        }
+                    }
+                }
+            }
+
+            rotate if rotate.starts_with("rotate_link_") => {
+                let sub_id = rotate
+                    .strip_prefix("rotate_link_")
+                    .unwrap_or("0")
+                    .parse::<i64>()
+                    .unwrap_or(0);
+                let _ = bot.answer_callback_query(callback_id).await;
+                let kb = InlineKeyboardMarkup::new(vec![
+                    vec![InlineKeyboardButton::callback(
+                        "✅ Yes, issue a new link",
+                        format!("rotate_link_ok_{}", sub_id),
+                    )],
+                    vec![InlineKeyboardButton::callback(
+                        "« Back to Services",
+                        "myservices_page_0",
+                    )],
+                ]);
+                if let Some(msg) = q.message {
+                    let _ = bot
+                        .send_message(
+                            msg.chat().id,
+                            "🔄 *New Subscription Link*\n\nUse this if your link leaked\\. Apps using the old link stop updating and need the new one\\.",
+                        )
+                        .parse_mode(ParseMode::MarkdownV2)
+                        .reply_markup(kb)
+                        .await;
+                }
+            }
+
+            wg_conf if wg_conf.starts_with("wg_conf_") => {
+                let sub_id = wg_conf
+                    .strip_prefix("wg_conf_")
+                    .unwrap_or("0")
+                    .parse::<i64>()
+                    .unwrap_or(0);
+                match state
+                    .store_service
+                    .get_wireguard_configs(sub_id, tg_id)
+                    .await
+                {
+                    Ok(configs) if !configs.is_empty() => {
+                        let _ = bot
+                            .answer_callback_query(callback_id)
+                            .text("Generating config...")
+                            .await;
+                        for config in configs {
+                            let file = teloxide::types::InputFile::memory(config.conf.into_bytes())
+                                .file_name(config.file_name);
+                            let _ = bot
+                                .send_document(ChatId(tg_id), file)
+                                .caption(format!(
+                                    "🛡 {}\n\nImport into AmneziaWG or AmneziaVPN, or scan the QR code below.",
+                                    config.name
+                                ))
+                                .await;
+                            match state
+                                .store_service
+                                .get_wireguard_qr(sub_id, config.inbound_id, tg_id)
+                                .await
+                            {
+                                Ok(png) => {
+                                    let photo = teloxide::types::InputFile::memory(png)
+                                        .file_name("wireguard.png");
+                                    let _ = bot.send_photo(ChatId(tg_id), photo).await;
+                                }
+                                Err(e) => error!("WireGuard QR error: {}", e),
+                            }
+                        }
+                    }
+                    Ok(_) => {
+                        let _ = bot
+                            .answer_callback_query(callback_id)
+                            .text("❌ No WireGuard servers in your plan.")
+                            .await;
+                    }
+                    Err(e) => {
+                        error!("WireGuard config error: {}", e);
+                        let _ = bot
+                            .answer_callback_query(callback_id)
+                            .text("❌ Failed to generate config.")
+                            .show_alert(true)
+                            .await;
+                    }
+                }
+            }
+
             get_config if get_config.starts_with("get_config_") => {
                 let _sub_id = get_config.strip_prefix("get_config_").unwrap_or("0");
                 let _ = bot
@@ -632,6 +874,63 @@ pub async fn callback_handler(
                 }
             }
 
+            revoke_dev if revoke_dev.starts_with("revoke_dev_") => {
+                let mut ids = revoke_dev
+                    .strip_prefix("revoke_dev_")
+                    .unwrap_or_default()
+                    .split('_')
+                    .map(|id| id.parse::<i64>().unwrap_or(0));
+                let (sub_id, device_id) = (ids.next().unwrap_or(0), ids.next().unwrap_or(0));
+                let user_res: AnyhowResult<Option<User>> =
+                    state.store_service.get_user_by_tg_id(tg_id).await;
+                if let Ok(Some(u)) = user_res {
+                    match state
+                        .store_service
+                        .revoke_subscription_device(sub_id, device_id, u.id)
+                        .await
+                    {
+                        Ok(true) => {
+                            let _ = bot
+                                .answer_callback_query(callback_id)
+                                .text("✅ Device removed. Your other devices stay connected.")
+                                .show_alert(true)
+                                .await;
+                            if let Some(msg) = q.message {
+                                let kb = InlineKeyboardMarkup::new(vec![vec![
+                                    InlineKeyboardButton::callback(
+                                        "📱 Connected Devices",
+                                        format!("devices_{}", sub_id),
+                                    ),
+                                ]]);
+                                let _ = bot
+                                    .edit_message_text(
+                                        msg.chat().id,
+                                        msg.id(),
+                                        "✅ *Device Removed*\n\nIt can no longer connect or fetch the subscription\\.",
+                                    )
+                                    .parse_mode(ParseMode::MarkdownV2)
+                                    .reply_markup(kb)
+                                    .await;
+                            }
+                        }
+                        Ok(false) => {
+                            let _ = bot
+                                .answer_callback_query(callback_id)
+                                .text("Device already removed.")
+                                .await;
+                        }
+                        Err(e) => {
+                            error!("Revoke device error: {}", e);
+                            let _ = bot
+                                .answer_callback_query(callback_id)
+                                .text("❌ Failed to remove device.")
+                                .show_alert(true)
+                                .await;
+                        }
+                    }
+                }
+            }
+
             devices if devices.starts_with("devices_") => {
                 let sub_id = devices
                     .strip_prefix("devices_")
@@ -672,11 +971,36 @@ pub async fn callback_handler(
                     }
                 }
 
-                let keyboard =
-                    InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
-                        "« Back to Services",
-                        format!("myservices_page_0"),
-                    )]]);
+                let devices: Vec<SubscriptionDevice> = state
+                    .store_service
+                    .get_subscription_devices(sub_id, tg_id)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|d| d.revoked_at.is_none())
+                    .collect();
+                let mut rows = Vec::new();
+                if !devices.is_empty() {
+                    response.push_str("\n🔐 *Devices with their own key:*\n");
+                    for device in &devices {
+                        let used_gb = device.used_traffic as f64 / 1024.0 / 1024.0 / 1024.0;
+                        response.push_str(&format!(
+                            "• {} \\({} GB, seen {}\\)\n",
+                            escape_md(device.display_name()),
+                            escape_md(&format!("{:.2}", used_gb)),
+                            escape_md(&device.last_seen_at.format("%Y-%m-%d %H:%M").to_string())
+                        ));
+                        rows.push(vec![InlineKeyboardButton::callback(
+                            format!("🚫 Remove {}", device.display_name()),
+                            format!("revoke_dev_{}_{}", sub_id, device.id),
+                        )]);
+                    }
+                }
+                rows.push(vec![InlineKeyboardButton::callback(
+                    "« Back to Services",
+                    format!("myservices_page_0"),
+                )]);
+                let keyboard = InlineKeyboardMarkup::new(rows);
 
                 if let Some(msg) = q.message {
                     let _ = bot
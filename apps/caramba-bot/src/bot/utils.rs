@@ -19,6 +19,17 @@ pub fn escape_md(text: &str) -> String {
         .replace("!", "\\!")
 }
 
+/// Display name of a connection link: its decoded `#fragment`, else its
+/// scheme.
+pub fn link_label(link: &str) -> String {
+    match link.rsplit_once('#') {
+        Some((_, label)) if !label.is_empty() => urlencoding::decode(label)
+            .map(|l| l.into_owned())
+            .unwrap_or_else(|_| label.to_string()),
+        _ => link.split("://").next().unwrap_or(link).to_uppercase(),
+    }
+}
+
 // Channel Trial Helpers
 use crate::AppState;
 use teloxide::prelude::*;
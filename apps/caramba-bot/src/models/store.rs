@@ -152,6 +152,35 @@ pub struct SubscriptionIpTracking {
     pub last_seen_at: DateTime<Utc>,
 }
 
+/// A device with its own proxy credential on a subscription.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionDevice {
+    pub id: i64,
+    pub subscription_id: i64,
+    pub device_name: Option<String>,
+    pub used_traffic: i64,
+    pub last_seen_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl SubscriptionDevice {
+    pub fn display_name(&self) -> &str {
+        self.device_name
+            .as_deref()
+            .filter(|name| !name.is_empty())
+            .unwrap_or("Unknown Device")
+    }
+}
+
+/// A WireGuard/AmneziaWG `.conf` file for one server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireguardConfig {
+    pub inbound_id: i64,
+    pub name: String,
+    pub file_name: String,
+    pub conf: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct SniPool {
@@ -1,7 +1,7 @@
 use crate::api_client::ApiClient;
 use crate::models::store::{
     CartItem, DetailedSubscription, GiftCode, Plan, Product, StoreCategory, Subscription,
-    SubscriptionIpTracking, User,
+    SubscriptionDevice, SubscriptionIpTracking, User, WireguardConfig,
 };
 use anyhow::Result;
 
@@ -356,4 +356,85 @@ impl StoreService {
             .get::<Vec<String>>(&format!("/subs/{}/links", sub_id))
             .await
     }
+
+    /// Issues a new subscription link; returns the hours the old one keeps working.
+    pub async fn rotate_subscription_link(&self, sub_id: i64, user_id: i64) -> Result<i64> {
+        #[derive(serde::Serialize)]
+        struct OwnerReq {
+            user_id: i64,
+        }
+        self.api
+            .post::<i64, _>(
+                &format!("/subs/{}/rotate-link", sub_id),
+                &OwnerReq { user_id },
+            )
+            .await
+    }
+
+    pub async fn get_subscription_devices(
+        &self,
+        sub_id: i64,
+        tg_id: i64,
+    ) -> Result<Vec<SubscriptionDevice>> {
+        self.api
+            .get::<Vec<SubscriptionDevice>>(&format!("/subs/{}/devices?tg_id={}", sub_id, tg_id))
+            .await
+    }
+
+    pub async fn revoke_subscription_device(
+        &self,
+        sub_id: i64,
+        device_id: i64,
+        user_id: i64,
+    ) -> Result<bool> {
+        #[derive(serde::Serialize)]
+        struct OwnerReq {
+            user_id: i64,
+        }
+        self.api
+            .post::<bool, _>(
+                &format!("/subs/{}/devices/{}/revoke", sub_id, device_id),
+                &OwnerReq { user_id },
+            )
+            .await
+    }
+
+    pub async fn get_wireguard_configs(
+        &self,
+        sub_id: i64,
+        tg_id: i64,
+    ) -> Result<Vec<WireguardConfig>> {
+        self.api
+            .get::<Vec<WireguardConfig>>(&format!("/subs/{}/wireguard?tg_id={}", sub_id, tg_id))
+            .await
+    }
+
+    /// PNG QR code of one of [`Self::get_wireguard_configs`].
+    pub async fn get_wireguard_qr(
+        &self,
+        sub_id: i64,
+        inbound_id: i64,
+        tg_id: i64,
+    ) -> Result<Vec<u8>> {
+        self.api
+            .get_bytes(&format!(
+                "/subs/{}/wireguard/{}/qr?tg_id={}",
+                sub_id, inbound_id, tg_id
+            ))
+            .await
+    }
+
+    /// PNG QR code of the subscription URL, or of its `link`-th connection link.
+    pub async fn get_subscription_qr(
+        &self,
+        sub_id: i64,
+        link: Option<usize>,
+        tg_id: i64,
+    ) -> Result<Vec<u8>> {
+        let path = match link {
+            Some(index) => format!("/subs/{}/qr?link={}&tg_id={}", sub_id, index, tg_id),
+            None => format!("/subs/{}/qr?tg_id={}", sub_id, tg_id),
+        };
+        self.api.get_bytes(&path).await
+    }
 }
@@ -0,0 +1,248 @@
+use serde_json::Value;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Suffix of the copy kept of the last config sing-box ran healthily with.
+const LAST_GOOD_SUFFIX: &str = ".last-good";
+/// Suffix of the new config while it is being checked.
+const CANDIDATE_SUFFIX: &str = ".candidate";
+/// How long sing-box must stay up after a restart to count as healthy.
+const HEALTH_CHECKS: u32 = 5;
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Longest failure reason reported to the panel.
+const MAX_REASON_LEN: usize = 500;
+
+/// Why a config could not be applied.
+#[derive(Debug)]
+pub struct ApplyError {
+    pub stage: &'static str,
+    pub reason: String,
+    pub rolled_back: bool,
+}
+
+impl std::fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} failed: {}", self.stage, self.reason)?;
+        if self.rolled_back {
+            write!(f, " (restored last known-good config)")?;
+        }
+        Ok(())
+    }
+}
+
+fn with_suffix(path: &str, suffix: &str) -> String {
+    format!("{}{}", path, suffix)
+}
+
+/// Trims command output to its last meaningful lines for reporting.
+pub fn summarize_output(output: &str) -> String {
+    let lines: Vec<&str> = output
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    let tail = lines[lines.len().saturating_sub(3)..].join(" | ");
+    if tail.len() <= MAX_REASON_LEN {
+        return tail;
+    }
+    let mut start = tail.len() - MAX_REASON_LEN;
+    while !tail.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("…{}", &tail[start..])
+}
+
+/// Runs `sing-box check` against `path`. A missing binary is not treated
+/// as a failure, since there is nothing to validate with.
+fn check_config(path: &str) -> Result<(), String> {
+    match Command::new("sing-box")
+        .args(["check", "-c", path])
+        .output()
+    {
+        Ok(out) if out.status.success() => Ok(()),
+        Ok(out) => {
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            let reason = summarize_output(if stderr.trim().is_empty() {
+                &stdout
+            } else {
+                &stderr
+            });
+            Err(if reason.is_empty() {
+                format!("sing-box check exited with {}", out.status)
+            } else {
+                reason
+            })
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            warn!("sing-box binary not found, skipping config validation");
+            Ok(())
+        }
+        Err(e) => Err(format!("Could not run sing-box check: {}", e)),
+    }
+}
+
+fn systemctl(action: &str) -> Result<(), String> {
+    match Command::new("systemctl")
+        .args([action, "sing-box"])
+        .output()
+    {
+        Ok(out) if out.status.success() => Ok(()),
+        Ok(out) => Err(summarize_output(&String::from_utf8_lossy(&out.stderr))),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn is_active() -> bool {
+    Command::new("systemctl")
+        .args(["is-active", "--quiet", "sing-box"])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+fn recent_service_log() -> String {
+    Command::new("journalctl")
+        .args(["-u", "sing-box", "-n", "5", "--no-pager", "-o", "cat"])
+        .output()
+        .map(|out| summarize_output(&String::from_utf8_lossy(&out.stdout)))
+        .unwrap_or_default()
+}
+
+/// Restarts sing-box and waits until it has stayed up for a few seconds.
+async fn restart_and_verify() -> Result<(), (&'static str, String)> {
+    systemctl("restart").map_err(|e| ("restart", format!("systemctl restart failed: {}", e)))?;
+
+    for _ in 0..HEALTH_CHECKS {
+        tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+        if !is_active() {
+            let log = recent_service_log();
+            return Err((
+                "health",
+                if log.is_empty() {
+                    "sing-box is not running after restart".to_string()
+                } else {
+                    format!("sing-box is not running after restart: {}", log)
+                },
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Swaps in `content` as the sing-box config at `path`.
+///
+/// The config is checked before it replaces the current one, and the
+/// current one is kept as last known-good while sing-box is healthy on it.
+/// If sing-box does not come back up on the new config, the last known-good
+/// one is restored and restarted.
+pub async fn apply_config(path: &str, content: &Value) -> Result<(), ApplyError> {
+    let fail = |stage, reason: String| ApplyError {
+        stage,
+        reason,
+        rolled_back: false,
+    };
+
+    let json_str = serde_json::to_string_pretty(content)
+        .map_err(|e| fail("validate", format!("Config is not serializable: {}", e)))?;
+    if let Some(parent) = Path::new(path).parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| fail("validate", e.to_string()))?;
+    }
+
+    // 1. Validate a candidate copy
+    let candidate = with_suffix(path, CANDIDATE_SUFFIX);
+    tokio::fs::write(&candidate, &json_str)
+        .await
+        .map_err(|e| fail("validate", format!("Cannot write {}: {}", candidate, e)))?;
+    if let Err(reason) = check_config(&candidate) {
+        let _ = tokio::fs::remove_file(&candidate).await;
+        error!("❌ New config failed validation: {}", reason);
+        return Err(fail("validate", reason));
+    }
+
+    // 2. Keep the running config as last known-good
+    let last_good = with_suffix(path, LAST_GOOD_SUFFIX);
+    if Path::new(path).exists()
+        && is_active()
+        && let Err(e) = tokio::fs::copy(path, &last_good).await
+    {
+        warn!("Could not keep last known-good config: {}", e);
+    }
+
+    // 3. Swap and restart
+    tokio::fs::rename(&candidate, path)
+        .await
+        .map_err(|e| fail("validate", format!("Cannot replace {}: {}", path, e)))?;
+    info!("💾 Config saved to {}", path);
+
+    let Err((stage, reason)) = restart_and_verify().await else {
+        info!("✅ Service restarted and healthy");
+        return Ok(());
+    };
+    error!("❌ sing-box unhealthy on new config: {}", reason);
+
+    // 4. Roll back
+    if !Path::new(&last_good).exists() {
+        return Err(fail(
+            stage,
+            format!("{}; no known-good config to restore", reason),
+        ));
+    }
+    if let Err(e) = tokio::fs::copy(&last_good, path).await {
+        return Err(fail(stage, format!("{}; restoring failed: {}", reason, e)));
+    }
+    if let Err((_, e)) = restart_and_verify().await {
+        return Err(fail(
+            stage,
+            format!("{}; last known-good config did not recover: {}", reason, e),
+        ));
+    }
+    warn!("↩️ Restored last known-good config");
+
+    Err(ApplyError {
+        stage,
+        reason,
+        rolled_back: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_keeps_last_lines() {
+        let out = "INFO start\n\nFATAL[0000] decode config: inbounds[0]: unknown field\nexit\n";
+        assert_eq!(
+            summarize_output(out),
+            "INFO start | FATAL[0000] decode config: inbounds[0]: unknown field | exit"
+        );
+        assert_eq!(summarize_output("a\nb\nc\nd"), "b | c | d");
+        assert_eq!(summarize_output("  \n"), "");
+    }
+
+    #[test]
+    fn summary_is_bounded() {
+        let long = "é".repeat(MAX_REASON_LEN);
+        let summary = summarize_output(&long);
+        assert!(summary.starts_with('…'));
+        assert!(summary.len() <= MAX_REASON_LEN + '…'.len_utf8());
+    }
+
+    #[test]
+    fn error_mentions_rollback() {
+        let err = ApplyError {
+            stage: "health",
+            reason: "sing-box is not running after restart".to_string(),
+            rolled_back: true,
+        };
+        assert_eq!(
+            err.to_string(),
+            "health failed: sing-box is not running after restart (restored last known-good config)"
+        );
+    }
+}
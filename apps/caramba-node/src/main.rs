@@ -7,10 +7,12 @@ use std::time::Duration;
 use sysinfo::System;
 use tracing::{error, info, warn};
 
+mod config_guard;
 mod decoy_service;
 mod scanner;
 mod self_update;
 mod sni_check; // NEW
+mod usage_journal;
 
 fn init_rustls_provider() {
     // rustls 0.23 requires explicit process-wide provider in some feature combinations.
@@ -37,8 +39,20 @@ struct Args {
     /// Config path (default: /etc/sing-box/config.json)
     #[arg(long, env = "CONFIG_PATH", default_value = "/etc/sing-box/config.json")]
     config_path: String,
+
+    /// Where unacknowledged per-user traffic is kept between heartbeats
+    #[arg(
+        long,
+        env = "USAGE_JOURNAL_PATH",
+        default_value = "/var/lib/caramba-node/usage-journal.json"
+    )]
+    usage_journal_path: String,
 }
 
+/// How often per-user traffic is sampled from the Clash API. Short
+/// connections are only counted if they live through one sample.
+const USAGE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
 struct AgentState {
     current_hash: Option<String>,
     // Kill Switch State
@@ -49,7 +63,9 @@ struct AgentState {
     cached_speed_mbps: Option<i32>,
     recent_discoveries: std::sync::Arc<tokio::sync::Mutex<Vec<caramba_shared::DiscoveredSni>>>,
     scan_trigger: tokio::sync::mpsc::Sender<()>, // NEW: Pulse for neighbor sniper
-    last_user_usage_totals: std::collections::HashMap<String, u64>,
+    usage_journal: std::sync::Arc<tokio::sync::Mutex<usage_journal::UsageJournal>>,
+    /// Last config that failed to apply; reported until a newer one succeeds.
+    config_failure: Option<caramba_shared::api::ConfigFailure>,
 }
 
 #[tokio::main]
@@ -101,7 +117,10 @@ async fn main() -> anyhow::Result<()> {
         cached_speed_mbps: None,
         recent_discoveries: std::sync::Arc::new(tokio::sync::Mutex::new(Vec::new())),
         scan_trigger: scan_tx,
-        last_user_usage_totals: std::collections::HashMap::new(),
+        usage_journal: std::sync::Arc::new(tokio::sync::Mutex::new(
+            usage_journal::UsageJournal::load(&args.usage_journal_path).await,
+        )),
+        config_failure: None,
     };
 
     // Initialize HTTP Client
@@ -138,6 +157,13 @@ async fn main() -> anyhow::Result<()> {
         decoy_svc.run_loop().await;
     });
 
+    // 5.2 Sample per-user traffic into the durable usage journal
+    let journal = state.usage_journal.clone();
+    let usage_client = client.clone();
+    tokio::spawn(async move {
+        collect_user_usage(usage_client, journal).await;
+    });
+
     // 5.5 Start Neighbor Sniper (Phase 7)
     let discoveries = state.recent_discoveries.clone();
     tokio::spawn(async move {
@@ -449,7 +475,14 @@ async fn send_heartbeat(
     let (latency, cpu, ram, connections, max_ram, cpu_cores, cpu_model) =
         collect_telemetry(client, sys).await;
     let (traffic_up, traffic_down) = collect_total_traffic(client).await.unwrap_or((0, 0));
-    let user_usage = collect_user_usage_delta(client, &mut state.last_user_usage_totals).await;
+    let usage_report = {
+        let mut journal = state.usage_journal.lock().await;
+        journal.seal();
+        if let Err(e) = journal.save().await {
+            error!("Failed to save usage journal: {}", e);
+        }
+        journal.report()
+    };
 
     let payload = HeartbeatRequest {
         version: env!("CARGO_PKG_VERSION").to_string(),
@@ -476,7 +509,8 @@ async fn send_heartbeat(
         max_ram,
         cpu_cores,
         cpu_model,
-        user_usage,
+        user_usage: None,
+        usage_report,
         discovered_snis: {
             let mut lock = state.recent_discoveries.lock().await;
             if lock.is_empty() {
@@ -487,6 +521,7 @@ async fn send_heartbeat(
                 Some(items)
             }
         },
+        config_failure: state.config_failure.clone(),
     };
 
     let resp = client
@@ -500,7 +535,22 @@ async fn send_heartbeat(
         anyhow::bail!("Server error: {}", resp.status());
     }
 
-    Ok(resp.json::<HeartbeatResponse>().await?)
+    let resp = resp.json::<HeartbeatResponse>().await?;
+    if let Some(seq) = resp.usage_acked_seq {
+        let mut journal = state.usage_journal.lock().await;
+        if journal.ack(seq)
+            && let Err(e) = journal.save().await
+        {
+            error!("Failed to save usage journal: {}", e);
+        }
+        if journal.pending_batches() > 0 {
+            warn!(
+                "📊 {} usage batches still waiting for the panel",
+                journal.pending_batches()
+            );
+        }
+    }
+    Ok(resp)
 }
 
 fn extract_counter_field(obj: &serde_json::Value, keys: &[&str]) -> Option<u64> {
@@ -602,52 +652,42 @@ async fn collect_total_traffic(client: &reqwest::Client) -> Option<(u64, u64)> {
     }
 }
 
-async fn collect_user_usage_delta(
-    client: &reqwest::Client,
-    last_totals: &mut std::collections::HashMap<String, u64>,
-) -> Option<std::collections::HashMap<String, u64>> {
-    let connections = fetch_clash_connections(client).await?;
-    let mut current_totals: std::collections::HashMap<String, u64> =
-        std::collections::HashMap::new();
-
-    for conn in connections {
-        let Some(user) = extract_subscription_identity(&conn) else {
-            continue;
-        };
-
-        let upload =
-            extract_counter_field(&conn, &["upload", "uploadTotal", "uplink", "sent"]).unwrap_or(0);
-        let download = extract_counter_field(
-            &conn,
-            &["download", "downloadTotal", "downlink", "received"],
-        )
-        .unwrap_or(0);
-
-        let total = upload.saturating_add(download);
-        let entry = current_totals.entry(user).or_insert(0);
-        *entry = entry.saturating_add(total);
-    }
-
-    let mut delta_map = std::collections::HashMap::new();
-    for (user, current_total) in &current_totals {
-        let previous_total = last_totals.get(user).copied().unwrap_or(0);
-        let delta = if *current_total >= previous_total {
-            current_total.saturating_sub(previous_total)
-        } else {
-            // Counter reset (agent restart/sing-box reload) — send the observed value once.
-            *current_total
-        };
-
-        if delta > 0 {
-            delta_map.insert(user.clone(), delta);
+/// Samples live connections into the usage journal until the agent exits.
+async fn collect_user_usage(
+    client: reqwest::Client,
+    journal: std::sync::Arc<tokio::sync::Mutex<usage_journal::UsageJournal>>,
+) {
+    loop {
+        if let Some(connections) = fetch_clash_connections(&client).await {
+            let samples: Vec<usage_journal::ConnectionSample> = connections
+                .iter()
+                .filter_map(|conn| {
+                    let id = conn.get("id").and_then(|v| v.as_str())?.to_string();
+                    let user = extract_subscription_identity(conn)?;
+                    let upload =
+                        extract_counter_field(conn, &["upload", "uploadTotal", "uplink", "sent"])
+                            .unwrap_or(0);
+                    let download = extract_counter_field(
+                        conn,
+                        &["download", "downloadTotal", "downlink", "received"],
+                    )
+                    .unwrap_or(0);
+                    Some(usage_journal::ConnectionSample {
+                        id,
+                        user,
+                        total: upload.saturating_add(download),
+                    })
+                })
+                .collect();
+
+            let mut journal = journal.lock().await;
+            if journal.record_connections(&samples)
+                && let Err(e) = journal.save().await
+            {
+                error!("Failed to save usage journal: {}", e);
+            }
         }
-    }
-
-    *last_totals = current_totals;
-    if delta_map.is_empty() {
-        None
-    } else {
-        Some(delta_map)
+        tokio::time::sleep(USAGE_POLL_INTERVAL).await;
     }
 }
 
@@ -703,12 +743,29 @@ async fn check_and_update_config(
             &config_resp.hash
         );
 
-        // Save new config
-        save_config(config_path, &config_resp.content).await?;
-        state.current_hash = Some(config_resp.hash);
+        // Don't retry a config that already failed; wait for a new one.
+        if let Some(failure) = &state.config_failure
+            && failure.config_hash == config_resp.hash
+        {
+            warn!(
+                "⏭️ Config {} failed to apply before ({}), keeping current config",
+                config_resp.hash, failure.reason
+            );
+            return Ok(());
+        }
 
-        // Restart sing-box
-        restart_singbox()?;
+        // Validate, swap and restart; rolls back on failure
+        if let Err(e) = config_guard::apply_config(config_path, &config_resp.content).await {
+            state.config_failure = Some(caramba_shared::api::ConfigFailure {
+                config_hash: config_resp.hash,
+                stage: e.stage.to_string(),
+                reason: e.reason.clone(),
+                rolled_back: e.rolled_back,
+            });
+            anyhow::bail!("{}", e);
+        }
+        state.current_hash = Some(config_resp.hash);
+        state.config_failure = None;
 
         info!("✅ Config updated and service restarted");
     } else {
@@ -743,19 +800,6 @@ async fn load_current_hash(config_path: &str) -> Option<String> {
     }
 }
 
-async fn save_config(path: &str, content: &serde_json::Value) -> anyhow::Result<()> {
-    let json_str = serde_json::to_string_pretty(content)?;
-
-    // Ensure directory exists
-    if let Some(parent) = Path::new(path).parent() {
-        tokio::fs::create_dir_all(parent).await?;
-    }
-
-    tokio::fs::write(path, json_str).await?;
-    info!("💾 Config saved to {}", path);
-    Ok(())
-}
-
 fn restart_singbox() -> anyhow::Result<()> {
     info!("🔄 Restarting sing-box service...");
 
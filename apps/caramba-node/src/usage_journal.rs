@@ -0,0 +1,232 @@
+use caramba_shared::api::{UsageBatch, UsageReport};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing::{error, warn};
+
+/// Batches sent per heartbeat; older ones go first, the rest follow.
+const MAX_BATCHES_PER_REPORT: usize = 50;
+/// Unacknowledged batches kept before new usage is only accumulated.
+const MAX_PENDING_BATCHES: usize = 1000;
+
+/// Per-user traffic not yet acknowledged by the panel, kept on disk so it
+/// survives agent restarts and panel outages.
+///
+/// Usage accumulates in an open bucket. Each heartbeat seals the bucket into
+/// a batch with the next sequence number and sends every unacknowledged
+/// batch; the panel applies each sequence number once and returns the
+/// highest one it has, after which those batches are dropped.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsageJournal {
+    /// Identifies this journal to the panel; sequence numbers restart with a new one.
+    journal_id: String,
+    next_seq: u64,
+    /// Bytes per user tag counted since the last batch was sealed.
+    open: HashMap<String, u64>,
+    pending: Vec<UsageBatch>,
+    /// Byte counter last seen per live connection id, so connections are
+    /// not counted twice across polls or agent restarts.
+    connections: HashMap<String, u64>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+/// A live connection's counter as reported by the Clash API.
+pub struct ConnectionSample {
+    pub id: String,
+    pub user: String,
+    pub total: u64,
+}
+
+impl UsageJournal {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            journal_id: uuid::Uuid::new_v4().to_string(),
+            next_seq: 1,
+            open: HashMap::new(),
+            pending: Vec::new(),
+            connections: HashMap::new(),
+            path,
+        }
+    }
+
+    /// Loads the journal at `path`, or starts a new one if there is none.
+    /// An unreadable journal is moved aside rather than overwritten.
+    pub async fn load(path: &str) -> Self {
+        let path = PathBuf::from(path);
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::new(path),
+            Err(e) => {
+                error!("Cannot read usage journal {}: {}", path.display(), e);
+                return Self::new(path);
+            }
+        };
+
+        match serde_json::from_str::<Self>(&content) {
+            Ok(mut journal) => {
+                journal.path = path;
+                journal
+            }
+            Err(e) => {
+                let aside = path.with_extension("corrupt");
+                warn!(
+                    "Usage journal {} is corrupt ({}), moving it to {}",
+                    path.display(),
+                    e,
+                    aside.display()
+                );
+                let _ = tokio::fs::rename(&path, &aside).await;
+                Self::new(path)
+            }
+        }
+    }
+
+    /// Writes the journal atomically.
+    pub async fn save(&self) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec(self)?).await?;
+        tokio::fs::rename(&tmp, &self.path).await?;
+        Ok(())
+    }
+
+    /// Counts the traffic of live connections since they were last seen.
+    /// Returns whether the journal changed and needs saving.
+    pub fn record_connections(&mut self, samples: &[ConnectionSample]) -> bool {
+        let mut seen = HashMap::with_capacity(samples.len());
+        let mut added = false;
+
+        for sample in samples {
+            let previous = self.connections.get(&sample.id).copied().unwrap_or(0);
+            // A lower counter means the id was reused by a new connection.
+            let delta = if sample.total >= previous {
+                sample.total - previous
+            } else {
+                sample.total
+            };
+            if delta > 0 {
+                let entry = self.open.entry(sample.user.clone()).or_insert(0);
+                *entry = entry.saturating_add(delta);
+                added = true;
+            }
+            seen.insert(sample.id.clone(), sample.total);
+        }
+
+        // Closed connections drop out; their traffic was counted while live.
+        let changed = added || seen.len() != self.connections.len();
+        self.connections = seen;
+        changed
+    }
+
+    /// Seals the open bucket into the next batch, unless it is empty or too
+    /// many batches are still waiting for the panel.
+    pub fn seal(&mut self) {
+        if self.open.is_empty() || self.pending.len() >= MAX_PENDING_BATCHES {
+            return;
+        }
+        self.pending.push(UsageBatch {
+            seq: self.next_seq,
+            usage: std::mem::take(&mut self.open),
+        });
+        self.next_seq += 1;
+    }
+
+    /// Oldest unacknowledged batches, or `None` when everything is acknowledged.
+    pub fn report(&self) -> Option<UsageReport> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        Some(UsageReport {
+            journal_id: self.journal_id.clone(),
+            batches: self
+                .pending
+                .iter()
+                .take(MAX_BATCHES_PER_REPORT)
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// Drops batches the panel has applied. Returns whether any were dropped.
+    pub fn ack(&mut self, seq: u64) -> bool {
+        let before = self.pending.len();
+        self.pending.retain(|b| b.seq > seq);
+        self.pending.len() != before
+    }
+
+    pub fn pending_batches(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal() -> UsageJournal {
+        UsageJournal::new(PathBuf::from("/tmp/unused-usage-journal.json"))
+    }
+
+    fn sample(id: &str, user: &str, total: u64) -> ConnectionSample {
+        ConnectionSample {
+            id: id.to_string(),
+            user: user.to_string(),
+            total,
+        }
+    }
+
+    #[test]
+    fn counts_each_connection_once() {
+        let mut j = journal();
+        j.record_connections(&[sample("a", "user_1", 100), sample("b", "user_1", 50)]);
+        j.record_connections(&[sample("a", "user_1", 180), sample("c", "user_2", 10)]);
+        assert_eq!(j.open["user_1"], 230);
+        assert_eq!(j.open["user_2"], 10);
+        // Nothing new
+        assert!(!j.record_connections(&[sample("a", "user_1", 180), sample("c", "user_2", 10)]));
+    }
+
+    #[test]
+    fn sealed_batches_are_replayed_until_acked() {
+        let mut j = journal();
+        j.record_connections(&[sample("a", "user_1", 100)]);
+        j.seal();
+        j.record_connections(&[sample("a", "user_1", 150)]);
+        j.seal();
+        // Empty bucket seals nothing
+        j.seal();
+
+        let report = j.report().unwrap();
+        let seqs: Vec<u64> = report.batches.iter().map(|b| b.seq).collect();
+        assert_eq!(seqs, vec![1, 2]);
+        assert_eq!(report.batches[1].usage["user_1"], 50);
+
+        assert!(j.ack(1));
+        assert_eq!(j.report().unwrap().batches[0].seq, 2);
+        assert!(!j.ack(1));
+        j.ack(2);
+        assert!(j.report().is_none());
+    }
+
+    #[test]
+    fn survives_a_round_trip_through_disk_format() {
+        let mut j = journal();
+        j.record_connections(&[sample("a", "user_1", 100)]);
+        j.seal();
+        j.record_connections(&[sample("a", "user_1", 120)]);
+
+        let restored: UsageJournal =
+            serde_json::from_slice(&serde_json::to_vec(&j).unwrap()).unwrap();
+        assert_eq!(restored.journal_id, j.journal_id);
+        assert_eq!(restored.next_seq, 2);
+        assert_eq!(restored.open["user_1"], 20);
+        assert_eq!(restored.pending_batches(), 1);
+        // Live connection is not recounted after a restart
+        let mut restored = restored;
+        restored.record_connections(&[sample("a", "user_1", 120)]);
+        assert_eq!(restored.open["user_1"], 20);
+    }
+}
@@ -0,0 +1,1271 @@
+// Admin API v1
+// Token-authenticated JSON API for automation, described by a generated OpenAPI document
+
+use crate::AppState;
+use crate::handlers::admin::nodes::{node_audit_snapshot, spawn_node_sync};
+use crate::handlers::admin_network::inbound_snapshot;
+use crate::services::audit_service::{AuditActor, redact};
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, Query, Request, State},
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+};
+use caramba_db::models::api_token::ApiScope;
+use caramba_db::models::network::Inbound;
+use caramba_db::models::node::Node;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use tracing::error;
+
+/// Mount points of this API; the router is nested under both.
+pub const API_PREFIXES: [&str; 2] = ["/api/admin/v1", "/caramba-api/admin/v1"];
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// One API operation. This table drives both scope enforcement and the
+/// OpenAPI document, so an endpoint cannot be routed without a scope.
+pub struct Endpoint {
+    pub method: &'static str,
+    /// Path relative to the API prefix, with `{param}` placeholders.
+    pub path: &'static str,
+    pub scope: ApiScope,
+    pub tag: &'static str,
+    pub summary: &'static str,
+    pub query: &'static [&'static str],
+    /// Component schema of the JSON request body.
+    pub request: Option<&'static str>,
+    /// Component schema of the response; `[Name]` for an array.
+    pub response: &'static str,
+}
+
+const fn endpoint(
+    method: &'static str,
+    path: &'static str,
+    scope: ApiScope,
+    tag: &'static str,
+    summary: &'static str,
+    response: &'static str,
+) -> Endpoint {
+    Endpoint {
+        method,
+        path,
+        scope,
+        tag,
+        summary,
+        query: &[],
+        request: None,
+        response,
+    }
+}
+
+pub const ENDPOINTS: &[Endpoint] = &[
+    Endpoint {
+        query: &["search", "limit", "offset"],
+        ..endpoint(
+            "GET",
+            "/users",
+            ApiScope::UsersRead,
+            "users",
+            "List users",
+            "[User]",
+        )
+    },
+    endpoint(
+        "GET",
+        "/users/{id}",
+        ApiScope::UsersRead,
+        "users",
+        "Get a user",
+        "User",
+    ),
+    Endpoint {
+        request: Some("UserUpdate"),
+        ..endpoint(
+            "PATCH",
+            "/users/{id}",
+            ApiScope::UsersWrite,
+            "users",
+            "Ban or unban a user",
+            "User",
+        )
+    },
+    Endpoint {
+        request: Some("BalanceUpdate"),
+        ..endpoint(
+            "PUT",
+            "/users/{id}/balance",
+            ApiScope::BillingWrite,
+            "users",
+            "Set a user's balance",
+            "User",
+        )
+    },
+    endpoint(
+        "GET",
+        "/users/{id}/subscriptions",
+        ApiScope::SubsRead,
+        "subscriptions",
+        "List a user's subscriptions",
+        "[Subscription]",
+    ),
+    Endpoint {
+        request: Some("SubscriptionCreate"),
+        ..endpoint(
+            "POST",
+            "/users/{id}/subscriptions",
+            ApiScope::SubsWrite,
+            "subscriptions",
+            "Gift a subscription to a user",
+            "Subscription",
+        )
+    },
+    endpoint(
+        "GET",
+        "/subscriptions/{id}",
+        ApiScope::SubsRead,
+        "subscriptions",
+        "Get a subscription",
+        "Subscription",
+    ),
+    Endpoint {
+        request: Some("SubscriptionExtend"),
+        ..endpoint(
+            "POST",
+            "/subscriptions/{id}/extend",
+            ApiScope::SubsWrite,
+            "subscriptions",
+            "Extend a subscription",
+            "Subscription",
+        )
+    },
+    endpoint(
+        "DELETE",
+        "/subscriptions/{id}",
+        ApiScope::SubsWrite,
+        "subscriptions",
+        "Delete a subscription",
+        "Ok",
+    ),
+    endpoint(
+        "GET",
+        "/plans",
+        ApiScope::PlansRead,
+        "plans",
+        "List plans",
+        "[Plan]",
+    ),
+    endpoint(
+        "GET",
+        "/plans/{id}",
+        ApiScope::PlansRead,
+        "plans",
+        "Get a plan",
+        "Plan",
+    ),
+    endpoint(
+        "GET",
+        "/nodes",
+        ApiScope::NodesRead,
+        "nodes",
+        "List nodes",
+        "[Node]",
+    ),
+    endpoint(
+        "GET",
+        "/nodes/{id}",
+        ApiScope::NodesRead,
+        "nodes",
+        "Get a node",
+        "Node",
+    ),
+    endpoint(
+        "POST",
+        "/nodes/{id}/sync",
+        ApiScope::NodesAdmin,
+        "nodes",
+        "Regenerate and push a node's config",
+        "Ok",
+    ),
+    endpoint(
+        "POST",
+        "/nodes/{id}/toggle",
+        ApiScope::NodesAdmin,
+        "nodes",
+        "Enable or disable a node",
+        "Node",
+    ),
+    endpoint(
+        "GET",
+        "/nodes/{id}/inbounds",
+        ApiScope::InboundsRead,
+        "inbounds",
+        "List a node's inbounds",
+        "[Inbound]",
+    ),
+    endpoint(
+        "GET",
+        "/inbounds/{id}",
+        ApiScope::InboundsRead,
+        "inbounds",
+        "Get an inbound",
+        "Inbound",
+    ),
+    endpoint(
+        "POST",
+        "/inbounds/{id}/toggle",
+        ApiScope::InboundsWrite,
+        "inbounds",
+        "Enable or disable an inbound",
+        "Inbound",
+    ),
+    endpoint(
+        "GET",
+        "/promo-codes",
+        ApiScope::PromoRead,
+        "promo-codes",
+        "List promo codes",
+        "[PromoCode]",
+    ),
+    Endpoint {
+        request: Some("PromoCodeCreate"),
+        ..endpoint(
+            "POST",
+            "/promo-codes",
+            ApiScope::PromoWrite,
+            "promo-codes",
+            "Create a promo code",
+            "PromoCode",
+        )
+    },
+    endpoint(
+        "DELETE",
+        "/promo-codes/{id}",
+        ApiScope::PromoWrite,
+        "promo-codes",
+        "Delete a promo code",
+        "Ok",
+    ),
+];
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/users", get(list_users))
+        .route("/users/{id}", get(get_user).patch(update_user))
+        .route("/users/{id}/balance", put(set_user_balance))
+        .route(
+            "/users/{id}/subscriptions",
+            get(list_user_subscriptions).post(create_subscription),
+        )
+        .route(
+            "/subscriptions/{id}",
+            get(get_subscription).delete(delete_subscription),
+        )
+        .route("/subscriptions/{id}/extend", post(extend_subscription))
+        .route("/plans", get(list_plans))
+        .route("/plans/{id}", get(get_plan))
+        .route("/nodes", get(list_nodes))
+        .route("/nodes/{id}", get(get_node))
+        .route("/nodes/{id}/sync", post(sync_node))
+        .route("/nodes/{id}/toggle", post(toggle_node))
+        .route("/nodes/{id}/inbounds", get(list_node_inbounds))
+        .route("/inbounds/{id}", get(get_inbound))
+        .route("/inbounds/{id}/toggle", post(toggle_inbound))
+        .route(
+            "/promo-codes",
+            get(list_promo_codes).post(create_promo_code),
+        )
+        .route(
+            "/promo-codes/{id}",
+            axum::routing::delete(delete_promo_code),
+        )
+        .route_layer(middleware::from_fn_with_state(state, token_auth))
+        // The description is public so tooling can fetch it without a token.
+        .route("/openapi.json", get(openapi_json))
+}
+
+/// Find the endpoint for a concrete request path relative to the API prefix.
+pub fn match_endpoint(method: &str, rel_path: &str) -> Option<&'static Endpoint> {
+    let segments: Vec<&str> = rel_path.split('/').filter(|s| !s.is_empty()).collect();
+    ENDPOINTS.iter().find(|e| {
+        let template: Vec<&str> = e.path.split('/').filter(|s| !s.is_empty()).collect();
+        e.method.eq_ignore_ascii_case(method)
+            && template.len() == segments.len()
+            && template
+                .iter()
+                .zip(&segments)
+                .all(|(t, s)| t.starts_with('{') || t == s)
+    })
+}
+
+fn strip_api_prefix(path: &str) -> &str {
+    API_PREFIXES
+        .iter()
+        .find_map(|prefix| path.strip_prefix(prefix))
+        .unwrap_or(path)
+}
+
+fn api_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+fn internal_error(e: anyhow::Error) -> Response {
+    error!("Admin API error: {:#}", e);
+    api_error(StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+}
+
+fn not_found() -> Response {
+    api_error(StatusCode::NOT_FOUND, "not found")
+}
+
+/// Authenticates the bearer token and enforces the endpoint's scope and the
+/// owning admin's current role. Requests then act as that admin.
+async fn token_auth(State(state): State<AppState>, mut req: Request, next: Next) -> Response {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim().to_string());
+    let Some(token) = token else {
+        return api_error(StatusCode::UNAUTHORIZED, "missing bearer token");
+    };
+
+    let ip = crate::subscription::extract_client_ip(req.headers());
+    let Some((api_token, admin)) = state.api_token_service.authenticate(&token, &ip).await else {
+        return api_error(
+            StatusCode::UNAUTHORIZED,
+            "invalid, expired or revoked token",
+        );
+    };
+
+    let method = req.method().clone();
+    let rel_path = strip_api_prefix(req.uri().path()).to_string();
+    let Some(endpoint) = match_endpoint(method.as_str(), &rel_path) else {
+        return not_found();
+    };
+
+    if !api_token.has_scope(endpoint.scope) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "forbidden",
+                "required_scope": endpoint.scope.as_str(),
+            })),
+        )
+            .into_response();
+    }
+
+    // Scopes never outgrow the owner: a demoted admin's tokens shrink with them.
+    let role = admin.role();
+    let permission = endpoint.scope.permission();
+    if !role.allows(permission) {
+        tracing::warn!(
+            "RBAC: API token '{}' of admin '{}' ({}) denied {} {} (requires {})",
+            api_token.name,
+            admin.username,
+            role,
+            method,
+            rel_path,
+            permission.as_str()
+        );
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "forbidden",
+                "role": role.as_str(),
+                "required_permission": permission.as_str(),
+            })),
+        )
+            .into_response();
+    }
+
+    let actor = AuditActor::new(
+        admin.id,
+        format!("{} (token: {})", admin.username, api_token.name),
+        ip,
+    );
+    req.extensions_mut().insert(actor.clone());
+    let response = next.run(req).await;
+
+    if method != axum::http::Method::GET && !actor.has_recorded() && response.status().is_success()
+    {
+        state
+            .audit_service
+            .record_request(&actor, method.as_str(), &rel_path)
+            .await;
+    }
+    response
+}
+
+// ============================================================================
+// Users
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct ListUsersQuery {
+    pub search: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+async fn list_users(State(state): State<AppState>, Query(q): Query<ListUsersQuery>) -> Response {
+    let users = match q.search.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(search) => state.user_service.search(search).await,
+        None => state.user_service.get_all().await,
+    };
+    match users {
+        Ok(users) => Json(
+            users
+                .into_iter()
+                .skip(q.offset.unwrap_or(0))
+                .take(q.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE))
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+async fn get_user(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    match state.user_service.get_by_id(id).await {
+        Ok(Some(user)) => Json(user).into_response(),
+        Ok(None) => not_found(),
+        Err(e) => internal_error(e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UserUpdate {
+    pub is_banned: Option<bool>,
+}
+
+async fn update_user(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+    Json(body): Json<UserUpdate>,
+) -> Response {
+    let before = match state.user_service.get_by_id(id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return not_found(),
+        Err(e) => return internal_error(e),
+    };
+
+    let is_banned = body.is_banned.unwrap_or(before.is_banned);
+    if let Err(e) = state
+        .user_service
+        .update_profile(
+            id,
+            before.balance,
+            is_banned,
+            before.referral_code.as_deref(),
+        )
+        .await
+    {
+        return internal_error(e);
+    }
+
+    match state.user_service.get_by_id(id).await {
+        Ok(Some(after)) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "user.update",
+                    "user",
+                    id,
+                    serde_json::to_value(&before).ok(),
+                    serde_json::to_value(&after).ok(),
+                )
+                .await;
+            Json(after).into_response()
+        }
+        Ok(None) => not_found(),
+        Err(e) => internal_error(e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BalanceUpdate {
+    pub balance: i64,
+}
+
+async fn set_user_balance(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+    Json(body): Json<BalanceUpdate>,
+) -> Response {
+    let before = match state.user_service.get_by_id(id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return not_found(),
+        Err(e) => return internal_error(e),
+    };
+
+    if let Err(e) = state.user_service.set_balance(id, body.balance).await {
+        return internal_error(e);
+    }
+
+    state
+        .audit_service
+        .record(
+            &actor,
+            "user.balance",
+            "user",
+            id,
+            Some(json!({ "balance": before.balance })),
+            Some(json!({ "balance": body.balance })),
+        )
+        .await;
+
+    match state.user_service.get_by_id(id).await {
+        Ok(Some(after)) => Json(after).into_response(),
+        Ok(None) => not_found(),
+        Err(e) => internal_error(e),
+    }
+}
+
+// ============================================================================
+// Subscriptions
+// ============================================================================
+
+async fn list_user_subscriptions(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    match state.subscription_service.get_user_subscriptions(id).await {
+        Ok(subs) => Json(subs).into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SubscriptionCreate {
+    pub plan_id: i64,
+    pub days: i32,
+}
+
+async fn create_subscription(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(user_id): Path<i64>,
+    Json(body): Json<SubscriptionCreate>,
+) -> Response {
+    if body.days <= 0 {
+        return api_error(StatusCode::BAD_REQUEST, "days must be greater than 0");
+    }
+    match state.user_service.get_by_id(user_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return not_found(),
+        Err(e) => return internal_error(e),
+    }
+    match state.catalog_service.get_plan_by_id(body.plan_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return api_error(StatusCode::BAD_REQUEST, "unknown plan_id"),
+        Err(e) => return internal_error(e),
+    }
+
+    match state
+        .subscription_service
+        .admin_gift_subscription(user_id, body.plan_id, body.days)
+        .await
+    {
+        Ok(sub) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "subscription.gift",
+                    "subscription",
+                    sub.id,
+                    None,
+                    Some(json!({
+                        "user_id": user_id,
+                        "plan_id": body.plan_id,
+                        "days": body.days,
+                    })),
+                )
+                .await;
+            (StatusCode::CREATED, Json(sub)).into_response()
+        }
+        Err(e) => internal_error(e),
+    }
+}
+
+async fn get_subscription(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    match state.subscription_service.get_by_id(id).await {
+        Ok(Some(sub)) => Json(sub).into_response(),
+        Ok(None) => not_found(),
+        Err(e) => internal_error(e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SubscriptionExtend {
+    pub days: i32,
+}
+
+async fn extend_subscription(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+    Json(body): Json<SubscriptionExtend>,
+) -> Response {
+    if body.days <= 0 {
+        return api_error(StatusCode::BAD_REQUEST, "days must be greater than 0");
+    }
+    let before = match state.subscription_service.get_by_id(id).await {
+        Ok(Some(sub)) => sub,
+        Ok(None) => return not_found(),
+        Err(e) => return internal_error(e),
+    };
+
+    if let Err(e) = state.subscription_service.admin_extend(id, body.days).await {
+        return internal_error(e);
+    }
+
+    match state.subscription_service.get_by_id(id).await {
+        Ok(Some(after)) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "subscription.extend",
+                    "subscription",
+                    id,
+                    Some(json!({ "expires_at": before.expires_at })),
+                    Some(json!({ "expires_at": after.expires_at })),
+                )
+                .await;
+            Json(after).into_response()
+        }
+        Ok(None) => not_found(),
+        Err(e) => internal_error(e),
+    }
+}
+
+async fn delete_subscription(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> Response {
+    let before = match state.subscription_service.get_by_id(id).await {
+        Ok(Some(sub)) => sub,
+        Ok(None) => return not_found(),
+        Err(e) => return internal_error(e),
+    };
+
+    if let Err(e) = state.subscription_service.admin_delete(id).await {
+        return internal_error(e);
+    }
+
+    state
+        .audit_service
+        .record(
+            &actor,
+            "subscription.delete",
+            "subscription",
+            id,
+            serde_json::to_value(&before).ok(),
+            None,
+        )
+        .await;
+    Json(json!({ "ok": true })).into_response()
+}
+
+// ============================================================================
+// Plans
+// ============================================================================
+
+async fn list_plans(State(state): State<AppState>) -> Response {
+    match state.catalog_service.get_plans_admin().await {
+        Ok(plans) => Json(plans).into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+async fn get_plan(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    match state.catalog_service.get_plan_by_id(id).await {
+        Ok(Some(plan)) => Json(plan).into_response(),
+        Ok(None) => not_found(),
+        Err(e) => internal_error(e),
+    }
+}
+
+// ============================================================================
+// Nodes & Inbounds
+// ============================================================================
+
+/// Public view of a node. Leaves out root passwords, join tokens and keys.
+#[derive(Serialize)]
+pub struct ApiNode {
+    pub id: i64,
+    pub name: String,
+    pub ip: String,
+    pub domain: Option<String>,
+    pub status: String,
+    pub is_enabled: bool,
+    pub vpn_port: i64,
+    pub country_code: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub flag: Option<String>,
+    pub reality_pub: Option<String>,
+    pub reality_sni: Option<String>,
+    pub is_relay: bool,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub last_latency: Option<f64>,
+    pub last_cpu: Option<f64>,
+    pub last_ram: Option<f64>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<&Node> for ApiNode {
+    fn from(node: &Node) -> Self {
+        Self {
+            id: node.id,
+            name: node.name.clone(),
+            ip: node.ip.clone(),
+            domain: node.domain.clone(),
+            status: node.status.clone(),
+            is_enabled: node.is_enabled,
+            vpn_port: node.vpn_port,
+            country_code: node.country_code.clone(),
+            country: node.country.clone(),
+            city: node.city.clone(),
+            flag: node.flag.clone(),
+            reality_pub: node.reality_pub.clone(),
+            reality_sni: node.reality_sni.clone(),
+            is_relay: node.is_relay,
+            last_seen: node.last_seen,
+            last_latency: node.last_latency,
+            last_cpu: node.last_cpu,
+            last_ram: node.last_ram,
+            created_at: node.created_at,
+        }
+    }
+}
+
+/// Inbound with its JSON settings expanded and secrets masked.
+fn api_inbound(inbound: &Inbound) -> Value {
+    inbound_snapshot(inbound)
+        .map(|v| redact(&v))
+        .unwrap_or(Value::Null)
+}
+
+async fn load_node(state: &AppState, id: i64) -> Option<Node> {
+    state
+        .infrastructure_service
+        .node_repo
+        .get_node_by_id(id)
+        .await
+        .ok()
+        .flatten()
+}
+
+async fn list_nodes(State(state): State<AppState>) -> Response {
+    match state.infrastructure_service.get_all_nodes().await {
+        Ok(nodes) => Json(nodes.iter().map(ApiNode::from).collect::<Vec<_>>()).into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+async fn get_node(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    match load_node(&state, id).await {
+        Some(node) => Json(ApiNode::from(&node)).into_response(),
+        None => not_found(),
+    }
+}
+
+async fn sync_node(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> Response {
+    if load_node(&state, id).await.is_none() {
+        return not_found();
+    }
+    spawn_node_sync(&state, id, "Manual sync", &actor.username).await;
+    state
+        .audit_service
+        .record(&actor, "node.sync", "node", id, None, None)
+        .await;
+    (StatusCode::ACCEPTED, Json(json!({ "ok": true }))).into_response()
+}
+
+async fn toggle_node(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> Response {
+    let Some(before) = load_node(&state, id).await else {
+        return not_found();
+    };
+    if let Err(e) = state.infrastructure_service.toggle_node_enable(id).await {
+        return internal_error(e);
+    }
+    let Some(after) = load_node(&state, id).await else {
+        return not_found();
+    };
+    state
+        .audit_service
+        .record(
+            &actor,
+            "node.toggle",
+            "node",
+            id,
+            Some(node_audit_snapshot(&before)),
+            Some(node_audit_snapshot(&after)),
+        )
+        .await;
+    Json(ApiNode::from(&after)).into_response()
+}
+
+async fn list_node_inbounds(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    if load_node(&state, id).await.is_none() {
+        return not_found();
+    }
+    match state.infrastructure_service.get_node_inbounds(id).await {
+        Ok(inbounds) => Json(inbounds.iter().map(api_inbound).collect::<Vec<_>>()).into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+async fn load_inbound(state: &AppState, id: i64) -> Option<Inbound> {
+    state
+        .infrastructure_service
+        .node_repo
+        .get_inbound_by_id(id)
+        .await
+        .ok()
+        .flatten()
+}
+
+async fn get_inbound(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    match load_inbound(&state, id).await {
+        Some(inbound) => Json(api_inbound(&inbound)).into_response(),
+        None => not_found(),
+    }
+}
+
+async fn toggle_inbound(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> Response {
+    let Some(inbound) = load_inbound(&state, id).await else {
+        return not_found();
+    };
+
+    let enable: bool = match sqlx::query_scalar(
+        "UPDATE inbounds SET enable = NOT enable WHERE id = $1 RETURNING enable",
+    )
+    .bind(id)
+    .fetch_one(&state.pool)
+    .await
+    {
+        Ok(enable) => enable,
+        Err(e) => return internal_error(e.into()),
+    };
+
+    state
+        .audit_service
+        .record(
+            &actor,
+            "inbound.toggle",
+            "inbound",
+            id,
+            Some(json!({ "enable": inbound.enable })),
+            Some(json!({ "enable": enable })),
+        )
+        .await;
+
+    spawn_node_sync(&state, inbound.node_id, "Inbound toggled", &actor.username).await;
+
+    match load_inbound(&state, id).await {
+        Some(inbound) => Json(api_inbound(&inbound)).into_response(),
+        None => not_found(),
+    }
+}
+
+// ============================================================================
+// Promo Codes
+// ============================================================================
+
+async fn list_promo_codes(State(state): State<AppState>) -> Response {
+    match state.promo_service.list_promos().await {
+        Ok(promos) => Json(promos).into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PromoCodeCreate {
+    pub code: String,
+    pub promo_type: String,
+    pub plan_id: Option<i64>,
+    pub balance_amount: Option<i32>,
+    pub duration_days: Option<i32>,
+    pub traffic_gb: Option<i32>,
+    pub max_uses: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+async fn create_promo_code(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Json(body): Json<PromoCodeCreate>,
+) -> Response {
+    let promo_type = body.promo_type.trim().to_ascii_lowercase();
+    if body.code.trim().is_empty() {
+        return api_error(StatusCode::BAD_REQUEST, "code is required");
+    }
+    if !matches!(promo_type.as_str(), "balance" | "subscription" | "trial") {
+        return api_error(StatusCode::BAD_REQUEST, "invalid promo_type");
+    }
+    if body.max_uses <= 0 {
+        return api_error(StatusCode::BAD_REQUEST, "max_uses must be greater than 0");
+    }
+    if matches!(promo_type.as_str(), "subscription" | "trial") && body.plan_id.is_none() {
+        return api_error(
+            StatusCode::BAD_REQUEST,
+            "plan_id is required for subscription/trial promo",
+        );
+    }
+
+    let id = match state
+        .promo_service
+        .create_promo(
+            &body.code,
+            &promo_type,
+            body.plan_id,
+            body.balance_amount,
+            body.duration_days,
+            body.traffic_gb,
+            body.max_uses,
+            body.expires_at,
+            actor.admin_id,
+        )
+        .await
+    {
+        Ok(id) => id,
+        Err(e) => return internal_error(e),
+    };
+
+    match state.promo_service.get_promo(id).await {
+        Ok(Some(promo)) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "promo.create",
+                    "promo",
+                    id,
+                    None,
+                    serde_json::to_value(&promo).ok(),
+                )
+                .await;
+            (StatusCode::CREATED, Json(promo)).into_response()
+        }
+        Ok(None) => not_found(),
+        Err(e) => internal_error(e),
+    }
+}
+
+async fn delete_promo_code(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> Response {
+    let before = match state.promo_service.get_promo(id).await {
+        Ok(Some(promo)) => promo,
+        Ok(None) => return not_found(),
+        Err(e) => return internal_error(e),
+    };
+    if let Err(e) = state.promo_service.delete_promo(id).await {
+        return internal_error(e);
+    }
+    state
+        .audit_service
+        .record(
+            &actor,
+            "promo.delete",
+            "promo",
+            id,
+            serde_json::to_value(&before).ok(),
+            None,
+        )
+        .await;
+    Json(json!({ "ok": true })).into_response()
+}
+
+// ============================================================================
+// OpenAPI
+// ============================================================================
+
+async fn openapi_json() -> Json<Value> {
+    Json(openapi_document())
+}
+
+fn schema_ref(name: &str) -> Value {
+    match name.strip_prefix('[').and_then(|n| n.strip_suffix(']')) {
+        Some(item) => json!({ "type": "array", "items": schema_ref(item) }),
+        None => json!({ "$ref": format!("#/components/schemas/{}", name) }),
+    }
+}
+
+fn object(required: &[&str], properties: Value) -> Value {
+    json!({ "type": "object", "required": required, "properties": properties })
+}
+
+fn component_schemas() -> Value {
+    let int = json!({ "type": "integer", "format": "int64" });
+    let int_opt = json!({ "type": "integer", "format": "int64", "nullable": true });
+    let string = json!({ "type": "string" });
+    let string_opt = json!({ "type": "string", "nullable": true });
+    let boolean = json!({ "type": "boolean" });
+    let time = json!({ "type": "string", "format": "date-time" });
+    let time_opt = json!({ "type": "string", "format": "date-time", "nullable": true });
+    let number_opt = json!({ "type": "number", "nullable": true });
+
+    json!({
+        "Ok": object(&["ok"], json!({ "ok": boolean })),
+        "Error": object(&["error"], json!({ "error": string })),
+        "User": object(&["id", "tg_id", "balance", "is_banned"], json!({
+            "id": int, "tg_id": int, "username": string_opt, "full_name": string_opt,
+            "balance": int, "referral_code": string_opt, "referrer_id": int_opt,
+            "is_banned": boolean, "language_code": string_opt, "warning_count": int,
+            "created_at": time, "parent_id": int_opt,
+        })),
+        "UserUpdate": object(&[], json!({ "is_banned": boolean })),
+        "BalanceUpdate": object(&["balance"], json!({ "balance": int })),
+        "Subscription": object(&["id", "user_id", "plan_id", "status", "expires_at"], json!({
+            "id": int, "user_id": int, "plan_id": int, "node_id": int_opt,
+            "vless_uuid": string_opt, "expires_at": time, "status": string,
+            "used_traffic": int, "note": string_opt, "is_trial": boolean,
+            "subscription_uuid": string, "last_sub_access": time_opt, "created_at": time,
+        })),
+        "SubscriptionCreate": object(&["plan_id", "days"], json!({
+            "plan_id": int, "days": { "type": "integer", "minimum": 1 },
+        })),
+        "SubscriptionExtend": object(&["days"], json!({
+            "days": { "type": "integer", "minimum": 1 },
+        })),
+        "Plan": object(&["id", "name", "is_active"], json!({
+            "id": int, "name": string, "description": string_opt, "is_active": boolean,
+            "traffic_limit_gb": int, "device_limit": int, "is_trial": boolean,
+            "created_at": time,
+            "durations": { "type": "array", "items": { "type": "object" } },
+        })),
+        "Node": object(&["id", "name", "ip", "status", "is_enabled"], json!({
+            "id": int, "name": string, "ip": string, "domain": string_opt,
+            "status": string, "is_enabled": boolean, "vpn_port": int,
+            "country_code": string_opt, "country": string_opt, "city": string_opt,
+            "flag": string_opt, "reality_pub": string_opt, "reality_sni": string_opt,
+            "is_relay": boolean, "last_seen": time_opt, "last_latency": number_opt,
+            "last_cpu": number_opt, "last_ram": number_opt, "created_at": time,
+        })),
+        "Inbound": object(&["id", "node_id", "tag", "protocol", "listen_port", "enable"], json!({
+            "id": int, "node_id": int, "tag": string, "protocol": string,
+            "listen_port": int, "listen_ip": string, "remark": string_opt,
+            "enable": boolean,
+            "settings": { "type": "object", "description": "Secrets are masked as \"***\"" },
+            "stream_settings": { "type": "object", "description": "Secrets are masked as \"***\"" },
+        })),
+        "PromoCode": object(&["id", "code", "promo_type", "max_uses"], json!({
+            "id": int, "code": string, "promo_type": string, "plan_id": int_opt,
+            "balance_amount": int_opt, "duration_days": int_opt, "traffic_gb": int_opt,
+            "max_uses": int, "current_uses": int, "expires_at": time_opt,
+            "created_at": time, "is_active": boolean,
+        })),
+        "PromoCodeCreate": object(&["code", "promo_type", "max_uses"], json!({
+            "code": string,
+            "promo_type": { "type": "string", "enum": ["balance", "subscription", "trial"] },
+            "plan_id": int_opt, "balance_amount": int_opt, "duration_days": int_opt,
+            "traffic_gb": int_opt, "max_uses": { "type": "integer", "minimum": 1 },
+            "expires_at": time_opt,
+        })),
+    })
+}
+
+/// OpenAPI 3.0 description generated from [`ENDPOINTS`].
+pub fn openapi_document() -> Value {
+    let mut paths = Map::new();
+    for e in ENDPOINTS {
+        let mut parameters: Vec<Value> = e
+            .path
+            .split('/')
+            .filter_map(|s| s.strip_prefix('{').and_then(|s| s.strip_suffix('}')))
+            .map(|name| {
+                json!({
+                    "name": name, "in": "path", "required": true,
+                    "schema": { "type": "integer", "format": "int64" },
+                })
+            })
+            .collect();
+        parameters.extend(e.query.iter().map(|name| {
+            let schema = if *name == "search" {
+                json!({ "type": "string" })
+            } else {
+                json!({ "type": "integer", "minimum": 0 })
+            };
+            json!({ "name": name, "in": "query", "required": false, "schema": schema })
+        }));
+
+        let success = match e.method {
+            "POST" if e.response != "Ok" => "201",
+            "POST" => "202",
+            _ => "200",
+        };
+        let error = json!({ "content": { "application/json": { "schema": schema_ref("Error") } } });
+        let mut operation = json!({
+            "operationId": operation_id(e),
+            "summary": e.summary,
+            "tags": [e.tag],
+            "security": [{ "bearerAuth": [e.scope.as_str()] }],
+            "x-required-scope": e.scope.as_str(),
+            "parameters": parameters,
+            "responses": {
+                success: {
+                    "description": "Success",
+                    "content": { "application/json": { "schema": schema_ref(e.response) } },
+                },
+                "401": { "description": "Missing, invalid or expired token", "content": error["content"] },
+                "403": { "description": "Token lacks the scope or the owner lacks the permission", "content": error["content"] },
+                "404": { "description": "Not found", "content": error["content"] },
+            },
+        });
+        if let Some(request) = e.request {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "application/json": { "schema": schema_ref(request) } },
+            });
+        }
+
+        paths
+            .entry(e.path)
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .expect("path item is an object")
+            .insert(e.method.to_ascii_lowercase(), operation);
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Caramba Admin API",
+            "version": "1",
+            "description": "Scoped bearer tokens are created under API Tokens in the panel. A token can never do more than its owner's role allows.",
+        },
+        "servers": API_PREFIXES.iter().map(|p| json!({ "url": p })).collect::<Vec<_>>(),
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer" },
+            },
+            "schemas": component_schemas(),
+        },
+    })
+}
+
+/// `GET /users/{id}/subscriptions` -> `getUsersSubscriptions`.
+fn operation_id(e: &Endpoint) -> String {
+    let verb = match e.method {
+        "GET" => "get",
+        "POST" => "post",
+        "PUT" => "put",
+        "PATCH" => "patch",
+        _ => "delete",
+    };
+    let mut id = verb.to_string();
+    for segment in e
+        .path
+        .split('/')
+        .filter(|s| !s.is_empty() && !s.starts_with('{'))
+    {
+        for word in segment.split('-') {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                id.push(first.to_ascii_uppercase());
+                id.extend(chars);
+            }
+        }
+    }
+    if e.path.ends_with('}') {
+        id.push_str("ById");
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn matches_concrete_paths_to_endpoints() {
+        let e = match_endpoint("GET", "/users/42").unwrap();
+        assert_eq!(e.scope, ApiScope::UsersRead);
+        let e = match_endpoint("PUT", "/users/42/balance").unwrap();
+        assert_eq!(e.scope, ApiScope::BillingWrite);
+        let e = match_endpoint("post", "/nodes/3/sync").unwrap();
+        assert_eq!(e.scope, ApiScope::NodesAdmin);
+        assert!(match_endpoint("DELETE", "/users/42").is_none());
+        assert!(match_endpoint("GET", "/users/42/unknown").is_none());
+    }
+
+    #[test]
+    fn strips_either_prefix() {
+        assert_eq!(strip_api_prefix("/api/admin/v1/plans"), "/plans");
+        assert_eq!(
+            strip_api_prefix("/caramba-api/admin/v1/plans/1"),
+            "/plans/1"
+        );
+        assert_eq!(strip_api_prefix("/plans"), "/plans");
+    }
+
+    #[test]
+    fn operation_ids_are_unique() {
+        let mut seen = HashSet::new();
+        for e in ENDPOINTS {
+            assert!(seen.insert(operation_id(e)), "{} {}", e.method, e.path);
+        }
+        assert_eq!(
+            operation_id(match_endpoint("GET", "/users/1/subscriptions").unwrap()),
+            "getUsersSubscriptions"
+        );
+    }
+
+    #[test]
+    fn openapi_document_covers_every_endpoint() {
+        let doc = openapi_document();
+        for e in ENDPOINTS {
+            let op = &doc["paths"][e.path][e.method.to_ascii_lowercase()];
+            assert_eq!(op["x-required-scope"], e.scope.as_str());
+            for name in [Some(e.response), e.request].into_iter().flatten() {
+                let name = name.trim_start_matches('[').trim_end_matches(']');
+                assert!(
+                    doc["components"]["schemas"].get(name).is_some(),
+                    "missing schema {}",
+                    name
+                );
+            }
+        }
+        let params = &doc["paths"]["/users/{id}/balance"]["put"]["parameters"];
+        assert_eq!(params[0]["name"], "id");
+        assert_eq!(params[0]["in"], "path");
+    }
+
+    #[test]
+    fn every_scope_is_reachable() {
+        for scope in ApiScope::ALL {
+            assert!(
+                ENDPOINTS.iter().any(|e| e.scope == scope),
+                "scope {} has no endpoint",
+                scope
+            );
+        }
+    }
+}
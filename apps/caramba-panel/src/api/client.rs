@@ -14,7 +14,7 @@ use sha2::Sha256;
 use sqlx::Row;
 use std::collections::HashMap;
 use std::env;
-use tracing::warn;
+use tracing::{error, warn};
 
 #[inline]
 fn ensure_jwt_crypto_provider() {
@@ -202,6 +202,13 @@ pub fn routes(state: AppState) -> Router<AppState> {
                 auth_middleware,
             )),
         )
+        .route(
+            "/subscription/{id}/rotate-link",
+            post(rotate_subscription_link).layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth_middleware,
+            )),
+        )
         .route(
             "/promo/redeem",
             post(redeem_promo_code).layer(middleware::from_fn_with_state(
@@ -633,7 +640,14 @@ async fn get_user_subscriptions(
         let primary_vless_link = vless_links.first().cloned();
         let used_gb = s.sub.used_traffic as f64 / 1024.0 / 1024.0 / 1024.0;
         let traffic_limit_gb = s.traffic_limit_gb.unwrap_or(0);
-        let sub_url = format!("{}/sub/{}", base_url, s.sub.subscription_uuid);
+        let sub_url = format!(
+            "{}/sub/{}",
+            base_url,
+            state
+                .sub_token_service
+                .current(s.sub.id, &s.sub.subscription_uuid)
+                .await
+        );
         let days_left = (s.sub.expires_at - chrono::Utc::now()).num_days().max(0);
         let duration_days = (s.sub.expires_at - s.sub.created_at).num_days();
         let active_devices = state
@@ -1469,8 +1483,13 @@ async fn get_subscription_links_for_user(
         .collect();
     let base_url = resolve_subscription_base_url(&state).await;
 
+    let token = state
+        .sub_token_service
+        .current(sub_id, &subscription_uuid)
+        .await;
+
     Json(serde_json::json!({
-        "subscription_url": format!("{}/sub/{}", base_url, subscription_uuid),
+        "subscription_url": format!("{}/sub/{}", base_url, token),
         "links": links,
         "vless_links": vless_links,
         "primary_vless_link": vless_links.first().cloned(),
@@ -1478,6 +1497,60 @@ async fn get_subscription_links_for_user(
     .into_response()
 }
 
+async fn rotate_subscription_link(
+    State(state): State<AppState>,
+    axum::Extension(claims): axum::Extension<Claims>,
+    Path(sub_id): Path<i64>,
+) -> impl IntoResponse {
+    let tg_id: i64 = claims.sub.parse().unwrap_or(0);
+    let user_id: Option<i64> = sqlx::query_scalar("SELECT id FROM users WHERE tg_id = $1")
+        .bind(tg_id)
+        .fetch_optional(&state.pool)
+        .await
+        .unwrap_or(None);
+
+    let user_id = match user_id {
+        Some(id) => id,
+        None => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+    };
+
+    let owner_id: Option<i64> =
+        sqlx::query_scalar("SELECT user_id FROM subscriptions WHERE id = $1")
+            .bind(sub_id)
+            .fetch_optional(&state.pool)
+            .await
+            .unwrap_or(None);
+    if owner_id != Some(user_id) {
+        return (
+            StatusCode::FORBIDDEN,
+            "Subscription not found or access denied",
+        )
+            .into_response();
+    }
+
+    let grace = crate::services::sub_token_service::SubTokenService::self_service_grace(
+        &state
+            .settings
+            .get_or_default("sub_link_grace_hours", "0")
+            .await,
+    );
+    match state.sub_token_service.rotate(sub_id, grace).await {
+        Ok(token) => {
+            let base_url = resolve_subscription_base_url(&state).await;
+            Json(serde_json::json!({
+                "ok": true,
+                "subscription_url": format!("{}/sub/{}", base_url, token),
+                "grace_hours": grace.num_hours(),
+            }))
+            .into_response()
+        }
+        Err(err) => {
+            error!("Failed to rotate link of subscription {}: {}", sub_id, err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to rotate link").into_response()
+        }
+    }
+}
+
 async fn get_my_gift_codes(
     State(state): State<AppState>,
     axum::Extension(claims): axum::Extension<Claims>,
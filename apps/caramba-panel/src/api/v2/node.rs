@@ -1,14 +1,17 @@
 use crate::AppState;
+use crate::services::config_version_service::config_hash;
+use crate::services::device_service::parse_user_tag;
 use axum::{
     extract::State,
     http::StatusCode,
     response::{IntoResponse, Json},
 };
-use caramba_shared::api::{AgentAction, HeartbeatRequest, HeartbeatResponse};
+use caramba_config::country_code_to_flag;
+use caramba_shared::api::{AgentAction, HeartbeatRequest, HeartbeatResponse, UsageReport};
 use caramba_shared::config::ConfigResponse;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use tracing::{error, info, warn};
 
 #[derive(Deserialize)]
@@ -21,19 +24,91 @@ struct IpApiResponse {
     lon: f64,
 }
 
-fn country_code_to_flag(code: &str) -> String {
-    let code = code.to_uppercase();
-    if code.len() != 2 {
-        return "🌐".to_string();
+fn subscription_id_of_tag(tag: &str) -> Option<i64> {
+    parse_user_tag(tag).map(|(sub_id, _)| sub_id)
+}
+
+/// Adds per-user byte counts to the subscriptions' used traffic, and to the
+/// device's when the user is a device credential.
+async fn add_subscription_usage(
+    conn: &mut sqlx::PgConnection,
+    usage: &HashMap<String, u64>,
+) -> Result<(), sqlx::Error> {
+    for (tag, bytes) in usage {
+        let Some((sub_id, device_id)) = parse_user_tag(tag) else {
+            continue;
+        };
+        sqlx::query("UPDATE subscriptions SET used_traffic = used_traffic + $1, traffic_updated_at = CURRENT_TIMESTAMP WHERE id = $2")
+            .bind(*bytes as i64)
+            .bind(sub_id)
+            .execute(&mut *conn)
+            .await?;
+        if let Some(device_id) = device_id {
+            sqlx::query("UPDATE subscription_devices SET used_traffic = used_traffic + $1 WHERE id = $2 AND subscription_id = $3")
+                .bind(*bytes as i64)
+                .bind(device_id)
+                .bind(sub_id)
+                .execute(&mut *conn)
+                .await?;
+        }
     }
-    let offset = 127397u32;
-    let first = code.chars().next().unwrap() as u32 + offset;
-    let second = code.chars().nth(1).unwrap() as u32 + offset;
-    format!(
-        "{}{}",
-        char::from_u32(first).unwrap_or('🌐'),
-        char::from_u32(second).unwrap_or(' ')
+    Ok(())
+}
+
+/// Batches in `report` not yet applied, merged per user tag, and the
+/// sequence number to acknowledge once they are. A journal id other than
+/// the stored one means the agent started a new journal, whose sequence
+/// numbers start over.
+fn unapplied_usage(
+    report: &UsageReport,
+    journal_id: Option<&str>,
+    acked_seq: u64,
+) -> (HashMap<String, u64>, u64) {
+    let base = if journal_id == Some(report.journal_id.as_str()) {
+        acked_seq
+    } else {
+        0
+    };
+
+    let mut usage: HashMap<String, u64> = HashMap::new();
+    let mut acked = base;
+    for batch in report.batches.iter().filter(|b| b.seq > base) {
+        for (tag, bytes) in &batch.usage {
+            let entry = usage.entry(tag.clone()).or_insert(0);
+            *entry = entry.saturating_add(*bytes);
+        }
+        acked = acked.max(batch.seq);
+    }
+    (usage, acked)
+}
+
+/// Applies the new batches of a usage report and records them as applied,
+/// in one transaction, so a replayed batch is never counted twice.
+/// Returns the usage applied and the sequence number to acknowledge.
+async fn apply_usage_report(
+    pool: &sqlx::PgPool,
+    node_id: i64,
+    report: &UsageReport,
+) -> Result<(HashMap<String, u64>, u64), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let (journal_id, acked_seq): (Option<String>, i64) = sqlx::query_as(
+        "SELECT usage_journal_id, usage_acked_seq FROM nodes WHERE id = $1 FOR UPDATE",
     )
+    .bind(node_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let (usage, acked) = unapplied_usage(report, journal_id.as_deref(), acked_seq.max(0) as u64);
+    add_subscription_usage(&mut tx, &usage).await?;
+    sqlx::query("UPDATE nodes SET usage_journal_id = $1, usage_acked_seq = $2 WHERE id = $3")
+        .bind(&report.journal_id)
+        .bind(acked as i64)
+        .bind(node_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok((usage, acked))
 }
 
 /// Agent Heartbeat
@@ -133,6 +208,15 @@ pub async fn heartbeat(
         .await;
     }
 
+    // Agents report configs they refused or rolled back until a newer one applies.
+    if let Err(e) = state
+        .config_version_service
+        .record_agent_report(node_id, req.config_failure.as_ref())
+        .await
+    {
+        warn!("Failed to store config report of node {}: {}", node_id, e);
+    }
+
     // GeoIP Check (Async) — trigger if country_code OR country/city/flag are missing
     if node_country_code.is_none() || node_country.is_none() {
         let pool = state.pool.clone();
@@ -168,20 +252,43 @@ pub async fn heartbeat(
     }
 
     // 4. Process Per-User Traffic Usage
+    // Journaled batches are applied once per sequence number and acknowledged;
+    // agents without a journal send unacknowledged deltas.
+    let mut usage_acked_seq = None;
+    let usage_map = match &req.usage_report {
+        Some(report) => match apply_usage_report(&state.pool, node_id, report).await {
+            Ok((usage, acked)) => {
+                usage_acked_seq = Some(acked);
+                Some(usage)
+            }
+            Err(e) => {
+                error!("Failed to apply usage report of node {}: {}", node_id, e);
+                None
+            }
+        },
+        None => match req.user_usage {
+            Some(usage) => match state.pool.acquire().await {
+                Ok(mut conn) => {
+                    if let Err(e) = add_subscription_usage(&mut conn, &usage).await {
+                        error!("Failed to record usage of node {}: {}", node_id, e);
+                    }
+                    Some(usage)
+                }
+                Err(e) => {
+                    error!("Failed to record usage of node {}: {}", node_id, e);
+                    None
+                }
+            },
+            None => None,
+        },
+    };
+
     let mut touched_subscriptions: HashSet<i64> = HashSet::new();
-    if let Some(usage_map) = req.user_usage {
+    if let Some(usage_map) = usage_map {
         let mut relay_legacy_usage_bytes: u64 = 0;
         for (tag, bytes) in usage_map {
-            if tag.starts_with("user_") {
-                if let Ok(sub_id) = tag[5..].parse::<i64>() {
-                    touched_subscriptions.insert(sub_id);
-                    // Increment used_traffic and update timestamp
-                    let _ = sqlx::query("UPDATE subscriptions SET used_traffic = used_traffic + $1, traffic_updated_at = CURRENT_TIMESTAMP WHERE id = $2")
-                        .bind(bytes as i64)
-                        .bind(sub_id)
-                        .execute(&state.pool)
-                        .await;
-                }
+            if let Some(sub_id) = subscription_id_of_tag(&tag) {
+                touched_subscriptions.insert(sub_id);
             }
 
             if tag.starts_with("relay_") && tag.ends_with("_legacy") {
@@ -331,6 +438,7 @@ pub async fn heartbeat(
             success: true,
             action,
             latest_version: target_version,
+            usage_acked_seq,
         }),
     )
         .into_response()
@@ -440,15 +548,43 @@ pub async fn get_config(
         return (StatusCode::FORBIDDEN, "Node is disabled").into_response();
     }
 
-    // 3. Generate Config
+    // 3. Serve the pinned version instead of regenerating, if any
+    match state.config_version_service.pinned(node_id).await {
+        Ok(Some(pinned)) => match serde_json::from_str::<serde_json::Value>(&pinned.config) {
+            Ok(content) => {
+                let _ = sqlx::query(
+                    "UPDATE nodes SET last_synced_at = CURRENT_TIMESTAMP WHERE id = $1",
+                )
+                .bind(node_id)
+                .execute(&state.pool)
+                .await;
+
+                return (
+                    StatusCode::OK,
+                    Json(ConfigResponse {
+                        hash: pinned.config_hash,
+                        content,
+                    }),
+                )
+                    .into_response();
+            }
+            Err(e) => error!(
+                "Pinned config v{} for node {} is unreadable, regenerating: {}",
+                pinned.version, node_id, e
+            ),
+        },
+        Ok(None) => {}
+        Err(e) => warn!("Failed to load pinned config for node {}: {}", node_id, e),
+    }
+
+    // 4. Generate Config
     match state
         .orchestration_service
-        .generate_node_config_json(node_id)
+        .generate_node_config_json(node_id, "Agent pull", "system")
         .await
     {
         Ok((_, config_value)) => {
-            let config_str: String = config_value.to_string();
-            let hash = format!("{:x}", md5::compute(config_str.as_bytes()));
+            let hash = config_hash(&config_value.to_string());
 
             // Update last_synced_at
             let _ =
@@ -881,3 +1017,52 @@ pub async fn report_node_logs(
     info!("✅ Logs received and stored for node {}", node_id);
     StatusCode::OK.into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use caramba_shared::api::UsageBatch;
+
+    fn report(journal: &str, batches: &[(u64, u64)]) -> UsageReport {
+        UsageReport {
+            journal_id: journal.to_string(),
+            batches: batches
+                .iter()
+                .map(|(seq, bytes)| UsageBatch {
+                    seq: *seq,
+                    usage: HashMap::from([("user_7".to_string(), *bytes)]),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn replayed_batches_are_not_counted_twice() {
+        let r = report("j1", &[(3, 100), (4, 50), (5, 25)]);
+        let (usage, acked) = unapplied_usage(&r, Some("j1"), 4);
+        assert_eq!(usage["user_7"], 25);
+        assert_eq!(acked, 5);
+
+        let (usage, acked) = unapplied_usage(&r, Some("j1"), 5);
+        assert!(usage.is_empty());
+        assert_eq!(acked, 5);
+    }
+
+    #[test]
+    fn new_journal_starts_over() {
+        let r = report("j2", &[(1, 100), (2, 50)]);
+        let (usage, acked) = unapplied_usage(&r, Some("j1"), 40);
+        assert_eq!(usage["user_7"], 150);
+        assert_eq!(acked, 2);
+        let (_, acked) = unapplied_usage(&r, None, 0);
+        assert_eq!(acked, 2);
+    }
+
+    #[test]
+    fn only_user_tags_map_to_subscriptions() {
+        assert_eq!(subscription_id_of_tag("user_42"), Some(42));
+        assert_eq!(subscription_id_of_tag("user_42_d3"), Some(42));
+        assert_eq!(subscription_id_of_tag("relay_3_legacy"), None);
+        assert_eq!(subscription_id_of_tag("user_x"), None);
+    }
+}
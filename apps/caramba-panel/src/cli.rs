@@ -15,13 +15,15 @@ pub async fn reset_password(pool: &PgPool, username: &str, new_pass: &str) -> Re
         .context("Failed to update password in database")?;
 
     if result.rows_affected() == 0 {
-        // If no rows affected, insert new admin
-        sqlx::query("INSERT INTO admins (username, password_hash) VALUES ($1, $2)")
-            .bind(username)
-            .bind(&hash)
-            .execute(pool)
-            .await
-            .context("Failed to create new admin")?;
+        // If no rows affected, insert new admin (CLI recovery always grants full access)
+        sqlx::query(
+            "INSERT INTO admins (username, password_hash, role) VALUES ($1, $2, 'superadmin')",
+        )
+        .bind(username)
+        .bind(&hash)
+        .execute(pool)
+        .await
+        .context("Failed to create new admin")?;
         println!("New admin user '{}' created successfully.", username);
     } else {
         println!(
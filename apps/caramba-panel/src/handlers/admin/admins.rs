@@ -0,0 +1,259 @@
+// Admin Accounts Module
+// Panel admin accounts and role assignment (superadmin only, enforced by RBAC middleware)
+
+use askama::Template;
+use askama_web::WebTemplate;
+use axum::{
+    Extension,
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+};
+use axum_extra::extract::cookie::CookieJar;
+use serde::Deserialize;
+use tracing::{error, info};
+
+use super::auth::get_auth_admin;
+use crate::AppState;
+use crate::services::audit_service::AuditActor;
+use crate::services::logging_service::LoggingService;
+use caramba_db::models::admin::{Admin, AdminRole};
+
+#[derive(Debug, Clone)]
+pub struct RoleOption {
+    pub value: String,
+    pub label: String,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "admins.html")]
+pub struct AdminsTemplate {
+    pub admins: Vec<Admin>,
+    pub roles: Vec<RoleOption>,
+    pub current_admin_id: i64,
+    pub admin_path: String,
+    pub active_page: String,
+    pub is_auth: bool,
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateAdminAccountForm {
+    pub username: String,
+    pub password: String,
+    pub role: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateAdminRoleForm {
+    pub role: String,
+}
+
+fn hx_refresh() -> axum::response::Response {
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("HX-Refresh", "true".parse().unwrap());
+    (StatusCode::OK, headers, "").into_response()
+}
+
+pub async fn get_admins_page(State(state): State<AppState>, jar: CookieJar) -> impl IntoResponse {
+    let Some(current) = get_auth_admin(&state, &jar).await else {
+        return axum::response::Redirect::to(&format!("{}/login", state.admin_path))
+            .into_response();
+    };
+
+    let admins = state.admin_repo.get_all().await.unwrap_or_default();
+    let roles = AdminRole::ALL
+        .iter()
+        .map(|r| RoleOption {
+            value: r.as_str().to_string(),
+            label: r.label().to_string(),
+        })
+        .collect();
+
+    let template = AdminsTemplate {
+        admins,
+        roles,
+        current_admin_id: current.id,
+        admin_path: state.admin_path.clone(),
+        active_page: "admins".to_string(),
+        is_auth: true,
+        username: current.username,
+    };
+
+    match template.render() {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Template error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn create_admin_account(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Form(form): Form<CreateAdminAccountForm>,
+) -> impl IntoResponse {
+    let username = form.username.trim();
+    if username.is_empty() || form.password.len() < 8 {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Username is required and password must be at least 8 characters",
+        )
+            .into_response();
+    }
+
+    let role = AdminRole::parse(&form.role);
+    let hash = match bcrypt::hash(&form.password, bcrypt::DEFAULT_COST) {
+        Ok(h) => h,
+        Err(_) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Password hashing failed").into_response();
+        }
+    };
+
+    match state.admin_repo.create(username, &hash, role).await {
+        Ok(id) => {
+            info!("Admin account '{}' created with role {}", username, role);
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "admin.create",
+                    "admin",
+                    id,
+                    None,
+                    Some(serde_json::json!({ "username": username, "role": role })),
+                )
+                .await;
+            let _ = LoggingService::log_system(
+                &state.pool,
+                "admin_created",
+                &format!("Admin #{} '{}' created with role {}", id, username, role),
+            )
+            .await;
+            hx_refresh()
+        }
+        Err(e) => {
+            error!("Failed to create admin account: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create admin (username taken?)",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn update_admin_role(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+    Form(form): Form<UpdateAdminRoleForm>,
+) -> impl IntoResponse {
+    let new_role = AdminRole::parse(&form.role);
+
+    if let Some(current) = get_auth_admin(&state, &jar).await
+        && current.id == id
+        && new_role != AdminRole::Superadmin
+    {
+        return (StatusCode::BAD_REQUEST, "You cannot demote yourself").into_response();
+    }
+
+    let admins = state.admin_repo.get_all().await.unwrap_or_default();
+    let Some(target) = admins.iter().find(|a| a.id == id) else {
+        return (StatusCode::NOT_FOUND, "Admin not found").into_response();
+    };
+
+    if target.role() == AdminRole::Superadmin && new_role != AdminRole::Superadmin {
+        let superadmins = state.admin_repo.count_superadmins().await.unwrap_or(0);
+        if superadmins <= 1 {
+            return (StatusCode::BAD_REQUEST, "Cannot demote the last superadmin").into_response();
+        }
+    }
+
+    if let Err(e) = state.admin_repo.update_role(id, new_role).await {
+        error!("Failed to update admin role: {}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update role").into_response();
+    }
+
+    state
+        .audit_service
+        .record(
+            &actor,
+            "admin.role",
+            "admin",
+            id,
+            Some(serde_json::json!({ "role": target.role })),
+            Some(serde_json::json!({ "role": new_role })),
+        )
+        .await;
+
+    let _ = LoggingService::log_system(
+        &state.pool,
+        "admin_role_changed",
+        &format!(
+            "Admin #{} '{}' role {} -> {}",
+            id, target.username, target.role, new_role
+        ),
+    )
+    .await;
+
+    hx_refresh()
+}
+
+pub async fn delete_admin_account(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    if let Some(current) = get_auth_admin(&state, &jar).await
+        && current.id == id
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            "You cannot delete your own account",
+        )
+            .into_response();
+    }
+
+    let admins = state.admin_repo.get_all().await.unwrap_or_default();
+    let Some(target) = admins.iter().find(|a| a.id == id) else {
+        return (StatusCode::NOT_FOUND, "Admin not found").into_response();
+    };
+
+    if target.role() == AdminRole::Superadmin {
+        let superadmins = state.admin_repo.count_superadmins().await.unwrap_or(0);
+        if superadmins <= 1 {
+            return (StatusCode::BAD_REQUEST, "Cannot delete the last superadmin").into_response();
+        }
+    }
+
+    if let Err(e) = state.admin_repo.delete(id).await {
+        error!("Failed to delete admin: {}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete admin").into_response();
+    }
+
+    state
+        .audit_service
+        .record(
+            &actor,
+            "admin.delete",
+            "admin",
+            id,
+            Some(serde_json::json!({ "username": target.username, "role": target.role })),
+            None,
+        )
+        .await;
+
+    let _ = LoggingService::log_system(
+        &state.pool,
+        "admin_deleted",
+        &format!("Admin #{} '{}' deleted", id, target.username),
+    )
+    .await;
+
+    hx_refresh()
+}
@@ -0,0 +1,248 @@
+// API Tokens Module
+// Scoped bearer tokens for the JSON admin API, managed by their owning admin
+
+use askama::Template;
+use askama_web::WebTemplate;
+use axum::{
+    Extension,
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+};
+use axum_extra::extract::cookie::CookieJar;
+use serde_json::json;
+use tracing::error;
+
+use super::auth::get_auth_admin;
+use crate::AppState;
+use crate::services::audit_service::AuditActor;
+use caramba_db::models::admin::AdminRole;
+use caramba_db::models::api_token::{AdminApiToken, ApiScope};
+
+/// Longest lifetime offered in the form; tokens may also never expire.
+const MAX_EXPIRY_DAYS: i64 = 365 * 5;
+
+#[derive(Template, WebTemplate)]
+#[template(path = "api_tokens.html")]
+pub struct ApiTokensTemplate {
+    pub tokens: Vec<AdminApiToken>,
+    /// Scopes the current admin's role can grant.
+    pub scopes: Vec<ApiScope>,
+    pub show_owner: bool,
+    pub admin_path: String,
+    pub active_page: String,
+    pub is_auth: bool,
+    pub username: String,
+}
+
+fn unauthorized() -> axum::response::Response {
+    (StatusCode::UNAUTHORIZED, "Not signed in").into_response()
+}
+
+/// Scopes an admin with `role` may put on a token.
+pub fn grantable_scopes(role: AdminRole) -> Vec<ApiScope> {
+    ApiScope::ALL
+        .into_iter()
+        .filter(|scope| role.allows(scope.permission()))
+        .collect()
+}
+
+/// GET /admin/api-tokens - Tokens of the current admin (all tokens for superadmins)
+pub async fn get_api_tokens_page(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return axum::response::Redirect::to(&format!("{}/login", state.admin_path))
+            .into_response();
+    };
+
+    let role = admin.role();
+    let show_owner = role == AdminRole::Superadmin;
+    let owner = if show_owner { None } else { Some(admin.id) };
+
+    let template = ApiTokensTemplate {
+        tokens: state
+            .api_token_service
+            .list(owner)
+            .await
+            .unwrap_or_default(),
+        scopes: grantable_scopes(role),
+        show_owner,
+        admin_path: state.admin_path.clone(),
+        active_page: "api_tokens".to_string(),
+        is_auth: true,
+        username: admin.username,
+    };
+
+    match template.render() {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Template error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /admin/api-tokens - Create a token and show it once
+///
+/// The form repeats `scopes` once per checked box, so it is read as pairs.
+pub async fn create_api_token(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    jar: CookieJar,
+    Form(form): Form<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return unauthorized();
+    };
+
+    let field = |key: &str| {
+        form.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.trim())
+            .unwrap_or_default()
+    };
+
+    let name = field("name");
+    if name.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Name is required").into_response();
+    }
+
+    let allowed = grantable_scopes(admin.role());
+    let mut scopes = Vec::new();
+    for (_, value) in form.iter().filter(|(k, _)| k == "scopes") {
+        match ApiScope::parse(value) {
+            Some(scope) if allowed.contains(&scope) => {
+                if !scopes.contains(&scope) {
+                    scopes.push(scope);
+                }
+            }
+            _ => {
+                return (
+                    StatusCode::FORBIDDEN,
+                    format!("Your role cannot grant the {} scope", value),
+                )
+                    .into_response();
+            }
+        }
+    }
+    if scopes.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Select at least one scope").into_response();
+    }
+
+    let expires_in_days = match field("expires_in_days") {
+        "" | "0" => None,
+        days => match days.parse::<i64>() {
+            Ok(days) if (1..=MAX_EXPIRY_DAYS).contains(&days) => Some(days),
+            _ => return (StatusCode::BAD_REQUEST, "Invalid expiry").into_response(),
+        },
+    };
+
+    let (id, token) = match state
+        .api_token_service
+        .create(admin.id, name, &scopes, expires_in_days)
+        .await
+    {
+        Ok(created) => created,
+        Err(e) => {
+            error!("Failed to create API token: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create token").into_response();
+        }
+    };
+
+    state
+        .audit_service
+        .record(
+            &actor,
+            "api_token.create",
+            "api_token",
+            id,
+            None,
+            Some(json!({
+                "name": name,
+                "scopes": scopes.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+                "expires_in_days": expires_in_days,
+            })),
+        )
+        .await;
+
+    Html(format!(
+        "<div class='bg-slate-950/60 border border-amber-500/30 rounded-xl p-4 space-y-3'>\
+         <p class='text-amber-400 text-sm font-medium'>Copy this token now. It will not be shown again.</p>\
+         <div class='font-mono text-white break-all select-all bg-slate-950 border border-white/10 rounded-lg p-3'>{}</div>\
+         <p class='text-xs text-slate-400'>Send it as <code>Authorization: Bearer &lt;token&gt;</code>.</p>\
+         <button onclick='location.reload()' class='text-xs text-indigo-400 hover:text-indigo-300'>I have saved it</button>\
+         </div>",
+        token
+    ))
+    .into_response()
+}
+
+/// POST /admin/api-tokens/{id}/revoke - Revoke one of the current admin's tokens (any token for superadmins)
+pub async fn revoke_api_token(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return unauthorized();
+    };
+
+    let owner = if admin.role() == AdminRole::Superadmin {
+        None
+    } else {
+        Some(admin.id)
+    };
+
+    match state.api_token_service.revoke(id, owner).await {
+        Ok(true) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "api_token.revoke",
+                    "api_token",
+                    id,
+                    Some(json!({ "revoked": false })),
+                    Some(json!({ "revoked": true })),
+                )
+                .await;
+            let mut headers = axum::http::HeaderMap::new();
+            headers.insert("HX-Refresh", "true".parse().unwrap());
+            (StatusCode::OK, headers, "").into_response()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "Token not found").into_response(),
+        Err(e) => {
+            error!("Failed to revoke API token {}: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to revoke token").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::grantable_scopes;
+    use caramba_db::models::admin::AdminRole;
+    use caramba_db::models::api_token::ApiScope;
+
+    #[test]
+    fn roles_only_grant_scopes_they_hold() {
+        assert_eq!(
+            grantable_scopes(AdminRole::Superadmin).len(),
+            ApiScope::ALL.len()
+        );
+
+        let support = grantable_scopes(AdminRole::Support);
+        assert!(support.contains(&ApiScope::SubsWrite));
+        assert!(!support.contains(&ApiScope::BillingWrite));
+        assert!(!support.contains(&ApiScope::NodesAdmin));
+
+        let read_only = grantable_scopes(AdminRole::ReadOnly);
+        assert!(read_only.contains(&ApiScope::NodesRead));
+        assert!(read_only.iter().all(|s| !s.as_str().ends_with(":write")));
+        assert!(!read_only.contains(&ApiScope::NodesAdmin));
+    }
+}
@@ -0,0 +1,149 @@
+// Audit Trail Module
+// Filterable view and export of structured admin changes
+
+use askama::Template;
+use askama_web::WebTemplate;
+use axum::{
+    extract::{Query, State},
+    http::{StatusCode, header},
+    response::{Html, IntoResponse},
+};
+use axum_extra::extract::cookie::CookieJar;
+use serde::Deserialize;
+use tracing::error;
+
+use super::auth::get_auth_user;
+use crate::AppState;
+use crate::services::audit_service::{AuditFilter, EXPORT_LIMIT, to_csv};
+use caramba_db::models::audit::AuditLogEntry;
+
+const PAGE_SIZE: i64 = 50;
+
+#[derive(Template, WebTemplate)]
+#[template(path = "audit.html")]
+pub struct AuditTemplate {
+    pub entries: Vec<AuditLogEntry>,
+    pub admins: Vec<String>,
+    pub entity_types: Vec<String>,
+    pub filter: AuditFilter,
+    /// Current filters as a query string, reused by pagination and export links.
+    pub filter_query: String,
+    pub current_page: i64,
+    pub has_next: bool,
+    pub admin_path: String,
+    pub active_page: String,
+    pub is_auth: bool,
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct AuditPageQuery {
+    pub page: Option<String>,
+    #[serde(flatten)]
+    pub filter: AuditFilter,
+}
+
+#[derive(Deserialize)]
+pub struct AuditExportQuery {
+    pub format: Option<String>,
+    #[serde(flatten)]
+    pub filter: AuditFilter,
+}
+
+/// GET /admin/audit - Filterable audit trail
+pub async fn get_audit_page(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Query(query): Query<AuditPageQuery>,
+) -> impl IntoResponse {
+    let page = query
+        .page
+        .as_deref()
+        .and_then(|p| p.parse::<i64>().ok())
+        .unwrap_or(1)
+        .max(1);
+    let offset = (page - 1) * PAGE_SIZE;
+
+    let mut entries = state
+        .audit_service
+        .list(&query.filter, PAGE_SIZE + 1, offset)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Failed to load audit log: {}", e);
+            Vec::new()
+        });
+    let has_next = entries.len() > PAGE_SIZE as usize;
+    entries.truncate(PAGE_SIZE as usize);
+
+    let (admins, entity_types) = state.audit_service.filter_options().await;
+
+    let template = AuditTemplate {
+        entries,
+        admins,
+        entity_types,
+        filter_query: serde_urlencoded::to_string(&query.filter).unwrap_or_default(),
+        filter: query.filter,
+        current_page: page,
+        has_next,
+        admin_path: state.admin_path.clone(),
+        active_page: "audit".to_string(),
+        is_auth: true,
+        username: get_auth_user(&state, &jar)
+            .await
+            .unwrap_or("Admin".to_string()),
+    };
+
+    match template.render() {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Template error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /admin/audit/export?format=csv|json - Download entries matching the filters
+pub async fn export_audit_log(
+    State(state): State<AppState>,
+    Query(query): Query<AuditExportQuery>,
+) -> impl IntoResponse {
+    let entries = match state
+        .audit_service
+        .list(&query.filter, EXPORT_LIMIT, 0)
+        .await
+    {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Audit export failed: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Export failed").into_response();
+        }
+    };
+
+    let stamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
+    let (content_type, filename, body) = match query.format.as_deref() {
+        Some("json") => (
+            "application/json",
+            format!("audit_{}.json", stamp),
+            serde_json::to_string_pretty(&entries).unwrap_or_default(),
+        ),
+        _ => (
+            "text/csv; charset=utf-8",
+            format!("audit_{}.csv", stamp),
+            to_csv(&entries),
+        ),
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
@@ -5,14 +5,19 @@ use askama::Template;
 use askama_web::WebTemplate;
 use axum::{
     extract::{Form, State},
+    http::HeaderMap,
     response::{Html, IntoResponse},
 };
 use axum_extra::extract::cookie::{Cookie, CookieJar};
 use serde::Deserialize;
 use time::Duration;
-use tracing::info;
+use tracing::{error, info, warn};
 
 use crate::AppState;
+use crate::services::admin_security_service::{LoginCheck, session_redis_key};
+use crate::services::logging_service::LoggingService;
+use crate::subscription::extract_client_ip;
+use caramba_db::models::admin::{Admin, AdminCredentials};
 
 // ============================================================================
 // Templates
@@ -27,6 +32,20 @@ pub struct LoginTemplate {
     pub username: String,
 }
 
+/// Code prompt swapped into the login form once the password is accepted.
+#[derive(Template)]
+#[template(path = "login_2fa.html")]
+pub struct TwoFactorPromptTemplate {
+    pub admin_path: String,
+    pub pending_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorForm {
+    pub pending_token: String,
+    pub code: String,
+}
+
 #[derive(Deserialize)]
 pub struct LoginForm {
     pub username: String,
@@ -39,36 +58,29 @@ pub struct LoginForm {
 
 /// Get authenticated username from session cookie
 pub async fn get_auth_user(state: &AppState, jar: &CookieJar) -> Option<String> {
-    if let Some(cookie) = jar.get("admin_session") {
-        let token = cookie.value();
-        // Check Redis
-        if let Ok(Some(username)) = state.redis.get(&format!("session:{}", token)).await {
-            return Some(username);
-        }
-    }
-    None
+    let token = jar.get("admin_session")?.value().to_string();
+    state
+        .redis
+        .get(&session_redis_key(&token))
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Get the authenticated admin record (including role) from session cookie
+pub async fn get_auth_admin(state: &AppState, jar: &CookieJar) -> Option<Admin> {
+    let username = get_auth_user(state, jar).await?;
+    state
+        .admin_repo
+        .get_by_username(&username)
+        .await
+        .ok()
+        .flatten()
 }
 
 /// Check if user is authenticated
 pub async fn is_authenticated(state: &AppState, jar: &CookieJar) -> bool {
-    if let Some(cookie) = jar.get("admin_session") {
-        let token = cookie.value();
-        let redis_key = format!("session:{}", token);
-
-        // Check if token exists in Redis
-        if let Ok(Some(username)) = state.redis.get(&redis_key).await {
-            // Verify this username actually exists in the DB
-            let user_exists: bool =
-                sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM admins WHERE username = $1)")
-                    .bind(&username)
-                    .fetch_one(&state.pool)
-                    .await
-                    .unwrap_or(false);
-
-            return user_exists;
-        }
-    }
-    false
+    get_auth_admin(state, jar).await.is_some()
 }
 
 /// Helper to create CookieJar with a cookie
@@ -96,85 +108,201 @@ pub async fn get_login(State(state): State<AppState>) -> impl IntoResponse {
     )
 }
 
+fn session_cookie(token: String) -> Cookie<'static> {
+    Cookie::build(("admin_session", token))
+        .path("/")
+        .http_only(true)
+        .build()
+}
+
+fn login_error(msg: &str) -> axum::response::Response {
+    Html(format!(
+        "<div class='text-red-500 text-sm mt-2'>{}</div>",
+        msg
+    ))
+    .into_response()
+}
+
+fn locked_message(until: chrono::DateTime<chrono::Utc>) -> String {
+    let minutes = (until - chrono::Utc::now()).num_minutes().max(1);
+    format!(
+        "Too many failed attempts. Account locked for {} more minute(s).",
+        minutes
+    )
+}
+
+/// Issue the session cookie and redirect to the dashboard.
+async fn complete_login(
+    state: &AppState,
+    admin: &AdminCredentials,
+    headers: &HeaderMap,
+) -> axum::response::Response {
+    let ip = extract_client_ip(headers);
+    let user_agent = headers
+        .get(axum::http::header::USER_AGENT)
+        .and_then(|v| v.to_str().ok());
+
+    let token = match state
+        .admin_security_service
+        .create_session(admin, &ip, user_agent)
+        .await
+    {
+        Ok(t) => t,
+        Err(e) => {
+            error!("Failed to create admin session: {}", e);
+            return login_error("Failed to create session");
+        }
+    };
+
+    info!(
+        "Creating session for user: '{}' from {} (token: {}...)",
+        admin.username,
+        ip,
+        &token[..6]
+    );
+    let _ = LoggingService::log_system(
+        &state.pool,
+        "admin_login",
+        &format!("Admin '{}' signed in from {}", admin.username, ip),
+    )
+    .await;
+
+    let mut resp_headers = HeaderMap::new();
+    resp_headers.insert(
+        "HX-Redirect",
+        format!("{}/dashboard", state.admin_path).parse().unwrap(),
+    );
+
+    (
+        axum::http::StatusCode::OK,
+        jar_with_cookie(session_cookie(token)),
+        resp_headers,
+        "Success",
+    )
+        .into_response()
+}
+
 /// POST /admin/login - Process login
 pub async fn login(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Form(form): Form<LoginForm>,
 ) -> impl IntoResponse {
-    // Check Database for user
-    let admin_opts: Option<(String,)> =
-        sqlx::query_as("SELECT password_hash FROM admins WHERE username = $1")
-            .bind(&form.username)
-            .fetch_optional(&state.pool)
-            .await
-            .unwrap_or(None);
-
-    // Verify password against DB hash (NO fallback for security)
-    let is_valid = if let Some((hash,)) = admin_opts {
-        bcrypt::verify(&form.password, &hash).unwrap_or(false)
-    } else {
-        false
+    let ip = extract_client_ip(&headers);
+    if !state.admin_security_service.allow_login_attempt(&ip).await {
+        warn!("Login rate limit exceeded for {}", ip);
+        return login_error("Too many login attempts. Try again later.");
+    }
+
+    let username = form.username.trim();
+    let check = match state
+        .admin_security_service
+        .check_password(username, &form.password)
+        .await
+    {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Login check failed: {}", e);
+            return login_error("Login failed");
+        }
     };
 
-    if is_valid {
-        let admin_path = state.admin_path.clone();
-
-        let mut headers = axum::http::HeaderMap::new();
-        headers.insert(
-            "HX-Redirect",
-            format!("{}/dashboard", admin_path).parse().unwrap(),
-        );
-
-        // Create session in Redis
-        let token = uuid::Uuid::new_v4().to_string();
-        let clean_username = form.username.trim().to_string();
-
-        info!(
-            "Creating session for user: '{}' (token: {}...)",
-            clean_username,
-            &token[..6]
-        );
-
-        let _ = state
-            .redis
-            .set(
-                &format!("session:{}", token),
-                &clean_username,
-                24 * 60 * 60, // 24 hours
+    match check {
+        LoginCheck::Success(admin) => complete_login(&state, &admin, &headers).await,
+        LoginCheck::NeedsTotp(admin) => {
+            let pending = match state
+                .admin_security_service
+                .create_pending_login(&admin.username)
+                .await
+            {
+                Ok(t) => t,
+                Err(e) => {
+                    error!("Failed to store pending 2FA login: {}", e);
+                    return login_error("Login failed");
+                }
+            };
+            Html(
+                TwoFactorPromptTemplate {
+                    admin_path: state.admin_path.clone(),
+                    pending_token: pending,
+                }
+                .render()
+                .unwrap_or_default(),
             )
-            .await;
-
-        let cookie = Cookie::build(("admin_session", token))
-            .path("/")
-            .http_only(true)
-            .build();
-
-        (
-            axum::http::StatusCode::OK,
-            jar_with_cookie(cookie),
-            headers,
-            "Success",
-        )
-            .into_response()
-    } else {
-        Html("<div class='text-red-500 text-sm mt-2'>Invalid username or password</div>")
             .into_response()
+        }
+        LoginCheck::Locked(until) => {
+            warn!("Login for locked admin '{}' from {}", username, ip);
+            login_error(&locked_message(until))
+        }
+        LoginCheck::Invalid => {
+            warn!("Failed login for '{}' from {}", username, ip);
+            login_error("Invalid username or password")
+        }
+    }
+}
+
+/// POST /admin/login/2fa - Second step for admins with TOTP enabled
+pub async fn login_2fa(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(form): Form<TwoFactorForm>,
+) -> impl IntoResponse {
+    let ip = extract_client_ip(&headers);
+    if !state.admin_security_service.allow_login_attempt(&ip).await {
+        return login_error("Too many login attempts. Try again later.");
+    }
+
+    let Some(username) = state
+        .admin_security_service
+        .get_pending_login(&form.pending_token)
+        .await
+    else {
+        return login_error("Login expired, please sign in again.");
+    };
+
+    match state
+        .admin_security_service
+        .check_second_factor(&username, &form.code)
+        .await
+    {
+        Ok(LoginCheck::Success(admin)) => {
+            state
+                .admin_security_service
+                .finish_pending_login(&form.pending_token)
+                .await;
+            complete_login(&state, &admin, &headers).await
+        }
+        Ok(LoginCheck::Locked(until)) => {
+            state
+                .admin_security_service
+                .finish_pending_login(&form.pending_token)
+                .await;
+            login_error(&locked_message(until))
+        }
+        Ok(_) => {
+            warn!("Invalid 2FA code for '{}' from {}", username, ip);
+            login_error("Invalid authentication code")
+        }
+        Err(e) => {
+            error!("2FA check failed: {}", e);
+            login_error("Login failed")
+        }
     }
 }
 
 /// POST /admin/logout - Logout user
-pub async fn logout(jar: CookieJar) -> impl IntoResponse {
+pub async fn logout(State(state): State<AppState>, jar: CookieJar) -> impl IntoResponse {
+    if let Some(token) = jar.get("admin_session").map(|c| c.value().to_string()) {
+        state.admin_security_service.end_session(&token).await;
+    }
+
     let mut cookie = Cookie::from("admin_session");
     cookie.set_value("");
     cookie.set_path("/");
     cookie.set_max_age(Duration::seconds(0)); // Expire immediately
 
-    let admin_path = std::env::var("ADMIN_PATH").unwrap_or_else(|_| "/admin".to_string());
-    let admin_path = if admin_path.starts_with('/') {
-        admin_path
-    } else {
-        format!("/{}", admin_path)
-    };
+    let admin_path = state.admin_path.clone();
 
     // Use HX-Redirect for HTMX clients (force full page reload)
     let mut headers = axum::http::HeaderMap::new();
@@ -0,0 +1,351 @@
+// Chain Profiles Module
+// Admin-defined client-side multi-hop chains (entry group -> exit group), per plan
+
+use askama::Template;
+use askama_web::WebTemplate;
+use axum::{
+    Extension,
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+};
+use axum_extra::extract::cookie::CookieJar;
+use serde_json::json;
+use tracing::error;
+
+use super::auth::get_auth_admin;
+use crate::AppState;
+use crate::services::audit_service::AuditActor;
+use crate::services::chain_profile_service::ChainInput;
+use caramba_db::models::chain_profile::ChainProfile;
+
+#[derive(Template, WebTemplate)]
+#[template(path = "chain_profiles.html")]
+pub struct ChainProfilesTemplate {
+    pub chains: Vec<ChainProfile>,
+    pub groups: Vec<(i64, String)>,
+    pub plans: Vec<(i64, String)>,
+    pub editor: ChainProfileEditTemplate,
+    pub admin_path: String,
+    pub active_page: String,
+    pub is_auth: bool,
+    pub username: String,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "partials/chain_profile_edit.html")]
+pub struct ChainProfileEditTemplate {
+    /// Chain being edited, 0 for a new one.
+    pub id: i64,
+    pub name: String,
+    pub entry_group_id: i64,
+    pub exit_group_id: i64,
+    pub plan_ids: Vec<i64>,
+    pub groups: Vec<(i64, String)>,
+    pub plans: Vec<(i64, String)>,
+    pub admin_path: String,
+}
+
+fn refresh() -> axum::response::Response {
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("HX-Refresh", "true".parse().unwrap());
+    (StatusCode::OK, headers, "").into_response()
+}
+
+fn render_html<T: Template>(template: T) -> axum::response::Response {
+    match template.render() {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Template error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+fn editor(
+    state: &AppState,
+    chain: Option<ChainProfile>,
+    groups: Vec<(i64, String)>,
+    plans: Vec<(i64, String)>,
+) -> ChainProfileEditTemplate {
+    let (id, name, entry_group_id, exit_group_id, plan_ids) = match chain {
+        Some(c) => (c.id, c.name, c.entry_group_id, c.exit_group_id, c.plan_ids),
+        None => (0, String::new(), 0, 0, Vec::new()),
+    };
+    ChainProfileEditTemplate {
+        id,
+        name,
+        entry_group_id,
+        exit_group_id,
+        plan_ids,
+        groups,
+        plans,
+        admin_path: state.admin_path.clone(),
+    }
+}
+
+/// Fields of the editor form. `plan_ids` repeats once per checked plan.
+#[derive(Debug)]
+struct EditorForm {
+    name: String,
+    entry_group_id: i64,
+    exit_group_id: i64,
+    plan_ids: Vec<i64>,
+}
+
+fn parse_form(form: &[(String, String)]) -> Result<EditorForm, String> {
+    let field = |key: &str| {
+        form.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_default()
+    };
+
+    let name = field("name").trim();
+    if name.is_empty() {
+        return Err("Name is required".to_string());
+    }
+    let group = |key: &str, label: &str| {
+        field(key)
+            .parse::<i64>()
+            .map_err(|_| format!("Choose an {} group", label))
+    };
+    let entry_group_id = group("entry_group_id", "entry")?;
+    let exit_group_id = group("exit_group_id", "exit")?;
+    if entry_group_id == exit_group_id {
+        return Err("Entry and exit groups must differ".to_string());
+    }
+
+    let mut plan_ids = Vec::new();
+    for (_, value) in form.iter().filter(|(k, _)| k == "plan_ids") {
+        match value.parse::<i64>() {
+            Ok(id) if !plan_ids.contains(&id) => plan_ids.push(id),
+            Ok(_) => {}
+            Err(_) => return Err(format!("Invalid plan {}", value)),
+        }
+    }
+
+    Ok(EditorForm {
+        name: name.to_string(),
+        entry_group_id,
+        exit_group_id,
+        plan_ids,
+    })
+}
+
+/// GET /admin/chains - Chain profiles with an editor for a new one
+pub async fn get_chain_profiles_page(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return axum::response::Redirect::to(&format!("{}/login", state.admin_path))
+            .into_response();
+    };
+
+    let chains = state.chain_profile_service.list().await.unwrap_or_default();
+    let groups = state
+        .chain_profile_service
+        .group_options()
+        .await
+        .unwrap_or_default();
+    let plans = state
+        .chain_profile_service
+        .plan_options()
+        .await
+        .unwrap_or_default();
+
+    render_html(ChainProfilesTemplate {
+        chains,
+        editor: editor(&state, None, groups.clone(), plans.clone()),
+        groups,
+        plans,
+        admin_path: state.admin_path.clone(),
+        active_page: "chains".to_string(),
+        is_auth: true,
+        username: admin.username,
+    })
+}
+
+/// GET /admin/chains/{id} - Editor filled with an existing chain profile
+pub async fn get_chain_profile_edit(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let chain = match state.chain_profile_service.get(id).await {
+        Ok(Some(c)) => c,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Chain not found").into_response(),
+        Err(e) => {
+            error!("Failed to load chain profile {}: {}", id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load chain").into_response();
+        }
+    };
+    let groups = state
+        .chain_profile_service
+        .group_options()
+        .await
+        .unwrap_or_default();
+    let plans = state
+        .chain_profile_service
+        .plan_options()
+        .await
+        .unwrap_or_default();
+
+    render_html(editor(&state, Some(chain), groups, plans))
+}
+
+async fn save(
+    state: &AppState,
+    actor: &AuditActor,
+    id: Option<i64>,
+    form: &[(String, String)],
+) -> axum::response::Response {
+    let input = match parse_form(form) {
+        Ok(input) => input,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let before = match id {
+        Some(id) => match state.chain_profile_service.get(id).await {
+            Ok(Some(c)) => Some(c),
+            Ok(None) => return (StatusCode::NOT_FOUND, "Chain not found").into_response(),
+            Err(e) => {
+                error!("Failed to load chain profile {}: {}", id, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load chain").into_response();
+            }
+        },
+        None => None,
+    };
+
+    let saved = state
+        .chain_profile_service
+        .save(
+            id,
+            &ChainInput {
+                name: &input.name,
+                entry_group_id: input.entry_group_id,
+                exit_group_id: input.exit_group_id,
+                plan_ids: &input.plan_ids,
+            },
+        )
+        .await;
+    let saved_id = match saved {
+        Ok(saved_id) => saved_id,
+        Err(e) => {
+            error!("Failed to save chain profile: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to save chain").into_response();
+        }
+    };
+
+    state
+        .audit_service
+        .record(
+            actor,
+            if id.is_some() {
+                "chain_profile.update"
+            } else {
+                "chain_profile.create"
+            },
+            "chain_profile",
+            saved_id,
+            before.and_then(|c| serde_json::to_value(c).ok()),
+            Some(json!({
+                "name": input.name,
+                "entry_group_id": input.entry_group_id,
+                "exit_group_id": input.exit_group_id,
+                "plan_ids": input.plan_ids,
+            })),
+        )
+        .await;
+    let _ = state.redis.invalidate_subscriptions().await;
+
+    refresh()
+}
+
+/// POST /admin/chains - Create a chain profile
+pub async fn create_chain_profile(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Form(form): Form<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    save(&state, &actor, None, &form).await
+}
+
+/// POST /admin/chains/{id} - Update a chain profile and its plans
+pub async fn update_chain_profile(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+    Form(form): Form<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    save(&state, &actor, Some(id), &form).await
+}
+
+/// POST /admin/chains/{id}/delete - Delete a chain profile; subscriptions
+/// drop the chain on their next refresh
+pub async fn delete_chain_profile(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let before = state.chain_profile_service.get(id).await.ok().flatten();
+
+    match state.chain_profile_service.delete(id).await {
+        Ok(true) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "chain_profile.delete",
+                    "chain_profile",
+                    id,
+                    before.and_then(|c| serde_json::to_value(c).ok()),
+                    None,
+                )
+                .await;
+            let _ = state.redis.invalidate_subscriptions().await;
+            refresh()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "Chain not found").into_response(),
+        Err(e) => {
+            error!("Failed to delete chain profile {}: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete chain").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_form;
+
+    fn form(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn form_is_validated_before_saving() {
+        let parsed = parse_form(&form(&[
+            ("name", " Via Riga "),
+            ("entry_group_id", "3"),
+            ("exit_group_id", "4"),
+            ("plan_ids", "2"),
+            ("plan_ids", "2"),
+            ("plan_ids", "5"),
+        ]))
+        .unwrap();
+        assert_eq!(parsed.name, "Via Riga");
+        assert_eq!(parsed.plan_ids, vec![2, 5]);
+
+        let same = parse_form(&form(&[
+            ("name", "Loop"),
+            ("entry_group_id", "3"),
+            ("exit_group_id", "3"),
+        ]));
+        assert_eq!(same.unwrap_err(), "Entry and exit groups must differ");
+
+        assert!(parse_form(&form(&[("name", "No groups"), ("entry_group_id", "3")])).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+// Change Plans Module
+// Dry-run previews of template sync, inbound rotation and inbound reset
+
+use askama::Template;
+use askama_web::WebTemplate;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+};
+use tracing::error;
+
+use crate::AppState;
+use crate::services::change_plan::{ChangePlan, NodePlan};
+
+#[derive(Template, WebTemplate)]
+#[template(path = "partials/change_plan.html")]
+pub struct ChangePlanTemplate {
+    pub plan: ChangePlan,
+}
+
+fn render(title: String, result: anyhow::Result<Vec<NodePlan>>) -> axum::response::Response {
+    let nodes = match result {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            error!("Failed to build change plan '{}': {}", title, e);
+            return (StatusCode::BAD_REQUEST, format!("Preview failed: {}", e)).into_response();
+        }
+    };
+
+    let template = ChangePlanTemplate {
+        plan: ChangePlan { title, nodes },
+    };
+    match template.render() {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Template error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn group_name(state: &AppState, group_id: i64) -> Option<String> {
+    sqlx::query_scalar("SELECT name FROM node_groups WHERE id = $1")
+        .bind(group_id)
+        .fetch_optional(&state.pool)
+        .await
+        .ok()
+        .flatten()
+}
+
+/// GET /admin/groups/{id}/sync/preview - What syncing the group's templates would change
+pub async fn preview_group_sync(
+    State(state): State<AppState>,
+    Path(group_id): Path<i64>,
+) -> impl IntoResponse {
+    let Some(name) = group_name(&state, group_id).await else {
+        return (StatusCode::BAD_REQUEST, "Group not found").into_response();
+    };
+    render(
+        format!("Sync templates to {}", name),
+        state.generator_service.plan_group_sync(group_id).await,
+    )
+}
+
+/// GET /admin/groups/{id}/rotate/preview - What rotating the group's inbounds would change
+pub async fn preview_group_rotation(
+    State(state): State<AppState>,
+    Path(group_id): Path<i64>,
+) -> impl IntoResponse {
+    let Some(name) = group_name(&state, group_id).await else {
+        return (StatusCode::BAD_REQUEST, "Group not found").into_response();
+    };
+    render(
+        format!("Rotate inbounds of {}", name),
+        state.generator_service.plan_group_rotation(group_id).await,
+    )
+}
+
+/// GET /admin/templates/{id}/sync/preview - What syncing a template's target group would change
+pub async fn preview_template_sync(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let group_id: Option<i64> =
+        sqlx::query_scalar("SELECT target_group_id FROM inbound_templates WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&state.pool)
+            .await
+            .ok()
+            .flatten();
+    let Some(group_id) = group_id else {
+        return (StatusCode::BAD_REQUEST, "Template has no target group").into_response();
+    };
+    preview_group_sync(State(state), Path(group_id))
+        .await
+        .into_response()
+}
+
+/// GET /admin/nodes/{id}/reset/preview - What recreating the node's inbounds would change
+pub async fn preview_node_reset(
+    State(state): State<AppState>,
+    Path(node_id): Path<i64>,
+) -> impl IntoResponse {
+    let result = state
+        .orchestration_service
+        .plan_reset_inbounds(node_id)
+        .await
+        .map(|node| vec![node]);
+    let title = match &result {
+        Ok(nodes) => format!("Reset inbounds of {}", nodes[0].node_name),
+        Err(_) => format!("Reset inbounds of node {}", node_id),
+    };
+    render(title, result)
+}
@@ -1,23 +1,49 @@
 // Admin Module - Modular Structure
+pub mod admins;
 pub mod analytics;
 pub mod api_keys;
+pub mod api_tokens;
+pub mod audit;
 pub mod auth;
+pub mod chain_profiles;
+pub mod change_plans;
 pub mod dashboard;
 pub mod frontends;
+pub mod node_configs;
 pub mod nodes;
 pub mod plans;
 pub mod promo;
+pub mod rbac;
+pub mod rollouts;
+pub mod security;
 pub mod settings;
 pub mod store;
+pub mod sub_templates;
 pub mod updates;
 pub mod users;
+pub mod webhooks;
 
 // Re-export commonly used functions for convenience
+pub use admins::{create_admin_account, delete_admin_account, get_admins_page, update_admin_role};
 pub use analytics::{get_system_logs_page, get_traffic_analytics, get_transactions};
 pub use api_keys::{create_api_key, delete_api_key, list_api_keys};
-pub use auth::{get_auth_user, get_login, is_authenticated, login, logout};
+pub use api_tokens::{create_api_token, get_api_tokens_page, revoke_api_token};
+pub use audit::{export_audit_log, get_audit_page};
+pub use auth::{
+    get_auth_admin, get_auth_user, get_login, is_authenticated, login, login_2fa, logout,
+};
+pub use chain_profiles::{
+    create_chain_profile, delete_chain_profile, get_chain_profile_edit, get_chain_profiles_page,
+    update_chain_profile,
+};
+pub use change_plans::{
+    preview_group_rotation, preview_group_sync, preview_node_reset, preview_template_sync,
+};
 pub use dashboard::{get_dashboard, get_statusbar};
 pub use frontends::get_frontends;
+pub use node_configs::{
+    get_node_config_diff, get_node_config_version, pin_node_config, unpin_node_config,
+};
 pub use nodes::{
     activate_node, block_sni, delete_node, get_install_sh, get_node_edit, get_node_install_script,
     get_node_logs, get_node_manage, get_node_raw_install_script, get_node_rescue, get_nodes,
@@ -25,6 +51,11 @@ pub use nodes::{
 };
 pub use plans::{add_plan, delete_plan, get_plan_edit, get_plans, update_plan};
 pub use promo::{add_promo, delete_promo, get_promos};
+pub use rollouts::{continue_rollout, get_rollouts_page, rollback_rollout, start_rollout};
+pub use security::{
+    disable_totp, enable_totp, get_security_page, regenerate_recovery_codes, reset_admin_security,
+    revoke_other_sessions, revoke_session, setup_totp,
+};
 pub use settings::{
     apply_deployment_topology, bot_logs_history, bot_logs_page, bot_logs_tail, check_update,
     export_database, get_settings, prepare_agent_update, queue_worker_update, rollout_agent_update,
@@ -34,11 +65,19 @@ pub use store::{
     create_category, create_product, delete_category, delete_product, get_store_categories_page,
     get_store_products_page,
 };
+pub use sub_templates::{
+    create_sub_template, delete_sub_template, get_sub_template_edit, get_sub_templates_page,
+    preview_sub_template, update_sub_template,
+};
 pub use users::{
     admin_gift_subscription, admin_kill_subscription_sessions, delete_user_subscription,
     extend_user_subscription, get_subscription_devices, get_user_details, get_users,
-    notify_all_users, notify_preview, notify_user, refund_user_subscription, update_user,
-    update_user_balance,
+    notify_all_users, notify_preview, notify_user, refund_user_subscription,
+    rotate_user_subscription_link, update_user, update_user_balance,
+};
+pub use webhooks::{
+    create_webhook, delete_webhook, get_webhooks_page, retry_webhook_delivery,
+    rotate_webhook_secret, test_webhook, toggle_webhook,
 };
 
 // Stubs removed
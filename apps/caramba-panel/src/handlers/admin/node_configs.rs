@@ -0,0 +1,207 @@
+// Node Configs Module
+// Generated config history per node: view, diff and pin a version
+
+use askama::Template;
+use askama_web::WebTemplate;
+use axum::{
+    Extension,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::error;
+
+use crate::AppState;
+use crate::services::audit_service::AuditActor;
+use crate::services::config_version_service::{DiffLine, diff_lines};
+use caramba_db::models::node_config::NodeConfigVersion;
+
+/// Unchanged lines shown around each change in a diff.
+const DIFF_CONTEXT: usize = 4;
+
+#[derive(Template, WebTemplate)]
+#[template(path = "partials/node_config_version.html")]
+pub struct NodeConfigVersionTemplate {
+    pub version: NodeConfigVersion,
+    pub config: String,
+    pub is_pinned: bool,
+    pub admin_path: String,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "partials/node_config_diff.html")]
+pub struct NodeConfigDiffTemplate {
+    pub from: NodeConfigVersion,
+    pub to: NodeConfigVersion,
+    pub lines: Vec<DiffLine>,
+    pub added: usize,
+    pub removed: usize,
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    pub from: i32,
+    pub to: i32,
+}
+
+fn render(template: impl Template) -> axum::response::Response {
+    match template.render() {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Template error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn load_version(
+    state: &AppState,
+    node_id: i64,
+    version: i32,
+) -> Result<NodeConfigVersion, axum::response::Response> {
+    match state.config_version_service.get(node_id, version).await {
+        Ok(Some(v)) => Ok(v),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Config version not found").into_response()),
+        Err(e) => {
+            error!(
+                "Failed to load config v{} of node {}: {}",
+                version, node_id, e
+            );
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load config version",
+            )
+                .into_response())
+        }
+    }
+}
+
+async fn pinned_version(state: &AppState, node_id: i64) -> Option<i32> {
+    state
+        .config_version_service
+        .pinned(node_id)
+        .await
+        .ok()
+        .flatten()
+        .map(|v| v.version)
+}
+
+/// GET /admin/nodes/{id}/config/versions/{version} - One stored config
+pub async fn get_node_config_version(
+    Path((id, version)): Path<(i64, i32)>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let version = match load_version(&state, id, version).await {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+
+    render(NodeConfigVersionTemplate {
+        config: version.config_pretty(),
+        is_pinned: pinned_version(&state, id).await == Some(version.version),
+        version,
+        admin_path: state.admin_path.clone(),
+    })
+}
+
+/// GET /admin/nodes/{id}/config/diff?from=&to= - Line diff between two stored configs
+pub async fn get_node_config_diff(
+    Path(id): Path<i64>,
+    State(state): State<AppState>,
+    Query(query): Query<DiffQuery>,
+) -> impl IntoResponse {
+    let from = match load_version(&state, id, query.from).await {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+    let to = match load_version(&state, id, query.to).await {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+
+    let lines = diff_lines(&from.config_pretty(), &to.config_pretty(), DIFF_CONTEXT);
+    render(NodeConfigDiffTemplate {
+        added: lines.iter().filter(|l| l.is_added()).count(),
+        removed: lines.iter().filter(|l| l.is_removed()).count(),
+        lines,
+        from,
+        to,
+    })
+}
+
+/// POST /admin/nodes/{id}/config/versions/{version}/pin - Serve this version to the node until unpinned
+pub async fn pin_node_config(
+    Path((id, version)): Path<(i64, i32)>,
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+) -> impl IntoResponse {
+    let before = pinned_version(&state, id).await;
+
+    match state.config_version_service.pin(id, version).await {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::NOT_FOUND, "Config version not found").into_response(),
+        Err(e) => {
+            error!("Failed to pin node {} to config v{}: {}", id, version, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to pin config").into_response();
+        }
+    }
+
+    state
+        .audit_service
+        .record(
+            &actor,
+            "node.config_pin",
+            "node",
+            id,
+            Some(json!({ "pinned_config_version": before })),
+            Some(json!({ "pinned_config_version": version })),
+        )
+        .await;
+
+    if let Err(e) = state.orchestration_service.notify_node_update(id).await {
+        error!("Failed to notify node {} after pinning config: {}", id, e);
+    }
+
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("HX-Refresh", "true".parse().unwrap());
+    (StatusCode::OK, headers, "").into_response()
+}
+
+/// POST /admin/nodes/{id}/config/unpin - Go back to serving freshly generated configs
+pub async fn unpin_node_config(
+    Path(id): Path<i64>,
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+) -> impl IntoResponse {
+    let before = pinned_version(&state, id).await;
+
+    if let Err(e) = state.config_version_service.unpin(id).await {
+        error!("Failed to unpin config of node {}: {}", id, e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to unpin config").into_response();
+    }
+
+    if before.is_some() {
+        state
+            .audit_service
+            .record(
+                &actor,
+                "node.config_unpin",
+                "node",
+                id,
+                Some(json!({ "pinned_config_version": before })),
+                Some(json!({ "pinned_config_version": null })),
+            )
+            .await;
+    }
+
+    if let Err(e) = state.orchestration_service.notify_node_update(id).await {
+        error!("Failed to notify node {} after unpinning config: {}", id, e);
+    }
+
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("HX-Refresh", "true".parse().unwrap());
+    (StatusCode::OK, headers, "").into_response()
+}
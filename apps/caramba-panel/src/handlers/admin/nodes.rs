@@ -4,6 +4,7 @@
 use askama::Template;
 use askama_web::WebTemplate;
 use axum::{
+    Extension,
     extract::{Form, Path, Query, State},
     http::HeaderMap,
     response::{Html, IntoResponse},
@@ -14,10 +15,27 @@ use tracing::{error, info};
 
 use super::auth::get_auth_user;
 use crate::AppState;
+use crate::services::audit_service::AuditActor;
 use caramba_db::models::node::Node;
 use chrono::Utc;
 use uuid::Uuid;
 
+/// Admin-editable node fields for the audit trail (telemetry left out so
+/// heartbeats between the two reads do not show up as changes).
+pub(crate) fn node_audit_snapshot(node: &Node) -> serde_json::Value {
+    serde_json::json!({
+        "name": node.name,
+        "ip": node.ip,
+        "is_enabled": node.is_enabled,
+        "is_relay": node.is_relay,
+        "relay_id": node.relay_id,
+        "config_block_torrent": node.config_block_torrent,
+        "config_block_ads": node.config_block_ads,
+        "config_block_porn": node.config_block_porn,
+        "config_qos_enabled": node.config_qos_enabled,
+    })
+}
+
 // ============================================================================
 // Templates
 // ============================================================================
@@ -73,8 +91,15 @@ pub struct NodeManageTemplate {
     pub is_auth: bool,
     pub inbounds: Vec<caramba_db::models::network::Inbound>,
     pub discovered_snis: Vec<NodeSniDisplay>,
+    pub config_versions: Vec<caramba_db::models::node_config::NodeConfigVersionSummary>,
+    /// Pinned config version, 0 when not pinned.
+    pub pinned_config_version: i32,
+    pub config_failure: Option<caramba_db::models::node_config::NodeConfigFailure>,
 }
 
+/// Config versions listed on the node page.
+const CONFIG_HISTORY_LIMIT: i64 = 30;
+
 #[derive(sqlx::FromRow, serde::Serialize)]
 pub struct NodeSniDisplay {
     pub id: i64,
@@ -328,10 +353,12 @@ pub async fn get_node_edit(
 pub async fn update_node(
     Path(id): Path<i64>,
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
     Form(form): Form<UpdateNodeForm>,
 ) -> impl IntoResponse {
     let is_relay = form.is_relay.is_some();
     info!("Updating node ID: {} (Relay: {})", id, is_relay);
+    let before = state.infrastructure_service.get_node_by_id(id).await.ok();
 
     // 1. Update core fields
     if let Err(e) = state
@@ -364,6 +391,19 @@ pub async fn update_node(
         .execute(&state.pool)
         .await;
 
+    let after = state.infrastructure_service.get_node_by_id(id).await.ok();
+    state
+        .audit_service
+        .record(
+            &actor,
+            "node.update",
+            "node",
+            id,
+            before.as_ref().map(node_audit_snapshot),
+            after.as_ref().map(node_audit_snapshot),
+        )
+        .await;
+
     // 3. Trigger sync if policies changed (Policy changes need config regent)
     let _ = state.orchestration_service.reset_inbounds(id).await;
     let _ = state
@@ -393,21 +433,32 @@ pub async fn update_node(
     (axum::http::StatusCode::OK, headers, "Updated").into_response()
 }
 
-pub async fn sync_node(Path(id): Path<i64>, State(state): State<AppState>) -> impl IntoResponse {
+pub async fn sync_node(
+    Path(id): Path<i64>,
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+) -> impl IntoResponse {
     info!("Manual sync triggered for node: {}", id);
+    spawn_node_sync(&state, id, "Manual sync", &actor.username).await;
+    axum::http::StatusCode::ACCEPTED
+}
 
+/// Regenerate a node's config in the background and ask the agent to pull it.
+pub(crate) async fn spawn_node_sync(state: &AppState, id: i64, reason: &str, author: &str) {
     // Update trigger tracking
-    let _ = sqlx::query("UPDATE nodes SET last_sync_trigger = 'Manual Update' WHERE id = $1")
+    let _ = sqlx::query("UPDATE nodes SET last_sync_trigger = $1 WHERE id = $2")
+        .bind(reason)
         .bind(id)
         .execute(&state.pool)
         .await;
 
     let orch = state.orchestration_service.clone();
     let pubsub = state.pubsub.clone();
+    let (reason, author) = (reason.to_string(), author.to_string());
 
     tokio::spawn(async move {
         // Sync should be non-destructive: keep chosen/manual inbounds and only regenerate effective config.
-        if let Err(e) = orch.generate_node_config_json(id).await {
+        if let Err(e) = orch.generate_node_config_json(id, &reason, &author).await {
             error!(
                 "Failed to generate config for node {} during manual sync: {}",
                 id, e
@@ -424,8 +475,6 @@ pub async fn sync_node(Path(id): Path<i64>, State(state): State<AppState>) -> im
             info!("Successfully requested config pull for node {}", id);
         }
     });
-
-    axum::http::StatusCode::ACCEPTED
 }
 
 pub async fn test_node_connection(
@@ -461,12 +510,28 @@ pub async fn test_node_connection(
     }
 }
 
-pub async fn delete_node(State(state): State<AppState>, Path(id): Path<i64>) -> impl IntoResponse {
+pub async fn delete_node(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
     info!("Request to delete node ID: {}", id);
+    let before = state.infrastructure_service.get_node_by_id(id).await.ok();
 
     match state.infrastructure_service.delete_node(id).await {
         Ok(_) => {
             info!("Node {} deleted successfully", id);
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "node.delete",
+                    "node",
+                    id,
+                    before.as_ref().map(node_audit_snapshot),
+                    None,
+                )
+                .await;
             (
                 axum::http::StatusCode::OK,
                 [("HX-Trigger", "refresh_nodes")],
@@ -905,6 +970,26 @@ pub async fn get_node_manage(
         }
     };
 
+    let config_versions = state
+        .config_version_service
+        .list(id, CONFIG_HISTORY_LIMIT)
+        .await
+        .unwrap_or_default();
+    let pinned_config_version = state
+        .config_version_service
+        .pinned(id)
+        .await
+        .ok()
+        .flatten()
+        .map(|v| v.version)
+        .unwrap_or_default();
+    let config_failure = state
+        .config_version_service
+        .failure(id)
+        .await
+        .ok()
+        .flatten();
+
     let admin_path = state.admin_path.clone();
     let username = get_auth_user(&state, &jar)
         .await
@@ -919,6 +1004,9 @@ pub async fn get_node_manage(
         is_auth: true,
         inbounds,
         discovered_snis,
+        config_versions,
+        pinned_config_version,
+        config_failure,
     };
 
     Html(template.render().unwrap()).into_response()
@@ -1130,10 +1218,11 @@ pub async fn rotate_node_inbounds(
 pub async fn get_node_config_preview(
     Path(id): Path<i64>,
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
 ) -> impl IntoResponse {
     match state
         .orchestration_service
-        .generate_node_config_json(id)
+        .generate_node_config_json(id, "Preview", &actor.username)
         .await
     {
         Ok((_, config)) => {
@@ -4,6 +4,7 @@
 use askama::Template;
 use askama_web::WebTemplate;
 use axum::{
+    Extension,
     extract::{Form, Path, State},
     http::HeaderMap,
     response::{Html, IntoResponse},
@@ -13,6 +14,7 @@ use axum_extra::extract::cookie::CookieJar;
 use tracing::{error, info};
 
 use crate::AppState;
+use crate::services::audit_service::AuditActor;
 use caramba_db::models::store::Plan;
 // use caramba_db::models::node::Node; // Removed
 
@@ -189,7 +191,11 @@ pub async fn add_plan(
     (axum::http::StatusCode::OK, headers, "Plan Created").into_response()
 }
 
-pub async fn delete_plan(Path(id): Path<i64>, State(state): State<AppState>) -> impl IntoResponse {
+pub async fn delete_plan(
+    Path(id): Path<i64>,
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+) -> impl IntoResponse {
     info!("Request to delete plan: {}", id);
 
     let is_trial = state
@@ -206,8 +212,24 @@ pub async fn delete_plan(Path(id): Path<i64>, State(state): State<AppState>) ->
             .into_response();
     }
 
+    let before = state
+        .catalog_service
+        .get_plan_by_id(id)
+        .await
+        .unwrap_or(None);
     match state.catalog_service.delete_plan_and_refund(id).await {
         Ok((refunded_users, total_refunded_cents)) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "plan.delete",
+                    "plan",
+                    id,
+                    before.as_ref().and_then(|p| serde_json::to_value(p).ok()),
+                    None,
+                )
+                .await;
             info!(
                 "Plan {} deleted. Refunded {} users (Total: ${:.2})",
                 id,
@@ -278,6 +300,7 @@ pub async fn get_plan_edit(
 
 pub async fn update_plan(
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
     Path(id): Path<i64>,
     Form(raw_form): Form<Vec<(String, String)>>,
 ) -> impl IntoResponse {
@@ -335,6 +358,11 @@ pub async fn update_plan(
             .into_response();
     }
 
+    let before = state
+        .catalog_service
+        .get_plan_by_id(id)
+        .await
+        .unwrap_or(None);
     if let Err(e) = state
         .catalog_service
         .update_plan(
@@ -357,6 +385,25 @@ pub async fn update_plan(
             .into_response();
     }
 
+    let after = state
+        .catalog_service
+        .get_plan_by_id(id)
+        .await
+        .unwrap_or(None);
+    state
+        .audit_service
+        .record(
+            &actor,
+            "plan.update",
+            "plan",
+            id,
+            before.as_ref().and_then(|p| serde_json::to_value(p).ok()),
+            after.as_ref().and_then(|p| serde_json::to_value(p).ok()),
+        )
+        .await;
+    // The plan's node groups decide which nodes its subscriptions list.
+    let _ = state.redis.invalidate_subscriptions().await;
+
     let _ = crate::services::activity_service::ActivityService::log(
         &state.pool,
         "Plan",
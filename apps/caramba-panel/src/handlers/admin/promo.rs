@@ -146,11 +146,7 @@ pub async fn add_promo(
 }
 
 pub async fn delete_promo(Path(id): Path<i64>, State(state): State<AppState>) -> impl IntoResponse {
-    match sqlx::query("DELETE FROM promo_codes WHERE id = $1")
-        .bind(id)
-        .execute(&state.pool)
-        .await
-    {
+    match state.promo_service.delete_promo(id).await {
         Ok(_) => {
             let mut headers = axum::http::HeaderMap::new();
             headers.insert("HX-Refresh", "true".parse().unwrap());
@@ -0,0 +1,270 @@
+// Role-Based Access Control
+// Maps admin routes to the permission they require and renders denials
+
+use axum::{
+    Json,
+    http::{HeaderMap, Method, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use caramba_db::models::admin::{AdminRole, Permission};
+
+/// Resolve the permission required for an admin route.
+///
+/// `path` may be relative to the admin prefix (`/nodes/1/edit`), carry the
+/// admin prefix itself, or be one of the JSON admin endpoints under
+/// `/api/admin` / `/caramba-api/admin`. Unknown routes require
+/// `SettingsManage` so that new pages are superadmin-only until mapped.
+pub fn required_permission(method: &Method, path: &str, admin_path: &str) -> Permission {
+    let mut rel = path;
+    for prefix in [admin_path, "/api/admin", "/caramba-api/admin"] {
+        if !prefix.is_empty()
+            && prefix != "/"
+            && let Some(stripped) = rel.strip_prefix(prefix)
+        {
+            rel = stripped;
+            break;
+        }
+    }
+
+    let segments: Vec<&str> = rel.split('/').filter(|s| !s.is_empty()).collect();
+    let write = !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+    let pick = |read: Permission, write_perm: Permission| if write { write_perm } else { read };
+
+    match segments.as_slice() {
+        [] | ["dashboard", ..] | ["partials", "statusbar"] | ["logout"] | ["assets", ..] => {
+            Permission::Dashboard
+        }
+        // Every admin manages their own 2FA, sessions and API tokens.
+        ["security", ..] | ["api-tokens", ..] => Permission::Dashboard,
+
+        // Money-moving user actions are finance, not support.
+        ["users", _, "balance"] | ["users", "subs", _, "refund"] => Permission::FinanceWrite,
+        ["users", "notify", ..] => Permission::Broadcast,
+        ["users", ..] | ["subs", ..] => pick(Permission::UsersRead, Permission::UsersWrite),
+
+        // A template preview renders a real subscription's credentials.
+        ["sub-templates", "preview"] => Permission::UsersRead,
+        ["nodes", ..]
+        | ["groups", ..]
+        | ["templates", ..]
+        | ["sub-templates", ..]
+        | ["chains", ..]
+        | ["rollouts", ..]
+        | ["sni", ..]
+        | ["frontends", ..]
+        | ["api-keys", ..]
+        | ["partials", "nodes_rows"]
+        | ["partials", "frontends_rows"] => pick(Permission::NodesRead, Permission::NodesWrite),
+
+        ["plans", ..]
+        | ["promo", ..]
+        | ["store", ..]
+        | ["orgs", ..]
+        | ["transactions", ..]
+        | ["analytics", ..] => pick(Permission::FinanceRead, Permission::FinanceWrite),
+
+        ["logs", ..] | ["bot-logs", ..] => Permission::LogsRead,
+        // The audit trail shows every admin's changes, superadmins only.
+        ["audit", ..] => Permission::AuditRead,
+
+        ["admins", ..] => Permission::AdminsManage,
+        // Endpoints receive payment and user data, superadmins only.
+        ["webhooks", ..] => Permission::SettingsManage,
+
+        _ => Permission::SettingsManage,
+    }
+}
+
+/// Response for an authenticated admin whose role lacks `permission`.
+pub fn forbidden_response(
+    headers: &HeaderMap,
+    path: &str,
+    role: AdminRole,
+    permission: Permission,
+) -> Response {
+    let is_json = path.starts_with("/api/")
+        || path.starts_with("/caramba-api/")
+        || headers
+            .get(axum::http::header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("application/json"));
+
+    if is_json {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "error": "forbidden",
+                "role": role.as_str(),
+                "required_permission": permission.as_str(),
+            })),
+        )
+            .into_response();
+    }
+
+    let body = format!(
+        "<div class='text-red-400 text-sm font-medium p-4'>Access denied: your role ({}) does not have the <code>{}</code> permission.</div>",
+        role.label(),
+        permission.as_str()
+    );
+    (StatusCode::FORBIDDEN, Html(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::required_permission;
+    use axum::http::Method;
+    use caramba_db::models::admin::{AdminRole, Permission};
+
+    #[test]
+    fn maps_node_routes_by_method() {
+        assert_eq!(
+            required_permission(&Method::GET, "/nodes", "/admin"),
+            Permission::NodesRead
+        );
+        assert_eq!(
+            required_permission(&Method::DELETE, "/nodes/3/delete", "/admin"),
+            Permission::NodesWrite
+        );
+    }
+
+    #[test]
+    fn rollouts_are_node_operations() {
+        assert_eq!(
+            required_permission(&Method::GET, "/rollouts", "/admin"),
+            Permission::NodesRead
+        );
+        assert_eq!(
+            required_permission(&Method::POST, "/rollouts/4/rollback", "/admin"),
+            Permission::NodesWrite
+        );
+    }
+
+    #[test]
+    fn change_previews_only_need_read_access() {
+        for path in [
+            "/admin/groups/2/sync/preview",
+            "/admin/groups/2/rotate/preview",
+            "/admin/templates/5/sync/preview",
+            "/admin/nodes/3/reset/preview",
+        ] {
+            assert_eq!(
+                required_permission(&Method::GET, path, "/admin"),
+                Permission::NodesRead
+            );
+        }
+    }
+
+    #[test]
+    fn subscription_templates_are_node_operations() {
+        assert_eq!(
+            required_permission(&Method::POST, "/sub-templates/preview", "/admin"),
+            Permission::UsersRead
+        );
+        assert_eq!(
+            required_permission(&Method::POST, "/sub-templates/4/delete", "/admin"),
+            Permission::NodesWrite
+        );
+        assert_eq!(
+            required_permission(&Method::GET, "/chains/2", "/admin"),
+            Permission::NodesRead
+        );
+    }
+
+    #[test]
+    fn refunds_and_balance_require_finance() {
+        assert_eq!(
+            required_permission(&Method::POST, "/users/subs/9/refund", "/admin"),
+            Permission::FinanceWrite
+        );
+        assert_eq!(
+            required_permission(&Method::POST, "/users/9/balance", "/admin"),
+            Permission::FinanceWrite
+        );
+        assert_eq!(
+            required_permission(&Method::POST, "/users/9/update", "/admin"),
+            Permission::UsersWrite
+        );
+    }
+
+    #[test]
+    fn strips_admin_prefix_and_json_api_prefix() {
+        assert_eq!(
+            required_permission(
+                &Method::POST,
+                "/secret-panel/settings/save",
+                "/secret-panel"
+            ),
+            Permission::SettingsManage
+        );
+        assert_eq!(
+            required_permission(&Method::DELETE, "/api/admin/frontends/4", "/admin"),
+            Permission::NodesWrite
+        );
+    }
+
+    #[test]
+    fn own_security_page_is_open_to_every_role_but_resets_are_not() {
+        let perm = required_permission(&Method::POST, "/security/2fa/setup", "/admin");
+        assert!(AdminRole::ReadOnly.allows(perm));
+        let reset = required_permission(&Method::POST, "/admins/3/security/reset", "/admin");
+        assert!(!AdminRole::Operator.allows(reset));
+    }
+
+    #[test]
+    fn own_api_tokens_are_open_to_every_role() {
+        let perm = required_permission(&Method::POST, "/api-tokens/7/revoke", "/admin");
+        assert!(AdminRole::ReadOnly.allows(perm));
+        // Enrollment keys stay with node management.
+        let keys = required_permission(&Method::POST, "/api-keys", "/admin");
+        assert_eq!(keys, Permission::NodesWrite);
+    }
+
+    #[test]
+    fn webhooks_are_superadmin_only() {
+        let perm = required_permission(&Method::GET, "/webhooks", "/admin");
+        assert_eq!(perm, Permission::SettingsManage);
+        assert!(!AdminRole::Operator.allows(perm));
+    }
+
+    #[test]
+    fn audit_trail_is_superadmin_only() {
+        let perm = required_permission(&Method::GET, "/audit/export", "/admin");
+        assert_eq!(perm, Permission::AuditRead);
+        assert!(AdminRole::Superadmin.allows(perm));
+        assert!(
+            AdminRole::ALL
+                .iter()
+                .filter(|r| **r != AdminRole::Superadmin)
+                .all(|r| !r.allows(perm))
+        );
+    }
+
+    #[test]
+    fn unknown_routes_default_to_superadmin_only() {
+        let perm = required_permission(&Method::GET, "/something-new", "/admin");
+        assert_eq!(perm, Permission::SettingsManage);
+        assert!(!AdminRole::Operator.allows(perm));
+        assert!(AdminRole::Superadmin.allows(perm));
+    }
+
+    #[test]
+    fn support_can_look_up_users_but_not_wipe_nodes_or_refund() {
+        let support = AdminRole::Support;
+        assert!(support.allows(required_permission(&Method::GET, "/users/5", "/admin")));
+        assert!(!support.allows(required_permission(
+            &Method::DELETE,
+            "/nodes/5/delete",
+            "/admin"
+        )));
+        assert!(!support.allows(required_permission(
+            &Method::POST,
+            "/users/subs/5/refund",
+            "/admin"
+        )));
+        assert!(!support.allows(required_permission(
+            &Method::POST,
+            "/users/notify/all",
+            "/admin"
+        )));
+    }
+}
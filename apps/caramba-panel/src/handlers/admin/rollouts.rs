@@ -0,0 +1,272 @@
+// Rollouts Module
+// Staged template rollouts: start, watch, approve and roll back
+
+use askama::Template;
+use askama_web::WebTemplate;
+use axum::{
+    Extension,
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+};
+use axum_extra::extract::cookie::CookieJar;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::error;
+
+use super::auth::get_auth_admin;
+use crate::AppState;
+use crate::services::audit_service::AuditActor;
+use crate::services::rollout_service::RolloutOptions;
+use caramba_db::models::rollout::{ConfigRollout, ConfigRolloutNode};
+
+/// Rollouts shown on the page.
+const ROLLOUT_LIST_LIMIT: i64 = 20;
+
+pub struct RolloutView {
+    pub rollout: ConfigRollout,
+    pub nodes: Vec<ConfigRolloutNode>,
+}
+
+pub struct TemplateOption {
+    pub id: i64,
+    pub label: String,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "rollouts.html")]
+pub struct RolloutsTemplate {
+    pub rollouts: Vec<RolloutView>,
+    pub templates: Vec<TemplateOption>,
+    /// Template preselected in the start form, 0 for none.
+    pub selected_template: i64,
+    pub admin_path: String,
+    pub active_page: String,
+    pub is_auth: bool,
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct RolloutsQuery {
+    pub template: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct StartRolloutForm {
+    pub template_id: i64,
+    pub canary_count: i32,
+    pub window_mins: i32,
+    pub auto_continue: Option<String>,
+    pub max_latency_increase_pct: i32,
+    pub min_connection_retention_pct: i32,
+}
+
+fn refresh() -> axum::response::Response {
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("HX-Refresh", "true".parse().unwrap());
+    (StatusCode::OK, headers, "").into_response()
+}
+
+/// GET /admin/rollouts - Start form and recent rollouts with per-node health
+pub async fn get_rollouts_page(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Query(query): Query<RolloutsQuery>,
+) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return axum::response::Redirect::to(&format!("{}/login", state.admin_path))
+            .into_response();
+    };
+
+    let templates: Vec<(i64, String, String)> = sqlx::query_as(
+        "SELECT t.id, t.name, g.name FROM inbound_templates t JOIN node_groups g ON g.id = t.target_group_id ORDER BY g.name, t.name",
+    )
+    .fetch_all(&state.pool)
+    .await
+    .unwrap_or_default();
+
+    let mut rollouts = Vec::new();
+    for rollout in state
+        .rollout_service
+        .list(ROLLOUT_LIST_LIMIT)
+        .await
+        .unwrap_or_default()
+    {
+        let nodes = state
+            .rollout_service
+            .nodes(rollout.id)
+            .await
+            .unwrap_or_default();
+        rollouts.push(RolloutView { rollout, nodes });
+    }
+
+    let template = RolloutsTemplate {
+        rollouts,
+        templates: templates
+            .into_iter()
+            .map(|(id, name, group)| TemplateOption {
+                id,
+                label: format!("{} → {}", name, group),
+            })
+            .collect(),
+        selected_template: query.template.unwrap_or_default(),
+        admin_path: state.admin_path.clone(),
+        active_page: "rollouts".to_string(),
+        is_auth: true,
+        username: admin.username,
+    };
+
+    match template.render() {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Template error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /admin/rollouts - Start a staged rollout of a template's group
+pub async fn start_rollout(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Form(form): Form<StartRolloutForm>,
+) -> impl IntoResponse {
+    let opts = RolloutOptions {
+        canary_count: form.canary_count,
+        window_mins: form.window_mins,
+        auto_continue: form.auto_continue.is_some(),
+        max_latency_increase_pct: form.max_latency_increase_pct,
+        min_connection_retention_pct: form.min_connection_retention_pct,
+    };
+    if let Err(msg) = opts.validate() {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    let group_id: Option<i64> =
+        sqlx::query_scalar("SELECT target_group_id FROM inbound_templates WHERE id = $1")
+            .bind(form.template_id)
+            .fetch_optional(&state.pool)
+            .await
+            .ok()
+            .flatten();
+    let Some(group_id) = group_id else {
+        return (StatusCode::BAD_REQUEST, "Template has no target group").into_response();
+    };
+
+    match state
+        .rollout_service
+        .start_rollout(group_id, Some(form.template_id), &opts, &actor.username)
+        .await
+    {
+        Ok(id) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "rollout.start",
+                    "rollout",
+                    id,
+                    None,
+                    Some(json!({
+                        "group_id": group_id,
+                        "template_id": form.template_id,
+                        "canary_count": opts.canary_count,
+                        "window_mins": opts.window_mins,
+                        "auto_continue": opts.auto_continue,
+                        "max_latency_increase_pct": opts.max_latency_increase_pct,
+                        "min_connection_retention_pct": opts.min_connection_retention_pct,
+                    })),
+                )
+                .await;
+            refresh()
+        }
+        Err(e) => {
+            error!("Failed to start rollout for group {}: {}", group_id, e);
+            (StatusCode::BAD_REQUEST, e.to_string()).into_response()
+        }
+    }
+}
+
+/// POST /admin/rollouts/{id}/continue - Approve the rest of the group after healthy canaries
+pub async fn continue_rollout(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match state
+        .rollout_service
+        .continue_rollout(id, &actor.username)
+        .await
+    {
+        Ok(true) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "rollout.continue",
+                    "rollout",
+                    id,
+                    Some(json!({ "status": "awaiting_approval" })),
+                    Some(json!({ "status": "rolling_out" })),
+                )
+                .await;
+            refresh()
+        }
+        Ok(false) => (StatusCode::BAD_REQUEST, "Rollout is not awaiting approval").into_response(),
+        Err(e) => {
+            error!("Failed to continue rollout {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to continue rollout",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// POST /admin/rollouts/{id}/rollback - Restore every node the rollout touched
+pub async fn rollback_rollout(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let before = state
+        .rollout_service
+        .get(id)
+        .await
+        .ok()
+        .flatten()
+        .map(|r| r.status);
+    let reason = format!("Rolled back by {}", actor.username);
+
+    match state
+        .rollout_service
+        .rollback(id, &reason, &actor.username)
+        .await
+    {
+        Ok(true) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "rollout.rollback",
+                    "rollout",
+                    id,
+                    Some(json!({ "status": before })),
+                    Some(json!({ "status": "rolled_back" })),
+                )
+                .await;
+            refresh()
+        }
+        Ok(false) => (StatusCode::BAD_REQUEST, "Rollout is already finished").into_response(),
+        Err(e) => {
+            error!("Failed to roll back rollout {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to roll back rollout",
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,371 @@
+// Account Security Module
+// Two-factor authentication enrollment and active session management for the signed-in admin
+
+use askama::Template;
+use askama_web::WebTemplate;
+use axum::{
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+};
+use axum_extra::extract::cookie::CookieJar;
+use serde::Deserialize;
+use tracing::error;
+
+use super::auth::get_auth_admin;
+use crate::AppState;
+use crate::services::admin_security_service::totp_uri;
+use crate::services::logging_service::LoggingService;
+use caramba_db::models::admin::AdminSession;
+
+#[derive(Template, WebTemplate)]
+#[template(path = "admin_security.html")]
+pub struct AdminSecurityTemplate {
+    pub totp_enabled: bool,
+    pub recovery_codes_left: i64,
+    pub sessions: Vec<AdminSession>,
+    pub current_session_id: i64,
+    pub admin_path: String,
+    pub active_page: String,
+    pub is_auth: bool,
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct TotpCodeForm {
+    pub code: String,
+}
+
+fn hx_refresh() -> axum::response::Response {
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("HX-Refresh", "true".parse().unwrap());
+    (StatusCode::OK, headers, "").into_response()
+}
+
+fn unauthorized() -> axum::response::Response {
+    (StatusCode::UNAUTHORIZED, "Not signed in").into_response()
+}
+
+fn recovery_codes_html(codes: &[String]) -> String {
+    let items: String = codes
+        .iter()
+        .map(|c| format!("<li class='font-mono text-white'>{}</li>", c))
+        .collect();
+    format!(
+        "<div class='bg-slate-950/60 border border-amber-500/30 rounded-xl p-4 space-y-3'>\
+         <p class='text-amber-400 text-sm font-medium'>Save these recovery codes now. Each works once and they will not be shown again.</p>\
+         <ul class='grid grid-cols-2 gap-2 text-sm'>{}</ul>\
+         <button onclick='location.reload()' class='text-xs text-indigo-400 hover:text-indigo-300'>I have saved them</button>\
+         </div>",
+        items
+    )
+}
+
+/// GET /admin/security - 2FA status and active sessions of the current admin
+pub async fn get_security_page(State(state): State<AppState>, jar: CookieJar) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return axum::response::Redirect::to(&format!("{}/login", state.admin_path))
+            .into_response();
+    };
+
+    let security = &state.admin_security_service;
+    let current_session_id = match jar.get("admin_session") {
+        Some(c) => security.session_id_for_token(c.value()).await.unwrap_or(0),
+        None => 0,
+    };
+
+    let template = AdminSecurityTemplate {
+        totp_enabled: security.is_totp_enabled(admin.id).await,
+        recovery_codes_left: security.remaining_recovery_codes(admin.id).await,
+        sessions: security
+            .list_active_sessions(admin.id)
+            .await
+            .unwrap_or_default(),
+        current_session_id,
+        admin_path: state.admin_path.clone(),
+        active_page: "security".to_string(),
+        is_auth: true,
+        username: admin.username,
+    };
+
+    match template.render() {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Template error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /admin/security/2fa/setup - Generate a new secret pending confirmation
+pub async fn setup_totp(State(state): State<AppState>, jar: CookieJar) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return unauthorized();
+    };
+
+    if state.admin_security_service.is_totp_enabled(admin.id).await {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Two-factor authentication is already enabled",
+        )
+            .into_response();
+    }
+
+    let secret = match state
+        .admin_security_service
+        .begin_totp_setup(admin.id)
+        .await
+    {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to start 2FA setup: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to start setup").into_response();
+        }
+    };
+    let uri = totp_uri("CARAMBA", &admin.username, &secret);
+
+    Html(format!(
+        "<div class='space-y-4'>\
+         <p class='text-sm text-slate-300'>Add this key to your authenticator app (Google Authenticator, Aegis, 1Password…), then enter the 6-digit code it shows.</p>\
+         <div class='bg-slate-950/60 border border-white/10 rounded-xl p-4 font-mono text-white break-all select-all'>{secret}</div>\
+         <a href='{uri}' class='text-xs text-indigo-400 hover:text-indigo-300 break-all'>{uri}</a>\
+         <form hx-post='{path}/security/2fa/enable' hx-target='#twofa-setup' hx-swap='innerHTML' class='flex gap-2'>\
+         <input name='code' required placeholder='123456' autocomplete='one-time-code' class='bg-slate-950 border border-white/10 rounded-lg px-3 py-2 text-white text-sm outline-none focus:border-indigo-500'>\
+         <button class='bg-indigo-600 hover:bg-indigo-500 text-white px-4 py-2 rounded-lg text-sm font-medium'>Enable</button>\
+         </form></div>",
+        secret = secret,
+        uri = uri,
+        path = state.admin_path
+    ))
+    .into_response()
+}
+
+/// POST /admin/security/2fa/enable - Confirm the secret with a code, returns recovery codes
+pub async fn enable_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Form(form): Form<TotpCodeForm>,
+) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return unauthorized();
+    };
+
+    match state
+        .admin_security_service
+        .confirm_totp_setup(admin.id, &form.code)
+        .await
+    {
+        Ok(Some(codes)) => {
+            let _ = LoggingService::log_system(
+                &state.pool,
+                "admin_2fa_enabled",
+                &format!(
+                    "Admin '{}' enabled two-factor authentication",
+                    admin.username
+                ),
+            )
+            .await;
+            Html(recovery_codes_html(&codes)).into_response()
+        }
+        Ok(None) => (StatusCode::BAD_REQUEST, "Invalid code, try again").into_response(),
+        Err(e) => {
+            error!("Failed to enable 2FA: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to enable 2FA").into_response()
+        }
+    }
+}
+
+/// POST /admin/security/2fa/disable - Requires a current code
+pub async fn disable_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Form(form): Form<TotpCodeForm>,
+) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return unauthorized();
+    };
+
+    let security = &state.admin_security_service;
+    if !security
+        .verify_current_totp(admin.id, &form.code)
+        .await
+        .unwrap_or(false)
+    {
+        return (StatusCode::BAD_REQUEST, "Invalid code").into_response();
+    }
+
+    if let Err(e) = security.disable_totp(admin.id).await {
+        error!("Failed to disable 2FA: {}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to disable 2FA").into_response();
+    }
+
+    let _ = LoggingService::log_system(
+        &state.pool,
+        "admin_2fa_disabled",
+        &format!(
+            "Admin '{}' disabled two-factor authentication",
+            admin.username
+        ),
+    )
+    .await;
+    hx_refresh()
+}
+
+/// POST /admin/security/recovery-codes - Replace recovery codes, requires a current code
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Form(form): Form<TotpCodeForm>,
+) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return unauthorized();
+    };
+
+    let security = &state.admin_security_service;
+    if !security
+        .verify_current_totp(admin.id, &form.code)
+        .await
+        .unwrap_or(false)
+    {
+        return (StatusCode::BAD_REQUEST, "Invalid code").into_response();
+    }
+
+    match security.regenerate_recovery_codes(admin.id).await {
+        Ok(codes) => {
+            let _ = LoggingService::log_system(
+                &state.pool,
+                "admin_recovery_codes_regenerated",
+                &format!("Admin '{}' regenerated 2FA recovery codes", admin.username),
+            )
+            .await;
+            Html(recovery_codes_html(&codes)).into_response()
+        }
+        Err(e) => {
+            error!("Failed to regenerate recovery codes: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to regenerate codes",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// POST /admin/security/sessions/{id}/revoke
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return unauthorized();
+    };
+
+    match state
+        .admin_security_service
+        .revoke_session(admin.id, id)
+        .await
+    {
+        Ok(true) => {
+            let _ = LoggingService::log_system(
+                &state.pool,
+                "admin_session_revoked",
+                &format!("Admin '{}' revoked session #{}", admin.username, id),
+            )
+            .await;
+            hx_refresh()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => {
+            error!("Failed to revoke session: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to revoke session",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// POST /admin/security/sessions/revoke-others - Sign out everywhere except here
+pub async fn revoke_other_sessions(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return unauthorized();
+    };
+
+    let security = &state.admin_security_service;
+    let current = match jar.get("admin_session") {
+        Some(c) => security.session_id_for_token(c.value()).await,
+        None => None,
+    };
+
+    match security.revoke_all_sessions(admin.id, current).await {
+        Ok(count) => {
+            let _ = LoggingService::log_system(
+                &state.pool,
+                "admin_sessions_revoked",
+                &format!(
+                    "Admin '{}' signed out {} other session(s)",
+                    admin.username, count
+                ),
+            )
+            .await;
+            hx_refresh()
+        }
+        Err(e) => {
+            error!("Failed to revoke sessions: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to revoke sessions",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// POST /admin/admins/{id}/security/reset - Superadmin recovery for a locked-out admin:
+/// disables 2FA, clears the lockout and signs out all sessions.
+pub async fn reset_admin_security(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let Some(current) = get_auth_admin(&state, &jar).await else {
+        return unauthorized();
+    };
+    if current.id == id {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Use the Security page to manage your own account",
+        )
+            .into_response();
+    }
+
+    let security = &state.admin_security_service;
+    let result = async {
+        security.disable_totp(id).await?;
+        security.clear_lockout(id).await?;
+        security.revoke_all_sessions(id, None).await
+    }
+    .await;
+
+    if let Err(e) = result {
+        error!("Failed to reset admin security: {}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to reset 2FA").into_response();
+    }
+
+    let _ = LoggingService::log_system(
+        &state.pool,
+        "admin_security_reset",
+        &format!(
+            "Admin '{}' reset 2FA and sessions of admin #{}",
+            current.username, id
+        ),
+    )
+    .await;
+    hx_refresh()
+}
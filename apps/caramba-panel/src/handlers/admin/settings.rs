@@ -4,6 +4,7 @@
 use askama::Template;
 use askama_web::WebTemplate;
 use axum::{
+    Extension,
     extract::{Form, State},
     http::{StatusCode, header},
     response::{Html, IntoResponse},
@@ -20,6 +21,7 @@ use tracing::{error, info};
 use uuid::Uuid;
 
 use crate::AppState;
+use crate::services::audit_service::AuditActor;
 
 use super::auth::{get_auth_user, is_authenticated};
 
@@ -308,6 +310,8 @@ pub struct SettingsTemplate {
     pub internal_api_token_source: String,
     pub bot_username: String,
     pub brand_name: String,
+    pub sub_update_interval: String,
+    pub sub_link_grace_hours: String,
     pub terms_of_service: String,
     pub decoy_enabled: bool,
     pub decoy_urls: String,
@@ -448,6 +452,8 @@ pub struct SaveSettingsForm {
     pub panel_url: Option<String>,
     pub bot_username: Option<String>,
     pub brand_name: Option<String>,
+    pub sub_update_interval: Option<String>,
+    pub sub_link_grace_hours: Option<String>,
     pub terms_of_service: Option<String>,
     pub decoy_enabled: Option<String>,
     pub decoy_urls: Option<String>,
@@ -527,6 +533,14 @@ pub async fn get_settings(State(state): State<AppState>, jar: CookieJar) -> impl
         resolve_internal_api_token(&state).await;
     let bot_username = state.settings.get_or_default("bot_username", "").await;
     let brand_name = state.settings.get_or_default("brand_name", "CARAMBA").await;
+    let sub_update_interval = state
+        .settings
+        .get_or_default("sub_update_interval", "12")
+        .await;
+    let sub_link_grace_hours = state
+        .settings
+        .get_or_default("sub_link_grace_hours", "0")
+        .await;
     let terms_of_service = state
         .settings
         .get_or_default("terms_of_service", "Welcome to CARAMBA.")
@@ -864,6 +878,8 @@ pub async fn get_settings(State(state): State<AppState>, jar: CookieJar) -> impl
         internal_api_token_source,
         bot_username,
         brand_name,
+        sub_update_interval,
+        sub_link_grace_hours,
         terms_of_service,
         decoy_enabled,
         decoy_urls,
@@ -931,6 +947,7 @@ pub async fn get_settings(State(state): State<AppState>, jar: CookieJar) -> impl
 
 pub async fn save_settings(
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
     Form(form): Form<SaveSettingsForm>,
 ) -> impl IntoResponse {
     info!("Saving system settings");
@@ -1108,6 +1125,16 @@ pub async fn save_settings(
     if let Some(v) = form.brand_name {
         settings.insert("brand_name".to_string(), v);
     }
+    if let Some(v) = form.sub_update_interval
+        && v.trim().parse::<u32>().is_ok_and(|h| h > 0)
+    {
+        settings.insert("sub_update_interval".to_string(), v.trim().to_string());
+    }
+    if let Some(v) = form.sub_link_grace_hours
+        && v.trim().parse::<u32>().is_ok_and(|h| h <= 168)
+    {
+        settings.insert("sub_link_grace_hours".to_string(), v.trim().to_string());
+    }
     if let Some(v) = form.terms_of_service {
         settings.insert("terms_of_service".to_string(), v);
     }
@@ -1245,8 +1272,29 @@ pub async fn save_settings(
         }
     }
 
+    let mut before = serde_json::Map::new();
+    for key in settings.keys() {
+        before.insert(
+            key.clone(),
+            serde_json::Value::String(state.settings.get_or_default(key, "").await),
+        );
+    }
+    let after = serde_json::to_value(&settings).unwrap_or_default();
+
     match state.settings.set_multiple(settings).await {
         Ok(_) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "settings.update",
+                    "settings",
+                    "global",
+                    Some(serde_json::Value::Object(before)),
+                    Some(after),
+                )
+                .await;
+
             let active_nodes = state
                 .store_service
                 .get_active_node_ids()
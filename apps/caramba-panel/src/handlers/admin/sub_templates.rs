@@ -0,0 +1,439 @@
+// Subscription Templates Module
+// Admin-edited sing-box and Clash base configs, per format and plan
+
+use askama::Template;
+use askama_web::WebTemplate;
+use axum::{
+    Extension,
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+};
+use axum_extra::extract::cookie::CookieJar;
+use serde_json::json;
+use tracing::error;
+
+use super::auth::get_auth_admin;
+use crate::AppState;
+use crate::services::audit_service::AuditActor;
+use crate::services::sub_template_service::TemplateInput;
+use crate::subscription;
+use caramba_config::{TEMPLATE_FORMATS, render_template, template_variables, validate_template};
+use caramba_db::models::sub_template::SubscriptionTemplate;
+
+#[derive(Template, WebTemplate)]
+#[template(path = "sub_templates.html")]
+pub struct SubTemplatesTemplate {
+    pub templates: Vec<SubscriptionTemplate>,
+    pub plans: Vec<(i64, String)>,
+    pub editor: SubTemplateEditTemplate,
+    pub admin_path: String,
+    pub active_page: String,
+    pub is_auth: bool,
+    pub username: String,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "partials/sub_template_edit.html")]
+pub struct SubTemplateEditTemplate {
+    /// Template being edited, 0 for a new one.
+    pub id: i64,
+    pub name: String,
+    pub client_format: String,
+    pub body: String,
+    pub is_default: bool,
+    pub plan_ids: Vec<i64>,
+    pub plans: Vec<(i64, String)>,
+    /// Each format with its placeholders, as written in a template.
+    pub formats: Vec<(&'static str, Vec<String>)>,
+    pub admin_path: String,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "partials/sub_template_preview.html")]
+pub struct SubTemplatePreviewTemplate {
+    pub title: String,
+    pub content: String,
+}
+
+fn refresh() -> axum::response::Response {
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("HX-Refresh", "true".parse().unwrap());
+    (StatusCode::OK, headers, "").into_response()
+}
+
+fn render_html<T: Template>(template: T) -> axum::response::Response {
+    match template.render() {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Template error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+fn editor(
+    state: &AppState,
+    template: Option<SubscriptionTemplate>,
+    plans: Vec<(i64, String)>,
+) -> SubTemplateEditTemplate {
+    let formats = TEMPLATE_FORMATS
+        .iter()
+        .map(|f| {
+            let vars = template_variables(f)
+                .iter()
+                .map(|v| format!("\"{{{{{}}}}}\"", v))
+                .collect();
+            (*f, vars)
+        })
+        .collect();
+    let template = template.unwrap_or_else(|| SubscriptionTemplate {
+        id: 0,
+        name: String::new(),
+        client_format: TEMPLATE_FORMATS[0].to_string(),
+        body: String::new(),
+        is_default: false,
+        plan_ids: Vec::new(),
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+    });
+    SubTemplateEditTemplate {
+        id: template.id,
+        name: template.name,
+        client_format: template.client_format,
+        body: template.body,
+        is_default: template.is_default,
+        plan_ids: template.plan_ids,
+        plans,
+        formats,
+        admin_path: state.admin_path.clone(),
+    }
+}
+
+/// Fields of the editor form. `plan_ids` repeats once per checked plan.
+struct EditorForm {
+    name: String,
+    client_format: String,
+    body: String,
+    is_default: bool,
+    plan_ids: Vec<i64>,
+}
+
+fn parse_form(form: &[(String, String)]) -> Result<EditorForm, String> {
+    let field = |key: &str| {
+        form.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_default()
+    };
+
+    let name = field("name").trim();
+    if name.is_empty() {
+        return Err("Name is required".to_string());
+    }
+    let client_format = field("client_format");
+    if !TEMPLATE_FORMATS.contains(&client_format) {
+        return Err(format!("Unsupported format {}", client_format));
+    }
+    let body = field("body").trim();
+    validate_template(client_format, body).map_err(|e| e.to_string())?;
+
+    let mut plan_ids = Vec::new();
+    for (_, value) in form.iter().filter(|(k, _)| k == "plan_ids") {
+        match value.parse::<i64>() {
+            Ok(id) if !plan_ids.contains(&id) => plan_ids.push(id),
+            Ok(_) => {}
+            Err(_) => return Err(format!("Invalid plan {}", value)),
+        }
+    }
+
+    Ok(EditorForm {
+        name: name.to_string(),
+        client_format: client_format.to_string(),
+        body: body.to_string(),
+        is_default: field("is_default") == "on",
+        plan_ids,
+    })
+}
+
+/// GET /admin/sub-templates - Templates with an editor for a new one
+pub async fn get_sub_templates_page(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return axum::response::Redirect::to(&format!("{}/login", state.admin_path))
+            .into_response();
+    };
+
+    let templates = state.sub_template_service.list().await.unwrap_or_default();
+    let plans = state
+        .sub_template_service
+        .plan_options()
+        .await
+        .unwrap_or_default();
+
+    render_html(SubTemplatesTemplate {
+        templates,
+        editor: editor(&state, None, plans.clone()),
+        plans,
+        admin_path: state.admin_path.clone(),
+        active_page: "sub_templates".to_string(),
+        is_auth: true,
+        username: admin.username,
+    })
+}
+
+/// GET /admin/sub-templates/{id} - Editor filled with an existing template
+pub async fn get_sub_template_edit(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let template = match state.sub_template_service.get(id).await {
+        Ok(Some(t)) => t,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Template not found").into_response(),
+        Err(e) => {
+            error!("Failed to load subscription template {}: {}", id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load template").into_response();
+        }
+    };
+    let plans = state
+        .sub_template_service
+        .plan_options()
+        .await
+        .unwrap_or_default();
+
+    render_html(editor(&state, Some(template), plans))
+}
+
+async fn save(
+    state: &AppState,
+    actor: &AuditActor,
+    id: Option<i64>,
+    form: &[(String, String)],
+) -> axum::response::Response {
+    let input = match parse_form(form) {
+        Ok(input) => input,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let before = match id {
+        Some(id) => match state.sub_template_service.get(id).await {
+            Ok(Some(t)) => Some(t),
+            Ok(None) => return (StatusCode::NOT_FOUND, "Template not found").into_response(),
+            Err(e) => {
+                error!("Failed to load subscription template {}: {}", id, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load template")
+                    .into_response();
+            }
+        },
+        None => None,
+    };
+
+    let saved = state
+        .sub_template_service
+        .save(
+            id,
+            &TemplateInput {
+                name: &input.name,
+                client_format: &input.client_format,
+                body: &input.body,
+                is_default: input.is_default,
+                plan_ids: &input.plan_ids,
+            },
+        )
+        .await;
+    let saved_id = match saved {
+        Ok(saved_id) => saved_id,
+        Err(e) => {
+            error!("Failed to save subscription template: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to save template").into_response();
+        }
+    };
+
+    state
+        .audit_service
+        .record(
+            actor,
+            if id.is_some() {
+                "sub_template.update"
+            } else {
+                "sub_template.create"
+            },
+            "sub_template",
+            saved_id,
+            before.and_then(|t| serde_json::to_value(t).ok()),
+            Some(json!({
+                "name": input.name,
+                "client_format": input.client_format,
+                "body": input.body,
+                "is_default": input.is_default,
+                "plan_ids": input.plan_ids,
+            })),
+        )
+        .await;
+    let _ = state.redis.invalidate_subscriptions().await;
+
+    refresh()
+}
+
+/// POST /admin/sub-templates - Create a template
+pub async fn create_sub_template(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Form(form): Form<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    save(&state, &actor, None, &form).await
+}
+
+/// POST /admin/sub-templates/{id} - Update a template and its plans
+pub async fn update_sub_template(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+    Form(form): Form<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    save(&state, &actor, Some(id), &form).await
+}
+
+/// POST /admin/sub-templates/{id}/delete - Delete a template; its plans
+/// go back to the default or built-in config
+pub async fn delete_sub_template(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let before = state.sub_template_service.get(id).await.ok().flatten();
+
+    match state.sub_template_service.delete(id).await {
+        Ok(true) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "sub_template.delete",
+                    "sub_template",
+                    id,
+                    before.and_then(|t| serde_json::to_value(t).ok()),
+                    None,
+                )
+                .await;
+            let _ = state.redis.invalidate_subscriptions().await;
+            refresh()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "Template not found").into_response(),
+        Err(e) => {
+            error!("Failed to delete subscription template {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete template",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// POST /admin/sub-templates/preview - Render the editor's template for a
+/// subscription, without saving it
+pub async fn preview_sub_template(
+    State(state): State<AppState>,
+    Form(form): Form<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    let field = |key: &str| {
+        form.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.trim())
+            .unwrap_or_default()
+    };
+    let format = field("client_format");
+    let body = field("body");
+
+    let Ok(sub_id) = field("preview_subscription_id").parse::<i64>() else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Enter a subscription ID to preview",
+        )
+            .into_response();
+    };
+    let sub = match state.subscription_service.get_by_id(sub_id).await {
+        Ok(Some(sub)) => sub,
+        Ok(None) => return (StatusCode::BAD_REQUEST, "Subscription not found").into_response(),
+        Err(e) => {
+            error!("Failed to load subscription {}: {}", sub_id, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load subscription",
+            )
+                .into_response();
+        }
+    };
+
+    let user_keys = match state.subscription_service.get_user_keys(&sub).await {
+        Ok(keys) => keys,
+        Err(e) => {
+            error!("Failed to get user keys for sub {}: {}", sub.id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+        }
+    };
+    let sub_id = sub.id;
+    // The same input the subscription is served from, with the editor's body
+    let input = match subscription::config_input(&state, sub, user_keys, None, format).await {
+        Ok(input) => input,
+        Err((status, message)) => return (status, message).into_response(),
+    };
+
+    match render_template(format, body, &input.nodes, &input.chains, &input.keys) {
+        Ok(content) => render_html(SubTemplatePreviewTemplate {
+            title: format!("{} config for subscription #{}", format, sub_id),
+            content,
+        }),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_form;
+
+    fn form(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn form_is_validated_before_saving() {
+        let body = r#"{"outbounds": ["{{outbounds}}"]}"#;
+        let parsed = parse_form(&form(&[
+            ("name", "Default"),
+            ("client_format", "singbox"),
+            ("body", body),
+            ("is_default", "on"),
+            ("plan_ids", "2"),
+            ("plan_ids", "2"),
+            ("plan_ids", "5"),
+        ]))
+        .unwrap();
+        assert!(parsed.is_default);
+        assert_eq!(parsed.plan_ids, vec![2, 5]);
+
+        let err = parse_form(&form(&[
+            ("name", "Bad"),
+            ("client_format", "singbox"),
+            ("body", r#"{"outbounds": []}"#),
+        ]))
+        .err()
+        .unwrap();
+        assert!(err.contains("{{outbounds}}"));
+
+        assert!(
+            parse_form(&form(&[
+                ("name", "X"),
+                ("client_format", "surge"),
+                ("body", body)
+            ]))
+            .is_err()
+        );
+    }
+}
@@ -4,6 +4,7 @@
 use askama::Template;
 use askama_web::WebTemplate;
 use axum::{
+    Extension,
     extract::{Form, Path, Query, State},
     response::{Html, IntoResponse},
 };
@@ -19,8 +20,10 @@ use tracing::{error, info};
 use super::auth::{get_auth_user, is_authenticated};
 use crate::AppState;
 use crate::bot_manager::{NotificationMediaType, NotificationParseMode, NotificationPayload};
+use crate::services::audit_service::AuditActor;
 use crate::services::logging_service::LoggingService;
 use caramba_db::models::store::{Plan, User};
+use caramba_db::models::sub_token::SubscriptionToken;
 
 // ============================================================================
 // Templates
@@ -110,6 +113,7 @@ pub struct AdminSubscriptionView {
     pub vless_links_count: usize,
     pub last_node_label: Option<String>,
     pub last_sub_access_label: Option<String>,
+    pub link_tokens: Vec<SubscriptionToken>,
 }
 
 #[derive(Deserialize)]
@@ -135,6 +139,12 @@ pub struct ExtendForm {
     pub days: i32,
 }
 
+#[derive(Deserialize)]
+pub struct RotateLinkForm {
+    #[serde(default)]
+    pub grace_hours: i64,
+}
+
 #[derive(Deserialize, Clone)]
 pub struct NotifyForm {
     pub campaign_title: Option<String>,
@@ -748,6 +758,7 @@ pub async fn get_users(
 pub async fn admin_gift_subscription(
     Path(user_id): Path<i64>,
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
     Form(form): Form<AdminGiftForm>,
 ) -> impl IntoResponse {
     let duration = match state
@@ -774,6 +785,18 @@ pub async fn admin_gift_subscription(
         .await
     {
         Ok(sub) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "subscription.gift",
+                    "subscription",
+                    sub.id,
+                    None,
+                    serde_json::to_value(&sub).ok(),
+                )
+                .await;
+
             if let Ok(Some(user)) = state.user_service.get_by_id(user_id).await {
                 let msg = format!(
                     "🎁 *Gift Received\\!*\\n\\nYou have received a new subscription\\.\\nExpires: {}",
@@ -852,10 +875,20 @@ pub async fn get_user_details(
                 None
             }
         };
-        let sub_uuid = full_sub
-            .as_ref()
-            .map(|full| full.subscription_uuid.clone())
-            .unwrap_or_else(|| format!("legacy-{}", sub.id));
+        let sub_uuid = match full_sub.as_ref() {
+            Some(full) => {
+                state
+                    .sub_token_service
+                    .current(sub.id, &full.subscription_uuid)
+                    .await
+            }
+            None => format!("legacy-{}", sub.id),
+        };
+        let link_tokens = state
+            .sub_token_service
+            .list(sub.id)
+            .await
+            .unwrap_or_default();
 
         let last_sub_access_label = full_sub.as_ref().and_then(|full| {
             full.last_sub_access
@@ -923,6 +956,7 @@ pub async fn get_user_details(
             vless_links_count: vless_links.len(),
             last_node_label,
             last_sub_access_label,
+            link_tokens,
         });
     }
 
@@ -992,6 +1026,7 @@ pub async fn get_user_details(
 pub async fn update_user(
     Path(id): Path<i64>,
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
     Form(form): Form<UpdateUserForm>,
 ) -> impl IntoResponse {
     let old_user = state.user_service.get_by_id(id).await.unwrap_or(None);
@@ -1018,6 +1053,19 @@ pub async fn update_user(
 
     match res {
         Ok(_) => {
+            let new_user = state.user_service.get_by_id(id).await.unwrap_or(None);
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "user.update",
+                    "user",
+                    id,
+                    old_user.as_ref().and_then(|u| serde_json::to_value(u).ok()),
+                    new_user.as_ref().and_then(|u| serde_json::to_value(u).ok()),
+                )
+                .await;
+
             let _ = crate::services::activity_service::ActivityService::log(
                 &state.pool,
                 "User",
@@ -1077,15 +1125,35 @@ pub async fn update_user(
 pub async fn update_user_balance(
     Path(id): Path<i64>,
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
     Form(form): Form<HashMap<String, String>>,
 ) -> impl IntoResponse {
     let balance_str = form.get("balance").unwrap_or(&"0".to_string()).clone();
     let balance: i64 = balance_str.parse().unwrap_or(0);
 
+    let old_balance = state
+        .user_service
+        .get_by_id(id)
+        .await
+        .ok()
+        .flatten()
+        .map(|u| u.balance);
     let res = state.user_service.set_balance(id, balance).await;
 
     match res {
         Ok(_) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "user.balance",
+                    "user",
+                    id,
+                    Some(serde_json::json!({ "balance": old_balance })),
+                    Some(serde_json::json!({ "balance": balance })),
+                )
+                .await;
+
             let _ = LoggingService::log_system(
                 &state.pool,
                 "admin_update_balance",
@@ -1114,10 +1182,29 @@ pub async fn update_user_balance(
 pub async fn delete_user_subscription(
     Path(id): Path<i64>,
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
 ) -> impl IntoResponse {
     info!("Request to delete subscription ID: {}", id);
+    let before = state
+        .subscription_service
+        .get_by_id(id)
+        .await
+        .unwrap_or(None);
     match state.subscription_service.admin_delete(id).await {
-        Ok(_) => (axum::http::StatusCode::OK, "").into_response(),
+        Ok(_) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "subscription.delete",
+                    "subscription",
+                    id,
+                    before.as_ref().and_then(|s| serde_json::to_value(s).ok()),
+                    None,
+                )
+                .await;
+            (axum::http::StatusCode::OK, "").into_response()
+        }
         Err(e) => {
             error!("Failed to delete subscripton {}: {}", id, e);
             (
@@ -1132,18 +1219,38 @@ pub async fn delete_user_subscription(
 pub async fn refund_user_subscription(
     Path(id): Path<i64>,
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
     Form(form): Form<RefundForm>,
 ) -> impl IntoResponse {
     info!(
         "Request to refund subscription ID: {} with amount {}",
         id, form.amount
     );
+    let before = state
+        .subscription_service
+        .get_by_id(id)
+        .await
+        .unwrap_or(None);
     match state
         .catalog_service
         .admin_refund_subscription(id, form.amount)
         .await
     {
-        Ok(_) => ([(("HX-Refresh", "true"))], "Refunded").into_response(),
+        Ok(_) => {
+            // Refund deletes the subscription and credits the amount to the user
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "subscription.refund",
+                    "subscription",
+                    id,
+                    Some(serde_json::json!({ "subscription": before, "refund_amount": null })),
+                    Some(serde_json::json!({ "subscription": null, "refund_amount": form.amount })),
+                )
+                .await;
+            ([(("HX-Refresh", "true"))], "Refunded").into_response()
+        }
         Err(e) => {
             error!("Failed to refund subscripton {}: {}", id, e);
             (
@@ -1158,14 +1265,38 @@ pub async fn refund_user_subscription(
 pub async fn extend_user_subscription(
     Path(id): Path<i64>,
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
     Form(form): Form<ExtendForm>,
 ) -> impl IntoResponse {
     info!(
         "Request to extend subscription ID: {} by {} days",
         id, form.days
     );
+    let before = state
+        .subscription_service
+        .get_by_id(id)
+        .await
+        .unwrap_or(None);
     match state.subscription_service.admin_extend(id, form.days).await {
-        Ok(_) => ([(("HX-Refresh", "true"))], "Extended").into_response(),
+        Ok(_) => {
+            let after = state
+                .subscription_service
+                .get_by_id(id)
+                .await
+                .unwrap_or(None);
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "subscription.extend",
+                    "subscription",
+                    id,
+                    before.as_ref().and_then(|s| serde_json::to_value(s).ok()),
+                    after.as_ref().and_then(|s| serde_json::to_value(s).ok()),
+                )
+                .await;
+            ([(("HX-Refresh", "true"))], "Extended").into_response()
+        }
         Err(e) => {
             error!("Failed to extend subscripton {}: {}", id, e);
             (
@@ -1177,6 +1308,43 @@ pub async fn extend_user_subscription(
     }
 }
 
+pub async fn rotate_user_subscription_link(
+    Path(id): Path<i64>,
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Form(form): Form<RotateLinkForm>,
+) -> impl IntoResponse {
+    let grace_hours = form.grace_hours.clamp(0, 168);
+    match state
+        .sub_token_service
+        .rotate(id, chrono::Duration::hours(grace_hours))
+        .await
+    {
+        Ok(_) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "subscription.rotate_link",
+                    "subscription",
+                    id,
+                    None,
+                    Some(serde_json::json!({ "grace_hours": grace_hours })),
+                )
+                .await;
+            ([("HX-Refresh", "true")], "Link rotated").into_response()
+        }
+        Err(e) => {
+            error!("Failed to rotate link of subscription {}: {}", id, e);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to rotate link: {}", e),
+            )
+                .into_response()
+        }
+    }
+}
+
 pub async fn notify_user(
     Path(id): Path<i64>,
     State(state): State<AppState>,
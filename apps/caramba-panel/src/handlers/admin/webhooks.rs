@@ -0,0 +1,348 @@
+// Webhooks Module
+// Outgoing event endpoints, their signing secrets and the delivery log
+
+use askama::Template;
+use askama_web::WebTemplate;
+use axum::{
+    Extension,
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+};
+use axum_extra::extract::cookie::CookieJar;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::error;
+
+use super::auth::get_auth_admin;
+use crate::AppState;
+use crate::services::audit_service::AuditActor;
+use caramba_db::models::webhook::{WebhookDelivery, WebhookEndpoint, WebhookEvent};
+
+/// Deliveries shown on the page.
+const DELIVERY_LOG_LIMIT: i64 = 100;
+
+#[derive(Template, WebTemplate)]
+#[template(path = "webhooks.html")]
+pub struct WebhooksTemplate {
+    pub endpoints: Vec<WebhookEndpoint>,
+    pub deliveries: Vec<WebhookDelivery>,
+    pub events: Vec<WebhookEvent>,
+    /// Endpoint id the log is filtered to, 0 for all.
+    pub filter_endpoint: i64,
+    pub filter_status: String,
+    pub admin_path: String,
+    pub active_page: String,
+    pub is_auth: bool,
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeliveryFilter {
+    pub endpoint: Option<String>,
+    pub status: Option<String>,
+}
+
+fn refresh() -> axum::response::Response {
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("HX-Refresh", "true".parse().unwrap());
+    (StatusCode::OK, headers, "").into_response()
+}
+
+fn secret_fragment(secret: &str) -> String {
+    format!(
+        "<div class='bg-slate-950/60 border border-amber-500/30 rounded-xl p-4 space-y-3'>\
+         <p class='text-amber-400 text-sm font-medium'>Copy this signing secret now. It will not be shown again.</p>\
+         <div class='font-mono text-white break-all select-all bg-slate-950 border border-white/10 rounded-lg p-3'>{}</div>\
+         <p class='text-xs text-slate-400'>Verify <code>X-Caramba-Signature</code> as HMAC-SHA256 of <code>timestamp.body</code>.</p>\
+         <button onclick='location.reload()' class='text-xs text-indigo-400 hover:text-indigo-300'>I have saved it</button>\
+         </div>",
+        secret
+    )
+}
+
+/// Only plain http(s) URLs are accepted as delivery targets.
+pub fn is_valid_target(url: &str) -> bool {
+    match reqwest::Url::parse(url.trim()) {
+        Ok(u) => matches!(u.scheme(), "http" | "https") && u.host_str().is_some(),
+        Err(_) => false,
+    }
+}
+
+/// GET /admin/webhooks - Endpoints and recent deliveries
+pub async fn get_webhooks_page(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Query(filter): Query<DeliveryFilter>,
+) -> impl IntoResponse {
+    let Some(admin) = get_auth_admin(&state, &jar).await else {
+        return axum::response::Redirect::to(&format!("{}/login", state.admin_path))
+            .into_response();
+    };
+
+    let filter_endpoint = filter.endpoint.and_then(|v| v.parse::<i64>().ok());
+    let filter_status = filter
+        .status
+        .filter(|s| matches!(s.as_str(), "pending" | "delivered" | "failed"))
+        .unwrap_or_default();
+
+    let endpoints = state
+        .webhook_service
+        .list_endpoints()
+        .await
+        .unwrap_or_default();
+    let deliveries = state
+        .webhook_service
+        .list_deliveries(
+            filter_endpoint,
+            (!filter_status.is_empty()).then_some(filter_status.as_str()),
+            DELIVERY_LOG_LIMIT,
+        )
+        .await
+        .unwrap_or_default();
+
+    let template = WebhooksTemplate {
+        endpoints,
+        deliveries,
+        events: WebhookEvent::ALL.to_vec(),
+        filter_endpoint: filter_endpoint.unwrap_or_default(),
+        filter_status,
+        admin_path: state.admin_path.clone(),
+        active_page: "webhooks".to_string(),
+        is_auth: true,
+        username: admin.username,
+    };
+
+    match template.render() {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Template error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /admin/webhooks - Create an endpoint and show its secret once
+///
+/// The form repeats `events` once per checked box, so it is read as pairs.
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Form(form): Form<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    let field = |key: &str| {
+        form.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.trim())
+            .unwrap_or_default()
+    };
+
+    let name = field("name");
+    let url = field("url");
+    if name.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Name is required").into_response();
+    }
+    if !is_valid_target(url) {
+        return (StatusCode::BAD_REQUEST, "URL must be an http(s) address").into_response();
+    }
+
+    let mut events = Vec::new();
+    for (_, value) in form.iter().filter(|(k, _)| k == "events") {
+        match WebhookEvent::parse(value) {
+            Some(event) if !events.contains(&event) => events.push(event),
+            Some(_) => {}
+            None => {
+                return (StatusCode::BAD_REQUEST, format!("Unknown event {}", value))
+                    .into_response();
+            }
+        }
+    }
+    if events.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Select at least one event").into_response();
+    }
+
+    let (id, secret) = match state
+        .webhook_service
+        .create_endpoint(name, url, &events)
+        .await
+    {
+        Ok(created) => created,
+        Err(e) => {
+            error!("Failed to create webhook endpoint: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create endpoint",
+            )
+                .into_response();
+        }
+    };
+
+    state
+        .audit_service
+        .record(
+            &actor,
+            "webhook.create",
+            "webhook",
+            id,
+            None,
+            Some(json!({
+                "name": name,
+                "url": url,
+                "events": events.iter().map(|e| e.as_str()).collect::<Vec<_>>(),
+            })),
+        )
+        .await;
+
+    Html(secret_fragment(&secret)).into_response()
+}
+
+/// POST /admin/webhooks/{id}/toggle - Pause or resume deliveries to an endpoint
+pub async fn toggle_webhook(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match state.webhook_service.toggle_endpoint(id).await {
+        Ok(Some(is_active)) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "webhook.update",
+                    "webhook",
+                    id,
+                    Some(json!({ "is_active": !is_active })),
+                    Some(json!({ "is_active": is_active })),
+                )
+                .await;
+            refresh()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Endpoint not found").into_response(),
+        Err(e) => {
+            error!("Failed to toggle webhook {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update endpoint",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// POST /admin/webhooks/{id}/rotate-secret - Replace the signing secret and show it once
+pub async fn rotate_webhook_secret(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match state.webhook_service.rotate_secret(id).await {
+        Ok(Some(secret)) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "webhook.update",
+                    "webhook",
+                    id,
+                    Some(json!({ "secret": "***" })),
+                    Some(json!({ "secret": "rotated" })),
+                )
+                .await;
+            Html(secret_fragment(&secret)).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Endpoint not found").into_response(),
+        Err(e) => {
+            error!("Failed to rotate webhook {} secret: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to rotate secret").into_response()
+        }
+    }
+}
+
+/// POST /admin/webhooks/{id}/test - Queue a test delivery
+pub async fn test_webhook(State(state): State<AppState>, Path(id): Path<i64>) -> impl IntoResponse {
+    match state.webhook_service.get_endpoint(id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return (StatusCode::NOT_FOUND, "Endpoint not found").into_response(),
+        Err(e) => {
+            error!("Failed to load webhook {}: {}", id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load endpoint").into_response();
+        }
+    }
+
+    match state.webhook_service.send_test(id).await {
+        Ok(()) => refresh(),
+        Err(e) => {
+            error!("Failed to queue test delivery for webhook {}: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to queue test").into_response()
+        }
+    }
+}
+
+/// POST /admin/webhooks/{id}/delete - Delete an endpoint and its delivery log
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let before = state.webhook_service.get_endpoint(id).await.ok().flatten();
+
+    match state.webhook_service.delete_endpoint(id).await {
+        Ok(true) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "webhook.delete",
+                    "webhook",
+                    id,
+                    before.and_then(|e| serde_json::to_value(e).ok()),
+                    None,
+                )
+                .await;
+            refresh()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "Endpoint not found").into_response(),
+        Err(e) => {
+            error!("Failed to delete webhook {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete endpoint",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// POST /admin/webhooks/deliveries/{id}/retry - Re-queue a delivery
+pub async fn retry_webhook_delivery(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match state.webhook_service.retry_delivery(id).await {
+        Ok(true) => refresh(),
+        Ok(false) => (StatusCode::BAD_REQUEST, "Delivery is already queued").into_response(),
+        Err(e) => {
+            error!("Failed to retry webhook delivery {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retry delivery",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_valid_target;
+
+    #[test]
+    fn only_http_targets_are_accepted() {
+        assert!(is_valid_target("https://hooks.example.com/caramba"));
+        assert!(is_valid_target("http://10.0.0.5:8080/hook"));
+        assert!(!is_valid_target("ftp://example.com/hook"));
+        assert!(!is_valid_target("example.com/hook"));
+        assert!(!is_valid_target(""));
+    }
+}
@@ -1,9 +1,11 @@
 use crate::AppState;
 use crate::handlers::admin::{get_auth_user, is_authenticated};
+use crate::services::audit_service::AuditActor;
 use crate::singbox::RelayAuthMode;
 use askama::Template;
 use askama_web::WebTemplate;
 use axum::{
+    Extension,
     body::Bytes,
     extract::{Form, Path, State},
     response::{Html, IntoResponse},
@@ -15,6 +17,34 @@ use caramba_db::models::store::Plan;
 use serde::Deserialize;
 use tracing::{error, info};
 
+/// Audit snapshot of an inbound with its JSON settings expanded, so the diff
+/// points at the changed field and secrets inside can be masked.
+pub(crate) fn inbound_snapshot(inbound: &Inbound) -> Option<serde_json::Value> {
+    let mut value = serde_json::to_value(inbound).ok()?;
+    if let Some(obj) = value.as_object_mut() {
+        for key in ["settings", "stream_settings"] {
+            if let Some(parsed) = obj
+                .get(key)
+                .and_then(|v| v.as_str())
+                .and_then(|raw| serde_json::from_str::<serde_json::Value>(raw).ok())
+            {
+                obj.insert(key.to_string(), parsed);
+            }
+        }
+    }
+    Some(value)
+}
+
+async fn load_inbound(state: &AppState, inbound_id: i64) -> Option<Inbound> {
+    state
+        .infrastructure_service
+        .node_repo
+        .get_inbound_by_id(inbound_id)
+        .await
+        .ok()
+        .flatten()
+}
+
 #[derive(Template, WebTemplate)]
 #[template(path = "node_inbounds.html")]
 pub struct NodeInboundsTemplate {
@@ -89,6 +119,7 @@ pub struct AddInboundForm {
 
 pub async fn add_inbound(
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
     Path(node_id): Path<i64>,
     Form(form): Form<AddInboundForm>,
 ) -> impl IntoResponse {
@@ -99,9 +130,11 @@ pub async fn add_inbound(
 
     // Validate JSON against Models
     // 1. Stream Settings
-    if let Err(e) =
+    let stream_check =
         serde_json::from_str::<caramba_db::models::network::StreamSettings>(&form.stream_settings)
-    {
+            .map_err(|e| e.to_string())
+            .and_then(|s| s.anti_dpi.map_or(Ok(()), |a| a.validate()));
+    if let Err(e) = stream_check {
         return (
             axum::http::StatusCode::BAD_REQUEST,
             format!("Invalid Stream Settings: {}", e),
@@ -190,6 +223,19 @@ pub async fn add_inbound(
 
     match res {
         Ok(inbound_id) => {
+            let created = load_inbound(&state, inbound_id).await;
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "inbound.create",
+                    "inbound",
+                    inbound_id,
+                    None,
+                    created.as_ref().and_then(inbound_snapshot),
+                )
+                .await;
+
             // Phase 46: Link this inbound to all plans that use this node
             // This ensures manual inbounds actually HAVE users in the generated config.
             let plans_res: Result<Vec<i64>, _> = sqlx::query_scalar(
@@ -218,7 +264,7 @@ pub async fn add_inbound(
 
             if let Err(e) = state
                 .orchestration_service
-                .generate_node_config_json(node_id)
+                .generate_node_config_json(node_id, "Inbound created", &actor.username)
                 .await
             {
                 error!(
@@ -262,9 +308,11 @@ pub async fn add_inbound(
 
 pub async fn delete_inbound(
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
     Path((node_id, inbound_id)): Path<(i64, i64)>,
 ) -> impl IntoResponse {
     info!("Deleting inbound {} from node {}", inbound_id, node_id);
+    let before = load_inbound(&state, inbound_id).await;
 
     // Clean up plan_inbound links first
     let _ = sqlx::query("DELETE FROM plan_inbounds WHERE inbound_id = $1")
@@ -279,9 +327,21 @@ pub async fn delete_inbound(
         .await
     {
         Ok(_) => {
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "inbound.delete",
+                    "inbound",
+                    inbound_id,
+                    before.as_ref().and_then(inbound_snapshot),
+                    None,
+                )
+                .await;
+
             if let Err(e) = state
                 .orchestration_service
-                .generate_node_config_json(node_id)
+                .generate_node_config_json(node_id, "Inbound deleted", &actor.username)
                 .await
             {
                 error!(
@@ -637,6 +697,7 @@ pub async fn get_edit_inbound(
 
 pub async fn update_inbound(
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
     Path((node_id, inbound_id)): Path<(i64, i64)>,
     Form(form): Form<AddInboundForm>,
 ) -> impl IntoResponse {
@@ -644,9 +705,11 @@ pub async fn update_inbound(
 
     // Validate JSON against Models
     // 1. Stream Settings
-    if let Err(e) =
+    let stream_check =
         serde_json::from_str::<caramba_db::models::network::StreamSettings>(&form.stream_settings)
-    {
+            .map_err(|e| e.to_string())
+            .and_then(|s| s.anti_dpi.map_or(Ok(()), |a| a.validate()));
+    if let Err(e) = stream_check {
         return (
             axum::http::StatusCode::BAD_REQUEST,
             format!("Invalid Stream Settings: {}", e),
@@ -723,6 +786,7 @@ pub async fn update_inbound(
             .into_response();
     }
 
+    let before = load_inbound(&state, inbound_id).await;
     let res = sqlx::query("UPDATE inbounds SET tag = $1, protocol = $2, listen_port = $3, listen_ip = $4, settings = $5, stream_settings = $6, remark = $7 WHERE id = $8 AND node_id = $9")
         .bind(&form.tag).bind(&form.protocol).bind(form.listen_port).bind(&form.listen_ip)
         .bind(&form.settings).bind(&form.stream_settings).bind(&form.remark).bind(inbound_id).bind(node_id)
@@ -730,9 +794,22 @@ pub async fn update_inbound(
 
     match res {
         Ok(_) => {
+            let after = load_inbound(&state, inbound_id).await;
+            state
+                .audit_service
+                .record(
+                    &actor,
+                    "inbound.update",
+                    "inbound",
+                    inbound_id,
+                    before.as_ref().and_then(inbound_snapshot),
+                    after.as_ref().and_then(inbound_snapshot),
+                )
+                .await;
+
             if let Err(e) = state
                 .orchestration_service
-                .generate_node_config_json(node_id)
+                .generate_node_config_json(node_id, "Inbound updated", &actor.username)
                 .await
             {
                 error!(
@@ -773,6 +850,7 @@ pub async fn update_inbound(
 pub async fn toggle_inbound(
     Path((node_id, inbound_id)): Path<(i64, i64)>,
     State(state): State<AppState>,
+    Extension(actor): Extension<AuditActor>,
     jar: CookieJar,
 ) -> impl IntoResponse {
     use axum::http::StatusCode;
@@ -782,15 +860,32 @@ pub async fn toggle_inbound(
     }
 
     // Toggle
-    let _ = sqlx::query("UPDATE inbounds SET enable = NOT enable WHERE id = $1 AND node_id = $2")
-        .bind(inbound_id)
-        .bind(node_id)
-        .execute(&state.pool)
-        .await;
+    let toggled: Option<bool> = sqlx::query_scalar(
+        "UPDATE inbounds SET enable = NOT enable WHERE id = $1 AND node_id = $2 RETURNING enable",
+    )
+    .bind(inbound_id)
+    .bind(node_id)
+    .fetch_optional(&state.pool)
+    .await
+    .unwrap_or(None);
+
+    if let Some(enable) = toggled {
+        state
+            .audit_service
+            .record(
+                &actor,
+                "inbound.toggle",
+                "inbound",
+                inbound_id,
+                Some(serde_json::json!({ "enable": !enable })),
+                Some(serde_json::json!({ "enable": enable })),
+            )
+            .await;
+    }
 
     if let Err(e) = state
         .orchestration_service
-        .generate_node_config_json(node_id)
+        .generate_node_config_json(node_id, "Inbound toggled", &actor.username)
         .await
     {
         error!(
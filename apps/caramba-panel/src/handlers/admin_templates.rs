@@ -1,5 +1,6 @@
 use crate::AppState;
 use crate::handlers::admin::{get_auth_user, is_authenticated};
+use crate::services::rollout_service::active_rollout_for_group;
 use askama::Template;
 use askama_web::WebTemplate;
 use axum::{
@@ -9,7 +10,7 @@ use axum::{
 use axum_extra::extract::cookie::CookieJar;
 use caramba_db::models::groups::{InboundTemplate, NodeGroup};
 use serde::Deserialize;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[derive(Template, WebTemplate)]
 #[template(path = "admin_templates.html")]
@@ -93,6 +94,9 @@ pub struct CreateTemplateForm {
     pub port_range_start: i64,
     pub port_range_end: i64,
     pub renew_interval_mins: i64,
+    /// "staged" saves without pushing so the change can go out as a rollout.
+    #[serde(default)]
+    pub sync_mode: Option<String>,
 }
 
 fn validate_template_bounds(form: &CreateTemplateForm) -> Result<(), String> {
@@ -313,6 +317,16 @@ pub async fn sync_template(
             .unwrap_or(None);
 
     if let Some(gid) = group_id {
+        if let Ok(Some(rollout_id)) = active_rollout_for_group(&state.pool, gid).await {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Staged rollout #{} is in progress for this group. Continue or roll it back first.",
+                    rollout_id
+                ),
+            )
+                .into_response();
+        }
         match state.generator_service.sync_group_inbounds(gid).await {
             Ok(_) => {
                 info!("Synced inbounds for group {}", gid);
@@ -542,11 +556,20 @@ pub async fn update_template(
 
     match res {
         Ok(_) => {
+            let admin_path = state.admin_path.clone();
+            if form.sync_mode.as_deref() == Some("staged") && form.target_group_id.is_some() {
+                return axum::response::Redirect::to(&format!(
+                    "{}/rollouts?template={}",
+                    admin_path, id
+                ))
+                .into_response();
+            }
             // Auto-sync
-            if let Some(gid) = form.target_group_id {
-                let _ = state.generator_service.sync_group_inbounds(gid).await;
+            if let Some(gid) = form.target_group_id
+                && let Err(e) = state.generator_service.sync_group_inbounds(gid).await
+            {
+                warn!("Template {} saved but not synced: {}", id, e);
             }
-            let admin_path = state.admin_path.clone();
             axum::response::Redirect::to(&format!("{}/templates", admin_path)).into_response()
         }
         Err(e) => (
@@ -1,7 +1,7 @@
 use crate::AppState;
 use axum::{
     Json,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
 };
@@ -204,3 +204,220 @@ pub async fn activate_sub(
         Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
     }
 }
+
+#[derive(Deserialize)]
+pub struct SubOwnerRequest {
+    pub user_id: i64,
+}
+
+/// The subscription, if `user_id` owns it.
+async fn owned_sub(
+    state: &AppState,
+    sub_id: i64,
+    user_id: i64,
+) -> Option<caramba_db::models::store::Subscription> {
+    sqlx::query_as::<_, caramba_db::models::store::Subscription>(
+        "SELECT * FROM subscriptions WHERE id = $1 AND user_id = $2",
+    )
+    .bind(sub_id)
+    .bind(user_id)
+    .fetch_optional(&state.pool)
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Telegram user a read-only subscription request is made for.
+#[derive(Deserialize)]
+pub struct SubOwnerQuery {
+    pub tg_id: i64,
+}
+
+/// The subscription, if the Telegram user `tg_id` owns it.
+async fn owned_sub_by_tg(
+    state: &AppState,
+    sub_id: i64,
+    tg_id: i64,
+) -> Option<caramba_db::models::store::Subscription> {
+    sqlx::query_as::<_, caramba_db::models::store::Subscription>(
+        "SELECT s.* FROM subscriptions s JOIN users u ON u.id = s.user_id WHERE s.id = $1 AND u.tg_id = $2",
+    )
+    .bind(sub_id)
+    .bind(tg_id)
+    .fetch_optional(&state.pool)
+    .await
+    .ok()
+    .flatten()
+}
+
+pub async fn rotate_sub_link(
+    State(state): State<AppState>,
+    Path(sub_id): Path<i64>,
+    Json(payload): Json<SubOwnerRequest>,
+) -> impl IntoResponse {
+    if owned_sub(&state, sub_id, payload.user_id).await.is_none() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let grace = crate::services::sub_token_service::SubTokenService::self_service_grace(
+        &state
+            .settings
+            .get_or_default("sub_link_grace_hours", "0")
+            .await,
+    );
+    match state.sub_token_service.rotate(sub_id, grace).await {
+        Ok(_) => Json(grace.num_hours()).into_response(),
+        Err(e) => {
+            tracing::error!("bot rotate_sub_link failed for sub {}: {}", sub_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn get_sub_devices(
+    State(state): State<AppState>,
+    Path(sub_id): Path<i64>,
+    Query(owner): Query<SubOwnerQuery>,
+) -> impl IntoResponse {
+    if owned_sub_by_tg(&state, sub_id, owner.tg_id).await.is_none() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    match state.device_service.list(sub_id).await {
+        Ok(devices) => Json(devices).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+pub async fn revoke_sub_device(
+    State(state): State<AppState>,
+    Path((sub_id, device_id)): Path<(i64, i64)>,
+    Json(payload): Json<SubOwnerRequest>,
+) -> impl IntoResponse {
+    let Some(sub) = owned_sub(&state, sub_id, payload.user_id).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match state.device_service.revoke(sub_id, device_id).await {
+        Ok(revoked) => {
+            if revoked {
+                let orchestration = state.orchestration_service.clone();
+                let connections = state.connection_service.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = orchestration.notify_plan_nodes(sub.plan_id).await {
+                        tracing::error!("Failed to push device revocation to nodes: {}", e);
+                    }
+                    if let Err(e) = connections.kill_device_connections(sub_id, device_id).await {
+                        tracing::error!(
+                            "Failed to close connections of device {}: {}",
+                            device_id,
+                            e
+                        );
+                    }
+                });
+            }
+            Json(revoked).into_response()
+        }
+        Err(e) => {
+            tracing::error!("bot revoke_sub_device failed for sub {}: {}", sub_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct WireguardConfig {
+    pub inbound_id: i64,
+    pub name: String,
+    pub file_name: String,
+    pub conf: String,
+}
+
+/// `.conf` files of the subscription's shared WireGuard peer, one per
+/// AmneziaWG inbound of its plan.
+pub async fn get_sub_wireguard(
+    State(state): State<AppState>,
+    Path(sub_id): Path<i64>,
+    Query(owner): Query<SubOwnerQuery>,
+) -> impl IntoResponse {
+    let Some(plan_id) = owned_sub_by_tg(&state, sub_id, owner.tg_id)
+        .await
+        .map(|sub| sub.plan_id)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match state
+        .wireguard_service
+        .shared_configs(sub_id, plan_id)
+        .await
+    {
+        Ok((configs, created)) => {
+            if created {
+                let _ = state.orchestration_service.notify_plan_nodes(plan_id).await;
+            }
+            Json(
+                configs
+                    .into_iter()
+                    .map(|(endpoint, conf)| WireguardConfig {
+                        inbound_id: endpoint.inbound_id,
+                        file_name: format!("{}.conf", endpoint.tunnel_name()),
+                        name: endpoint.node_name,
+                        conf,
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .into_response()
+        }
+        Err(e) => {
+            tracing::error!("bot get_sub_wireguard failed for sub {}: {}", sub_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// PNG QR code of one `.conf` from [`get_sub_wireguard`].
+pub async fn get_sub_wireguard_qr(
+    State(state): State<AppState>,
+    Path((sub_id, inbound_id)): Path<(i64, i64)>,
+    Query(owner): Query<SubOwnerQuery>,
+) -> impl IntoResponse {
+    let Some(plan_id) = owned_sub_by_tg(&state, sub_id, owner.tg_id)
+        .await
+        .map(|sub| sub.plan_id)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let conf = match state
+        .wireguard_service
+        .shared_configs(sub_id, plan_id)
+        .await
+    {
+        Ok((configs, _)) => configs
+            .into_iter()
+            .find(|(endpoint, _)| endpoint.inbound_id == inbound_id)
+            .map(|(_, conf)| conf),
+        Err(_) => None,
+    };
+    let Some(conf) = conf else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match crate::services::qr_service::png(&conf, crate::services::qr_service::PNG_SCALE) {
+        Ok(png) => ([(axum::http::header::CONTENT_TYPE, "image/png")], png).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// PNG QR code of the subscription URL, or of its `link`-th connection link.
+pub async fn get_sub_qr(
+    State(state): State<AppState>,
+    Path(sub_id): Path<i64>,
+    Query(params): Query<crate::subscription::QrParams>,
+    Query(owner): Query<SubOwnerQuery>,
+) -> impl IntoResponse {
+    let Some(sub) = owned_sub_by_tg(&state, sub_id, owner.tg_id).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match crate::subscription::qr_payload(&state, &sub, params.link).await {
+        Some(data) => {
+            crate::subscription::qr_response(&data, params.format.as_deref().unwrap_or("png"))
+        }
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
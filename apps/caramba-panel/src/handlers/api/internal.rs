@@ -1,4 +1,5 @@
 use crate::AppState;
+use crate::subscription;
 use axum::{
     Json,
     extract::{Path, Query, State},
@@ -162,13 +163,20 @@ pub async fn get_subscription(
         return status.into_response();
     }
 
-    let sub: Option<InternalSubscription> = sqlx::query_as(
-        "SELECT id, user_id, status, used_traffic, subscription_uuid FROM subscriptions WHERE subscription_uuid = $1",
-    )
-    .bind(uuid)
-    .fetch_optional(&state.pool)
-    .await
-    .unwrap_or(None);
+    // `uuid` is the link token the client fetched with, which rotates
+    // independently of the subscription's own uuid.
+    let sub = state
+        .sub_token_service
+        .resolve(&uuid)
+        .await
+        .unwrap_or(None)
+        .map(|s| InternalSubscription {
+            id: s.id,
+            user_id: s.user_id,
+            status: s.status,
+            used_traffic: s.used_traffic,
+            subscription_uuid: s.subscription_uuid,
+        });
 
     if let Some(s) = sub {
         Json(s).into_response()
@@ -177,6 +185,37 @@ pub async fn get_subscription(
     }
 }
 
+#[derive(Deserialize)]
+pub struct ConfigInputQuery {
+    pub client: Option<String>,
+    pub node_id: Option<i64>,
+}
+
+/// The config fetch of link token `uuid` by the client a worker forwards,
+/// admitted as `/sub/{uuid}` admits it: device credentials, limits and
+/// profile included, for the worker to render with the shared generator.
+pub async fn get_config_input(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(uuid): Path<String>,
+    Query(query): Query<ConfigInputQuery>,
+) -> impl IntoResponse {
+    if let Err(status) = authorize_internal_request(&state, &headers).await {
+        return status.into_response();
+    }
+
+    let ip = headers
+        .get(caramba_config::serve::CLIENT_IP_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(subscription::normalize_ip)
+        .unwrap_or_else(|| "0.0.0.0".to_string());
+    let fetcher = subscription::Fetcher::new(ip, &headers);
+    match subscription::prepare_config(&state, &uuid, &fetcher, query.client, query.node_id).await {
+        Ok(prepared) => Json(prepared).into_response(),
+        Err(rejection) => rejection,
+    }
+}
+
 pub async fn get_user_keys(
     State(state): State<AppState>,
     headers: HeaderMap,
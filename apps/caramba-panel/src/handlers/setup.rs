@@ -58,11 +58,13 @@ pub async fn create_admin(
         }
     };
 
-    match sqlx::query("INSERT INTO admins (username, password_hash) VALUES ($1, $2)")
-        .bind(&form.username)
-        .bind(hash)
-        .execute(&state.pool)
-        .await
+    match sqlx::query(
+        "INSERT INTO admins (username, password_hash, role) VALUES ($1, $2, 'superadmin')",
+    )
+    .bind(&form.username)
+    .bind(hash)
+    .execute(&state.pool)
+    .await
     {
         Ok(_) => {
             info!("Setup: Admin {} created successfully.", form.username);
@@ -51,6 +51,7 @@ pub struct AppState {
 
     pub store_service: Arc<services::store_service::StoreService>,
     pub orchestration_service: Arc<services::orchestration_service::OrchestrationService>,
+    pub config_version_service: Arc<services::config_version_service::ConfigVersionService>,
     pub pay_service: Arc<services::pay_service::PayService>,
     pub export_service: Arc<services::export_service::ExportService>,
     pub notification_service: Arc<services::notification_service::NotificationService>,
@@ -65,8 +66,19 @@ pub struct AppState {
     pub catalog_service: Arc<services::catalog_service::CatalogService>,
     pub analytics_service: Arc<services::analytics_service::AnalyticsService>,
     pub generator_service: Arc<services::generator_service::GeneratorService>, // Phase 1.8
-    pub org_service: Arc<services::org_service::OrganizationService>,          // Phase 3
+    pub rollout_service: Arc<services::rollout_service::RolloutService>,
+    pub org_service: Arc<services::org_service::OrganizationService>, // Phase 3
     pub sni_repo: Arc<repositories::sni_repo::SniRepository>,
+    pub admin_repo: Arc<repositories::admin_repo::AdminRepository>,
+    pub admin_security_service: Arc<services::admin_security_service::AdminSecurityService>,
+    pub audit_service: Arc<services::audit_service::AuditService>,
+    pub api_token_service: Arc<services::api_token_service::ApiTokenService>,
+    pub webhook_service: Arc<services::webhook_service::WebhookService>,
+    pub sub_template_service: Arc<services::sub_template_service::SubTemplateService>,
+    pub chain_profile_service: Arc<services::chain_profile_service::ChainProfileService>,
+    pub sub_token_service: Arc<services::sub_token_service::SubTokenService>,
+    pub device_service: Arc<services::device_service::DeviceService>,
+    pub wireguard_service: Arc<services::wireguard_service::WireguardService>,
     pub telemetry_service: Arc<services::telemetry_service::TelemetryService>,
     pub infrastructure_service: Arc<services::infrastructure_service::InfrastructureService>,
     pub security_service: Arc<services::security_service::SecurityService>,
@@ -117,10 +129,11 @@ enum AdminCommands {
 async fn auth_middleware(
     State(state): State<AppState>,
     jar: CookieJar,
-    req: axum::extract::Request,
+    mut req: axum::extract::Request,
     next: axum::middleware::Next,
 ) -> impl IntoResponse {
-    let path = req.uri().path();
+    let path = req.uri().path().to_string();
+    let path = path.as_str();
     let admin_path = std::env::var("ADMIN_PATH").unwrap_or_else(|_| "/admin".to_string());
     // Ensure leading slash
     let admin_path = if admin_path.starts_with('/') {
@@ -137,21 +150,69 @@ async fn auth_middleware(
         return next.run(req).await;
     }
 
+    let is_json_api = path.starts_with("/api/") || path.starts_with("/caramba-api/");
+
     if let Some(cookie) = jar.get("admin_session") {
         let token = cookie.value();
-        let redis_key = format!("session:{}", token);
+        let redis_key = services::admin_security_service::session_redis_key(token);
         // Check if token exists in Redis
         if let Ok(Some(username)) = state.redis.get(&redis_key).await {
             // Verify this username actually exists in the DB (prevents ghost sessions after reinstall)
-            let user_exists: bool =
-                sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM admins WHERE username = $1)")
-                    .bind(&username)
-                    .fetch_one(&state.pool)
-                    .await
-                    .unwrap_or(false);
-
-            if user_exists {
-                return next.run(req).await;
+            let admin = state
+                .admin_repo
+                .get_by_username(&username)
+                .await
+                .unwrap_or(None);
+
+            if let Some(admin) = admin {
+                // Enforce role permissions for the requested route
+                let role = admin.role();
+                let permission =
+                    handlers::admin::rbac::required_permission(req.method(), path, &admin_path);
+                if !role.allows(permission) {
+                    tracing::warn!(
+                        "RBAC: admin '{}' ({}) denied {} {} (requires {})",
+                        admin.username,
+                        role,
+                        req.method(),
+                        path,
+                        permission.as_str()
+                    );
+                    return handlers::admin::rbac::forbidden_response(
+                        req.headers(),
+                        path,
+                        role,
+                        permission,
+                    );
+                }
+                let ip = subscription::extract_client_ip(req.headers());
+                state.admin_security_service.touch_session(token, &ip).await;
+
+                // Make the acting admin available to handlers for the audit trail
+                let actor =
+                    services::audit_service::AuditActor::new(admin.id, admin.username.clone(), ip);
+                let method = req.method().clone();
+                req.extensions_mut().insert(actor.clone());
+                let response = next.run(req).await;
+
+                let mutating = !matches!(
+                    method,
+                    axum::http::Method::GET
+                        | axum::http::Method::HEAD
+                        | axum::http::Method::OPTIONS
+                );
+                let status = response.status();
+                if mutating
+                    && !actor.has_recorded()
+                    && (status.is_success() || status.is_redirection())
+                {
+                    let rel_path = path.strip_prefix(admin_path.as_str()).unwrap_or(path);
+                    state
+                        .audit_service
+                        .record_request(&actor, method.as_str(), rel_path)
+                        .await;
+                }
+                return response;
             } else {
                 tracing::warn!(
                     "Session INVALID: Redis has username '{}' but DB check failed. (Ghost session?)",
@@ -163,6 +224,14 @@ async fn auth_middleware(
         }
     }
 
+    if is_json_api {
+        return (
+            axum::http::StatusCode::UNAUTHORIZED,
+            axum::Json(serde_json::json!({ "error": "unauthorized" })),
+        )
+            .into_response();
+    }
+
     // Check if any admin exists
     let admin_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM admins")
         .fetch_one(&state.pool)
@@ -299,6 +368,10 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
         pool.clone(),
     ));
 
+    let config_version_service = Arc::new(
+        services::config_version_service::ConfigVersionService::new(pool.clone()),
+    );
+
     // Initialize orchestration service
     let orchestration_service =
         Arc::new(services::orchestration_service::OrchestrationService::new(
@@ -306,6 +379,7 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
             store_service.clone(),
             security_service.clone(),
             pubsub_service.clone(),
+            config_version_service.clone(),
         ));
 
     // Initialize new modular services
@@ -321,9 +395,15 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
         orchestration_service.clone(),
         pubsub_service.clone(),
     )); // Phase 1.8
+    let rollout_service = Arc::new(services::rollout_service::RolloutService::new(
+        pool.clone(),
+        generator_service.clone(),
+        orchestration_service.clone(),
+    ));
     let org_repo = repositories::org_repo::OrganizationRepository::new(pool.clone());
     let org_service = Arc::new(services::org_service::OrganizationService::new(org_repo));
     let sni_repo = Arc::new(repositories::sni_repo::SniRepository::new(pool.clone()));
+    let admin_repo = Arc::new(repositories::admin_repo::AdminRepository::new(pool.clone()));
 
     // Initialize Update Service (Phase 66)
     let update_service = Arc::new(services::update_service::UpdateService::new(
@@ -419,6 +499,30 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
         pool.clone(),
     ));
 
+    let admin_security_service =
+        Arc::new(services::admin_security_service::AdminSecurityService::new(
+            pool.clone(),
+            redis_service.clone(),
+        ));
+    let audit_service = Arc::new(services::audit_service::AuditService::new(pool.clone()));
+    let api_token_service = Arc::new(services::api_token_service::ApiTokenService::new(
+        pool.clone(),
+    ));
+    let webhook_service = Arc::new(services::webhook_service::WebhookService::new(pool.clone()));
+    let sub_template_service = Arc::new(services::sub_template_service::SubTemplateService::new(
+        pool.clone(),
+    ));
+    let chain_profile_service = Arc::new(
+        services::chain_profile_service::ChainProfileService::new(pool.clone()),
+    );
+    let sub_token_service = Arc::new(services::sub_token_service::SubTokenService::new(
+        pool.clone(),
+    ));
+    let device_service = Arc::new(services::device_service::DeviceService::new(pool.clone()));
+    let wireguard_service = Arc::new(services::wireguard_service::WireguardService::new(
+        pool.clone(),
+    ));
+
     let session_secret = std::env::var("SESSION_SECRET").unwrap_or_else(|_| "secret".to_string());
 
     let promo_service = Arc::new(services::promo_service::PromoService::new(pool.clone()));
@@ -442,6 +546,7 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
         bot_manager: bot_manager.clone(),
         store_service: store_service.clone(),
         orchestration_service: orchestration_service.clone(),
+        config_version_service,
         pay_service: pay_service.clone(),
         export_service: export_service.clone(),
         notification_service: notification_service.clone(),
@@ -455,8 +560,19 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
         catalog_service,
         analytics_service,
         generator_service,
+        rollout_service,
         org_service,
         sni_repo,
+        admin_repo,
+        admin_security_service,
+        audit_service,
+        api_token_service,
+        webhook_service,
+        sub_template_service,
+        chain_profile_service,
+        sub_token_service,
+        device_service,
+        wireguard_service,
         telemetry_service,
         infrastructure_service,
         security_service,
@@ -509,6 +625,18 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
         connection_svc.start_monitoring().await;
     });
 
+    // Start Webhook Delivery Worker
+    let webhook_svc = state.webhook_service.clone();
+    tokio::spawn(async move {
+        webhook_svc.start().await;
+    });
+
+    // Start Rollout Health Watcher
+    let rollout_svc = state.rollout_service.clone();
+    tokio::spawn(async move {
+        rollout_svc.start().await;
+    });
+
     // Start Inbound Rotation Scheduler (Phase 5)
     let rotation_state = state.clone();
     let rotation_generator = state.generator_service.clone();
@@ -610,6 +738,10 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
             "/nodes/{id}/rotate",
             axum::routing::post(handlers::admin::nodes::rotate_node_inbounds),
         )
+        .route(
+            "/nodes/{id}/reset/preview",
+            axum::routing::get(handlers::admin::preview_node_reset),
+        )
         .route(
             "/nodes/{id}/rescan",
             axum::routing::post(handlers::admin::nodes::trigger_scan),
@@ -622,6 +754,22 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
             "/nodes/{id}/config/preview",
             axum::routing::get(handlers::admin::nodes::get_node_config_preview),
         )
+        .route(
+            "/nodes/{id}/config/versions/{version}",
+            axum::routing::get(handlers::admin::get_node_config_version),
+        )
+        .route(
+            "/nodes/{id}/config/versions/{version}/pin",
+            axum::routing::post(handlers::admin::pin_node_config),
+        )
+        .route(
+            "/nodes/{id}/config/unpin",
+            axum::routing::post(handlers::admin::unpin_node_config),
+        )
+        .route(
+            "/nodes/{id}/config/diff",
+            axum::routing::get(handlers::admin::get_node_config_diff),
+        )
         .route(
             "/nodes/{id}/update",
             axum::routing::post(handlers::admin::update_node),
@@ -724,6 +872,10 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
             "/users/subs/{id}/extend",
             axum::routing::post(handlers::admin::extend_user_subscription),
         )
+        .route(
+            "/users/subs/{id}/rotate-link",
+            axum::routing::post(handlers::admin::rotate_user_subscription_link),
+        )
         .route(
             "/subs/{id}/devices",
             axum::routing::get(handlers::admin::get_subscription_devices),
@@ -844,6 +996,14 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
             "/groups/{id}/rotate",
             axum::routing::post(handlers::admin_groups::rotate_group_inbounds),
         )
+        .route(
+            "/groups/{id}/rotate/preview",
+            axum::routing::get(handlers::admin::preview_group_rotation),
+        )
+        .route(
+            "/groups/{id}/sync/preview",
+            axum::routing::get(handlers::admin::preview_group_sync),
+        )
         // Templates Management
         .route(
             "/templates",
@@ -863,10 +1023,154 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
             "/templates/{id}/sync",
             axum::routing::post(handlers::admin_templates::sync_template),
         )
+        .route(
+            "/templates/{id}/sync/preview",
+            axum::routing::get(handlers::admin::preview_template_sync),
+        )
         .route(
             "/templates/{id}/json",
             axum::routing::get(handlers::admin_templates::get_template_json),
         )
+        // Staged template rollouts
+        .route(
+            "/rollouts",
+            axum::routing::get(handlers::admin::get_rollouts_page)
+                .post(handlers::admin::start_rollout),
+        )
+        .route(
+            "/rollouts/{id}/continue",
+            axum::routing::post(handlers::admin::continue_rollout),
+        )
+        .route(
+            "/rollouts/{id}/rollback",
+            axum::routing::post(handlers::admin::rollback_rollout),
+        )
+        // Admin Accounts & Roles
+        .route(
+            "/admins",
+            axum::routing::get(handlers::admin::get_admins_page)
+                .post(handlers::admin::create_admin_account),
+        )
+        .route(
+            "/admins/{id}/role",
+            axum::routing::post(handlers::admin::update_admin_role),
+        )
+        .route(
+            "/admins/{id}",
+            axum::routing::delete(handlers::admin::delete_admin_account),
+        )
+        .route(
+            "/admins/{id}/security/reset",
+            axum::routing::post(handlers::admin::reset_admin_security),
+        )
+        // Audit Trail
+        .route(
+            "/audit",
+            axum::routing::get(handlers::admin::get_audit_page),
+        )
+        .route(
+            "/audit/export",
+            axum::routing::get(handlers::admin::export_audit_log),
+        )
+        // Account Security (own 2FA and sessions)
+        .route(
+            "/security",
+            axum::routing::get(handlers::admin::get_security_page),
+        )
+        .route(
+            "/security/2fa/setup",
+            axum::routing::post(handlers::admin::setup_totp),
+        )
+        .route(
+            "/security/2fa/enable",
+            axum::routing::post(handlers::admin::enable_totp),
+        )
+        .route(
+            "/security/2fa/disable",
+            axum::routing::post(handlers::admin::disable_totp),
+        )
+        .route(
+            "/security/recovery-codes",
+            axum::routing::post(handlers::admin::regenerate_recovery_codes),
+        )
+        .route(
+            "/security/sessions/revoke-others",
+            axum::routing::post(handlers::admin::revoke_other_sessions),
+        )
+        .route(
+            "/security/sessions/{id}/revoke",
+            axum::routing::post(handlers::admin::revoke_session),
+        )
+        // Admin API tokens (own tokens; superadmins see all)
+        .route(
+            "/api-tokens",
+            axum::routing::get(handlers::admin::get_api_tokens_page)
+                .post(handlers::admin::create_api_token),
+        )
+        .route(
+            "/api-tokens/{id}/revoke",
+            axum::routing::post(handlers::admin::revoke_api_token),
+        )
+        // Outgoing event webhooks
+        .route(
+            "/webhooks",
+            axum::routing::get(handlers::admin::get_webhooks_page)
+                .post(handlers::admin::create_webhook),
+        )
+        .route(
+            "/webhooks/{id}/toggle",
+            axum::routing::post(handlers::admin::toggle_webhook),
+        )
+        .route(
+            "/webhooks/{id}/test",
+            axum::routing::post(handlers::admin::test_webhook),
+        )
+        .route(
+            "/webhooks/{id}/rotate-secret",
+            axum::routing::post(handlers::admin::rotate_webhook_secret),
+        )
+        .route(
+            "/webhooks/{id}/delete",
+            axum::routing::post(handlers::admin::delete_webhook),
+        )
+        .route(
+            "/webhooks/deliveries/{id}/retry",
+            axum::routing::post(handlers::admin::retry_webhook_delivery),
+        )
+        // Admin-edited subscription config templates
+        .route(
+            "/sub-templates",
+            axum::routing::get(handlers::admin::get_sub_templates_page)
+                .post(handlers::admin::create_sub_template),
+        )
+        .route(
+            "/sub-templates/preview",
+            axum::routing::post(handlers::admin::preview_sub_template),
+        )
+        .route(
+            "/sub-templates/{id}",
+            axum::routing::get(handlers::admin::get_sub_template_edit)
+                .post(handlers::admin::update_sub_template),
+        )
+        .route(
+            "/sub-templates/{id}/delete",
+            axum::routing::post(handlers::admin::delete_sub_template),
+        )
+        // Client-side multi-hop chains per plan
+        .route(
+            "/chains",
+            axum::routing::get(handlers::admin::get_chain_profiles_page)
+                .post(handlers::admin::create_chain_profile),
+        )
+        .route(
+            "/chains/{id}",
+            axum::routing::get(handlers::admin::get_chain_profile_edit)
+                .post(handlers::admin::update_chain_profile),
+        )
+        .route(
+            "/chains/{id}/delete",
+            axum::routing::post(handlers::admin::delete_chain_profile),
+        )
         // Organization Management (Phase 3)
         .route(
             "/orgs",
@@ -879,6 +1183,47 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
             auth_middleware,
         ));
 
+    // JSON admin endpoints share the admin session and RBAC checks
+    let admin_api_routes = axum::Router::new()
+        .route(
+            "/api/admin/frontends",
+            axum::routing::get(handlers::frontend::list_frontends)
+                .post(handlers::frontend::create_frontend),
+        )
+        .route(
+            "/caramba-api/admin/frontends",
+            axum::routing::get(handlers::frontend::list_frontends)
+                .post(handlers::frontend::create_frontend),
+        )
+        .route(
+            "/api/admin/frontends/by-region/{region}",
+            axum::routing::get(handlers::frontend::get_active_frontends),
+        )
+        .route(
+            "/caramba-api/admin/frontends/by-region/{region}",
+            axum::routing::get(handlers::frontend::get_active_frontends),
+        )
+        .route(
+            "/api/admin/frontends/{id}",
+            axum::routing::delete(handlers::frontend::delete_frontend),
+        )
+        .route(
+            "/caramba-api/admin/frontends/{id}",
+            axum::routing::delete(handlers::frontend::delete_frontend),
+        )
+        .route(
+            "/api/admin/frontends/{id}/rotate-token",
+            axum::routing::post(handlers::frontend::rotate_token),
+        )
+        .route(
+            "/caramba-api/admin/frontends/{id}/rotate-token",
+            axum::routing::post(handlers::frontend::rotate_token),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth_middleware,
+        ));
+
     let admin_path = std::env::var("ADMIN_PATH").unwrap_or_else(|_| "/admin".to_string());
     // Ensure leading slash
     let admin_path = if admin_path.starts_with('/') {
@@ -914,6 +1259,10 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
             &format!("{}/login", admin_path),
             axum::routing::get(handlers::admin::get_login).post(handlers::admin::login),
         )
+        .route(
+            &format!("{}/login/2fa", admin_path),
+            axum::routing::post(handlers::admin::login_2fa),
+        )
         // Serve Downloads (for frontend binaries)
         .nest_service("/downloads", ServeDir::new("apps/caramba-panel/downloads"))
         // Serve Assets (Public)
@@ -1117,6 +1466,54 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
             "/caramba-api/v2/bot/subs/{id}/activate",
             axum::routing::post(handlers::api::bot::activate_sub),
         )
+        .route(
+            "/api/v2/bot/subs/{id}/rotate-link",
+            axum::routing::post(handlers::api::bot::rotate_sub_link),
+        )
+        .route(
+            "/caramba-api/v2/bot/subs/{id}/rotate-link",
+            axum::routing::post(handlers::api::bot::rotate_sub_link),
+        )
+        .route(
+            "/api/v2/bot/subs/{id}/devices",
+            axum::routing::get(handlers::api::bot::get_sub_devices),
+        )
+        .route(
+            "/caramba-api/v2/bot/subs/{id}/devices",
+            axum::routing::get(handlers::api::bot::get_sub_devices),
+        )
+        .route(
+            "/api/v2/bot/subs/{id}/devices/{device_id}/revoke",
+            axum::routing::post(handlers::api::bot::revoke_sub_device),
+        )
+        .route(
+            "/caramba-api/v2/bot/subs/{id}/devices/{device_id}/revoke",
+            axum::routing::post(handlers::api::bot::revoke_sub_device),
+        )
+        .route(
+            "/api/v2/bot/subs/{id}/wireguard",
+            axum::routing::get(handlers::api::bot::get_sub_wireguard),
+        )
+        .route(
+            "/caramba-api/v2/bot/subs/{id}/wireguard",
+            axum::routing::get(handlers::api::bot::get_sub_wireguard),
+        )
+        .route(
+            "/api/v2/bot/subs/{id}/wireguard/{inbound_id}/qr",
+            axum::routing::get(handlers::api::bot::get_sub_wireguard_qr),
+        )
+        .route(
+            "/caramba-api/v2/bot/subs/{id}/wireguard/{inbound_id}/qr",
+            axum::routing::get(handlers::api::bot::get_sub_wireguard_qr),
+        )
+        .route(
+            "/api/v2/bot/subs/{id}/qr",
+            axum::routing::get(handlers::api::bot::get_sub_qr),
+        )
+        .route(
+            "/caramba-api/v2/bot/subs/{id}/qr",
+            axum::routing::get(handlers::api::bot::get_sub_qr),
+        )
         .route(
             "/api/v2/client/recommended",
             axum::routing::get(api::v2::client::get_recommended_nodes),
@@ -1128,11 +1525,22 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
         // Client API
         .nest("/api/client", api::client::routes(state.clone()))
         .nest("/caramba-api/client", api::client::routes(state.clone()))
+        // Admin API v1 (scoped bearer tokens)
+        .nest("/api/admin/v1", api::admin::routes(state.clone()))
+        .nest("/caramba-api/admin/v1", api::admin::routes(state.clone()))
         // Public Subscription URL endpoint
         .route(
             "/sub/{uuid}",
             axum::routing::get(subscription::subscription_handler),
         )
+        .route(
+            "/sub/{uuid}/qr",
+            axum::routing::get(subscription::qr_handler),
+        )
+        .route(
+            "/sub/{uuid}/wg/{inbound_id}",
+            axum::routing::get(subscription::wireguard_config_handler),
+        )
         // Local Mini App Serving
         .route("/app", axum::routing::get(handlers::local_app::serve_app))
         .route(
@@ -1156,6 +1564,14 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
             "/caramba-api/internal/subscriptions/{uuid}",
             axum::routing::get(handlers::api::internal::get_subscription),
         )
+        .route(
+            "/api/internal/subscriptions/{uuid}/config",
+            axum::routing::get(handlers::api::internal::get_config_input),
+        )
+        .route(
+            "/caramba-api/internal/subscriptions/{uuid}/config",
+            axum::routing::get(handlers::api::internal::get_config_input),
+        )
         .route(
             "/api/internal/users/{id}/keys",
             axum::routing::get(handlers::api::internal::get_user_keys),
@@ -1189,40 +1605,7 @@ async fn run_server(pool: sqlx::PgPool, ssh_public_key: String) -> Result<()> {
             axum::routing::post(handlers::api::internal::report_worker_update),
         )
         // Frontend API Routes (Must be top level to match /api/admin/frontends)
-        .route(
-            "/api/admin/frontends",
-            axum::routing::get(handlers::frontend::list_frontends)
-                .post(handlers::frontend::create_frontend),
-        )
-        .route(
-            "/caramba-api/admin/frontends",
-            axum::routing::get(handlers::frontend::list_frontends)
-                .post(handlers::frontend::create_frontend),
-        )
-        .route(
-            "/api/admin/frontends/by-region/{region}",
-            axum::routing::get(handlers::frontend::get_active_frontends),
-        )
-        .route(
-            "/caramba-api/admin/frontends/by-region/{region}",
-            axum::routing::get(handlers::frontend::get_active_frontends),
-        )
-        .route(
-            "/api/admin/frontends/{id}",
-            axum::routing::delete(handlers::frontend::delete_frontend),
-        )
-        .route(
-            "/caramba-api/admin/frontends/{id}",
-            axum::routing::delete(handlers::frontend::delete_frontend),
-        )
-        .route(
-            "/api/admin/frontends/{id}/rotate-token",
-            axum::routing::post(handlers::frontend::rotate_token),
-        )
-        .route(
-            "/caramba-api/admin/frontends/{id}/rotate-token",
-            axum::routing::post(handlers::frontend::rotate_token),
-        )
+        .merge(admin_api_routes)
         .route(
             "/api/admin/frontends/{domain}/heartbeat",
             axum::routing::post(handlers::frontend::frontend_heartbeat),
@@ -0,0 +1,593 @@
+use anyhow::{Context, Result};
+use caramba_db::models::admin::{AdminCredentials, AdminSession};
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::services::redis_service::RedisService;
+
+/// Failed password/2FA attempts before an account is temporarily locked.
+pub const MAX_FAILED_ATTEMPTS: i32 = 5;
+pub const LOCKOUT_MINUTES: i64 = 15;
+/// Per-IP login attempts allowed inside `LOGIN_RATE_WINDOW_SECS`.
+pub const LOGIN_RATE_LIMIT: usize = 10;
+pub const LOGIN_RATE_WINDOW_SECS: usize = 15 * 60;
+pub const SESSION_TTL_SECS: usize = 24 * 60 * 60;
+/// Time allowed to enter a TOTP code after a correct password.
+pub const PENDING_2FA_TTL_SECS: usize = 5 * 60;
+
+const TOTP_STEP_SECS: u64 = 30;
+const TOTP_DIGITS: u32 = 6;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+pub enum LoginCheck {
+    /// Password accepted, no second factor configured.
+    Success(AdminCredentials),
+    /// Password accepted, TOTP code still required.
+    NeedsTotp(AdminCredentials),
+    Locked(chrono::DateTime<Utc>),
+    Invalid,
+}
+
+pub struct AdminSecurityService {
+    pool: PgPool,
+    redis: Arc<RedisService>,
+}
+
+impl AdminSecurityService {
+    pub fn new(pool: PgPool, redis: Arc<RedisService>) -> Self {
+        Self { pool, redis }
+    }
+
+    async fn get_credentials(&self, username: &str) -> Result<Option<AdminCredentials>> {
+        sqlx::query_as::<_, AdminCredentials>(
+            r#"
+            SELECT id, username, password_hash, totp_secret, totp_enabled,
+                   failed_login_attempts, locked_until
+            FROM admins WHERE username = $1
+            "#,
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to load admin credentials")
+    }
+
+    /// Per-IP throttle in front of password checks. Returns false when exceeded.
+    pub async fn allow_login_attempt(&self, ip: &str) -> bool {
+        self.redis
+            .check_rate_limit(
+                &format!("admin_login_rate:{}", ip),
+                LOGIN_RATE_LIMIT,
+                LOGIN_RATE_WINDOW_SECS,
+            )
+            .await
+            .unwrap_or(true)
+    }
+
+    pub async fn check_password(&self, username: &str, password: &str) -> Result<LoginCheck> {
+        let Some(admin) = self.get_credentials(username).await? else {
+            return Ok(LoginCheck::Invalid);
+        };
+
+        if let Some(until) = admin.locked_until
+            && until > Utc::now()
+        {
+            return Ok(LoginCheck::Locked(until));
+        }
+
+        if !bcrypt::verify(password, &admin.password_hash).unwrap_or(false) {
+            return Ok(match self.register_failure(admin.id).await? {
+                Some(until) => LoginCheck::Locked(until),
+                None => LoginCheck::Invalid,
+            });
+        }
+
+        if admin.totp_enabled && admin.totp_secret.is_some() {
+            Ok(LoginCheck::NeedsTotp(admin))
+        } else {
+            Ok(LoginCheck::Success(admin))
+        }
+    }
+
+    /// Verify a TOTP code or an unused recovery code for a pending 2FA login.
+    pub async fn check_second_factor(&self, username: &str, code: &str) -> Result<LoginCheck> {
+        let Some(admin) = self.get_credentials(username).await? else {
+            return Ok(LoginCheck::Invalid);
+        };
+
+        if let Some(until) = admin.locked_until
+            && until > Utc::now()
+        {
+            return Ok(LoginCheck::Locked(until));
+        }
+
+        let totp_ok = admin
+            .totp_secret
+            .as_deref()
+            .is_some_and(|secret| verify_totp(secret, code, Utc::now().timestamp() as u64));
+
+        if totp_ok || self.consume_recovery_code(admin.id, code).await? {
+            Ok(LoginCheck::Success(admin))
+        } else {
+            Ok(match self.register_failure(admin.id).await? {
+                Some(until) => LoginCheck::Locked(until),
+                None => LoginCheck::Invalid,
+            })
+        }
+    }
+
+    /// Record a failed attempt; returns the lock expiry when the account got locked.
+    async fn register_failure(&self, admin_id: i64) -> Result<Option<chrono::DateTime<Utc>>> {
+        let attempts: i32 = sqlx::query_scalar(
+            "UPDATE admins SET failed_login_attempts = failed_login_attempts + 1 WHERE id = $1 RETURNING failed_login_attempts",
+        )
+        .bind(admin_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if attempts >= MAX_FAILED_ATTEMPTS {
+            let until = Utc::now() + Duration::minutes(LOCKOUT_MINUTES);
+            sqlx::query(
+                "UPDATE admins SET locked_until = $1, failed_login_attempts = 0 WHERE id = $2",
+            )
+            .bind(until)
+            .bind(admin_id)
+            .execute(&self.pool)
+            .await?;
+            return Ok(Some(until));
+        }
+        Ok(None)
+    }
+
+    pub async fn clear_lockout(&self, admin_id: i64) -> Result<()> {
+        sqlx::query(
+            "UPDATE admins SET failed_login_attempts = 0, locked_until = NULL WHERE id = $1",
+        )
+        .bind(admin_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // --- Pending 2FA logins ---
+
+    pub async fn create_pending_login(&self, username: &str) -> Result<String> {
+        let token = uuid::Uuid::new_v4().to_string();
+        self.redis
+            .set(
+                &format!("admin_2fa_pending:{}", token),
+                username,
+                PENDING_2FA_TTL_SECS,
+            )
+            .await?;
+        Ok(token)
+    }
+
+    pub async fn get_pending_login(&self, token: &str) -> Option<String> {
+        self.redis
+            .get(&format!("admin_2fa_pending:{}", token))
+            .await
+            .ok()
+            .flatten()
+    }
+
+    pub async fn finish_pending_login(&self, token: &str) {
+        let _ = self
+            .redis
+            .del(&format!("admin_2fa_pending:{}", token))
+            .await;
+    }
+
+    // --- Sessions ---
+
+    /// Create a tracked session and return the raw cookie token.
+    pub async fn create_session(
+        &self,
+        admin: &AdminCredentials,
+        ip: &str,
+        user_agent: Option<&str>,
+    ) -> Result<String> {
+        let token = uuid::Uuid::new_v4().to_string();
+        let token_hash = hash_token(&token);
+        let expires_at = Utc::now() + Duration::seconds(SESSION_TTL_SECS as i64);
+
+        sqlx::query(
+            r#"
+            INSERT INTO admin_sessions (admin_id, token_hash, ip_address, user_agent, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(admin.id)
+        .bind(&token_hash)
+        .bind(ip)
+        .bind(user_agent)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record admin session")?;
+
+        self.redis
+            .set(
+                &session_redis_key(&token),
+                &admin.username,
+                SESSION_TTL_SECS,
+            )
+            .await?;
+
+        self.clear_lockout(admin.id).await?;
+        sqlx::query("UPDATE admins SET last_login_at = CURRENT_TIMESTAMP WHERE id = $1")
+            .bind(admin.id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(token)
+    }
+
+    /// Bump last-seen at most once a minute per session.
+    pub async fn touch_session(&self, token: &str, ip: &str) {
+        let _ = sqlx::query(
+            r#"
+            UPDATE admin_sessions SET last_seen_at = CURRENT_TIMESTAMP, ip_address = $2
+            WHERE token_hash = $1 AND last_seen_at < CURRENT_TIMESTAMP - INTERVAL '1 minute'
+            "#,
+        )
+        .bind(hash_token(token))
+        .bind(ip)
+        .execute(&self.pool)
+        .await;
+    }
+
+    pub async fn session_id_for_token(&self, token: &str) -> Option<i64> {
+        sqlx::query_scalar("SELECT id FROM admin_sessions WHERE token_hash = $1")
+            .bind(hash_token(token))
+            .fetch_optional(&self.pool)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    pub async fn list_active_sessions(&self, admin_id: i64) -> Result<Vec<AdminSession>> {
+        sqlx::query_as::<_, AdminSession>(
+            r#"
+            SELECT id, admin_id, ip_address, user_agent, created_at, last_seen_at, expires_at
+            FROM admin_sessions
+            WHERE admin_id = $1 AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+            ORDER BY last_seen_at DESC
+            "#,
+        )
+        .bind(admin_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list admin sessions")
+    }
+
+    /// Revoke a session owned by `admin_id`. Returns false if nothing matched.
+    pub async fn revoke_session(&self, admin_id: i64, session_id: i64) -> Result<bool> {
+        let token_hash: Option<String> = sqlx::query_scalar(
+            r#"
+            UPDATE admin_sessions SET revoked_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND admin_id = $2 AND revoked_at IS NULL
+            RETURNING token_hash
+            "#,
+        )
+        .bind(session_id)
+        .bind(admin_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match token_hash {
+            Some(hash) => {
+                self.redis.del(&format!("session:{}", hash)).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Revoke every session of an admin except `keep_session_id`.
+    pub async fn revoke_all_sessions(
+        &self,
+        admin_id: i64,
+        keep_session_id: Option<i64>,
+    ) -> Result<u64> {
+        let hashes: Vec<String> = sqlx::query_scalar(
+            r#"
+            UPDATE admin_sessions SET revoked_at = CURRENT_TIMESTAMP
+            WHERE admin_id = $1 AND revoked_at IS NULL AND ($2::BIGINT IS NULL OR id <> $2)
+            RETURNING token_hash
+            "#,
+        )
+        .bind(admin_id)
+        .bind(keep_session_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        for hash in &hashes {
+            let _ = self.redis.del(&format!("session:{}", hash)).await;
+        }
+        Ok(hashes.len() as u64)
+    }
+
+    /// Drop the session behind a cookie token (logout).
+    pub async fn end_session(&self, token: &str) {
+        let _ = self.redis.del(&session_redis_key(token)).await;
+        let _ = sqlx::query(
+            "UPDATE admin_sessions SET revoked_at = CURRENT_TIMESTAMP WHERE token_hash = $1 AND revoked_at IS NULL",
+        )
+        .bind(hash_token(token))
+        .execute(&self.pool)
+        .await;
+    }
+
+    // --- TOTP enrollment ---
+
+    /// Store a fresh (not yet enabled) TOTP secret and return it base32-encoded.
+    pub async fn begin_totp_setup(&self, admin_id: i64) -> Result<String> {
+        let secret = generate_totp_secret();
+        sqlx::query("UPDATE admins SET totp_secret = $1, totp_enabled = FALSE WHERE id = $2")
+            .bind(&secret)
+            .bind(admin_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(secret)
+    }
+
+    /// Enable 2FA once the admin proves possession; returns fresh recovery codes.
+    pub async fn confirm_totp_setup(
+        &self,
+        admin_id: i64,
+        code: &str,
+    ) -> Result<Option<Vec<String>>> {
+        let secret: Option<String> =
+            sqlx::query_scalar("SELECT totp_secret FROM admins WHERE id = $1")
+                .bind(admin_id)
+                .fetch_one(&self.pool)
+                .await?;
+
+        let Some(secret) = secret else {
+            return Ok(None);
+        };
+        if !verify_totp(&secret, code, Utc::now().timestamp() as u64) {
+            return Ok(None);
+        }
+
+        sqlx::query("UPDATE admins SET totp_enabled = TRUE WHERE id = $1")
+            .bind(admin_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(Some(self.regenerate_recovery_codes(admin_id).await?))
+    }
+
+    pub async fn verify_current_totp(&self, admin_id: i64, code: &str) -> Result<bool> {
+        let secret: Option<String> = sqlx::query_scalar(
+            "SELECT totp_secret FROM admins WHERE id = $1 AND totp_enabled = TRUE",
+        )
+        .bind(admin_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .flatten();
+
+        Ok(secret.is_some_and(|s| verify_totp(&s, code, Utc::now().timestamp() as u64)))
+    }
+
+    pub async fn disable_totp(&self, admin_id: i64) -> Result<()> {
+        sqlx::query("UPDATE admins SET totp_secret = NULL, totp_enabled = FALSE WHERE id = $1")
+            .bind(admin_id)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM admin_recovery_codes WHERE admin_id = $1")
+            .bind(admin_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn is_totp_enabled(&self, admin_id: i64) -> bool {
+        sqlx::query_scalar("SELECT totp_enabled FROM admins WHERE id = $1")
+            .bind(admin_id)
+            .fetch_one(&self.pool)
+            .await
+            .unwrap_or(false)
+    }
+
+    pub async fn remaining_recovery_codes(&self, admin_id: i64) -> i64 {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) FROM admin_recovery_codes WHERE admin_id = $1 AND used_at IS NULL",
+        )
+        .bind(admin_id)
+        .fetch_one(&self.pool)
+        .await
+        .unwrap_or(0)
+    }
+
+    pub async fn regenerate_recovery_codes(&self, admin_id: i64) -> Result<Vec<String>> {
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| generate_recovery_code())
+            .collect();
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM admin_recovery_codes WHERE admin_id = $1")
+            .bind(admin_id)
+            .execute(&mut *tx)
+            .await?;
+        for code in &codes {
+            sqlx::query("INSERT INTO admin_recovery_codes (admin_id, code_hash) VALUES ($1, $2)")
+                .bind(admin_id)
+                .bind(hash_token(&normalize_recovery_code(code)))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(codes)
+    }
+
+    async fn consume_recovery_code(&self, admin_id: i64, code: &str) -> Result<bool> {
+        let normalized = normalize_recovery_code(code);
+        if normalized.len() != 8 {
+            return Ok(false);
+        }
+        let res = sqlx::query(
+            r#"
+            UPDATE admin_recovery_codes SET used_at = CURRENT_TIMESTAMP
+            WHERE admin_id = $1 AND code_hash = $2 AND used_at IS NULL
+            "#,
+        )
+        .bind(admin_id)
+        .bind(hash_token(&normalized))
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+/// Redis key for a session token. Keyed by hash so a session can be revoked
+/// from its DB row without storing the raw token.
+pub fn session_redis_key(token: &str) -> String {
+    format!("session:{}", hash_token(token))
+}
+
+fn hash_token(token: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(token.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn generate_totp_secret() -> String {
+    base32_encode(&rand::random::<[u8; 20]>())
+}
+
+fn generate_recovery_code() -> String {
+    let raw = hex::encode(rand::random::<[u8; 4]>());
+    format!("{}-{}", &raw[..4], &raw[4..])
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// otpauth:// URI understood by authenticator apps.
+pub fn totp_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&digits={}&period={}",
+        urlencoding::encode(issuer),
+        urlencoding::encode(account),
+        secret,
+        urlencoding::encode(issuer),
+        TOTP_DIGITS,
+        TOTP_STEP_SECS
+    )
+}
+
+/// RFC 4226 HOTP value for `counter`.
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let bin = ((digest[offset] as u32 & 0x7f) << 24)
+        | ((digest[offset + 1] as u32) << 16)
+        | ((digest[offset + 2] as u32) << 8)
+        | (digest[offset + 3] as u32);
+    bin % 10u32.pow(TOTP_DIGITS)
+}
+
+/// RFC 6238 check allowing one step of clock drift either way.
+pub fn verify_totp(secret_b32: &str, code: &str, unix_time: u64) -> bool {
+    let code = code.trim().replace(' ', "");
+    if code.len() != TOTP_DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    let Ok(expected) = code.parse::<u32>() else {
+        return false;
+    };
+    let Some(key) = base32_decode(secret_b32) else {
+        return false;
+    };
+
+    let step = unix_time / TOTP_STEP_SECS;
+    [step.saturating_sub(1), step, step + 1]
+        .iter()
+        .any(|&counter| hotp(&key, counter) == expected)
+}
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in input.chars().filter(|c| *c != '=' && !c.is_whitespace()) {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base32_decode, base32_encode, hotp, normalize_recovery_code, verify_totp};
+
+    // RFC 6238 Appendix B shared secret "12345678901234567890"
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn hotp_matches_rfc6238_vectors() {
+        // 8-digit vectors truncated to our 6 digits
+        assert_eq!(hotp(RFC_SECRET, 59 / 30), 287082);
+        assert_eq!(hotp(RFC_SECRET, 1111111109 / 30), 81804);
+        assert_eq!(hotp(RFC_SECRET, 1234567890 / 30), 5924);
+    }
+
+    #[test]
+    fn base32_roundtrip() {
+        let encoded = base32_encode(RFC_SECRET);
+        assert_eq!(encoded, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(base32_decode(&encoded).unwrap(), RFC_SECRET);
+    }
+
+    #[test]
+    fn verify_totp_accepts_adjacent_steps_only() {
+        let secret = base32_encode(RFC_SECRET);
+        assert!(verify_totp(&secret, "287082", 59));
+        assert!(verify_totp(&secret, "287 082", 59 + 30));
+        assert!(!verify_totp(&secret, "287082", 59 + 90));
+        assert!(!verify_totp(&secret, "12345", 59));
+    }
+
+    #[test]
+    fn recovery_codes_normalize_dashes_and_case() {
+        assert_eq!(normalize_recovery_code(" AB12-cd34 "), "ab12cd34");
+    }
+}
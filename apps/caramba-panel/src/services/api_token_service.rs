@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use caramba_db::models::admin::Admin;
+use caramba_db::models::api_token::{AdminApiToken, ApiScope};
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+/// Every token starts with this, so leaked tokens are easy to grep for.
+pub const TOKEN_PREFIX: &str = "cba_";
+/// Characters of the token kept in plain text for display.
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+const TOKEN_COLUMNS: &str = "t.id, t.admin_id, a.username AS admin_username, t.name, t.token_prefix, t.scopes, t.created_at, t.expires_at, t.last_used_at, t.last_used_ip, t.revoked_at";
+
+pub struct ApiTokenService {
+    pool: PgPool,
+}
+
+impl ApiTokenService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Create a token and return its id with the raw token. The raw token is
+    /// not stored and cannot be shown again.
+    pub async fn create(
+        &self,
+        admin_id: i64,
+        name: &str,
+        scopes: &[ApiScope],
+        expires_in_days: Option<i64>,
+    ) -> Result<(i64, String)> {
+        let token = generate_token();
+        let scopes: Vec<String> = scopes.iter().map(|s| s.as_str().to_string()).collect();
+        let expires_at = expires_in_days.map(|days| Utc::now() + Duration::days(days));
+
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO admin_api_tokens (admin_id, name, token_prefix, token_hash, scopes, expires_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+        )
+        .bind(admin_id)
+        .bind(name.trim())
+        .bind(&token[..DISPLAY_PREFIX_LEN])
+        .bind(hash_token(&token))
+        .bind(&scopes)
+        .bind(expires_at)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to create API token")?;
+
+        Ok((id, token))
+    }
+
+    /// Tokens owned by `admin_id`, or every token when `None`.
+    pub async fn list(&self, admin_id: Option<i64>) -> Result<Vec<AdminApiToken>> {
+        sqlx::query_as::<_, AdminApiToken>(&format!(
+            "SELECT {} FROM admin_api_tokens t JOIN admins a ON a.id = t.admin_id WHERE ($1::BIGINT IS NULL OR t.admin_id = $1) ORDER BY t.revoked_at IS NOT NULL, t.created_at DESC",
+            TOKEN_COLUMNS
+        ))
+        .bind(admin_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list API tokens")
+    }
+
+    /// Revoke a token. With `owner` set, only that admin's token is touched.
+    pub async fn revoke(&self, token_id: i64, owner: Option<i64>) -> Result<bool> {
+        let res = sqlx::query(
+            "UPDATE admin_api_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE id = $1 AND revoked_at IS NULL AND ($2::BIGINT IS NULL OR admin_id = $2)",
+        )
+        .bind(token_id)
+        .bind(owner)
+        .execute(&self.pool)
+        .await
+        .context("Failed to revoke API token")?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Resolve a raw bearer token to the token row and its owning admin.
+    /// Returns `None` for unknown, revoked or expired tokens.
+    pub async fn authenticate(&self, token: &str, ip: &str) -> Option<(AdminApiToken, Admin)> {
+        if !token.starts_with(TOKEN_PREFIX) {
+            return None;
+        }
+
+        let api_token = sqlx::query_as::<_, AdminApiToken>(&format!(
+            "SELECT {} FROM admin_api_tokens t JOIN admins a ON a.id = t.admin_id WHERE t.token_hash = $1",
+            TOKEN_COLUMNS
+        ))
+        .bind(hash_token(token))
+        .fetch_optional(&self.pool)
+        .await
+        .ok()
+        .flatten()
+        .filter(|t| t.is_active())?;
+
+        let admin = sqlx::query_as::<_, Admin>(
+            "SELECT id, username, role, created_at FROM admins WHERE id = $1",
+        )
+        .bind(api_token.admin_id)
+        .fetch_optional(&self.pool)
+        .await
+        .ok()
+        .flatten()?;
+
+        let _ = sqlx::query(
+            "UPDATE admin_api_tokens SET last_used_at = CURRENT_TIMESTAMP, last_used_ip = $1 WHERE id = $2",
+        )
+        .bind(ip)
+        .bind(api_token.id)
+        .execute(&self.pool)
+        .await;
+
+        Some((api_token, admin))
+    }
+}
+
+fn generate_token() -> String {
+    format!(
+        "{}{}",
+        TOKEN_PREFIX,
+        hex::encode(rand::random::<[u8; 24]>())
+    )
+}
+
+fn hash_token(token: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(token.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_tokens_are_prefixed_and_unique() {
+        let a = generate_token();
+        let b = generate_token();
+        assert!(a.starts_with(TOKEN_PREFIX));
+        assert_eq!(a.len(), TOKEN_PREFIX.len() + 48);
+        assert_ne!(a, b);
+        assert_ne!(hash_token(&a), hash_token(&b));
+    }
+}
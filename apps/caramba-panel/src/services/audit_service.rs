@@ -0,0 +1,411 @@
+use anyhow::{Context, Result};
+use caramba_db::models::audit::AuditLogEntry;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::error;
+
+/// Max rows returned by a single export.
+pub const EXPORT_LIMIT: i64 = 10_000;
+
+const REDACTED: &str = "***";
+
+/// The admin behind the current request.
+///
+/// Inserted into request extensions by the auth middleware. Handlers that
+/// record a detailed entry mark it, so the middleware does not add a second,
+/// generic entry for the same request.
+#[derive(Clone, Debug)]
+pub struct AuditActor {
+    pub admin_id: i64,
+    pub username: String,
+    pub ip: String,
+    recorded: Arc<AtomicBool>,
+}
+
+impl AuditActor {
+    pub fn new(admin_id: i64, username: String, ip: String) -> Self {
+        Self {
+            admin_id,
+            username,
+            ip,
+            recorded: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn has_recorded(&self) -> bool {
+        self.recorded.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AuditFilter {
+    pub admin: Option<String>,
+    pub action: Option<String>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    /// Inclusive lower bound, `YYYY-MM-DD`.
+    pub from: Option<String>,
+    /// Inclusive upper bound, `YYYY-MM-DD`.
+    pub to: Option<String>,
+}
+
+pub struct AuditService {
+    pool: PgPool,
+}
+
+impl AuditService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record a change made by `actor`. Secrets in `before`/`after` are masked
+    /// and only differing fields are stored; an update that changed nothing is
+    /// skipped. Failures are logged, never propagated to the caller.
+    pub async fn record(
+        &self,
+        actor: &AuditActor,
+        action: &str,
+        entity_type: &str,
+        entity_id: impl std::fmt::Display,
+        before: Option<Value>,
+        after: Option<Value>,
+    ) {
+        actor.recorded.store(true, Ordering::Relaxed);
+
+        let is_update = before.is_some() && after.is_some();
+        // Diff before masking so a rotated secret still shows up as changed
+        let changes = redact(&json_diff(
+            &before.unwrap_or(Value::Null),
+            &after.unwrap_or(Value::Null),
+        ));
+        if is_update && changes.as_object().is_some_and(|m| m.is_empty()) {
+            return;
+        }
+
+        let entity_id = entity_id.to_string();
+        self.insert(
+            actor,
+            action,
+            entity_type,
+            Some(entity_id.as_str()).filter(|s| !s.is_empty()),
+            Some(changes),
+        )
+        .await;
+    }
+
+    /// Fallback entry for a successful mutating request whose handler did not
+    /// record anything itself. `rel_path` is relative to the admin prefix.
+    pub async fn record_request(&self, actor: &AuditActor, method: &str, rel_path: &str) {
+        let (route, entity_type, entity_id) = describe_path(rel_path);
+        self.insert(
+            actor,
+            &format!("{} {}", method, route),
+            &entity_type,
+            entity_id.as_deref(),
+            None,
+        )
+        .await;
+    }
+
+    async fn insert(
+        &self,
+        actor: &AuditActor,
+        action: &str,
+        entity_type: &str,
+        entity_id: Option<&str>,
+        changes: Option<Value>,
+    ) {
+        let res = sqlx::query(
+            r#"
+            INSERT INTO admin_audit_log (admin_id, admin_username, action, entity_type, entity_id, changes, ip_address)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(actor.admin_id)
+        .bind(&actor.username)
+        .bind(action)
+        .bind(entity_type)
+        .bind(entity_id)
+        .bind(changes)
+        .bind(&actor.ip)
+        .execute(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "Failed to write audit entry '{}' by '{}': {}",
+                action, actor.username, e
+            );
+        }
+    }
+
+    pub async fn list(
+        &self,
+        filter: &AuditFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuditLogEntry>> {
+        let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
+            "SELECT id, admin_id, admin_username, action, entity_type, entity_id, changes, ip_address, created_at FROM admin_audit_log WHERE 1=1",
+        );
+
+        if let Some(admin) = non_empty(&filter.admin) {
+            qb.push(" AND admin_username = ")
+                .push_bind(admin.to_string());
+        }
+        if let Some(action) = non_empty(&filter.action) {
+            qb.push(" AND action ILIKE ")
+                .push_bind(format!("%{}%", action));
+        }
+        if let Some(entity_type) = non_empty(&filter.entity_type) {
+            qb.push(" AND entity_type = ")
+                .push_bind(entity_type.to_string());
+        }
+        if let Some(entity_id) = non_empty(&filter.entity_id) {
+            qb.push(" AND entity_id = ")
+                .push_bind(entity_id.to_string());
+        }
+        if let Some(from) = parse_day(&filter.from) {
+            qb.push(" AND created_at >= ").push_bind(from);
+        }
+        if let Some(to) = parse_day(&filter.to) {
+            qb.push(" AND created_at < ")
+                .push_bind(to + chrono::Duration::days(1));
+        }
+
+        qb.push(" ORDER BY created_at DESC, id DESC LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+
+        qb.build_query_as::<AuditLogEntry>()
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to load audit log")
+    }
+
+    /// Distinct admins and entity types, for the filter dropdowns.
+    pub async fn filter_options(&self) -> (Vec<String>, Vec<String>) {
+        let admins = sqlx::query_scalar(
+            "SELECT DISTINCT admin_username FROM admin_audit_log ORDER BY admin_username",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .unwrap_or_default();
+        let entity_types = sqlx::query_scalar(
+            "SELECT DISTINCT entity_type FROM admin_audit_log ORDER BY entity_type",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .unwrap_or_default();
+        (admins, entity_types)
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn parse_day(value: &Option<String>) -> Option<DateTime<Utc>> {
+    let day = chrono::NaiveDate::parse_from_str(non_empty(value)?, "%Y-%m-%d").ok()?;
+    Some(day.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+/// Render entries as CSV (RFC 4180 quoting).
+pub fn to_csv(entries: &[AuditLogEntry]) -> String {
+    let mut out = String::from("id,created_at,admin,action,entity_type,entity_id,ip,changes\n");
+    for e in entries {
+        let row = [
+            e.id.to_string(),
+            e.created_at.to_rfc3339(),
+            e.admin_username.clone(),
+            e.action.clone(),
+            e.entity_type.clone(),
+            e.entity_id.clone().unwrap_or_default(),
+            e.ip_address.clone().unwrap_or_default(),
+            e.changes
+                .as_ref()
+                .map(|c| c.to_string())
+                .unwrap_or_default(),
+        ];
+        let line: Vec<String> = row.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&line.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Field-level diff of two JSON values.
+///
+/// Objects are compared key by key (one level deep) and produce
+/// `{"key": {"before": .., "after": ..}}` for differing keys only. Anything
+/// else is treated as a single value under the `"value"` key.
+pub fn json_diff(before: &Value, after: &Value) -> Value {
+    let empty = Map::new();
+    let (b, a) = match (before, after) {
+        (Value::Object(b), Value::Object(a)) => (b, a),
+        (Value::Object(b), Value::Null) => (b, &empty),
+        (Value::Null, Value::Object(a)) => (&empty, a),
+        _ if before == after => return Value::Object(Map::new()),
+        _ => {
+            let mut m = Map::new();
+            m.insert("value".to_string(), change(before, after));
+            return Value::Object(m);
+        }
+    };
+
+    let mut out = Map::new();
+    for key in b.keys().chain(a.keys().filter(|k| !b.contains_key(*k))) {
+        let old = b.get(key).unwrap_or(&Value::Null);
+        let new = a.get(key).unwrap_or(&Value::Null);
+        if old != new {
+            out.insert(key.clone(), change(old, new));
+        }
+    }
+    Value::Object(out)
+}
+
+fn change(before: &Value, after: &Value) -> Value {
+    serde_json::json!({ "before": before, "after": after })
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let k = key.to_ascii_lowercase().replace(['_', '-'], "");
+    ["secret", "password", "token", "priv", "apikey"]
+        .iter()
+        .any(|needle| k.contains(needle))
+}
+
+/// Mask values stored under secret-looking keys, recursively.
+pub fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| {
+                    let v = if is_secret_key(k) && !v.is_null() {
+                        Value::String(REDACTED.to_string())
+                    } else {
+                        redact(v)
+                    };
+                    (k.clone(), v)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Route template, entity type and entity id for an admin path,
+/// e.g. `/nodes/5/sync` -> (`/nodes/{id}/sync`, `nodes`, `Some("5")`).
+pub fn describe_path(rel_path: &str) -> (String, String, Option<String>) {
+    let segments: Vec<&str> = rel_path.split('/').filter(|s| !s.is_empty()).collect();
+    let entity_id = segments
+        .iter()
+        .find(|s| s.parse::<i64>().is_ok())
+        .map(|s| s.to_string());
+    let route = segments
+        .iter()
+        .map(|s| if s.parse::<i64>().is_ok() { "{id}" } else { s })
+        .collect::<Vec<_>>()
+        .join("/");
+    let entity_type = segments
+        .iter()
+        .find(|s| !matches!(**s, "api" | "caramba-api" | "admin"))
+        .unwrap_or(&"panel")
+        .to_string();
+    (format!("/{}", route), entity_type, entity_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv_field, describe_path, json_diff, redact};
+    use serde_json::json;
+
+    #[test]
+    fn diff_keeps_only_changed_fields() {
+        let before = json!({"balance": 100, "is_banned": false, "name": "a"});
+        let after = json!({"balance": 250, "is_banned": false, "name": "a"});
+        assert_eq!(
+            json_diff(&before, &after),
+            json!({"balance": {"before": 100, "after": 250}})
+        );
+        assert_eq!(json_diff(&before, &before), json!({}));
+    }
+
+    #[test]
+    fn diff_of_create_and_delete_lists_every_field() {
+        let obj = json!({"id": 3, "name": "Pro"});
+        assert_eq!(
+            json_diff(&serde_json::Value::Null, &obj),
+            json!({"id": {"before": null, "after": 3}, "name": {"before": null, "after": "Pro"}})
+        );
+        assert_eq!(
+            json_diff(&obj, &serde_json::Value::Null)["name"],
+            json!({"before": "Pro", "after": null})
+        );
+    }
+
+    #[test]
+    fn redacts_secret_keys_recursively() {
+        let v = json!({
+            "bot_token": "123:abc",
+            "brand_name": "Caramba",
+            "stream": {"reality": {"privateKey": "xyz", "short_ids": ["a"]}}
+        });
+        let r = redact(&v);
+        assert_eq!(r["bot_token"], "***");
+        assert_eq!(r["brand_name"], "Caramba");
+        assert_eq!(r["stream"]["reality"]["privateKey"], "***");
+        assert_eq!(r["stream"]["reality"]["short_ids"], json!(["a"]));
+    }
+
+    #[test]
+    fn changed_secret_is_reported_but_masked() {
+        let diff = redact(&json_diff(
+            &json!({"bot_token": "old"}),
+            &json!({"bot_token": "new"}),
+        ));
+        assert_eq!(diff, json!({"bot_token": "***"}));
+    }
+
+    #[test]
+    fn describes_admin_paths() {
+        assert_eq!(
+            describe_path("/nodes/5/sync"),
+            (
+                "/nodes/{id}/sync".to_string(),
+                "nodes".to_string(),
+                Some("5".to_string())
+            )
+        );
+        assert_eq!(
+            describe_path("/api/admin/frontends/7"),
+            (
+                "/api/admin/frontends/{id}".to_string(),
+                "frontends".to_string(),
+                Some("7".to_string())
+            )
+        );
+        assert_eq!(describe_path("/settings/save").2, None);
+    }
+
+    #[test]
+    fn csv_quotes_fields_with_separators() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
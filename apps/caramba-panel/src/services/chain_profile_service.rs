@@ -0,0 +1,128 @@
+use anyhow::Result;
+use caramba_config::Chain;
+use caramba_db::models::chain_profile::ChainProfile;
+use sqlx::PgPool;
+
+const CHAIN_COLUMNS: &str = "c.id, c.name, c.entry_group_id, c.exit_group_id, COALESCE((SELECT ARRAY_AGG(p.plan_id ORDER BY p.plan_id) FROM plan_chain_profiles p WHERE p.chain_id = c.id), '{}') AS plan_ids, c.created_at, c.updated_at";
+
+/// Fields of a chain profile as submitted from the admin form.
+pub struct ChainInput<'a> {
+    pub name: &'a str,
+    pub entry_group_id: i64,
+    pub exit_group_id: i64,
+    pub plan_ids: &'a [i64],
+}
+
+pub struct ChainProfileService {
+    pool: PgPool,
+}
+
+impl ChainProfileService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self) -> Result<Vec<ChainProfile>> {
+        Ok(sqlx::query_as::<_, ChainProfile>(&format!(
+            "SELECT {} FROM chain_profiles c ORDER BY c.name",
+            CHAIN_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<ChainProfile>> {
+        Ok(sqlx::query_as::<_, ChainProfile>(&format!(
+            "SELECT {} FROM chain_profiles c WHERE c.id = $1",
+            CHAIN_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Chains a plan's subscriptions get, with their groups by name as the
+    /// generators match them against `NodeInfo::groups`.
+    pub async fn chains_for(&self, plan_id: i64) -> Result<Vec<Chain>> {
+        let rows: Vec<(String, String, String)> = sqlx::query_as(
+            "SELECT c.name, e.name, x.name FROM chain_profiles c JOIN plan_chain_profiles p ON p.chain_id = c.id JOIN node_groups e ON e.id = c.entry_group_id JOIN node_groups x ON x.id = c.exit_group_id WHERE p.plan_id = $1 ORDER BY c.name",
+        )
+        .bind(plan_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(name, entry_group, exit_group)| Chain {
+                name,
+                entry_group,
+                exit_group,
+            })
+            .collect())
+    }
+
+    /// Create (`id` = None) or update a chain profile and reassign its plans.
+    pub async fn save(&self, id: Option<i64>, input: &ChainInput<'_>) -> Result<i64> {
+        let mut tx = self.pool.begin().await?;
+
+        let id: i64 = match id {
+            Some(id) => sqlx::query_scalar(
+                "UPDATE chain_profiles SET name = $1, entry_group_id = $2, exit_group_id = $3, updated_at = CURRENT_TIMESTAMP WHERE id = $4 RETURNING id",
+            )
+            .bind(input.name)
+            .bind(input.entry_group_id)
+            .bind(input.exit_group_id)
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?,
+            None => sqlx::query_scalar(
+                "INSERT INTO chain_profiles (name, entry_group_id, exit_group_id) VALUES ($1, $2, $3) RETURNING id",
+            )
+            .bind(input.name)
+            .bind(input.entry_group_id)
+            .bind(input.exit_group_id)
+            .fetch_one(&mut *tx)
+            .await?,
+        };
+
+        sqlx::query("DELETE FROM plan_chain_profiles WHERE chain_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        if !input.plan_ids.is_empty() {
+            sqlx::query(
+                "INSERT INTO plan_chain_profiles (plan_id, chain_id) SELECT UNNEST($1::BIGINT[]), $2",
+            )
+            .bind(input.plan_ids)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    pub async fn delete(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM chain_profiles WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Node groups a chain can enter or exit through, as (id, name).
+    pub async fn group_options(&self) -> Result<Vec<(i64, String)>> {
+        Ok(
+            sqlx::query_as("SELECT id, name FROM node_groups ORDER BY name")
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    /// Plans that can offer a chain, as (id, name).
+    pub async fn plan_options(&self) -> Result<Vec<(i64, String)>> {
+        Ok(sqlx::query_as("SELECT id, name FROM plans ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?)
+    }
+}
@@ -0,0 +1,380 @@
+use crate::services::config_version_service::{DiffLine, diff_lines};
+use crate::services::orchestration_service::OrchestrationService;
+use caramba_db::models::network::Inbound;
+use caramba_db::models::node::Node;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Unchanged config lines shown around each change.
+const DIFF_CONTEXT: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Create,
+    Modify,
+    Remove,
+}
+
+impl ChangeKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChangeKind::Create => "create",
+            ChangeKind::Modify => "modify",
+            ChangeKind::Remove => "remove",
+        }
+    }
+}
+
+/// One inbound that would be created, modified or removed.
+#[derive(Debug, Clone)]
+pub struct InboundChange {
+    pub kind: ChangeKind,
+    pub tag: String,
+    pub protocol: String,
+    pub port_before: Option<i64>,
+    pub port_after: Option<i64>,
+    pub sni_before: Option<String>,
+    pub sni_after: Option<String>,
+    /// Reality/WireGuard keys or short ids differ.
+    pub keys_changed: bool,
+    /// Any other settings differ (user UUIDs, transport options, ...).
+    pub settings_changed: bool,
+    /// Inbound id whose plan links apply: the current row, or the planned one.
+    inbound_id: i64,
+}
+
+impl InboundChange {
+    pub fn port_changed(&self) -> bool {
+        self.kind == ChangeKind::Modify && self.port_before != self.port_after
+    }
+
+    pub fn sni_changed(&self) -> bool {
+        self.kind == ChangeKind::Modify && self.sni_before != self.sni_after
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AffectedPlan {
+    pub id: i64,
+    pub name: String,
+    pub active_subscriptions: i64,
+}
+
+/// What would happen on one node.
+#[derive(Debug, Clone)]
+pub struct NodePlan {
+    pub node_id: i64,
+    pub node_name: String,
+    pub changes: Vec<InboundChange>,
+    pub affected_plans: Vec<AffectedPlan>,
+    pub diff: Vec<DiffLine>,
+    pub added: usize,
+    pub removed: usize,
+    /// Set when the current or planned config cannot be rendered.
+    pub error: Option<String>,
+}
+
+impl NodePlan {
+    pub fn failed(node: &Node, error: String) -> Self {
+        Self {
+            node_id: node.id,
+            node_name: node.name.clone(),
+            changes: Vec::new(),
+            affected_plans: Vec::new(),
+            diff: Vec::new(),
+            added: 0,
+            removed: 0,
+            error: Some(error),
+        }
+    }
+
+    pub fn is_noop(&self) -> bool {
+        self.changes.is_empty() && self.added == 0 && self.removed == 0 && self.error.is_none()
+    }
+}
+
+/// Dry run of an inbound sync, rotation or reset, e.g. "Sync templates to
+/// group EU". Building one never writes to the database or signals nodes.
+#[derive(Debug, Clone)]
+pub struct ChangePlan {
+    pub title: String,
+    pub nodes: Vec<NodePlan>,
+}
+
+impl ChangePlan {
+    pub fn change_count(&self) -> usize {
+        self.nodes.iter().map(|n| n.changes.len()).sum()
+    }
+
+    pub fn changed_nodes(&self) -> usize {
+        self.nodes.iter().filter(|n| !n.is_noop()).count()
+    }
+}
+
+/// First string found under any of `keys`, searching nested objects.
+/// Arrays yield their first string element.
+fn find_str(value: &Value, keys: &[&str]) -> Option<String> {
+    match value {
+        Value::Object(map) => {
+            for key in keys {
+                match map.get(*key) {
+                    Some(Value::String(s)) if !s.is_empty() => return Some(s.clone()),
+                    Some(Value::Array(items)) => {
+                        if let Some(s) = items.iter().find_map(|v| v.as_str()) {
+                            return Some(s.to_string());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            map.values().find_map(|v| find_str(v, keys))
+        }
+        _ => None,
+    }
+}
+
+/// Every string under any of `keys`, searching nested objects.
+fn collect_strs(value: &Value, keys: &[&str], out: &mut Vec<String>) {
+    if let Value::Object(map) = value {
+        for (key, v) in map {
+            if keys.contains(&key.as_str()) {
+                match v {
+                    Value::String(s) => out.push(s.clone()),
+                    Value::Array(items) => {
+                        out.extend(items.iter().filter_map(|i| i.as_str()).map(str::to_string))
+                    }
+                    _ => {}
+                }
+            } else {
+                collect_strs(v, keys, out);
+            }
+        }
+    }
+}
+
+fn parse(json: &str) -> Value {
+    serde_json::from_str(json).unwrap_or(Value::Null)
+}
+
+fn sni_of(inbound: &Inbound) -> Option<String> {
+    find_str(
+        &parse(&inbound.stream_settings),
+        &["server_names", "serverNames", "server_name", "serverName"],
+    )
+}
+
+fn keys_of(inbound: &Inbound) -> Vec<String> {
+    const KEY_FIELDS: [&str; 4] = ["private_key", "privateKey", "short_ids", "shortIds"];
+    let mut keys = Vec::new();
+    collect_strs(&parse(&inbound.stream_settings), &KEY_FIELDS, &mut keys);
+    collect_strs(&parse(&inbound.settings), &KEY_FIELDS, &mut keys);
+    keys
+}
+
+/// Compare a node's inbounds before and after, matched by tag.
+/// Inbounds that come out identical are left out.
+pub fn compare_inbounds(before: &[Inbound], after: &[Inbound]) -> Vec<InboundChange> {
+    let mut changes = Vec::new();
+
+    for new in after {
+        match before.iter().find(|old| old.tag == new.tag) {
+            None => changes.push(InboundChange {
+                kind: ChangeKind::Create,
+                tag: new.tag.clone(),
+                protocol: new.protocol.clone(),
+                port_before: None,
+                port_after: Some(new.listen_port),
+                sni_before: None,
+                sni_after: sni_of(new),
+                keys_changed: false,
+                settings_changed: false,
+                inbound_id: new.id,
+            }),
+            Some(old) => {
+                let keys_changed = keys_of(old) != keys_of(new);
+                let settings_changed = old.settings != new.settings
+                    || old.stream_settings != new.stream_settings
+                    || old.protocol != new.protocol
+                    || old.enable != new.enable;
+                if old.listen_port == new.listen_port && !keys_changed && !settings_changed {
+                    continue;
+                }
+                changes.push(InboundChange {
+                    kind: ChangeKind::Modify,
+                    tag: new.tag.clone(),
+                    protocol: new.protocol.clone(),
+                    port_before: Some(old.listen_port),
+                    port_after: Some(new.listen_port),
+                    sni_before: sni_of(old),
+                    sni_after: sni_of(new),
+                    keys_changed,
+                    settings_changed,
+                    inbound_id: old.id,
+                });
+            }
+        }
+    }
+
+    for old in before {
+        if !after.iter().any(|new| new.tag == old.tag) {
+            changes.push(InboundChange {
+                kind: ChangeKind::Remove,
+                tag: old.tag.clone(),
+                protocol: old.protocol.clone(),
+                port_before: Some(old.listen_port),
+                port_after: None,
+                sni_before: sni_of(old),
+                sni_after: None,
+                keys_changed: false,
+                settings_changed: false,
+                inbound_id: old.id,
+            });
+        }
+    }
+
+    changes
+}
+
+fn pretty(config: &Value) -> String {
+    serde_json::to_string_pretty(config).unwrap_or_default()
+}
+
+/// Build the plan for one node from its current and planned inbounds.
+///
+/// Planned inbounds that do not exist yet should carry negative ids;
+/// `link_overrides` gives the plans they would be linked to where that
+/// differs from the node's and groups' plans.
+pub async fn plan_node(
+    orchestration: &OrchestrationService,
+    node: &Node,
+    before: Vec<Inbound>,
+    after: Vec<Inbound>,
+    link_overrides: &HashMap<i64, Vec<i64>>,
+) -> NodePlan {
+    let changes = compare_inbounds(&before, &after);
+
+    let mut plan_ids: Vec<i64> = Vec::new();
+    for change in &changes {
+        let links = match link_overrides.get(&change.inbound_id) {
+            Some(links) => links.clone(),
+            None => orchestration
+                .node_repo
+                .get_linked_plans(node.id, change.inbound_id)
+                .await
+                .unwrap_or_default(),
+        };
+        for id in links {
+            if !plan_ids.contains(&id) {
+                plan_ids.push(id);
+            }
+        }
+    }
+
+    let affected_plans = if plan_ids.is_empty() {
+        Vec::new()
+    } else {
+        sqlx::query_as::<_, AffectedPlan>(
+            "SELECT p.id, p.name, (SELECT COUNT(*) FROM subscriptions s WHERE s.plan_id = p.id AND LOWER(s.status) = 'active') AS active_subscriptions FROM plans p WHERE p.id = ANY($1) ORDER BY p.name",
+        )
+        .bind(&plan_ids)
+        .fetch_all(&orchestration.pool)
+        .await
+        .unwrap_or_default()
+    };
+
+    let current = orchestration
+        .render_node_config(node, before, &HashMap::new())
+        .await;
+    let planned = orchestration
+        .render_node_config(node, after, link_overrides)
+        .await;
+
+    let (diff, error) = match (current, planned) {
+        (Ok(current), Ok(planned)) => (
+            diff_lines(&pretty(&current), &pretty(&planned), DIFF_CONTEXT),
+            None,
+        ),
+        (Err(e), Ok(planned)) => (
+            diff_lines("", &pretty(&planned), DIFF_CONTEXT),
+            Some(format!("Current config does not render: {}", e)),
+        ),
+        (_, Err(e)) => (
+            Vec::new(),
+            Some(format!("Planned config would fail validation: {}", e)),
+        ),
+    };
+
+    NodePlan {
+        node_id: node.id,
+        node_name: node.name.clone(),
+        added: diff.iter().filter(|l| l.is_added()).count(),
+        removed: diff.iter().filter(|l| l.is_removed()).count(),
+        changes,
+        affected_plans,
+        diff,
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inbound(id: i64, tag: &str, port: i64, sni: &str, key: &str) -> Inbound {
+        Inbound {
+            id,
+            node_id: 1,
+            tag: tag.to_string(),
+            protocol: "vless".to_string(),
+            listen_port: port,
+            listen_ip: "::".to_string(),
+            settings: r#"{"clients":[],"decryption":"none"}"#.to_string(),
+            stream_settings: format!(
+                r#"{{"network":"tcp","security":"reality","reality_settings":{{"server_names":["{}"],"private_key":"{}","short_ids":["ab"]}}}}"#,
+                sni, key
+            ),
+            remark: None,
+            enable: true,
+            renew_interval_mins: 0,
+            port_range_start: 0,
+            port_range_end: 0,
+            last_rotated_at: None,
+            created_at: None,
+        }
+    }
+
+    #[test]
+    fn unchanged_inbounds_are_omitted() {
+        let before = vec![inbound(1, "tpl_1", 443, "a.com", "k1")];
+        assert!(compare_inbounds(&before, &before.clone()).is_empty());
+    }
+
+    #[test]
+    fn detects_port_sni_and_key_changes() {
+        let before = vec![inbound(1, "tpl_1", 443, "a.com", "k1")];
+        let after = vec![inbound(1, "tpl_1", 8443, "b.com", "k2")];
+        let changes = compare_inbounds(&before, &after);
+        assert_eq!(changes.len(), 1);
+        let change = &changes[0];
+        assert_eq!(change.kind, ChangeKind::Modify);
+        assert!(change.port_changed());
+        assert!(change.sni_changed());
+        assert_eq!(change.sni_after.as_deref(), Some("b.com"));
+        assert!(change.keys_changed);
+    }
+
+    #[test]
+    fn matches_by_tag_for_creates_and_removes() {
+        let before = vec![inbound(1, "tpl_old", 443, "a.com", "k1")];
+        let after = vec![inbound(-1, "tpl_new", 2053, "a.com", "k1")];
+        let changes = compare_inbounds(&before, &after);
+        let kinds: Vec<_> = changes.iter().map(|c| (c.kind, c.tag.as_str())).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ChangeKind::Create, "tpl_new"),
+                (ChangeKind::Remove, "tpl_old")
+            ]
+        );
+    }
+}
@@ -0,0 +1,454 @@
+use anyhow::{Context, Result};
+use caramba_db::models::node_config::{
+    NodeConfigFailure, NodeConfigVersion, NodeConfigVersionSummary,
+};
+use caramba_shared::api::ConfigFailure;
+use serde_json::Value;
+use sqlx::PgPool;
+
+/// Versions kept per node. Older ones are pruned, except a pinned version.
+const MAX_VERSIONS_PER_NODE: i64 = 200;
+/// Diffs needing more edits than this are shown as a full replacement.
+const MAX_DIFF_EDITS: usize = 2000;
+
+const SUMMARY_COLUMNS: &str =
+    "id, node_id, version, config_hash, reason, author, created_at, LENGTH(config) AS size";
+
+/// Hash sent to agents with a config. Agents only re-apply when it changes.
+pub fn config_hash(config_text: &str) -> String {
+    format!("{:x}", md5::compute(config_text.as_bytes()))
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigVersionService {
+    pool: PgPool,
+}
+
+impl ConfigVersionService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Store a generated config as the node's next version.
+    ///
+    /// Identical to the latest version means nothing changed, so no row is
+    /// added and the latest version is returned.
+    pub async fn record(
+        &self,
+        node_id: i64,
+        config: &Value,
+        reason: &str,
+        author: &str,
+    ) -> Result<NodeConfigVersionSummary> {
+        let text = config.to_string();
+        let hash = config_hash(&text);
+
+        let mut tx = self.pool.begin().await?;
+        // Serialise version numbering per node.
+        sqlx::query("SELECT id FROM nodes WHERE id = $1 FOR UPDATE")
+            .bind(node_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let latest = sqlx::query_as::<_, NodeConfigVersionSummary>(&format!(
+            "SELECT {} FROM node_config_versions WHERE node_id = $1 ORDER BY version DESC LIMIT 1",
+            SUMMARY_COLUMNS
+        ))
+        .bind(node_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(latest) = latest
+            && latest.config_hash == hash
+        {
+            tx.commit().await?;
+            return Ok(latest);
+        }
+
+        let recorded = sqlx::query_as::<_, NodeConfigVersionSummary>(&format!(
+            "INSERT INTO node_config_versions (node_id, version, config_hash, config, reason, author) \
+             SELECT $1, COALESCE(MAX(version), 0) + 1, $2, $3, $4, $5 FROM node_config_versions WHERE node_id = $1 \
+             RETURNING {}",
+            SUMMARY_COLUMNS
+        ))
+        .bind(node_id)
+        .bind(&hash)
+        .bind(&text)
+        .bind(reason)
+        .bind(author)
+        .fetch_one(&mut *tx)
+        .await
+        .context("Failed to record config version")?;
+
+        sqlx::query(
+            "DELETE FROM node_config_versions WHERE node_id = $1 AND version <= $2 \
+             AND id IS DISTINCT FROM (SELECT pinned_config_version_id FROM nodes WHERE id = $1)",
+        )
+        .bind(node_id)
+        .bind(recorded.version as i64 - MAX_VERSIONS_PER_NODE)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(recorded)
+    }
+
+    pub async fn list(&self, node_id: i64, limit: i64) -> Result<Vec<NodeConfigVersionSummary>> {
+        sqlx::query_as::<_, NodeConfigVersionSummary>(&format!(
+            "SELECT {} FROM node_config_versions WHERE node_id = $1 ORDER BY version DESC LIMIT $2",
+            SUMMARY_COLUMNS
+        ))
+        .bind(node_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list config versions")
+    }
+
+    pub async fn get(&self, node_id: i64, version: i32) -> Result<Option<NodeConfigVersion>> {
+        sqlx::query_as::<_, NodeConfigVersion>(
+            "SELECT * FROM node_config_versions WHERE node_id = $1 AND version = $2",
+        )
+        .bind(node_id)
+        .bind(version)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to fetch config version")
+    }
+
+    /// The version the node is pinned to, if any.
+    pub async fn pinned(&self, node_id: i64) -> Result<Option<NodeConfigVersion>> {
+        sqlx::query_as::<_, NodeConfigVersion>(
+            "SELECT v.* FROM nodes n JOIN node_config_versions v ON v.id = n.pinned_config_version_id WHERE n.id = $1",
+        )
+        .bind(node_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to fetch pinned config version")
+    }
+
+    /// Pin a node to one of its versions. Returns false if the version does not exist.
+    pub async fn pin(&self, node_id: i64, version: i32) -> Result<bool> {
+        let res = sqlx::query(
+            "UPDATE nodes SET pinned_config_version_id = v.id FROM node_config_versions v \
+             WHERE nodes.id = $1 AND v.node_id = $1 AND v.version = $2",
+        )
+        .bind(node_id)
+        .bind(version)
+        .execute(&self.pool)
+        .await
+        .context("Failed to pin config version")?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    pub async fn unpin(&self, node_id: i64) -> Result<()> {
+        sqlx::query("UPDATE nodes SET pinned_config_version_id = NULL WHERE id = $1")
+            .bind(node_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to unpin config version")?;
+        Ok(())
+    }
+
+    /// Store what the agent reported about its last config apply. `None`
+    /// means it runs the config it was served, which clears any failure.
+    pub async fn record_agent_report(
+        &self,
+        node_id: i64,
+        failure: Option<&ConfigFailure>,
+    ) -> Result<()> {
+        match failure {
+            Some(f) => {
+                sqlx::query(
+                    "UPDATE nodes SET config_error = $2, config_error_stage = $3, config_error_rolled_back = $4, \
+                     config_error_at = CASE WHEN config_error_hash IS DISTINCT FROM $5 THEN CURRENT_TIMESTAMP ELSE config_error_at END, \
+                     config_error_hash = $5 WHERE id = $1",
+                )
+                .bind(node_id)
+                .bind(&f.reason)
+                .bind(&f.stage)
+                .bind(f.rolled_back)
+                .bind(&f.config_hash)
+                .execute(&self.pool)
+                .await
+                .context("Failed to record config failure")?;
+            }
+            None => {
+                sqlx::query(
+                    "UPDATE nodes SET config_error = NULL, config_error_stage = NULL, config_error_hash = NULL, \
+                     config_error_rolled_back = FALSE, config_error_at = NULL WHERE id = $1 AND config_error_hash IS NOT NULL",
+                )
+                .bind(node_id)
+                .execute(&self.pool)
+                .await
+                .context("Failed to clear config failure")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The config failure the node's agent last reported, if still current.
+    pub async fn failure(&self, node_id: i64) -> Result<Option<NodeConfigFailure>> {
+        sqlx::query_as::<_, NodeConfigFailure>(
+            "SELECT n.config_error_stage AS stage, n.config_error AS reason, n.config_error_hash AS config_hash, \
+             n.config_error_rolled_back AS rolled_back, n.config_error_at AS reported_at, \
+             COALESCE((SELECT MAX(v.version) FROM node_config_versions v WHERE v.node_id = n.id AND v.config_hash = n.config_error_hash), 0) AS version \
+             FROM nodes n WHERE n.id = $1 AND n.config_error_hash IS NOT NULL",
+        )
+        .bind(node_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to fetch config failure")
+    }
+}
+
+// ----------------------------------------------------------------------
+// Line diff
+// ----------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Same,
+    Added,
+    Removed,
+    /// Collapsed run of unchanged lines; `text` holds the count.
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+impl DiffLine {
+    fn new(kind: DiffKind, text: &str) -> Self {
+        Self {
+            kind,
+            text: text.to_string(),
+        }
+    }
+
+    pub fn is_added(&self) -> bool {
+        self.kind == DiffKind::Added
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.kind == DiffKind::Removed
+    }
+
+    pub fn is_skipped(&self) -> bool {
+        self.kind == DiffKind::Skipped
+    }
+}
+
+/// Line diff of `old` against `new`, keeping `context` unchanged lines
+/// around each change and collapsing the rest.
+pub fn diff_lines(old: &str, new: &str, context: usize) -> Vec<DiffLine> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // Shared head and tail are cheap to strip and usually most of a config.
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut lines: Vec<DiffLine> = a[..prefix]
+        .iter()
+        .map(|l| DiffLine::new(DiffKind::Same, l))
+        .collect();
+
+    let (mid_a, mid_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    match myers(mid_a, mid_b, MAX_DIFF_EDITS) {
+        Some(ops) => lines.extend(ops),
+        None => {
+            lines.extend(mid_a.iter().map(|l| DiffLine::new(DiffKind::Removed, l)));
+            lines.extend(mid_b.iter().map(|l| DiffLine::new(DiffKind::Added, l)));
+        }
+    }
+    lines.extend(
+        a[a.len() - suffix..]
+            .iter()
+            .map(|l| DiffLine::new(DiffKind::Same, l)),
+    );
+
+    collapse(lines, context)
+}
+
+/// Myers' O(ND) shortest edit script. `None` if more than `max_edits` are needed.
+fn myers(a: &[&str], b: &[&str], max_edits: usize) -> Option<Vec<DiffLine>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = a.len() + b.len();
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    // trace[d] holds v for diagonals -(d+1)..=(d+1) before step d.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    for d in 0..=max.min(max_edits) as isize {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        let mut k = -d;
+        while k <= d {
+            let idx = |k: isize| (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+                v[idx(k + 1)]
+            } else {
+                v[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx(k)] = x;
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, a, b));
+            }
+            k += 2;
+        }
+    }
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], a: &[&str], b: &[&str]) -> Vec<DiffLine> {
+    let (mut x, mut y) = (a.len() as isize, b.len() as isize);
+    let mut ops = Vec::new();
+
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            ops.push(DiffLine::new(DiffKind::Same, a[(x - 1) as usize]));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops.push(DiffLine::new(DiffKind::Added, b[(y - 1) as usize]));
+            } else {
+                ops.push(DiffLine::new(DiffKind::Removed, a[(x - 1) as usize]));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    ops.reverse();
+    ops
+}
+
+/// Replace runs of unchanged lines further than `context` from a change with
+/// a single `Skipped` marker.
+fn collapse(lines: Vec<DiffLine>, context: usize) -> Vec<DiffLine> {
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| l.kind != DiffKind::Same)
+        .map(|(i, _)| i)
+        .collect();
+
+    let near_change = |i: usize| {
+        let pos = changed.partition_point(|&c| c < i);
+        let after = changed.get(pos).is_some_and(|&c| c - i <= context);
+        let before = pos > 0 && i - changed[pos - 1] <= context;
+        after || before
+    };
+
+    let mut out = Vec::new();
+    let mut skipped = 0usize;
+    for (i, line) in lines.into_iter().enumerate() {
+        if line.kind == DiffKind::Same && !near_change(i) {
+            skipped += 1;
+            continue;
+        }
+        if skipped > 0 {
+            out.push(DiffLine::new(DiffKind::Skipped, &skipped.to_string()));
+            skipped = 0;
+        }
+        out.push(line);
+    }
+    if skipped > 0 {
+        out.push(DiffLine::new(DiffKind::Skipped, &skipped.to_string()));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(lines: &[DiffLine]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|l| match l.kind {
+                DiffKind::Same => format!(" {}", l.text),
+                DiffKind::Added => format!("+{}", l.text),
+                DiffKind::Removed => format!("-{}", l.text),
+                DiffKind::Skipped => format!("@{}", l.text),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_input_collapses_to_one_marker() {
+        let text = "a\nb\nc";
+        assert_eq!(render(&diff_lines(text, text, 1)), vec!["@3"]);
+        assert!(diff_lines("", "", 3).is_empty());
+    }
+
+    #[test]
+    fn finds_minimal_edits_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8";
+        let new = "1\n2\n3\nfour\n5\n6\n7\n8\n9";
+        assert_eq!(
+            render(&diff_lines(old, new, 1)),
+            vec!["@2", " 3", "-4", "+four", " 5", "@2", " 8", "+9"]
+        );
+    }
+
+    #[test]
+    fn handles_interleaved_changes() {
+        let old = "a\nb\nc\na\nb\nb\na";
+        let new = "c\nb\na\nb\na\nc";
+        let lines = diff_lines(old, new, 10);
+        // Applying the script to `old` must yield `new`.
+        let rebuilt: Vec<&str> = lines
+            .iter()
+            .filter(|l| l.kind != DiffKind::Removed)
+            .map(|l| l.text.as_str())
+            .collect();
+        assert_eq!(rebuilt, new.lines().collect::<Vec<_>>());
+        let kept: Vec<&str> = lines
+            .iter()
+            .filter(|l| l.kind != DiffKind::Added)
+            .map(|l| l.text.as_str())
+            .collect();
+        assert_eq!(kept, old.lines().collect::<Vec<_>>());
+        // Known shortest edit script length for this pair is 5.
+        assert_eq!(lines.iter().filter(|l| l.kind != DiffKind::Same).count(), 5);
+    }
+
+    #[test]
+    fn oversized_diffs_fall_back_to_replacement() {
+        let old: Vec<String> = (0..50).map(|i| format!("old {}", i)).collect();
+        let new: Vec<String> = (0..50).map(|i| format!("new {}", i)).collect();
+        let ops = myers(
+            &old.iter().map(String::as_str).collect::<Vec<_>>(),
+            &new.iter().map(String::as_str).collect::<Vec<_>>(),
+            10,
+        );
+        assert!(ops.is_none());
+    }
+}
@@ -8,6 +8,7 @@ use std::sync::Arc;
 use tokio::time;
 use tracing::{error, info, warn};
 
+use crate::services::device_service::{parse_user_tag, user_tag};
 use crate::services::orchestration_service::OrchestrationService;
 use crate::services::store_service::StoreService;
 use crate::services::subscription_service::SubscriptionService;
@@ -139,11 +140,7 @@ impl ConnectionService {
                         let mut sub_id_opt = None;
 
                         if let Some(user_tag) = &conn.metadata.user {
-                            if user_tag.starts_with("user_") {
-                                if let Ok(id) = user_tag[5..].parse::<i64>() {
-                                    sub_id_opt = Some(id);
-                                }
-                            }
+                            sub_id_opt = parse_user_tag(user_tag).map(|(id, _)| id);
                         }
 
                         // Strategy 2: Check chains for UUID if Strategy 1 failed
@@ -403,33 +400,41 @@ impl ConnectionService {
 
     /// Kill all active connections for a specific subscription across all nodes
     pub async fn kill_subscription_connections(&self, sub_id: i64) -> Result<()> {
+        self.kill_connections_where(|user| parse_user_tag(user).is_some_and(|(id, _)| id == sub_id))
+            .await
+    }
+
+    /// Closes the connections of one device credential, leaving the
+    /// subscription's other devices connected.
+    pub async fn kill_device_connections(&self, sub_id: i64, device_id: i64) -> Result<()> {
+        let target_user = user_tag(sub_id, Some(device_id));
+        self.kill_connections_where(|user| user == target_user)
+            .await
+    }
+
+    async fn kill_connections_where(&self, matches: impl Fn(&str) -> bool) -> Result<()> {
         let nodes: Vec<caramba_db::models::node::Node> =
             self.orchestration.node_repo.get_all_nodes().await?;
-        let target_user = format!("user_{}", sub_id);
 
         for node in nodes {
             match self.fetch_node_connections(&node.ip).await {
                 Ok(connections) => {
                     for conn in connections {
                         // Check metadata.user
-                        let mut match_found = false;
-                        if let Some(user) = &conn.metadata.user {
-                            if user == &target_user {
-                                match_found = true;
-                            }
-                        }
-
-                        if match_found {
-                            info!(
-                                "Killing connection {} on node {} for {}",
-                                conn.id, node.name, target_user
+                        let Some(user) = conn.metadata.user.as_deref().filter(|u| matches(u))
+                        else {
+                            continue;
+                        };
+
+                        info!(
+                            "Killing connection {} on node {} for {}",
+                            conn.id, node.name, user
+                        );
+                        if let Err(e) = self.close_connection(&node.ip, &conn.id).await {
+                            error!(
+                                "Failed to close connection {} on {}: {}",
+                                conn.id, node.name, e
                             );
-                            if let Err(e) = self.close_connection(&node.ip, &conn.id).await {
-                                error!(
-                                    "Failed to close connection {} on {}: {}",
-                                    conn.id, node.name, e
-                                );
-                            }
                         }
                     }
                 }
@@ -0,0 +1,358 @@
+use anyhow::Result;
+use caramba_db::models::sub_device::SubscriptionDevice;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Name of a proxy user on the nodes: `user_{sub}` for the subscription's own
+/// credential, `user_{sub}_d{device}` for a device's.
+pub fn user_tag(subscription_id: i64, device_id: Option<i64>) -> String {
+    match device_id {
+        Some(device_id) => format!("user_{}_d{}", subscription_id, device_id),
+        None => format!("user_{}", subscription_id),
+    }
+}
+
+/// Subscription and device a node-reported user tag belongs to.
+pub fn parse_user_tag(tag: &str) -> Option<(i64, Option<i64>)> {
+    let rest = tag.strip_prefix("user_")?;
+    match rest.split_once("_d") {
+        Some((sub, device)) => Some((sub.parse().ok()?, Some(device.parse().ok()?))),
+        None => Some((rest.parse().ok()?, None)),
+    }
+}
+
+/// Proxy users to deploy for `subs` (id, shared uuid) as `(tag, uuid)`. A
+/// subscription in `with_devices` no longer gets its shared credential, so
+/// neither a revoked device nor one over the limit can fall back to it.
+pub fn proxy_credentials(
+    subs: &[(i64, Option<String>)],
+    devices: &[(i64, i64, String)],
+    with_devices: &HashSet<i64>,
+) -> Vec<(String, String)> {
+    let shared = subs
+        .iter()
+        .filter(|(sub_id, _)| !with_devices.contains(sub_id))
+        .filter_map(|(sub_id, uuid)| Some((user_tag(*sub_id, None), uuid.clone()?)));
+    let devices = devices
+        .iter()
+        .map(|(sub_id, device_id, uuid)| (user_tag(*sub_id, Some(*device_id)), uuid.clone()));
+    shared.chain(devices).collect()
+}
+
+/// Outcome of a device fetching its subscription config.
+pub enum DeviceAdmission {
+    Admitted {
+        device: SubscriptionDevice,
+        is_new: bool,
+    },
+    Revoked,
+    LimitReached,
+}
+
+/// Per-device proxy credentials of subscriptions.
+pub struct DeviceService {
+    pool: PgPool,
+}
+
+impl DeviceService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// What tells a client's devices apart: the hardware id Happ and Hiddify
+    /// send, else the app and platform in the user agent, so an app update
+    /// stays the same device. Fetches with neither keep the shared
+    /// credential.
+    pub fn device_key(hwid: Option<&str>, user_agent: Option<&str>) -> Option<String> {
+        hwid.map(|s| s.trim().to_ascii_lowercase())
+            .filter(|s| !s.is_empty())
+            .map(|hwid| format!("hwid:{}", hwid))
+            .or_else(|| {
+                user_agent
+                    .map(without_versions)
+                    .filter(|s| !s.is_empty())
+                    .map(|ua| format!("ua:{}", ua))
+            })
+    }
+
+    /// Credential of the device behind `device_key`, issuing one while the
+    /// subscription has fewer than `device_limit` (0 = unlimited).
+    pub async fn admit(
+        &self,
+        subscription_id: i64,
+        device_key: &str,
+        device_name: &str,
+        ip: &str,
+        device_limit: i32,
+    ) -> Result<DeviceAdmission> {
+        let existing = sqlx::query_as::<_, SubscriptionDevice>(
+            r#"
+            UPDATE subscription_devices
+            SET last_seen_at = CURRENT_TIMESTAMP, last_ip = $3
+            WHERE subscription_id = $1 AND device_key = $2
+            RETURNING *
+            "#,
+        )
+        .bind(subscription_id)
+        .bind(device_key)
+        .bind(ip)
+        .fetch_optional(&self.pool)
+        .await?;
+        let existing = match existing {
+            Some(device) => Some(device),
+            None => {
+                self.rekey_versioned(subscription_id, device_key, ip)
+                    .await?
+            }
+        };
+        if let Some(device) = existing {
+            return Ok(if device.is_revoked() {
+                DeviceAdmission::Revoked
+            } else {
+                DeviceAdmission::Admitted {
+                    device,
+                    is_new: false,
+                }
+            });
+        }
+
+        if device_limit > 0 {
+            let active: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM subscription_devices WHERE subscription_id = $1 AND revoked_at IS NULL",
+            )
+            .bind(subscription_id)
+            .fetch_one(&self.pool)
+            .await?;
+            if active >= device_limit as i64 {
+                return Ok(DeviceAdmission::LimitReached);
+            }
+        }
+
+        let device = sqlx::query_as::<_, SubscriptionDevice>(
+            r#"
+            INSERT INTO subscription_devices (subscription_id, device_key, device_name, proxy_uuid, last_ip)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (subscription_id, device_key) DO UPDATE SET last_seen_at = CURRENT_TIMESTAMP
+            RETURNING *
+            "#,
+        )
+        .bind(subscription_id)
+        .bind(device_key)
+        .bind(device_name)
+        .bind(Uuid::new_v4().to_string())
+        .bind(ip)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(DeviceAdmission::Admitted {
+            device,
+            is_new: true,
+        })
+    }
+
+    /// Moves the device recorded under a user agent key that still carried
+    /// version numbers onto `device_key`, revoked ones first so a revocation
+    /// survives the move.
+    async fn rekey_versioned(
+        &self,
+        subscription_id: i64,
+        device_key: &str,
+        ip: &str,
+    ) -> Result<Option<SubscriptionDevice>> {
+        if !device_key.starts_with("ua:") {
+            return Ok(None);
+        }
+        let keys: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT id, device_key FROM subscription_devices WHERE subscription_id = $1 AND device_key LIKE 'ua:%' ORDER BY revoked_at IS NULL, last_seen_at DESC",
+        )
+        .bind(subscription_id)
+        .fetch_all(&self.pool)
+        .await?;
+        let Some(id) = keys
+            .into_iter()
+            .find(|(_, key)| same_ua_key(key, device_key))
+            .map(|(id, _)| id)
+        else {
+            return Ok(None);
+        };
+        Ok(Some(
+            sqlx::query_as::<_, SubscriptionDevice>(
+                r#"
+                UPDATE subscription_devices
+                SET device_key = $2, last_seen_at = CURRENT_TIMESTAMP, last_ip = $3
+                WHERE id = $1
+                RETURNING *
+                "#,
+            )
+            .bind(id)
+            .bind(device_key)
+            .bind(ip)
+            .fetch_one(&self.pool)
+            .await?,
+        ))
+    }
+
+    /// Devices of a subscription, active ones first.
+    pub async fn list(&self, subscription_id: i64) -> Result<Vec<SubscriptionDevice>> {
+        Ok(sqlx::query_as::<_, SubscriptionDevice>(
+            "SELECT * FROM subscription_devices WHERE subscription_id = $1 ORDER BY revoked_at IS NOT NULL, last_seen_at DESC",
+        )
+        .bind(subscription_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Revokes one device's credential. The device also stops getting
+    /// configs, so it cannot come back as a new device.
+    pub async fn revoke(&self, subscription_id: i64, device_id: i64) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE subscription_devices SET revoked_at = CURRENT_TIMESTAMP WHERE id = $1 AND subscription_id = $2 AND revoked_at IS NULL",
+        )
+        .bind(device_id)
+        .bind(subscription_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Those of `subscription_ids` that ever issued a device credential,
+    /// revoked ones included.
+    pub async fn with_devices(&self, subscription_ids: &[i64]) -> Result<HashSet<i64>> {
+        if subscription_ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let ids: Vec<i64> = sqlx::query_scalar(
+            "SELECT DISTINCT subscription_id FROM subscription_devices WHERE subscription_id = ANY($1)",
+        )
+        .bind(subscription_ids)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ids.into_iter().collect())
+    }
+
+    /// `(subscription_id, device_id, proxy_uuid)` of every unrevoked device of
+    /// the given subscriptions, for injection into node configs.
+    pub async fn active_credentials(
+        &self,
+        subscription_ids: &[i64],
+    ) -> Result<Vec<(i64, i64, String)>> {
+        if subscription_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(sqlx::query_as::<_, (i64, i64, String)>(
+            "SELECT subscription_id, id, proxy_uuid FROM subscription_devices WHERE subscription_id = ANY($1) AND revoked_at IS NULL ORDER BY id",
+        )
+        .bind(subscription_ids)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
+
+/// `user_agent` lowercased without its version numbers: `Happ/1.0.3 (iOS)`
+/// becomes `happ (ios)`.
+fn without_versions(user_agent: &str) -> String {
+    let is_version = |token: &str| {
+        let token = token.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+        let token = token.strip_prefix('v').unwrap_or(token);
+        token.starts_with(|c: char| c.is_ascii_digit())
+            && token.chars().all(|c| c.is_ascii_digit() || c == '.')
+    };
+    user_agent
+        .to_ascii_lowercase()
+        .split_whitespace()
+        .map(|token| match token.split_once('/') {
+            Some((app, _)) => app,
+            None => token,
+        })
+        .filter(|token| !token.is_empty() && !is_version(token))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether `stored`, a user agent key possibly recorded with version
+/// numbers, is the device `device_key` names.
+fn same_ua_key(stored: &str, device_key: &str) -> bool {
+    stored
+        .strip_prefix("ua:")
+        .and_then(|ua| DeviceService::device_key(None, Some(ua)))
+        .is_some_and(|key| key == device_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DeviceService, parse_user_tag, proxy_credentials, same_ua_key, user_tag, without_versions,
+    };
+    use std::collections::HashSet;
+
+    #[test]
+    fn user_tags_round_trip() {
+        assert_eq!(user_tag(42, None), "user_42");
+        assert_eq!(user_tag(42, Some(7)), "user_42_d7");
+        assert_eq!(parse_user_tag("user_42"), Some((42, None)));
+        assert_eq!(parse_user_tag("user_42_d7"), Some((42, Some(7))));
+        assert_eq!(parse_user_tag("user_42_dx"), None);
+        assert_eq!(parse_user_tag("relay_3_legacy"), None);
+    }
+
+    #[test]
+    fn device_key_prefers_hardware_id() {
+        assert_eq!(
+            DeviceService::device_key(Some(" ABC123 "), Some("Happ/1.0")),
+            Some("hwid:abc123".to_string())
+        );
+        assert_eq!(
+            DeviceService::device_key(Some(""), Some("Happ/1.0")),
+            Some("ua:happ".to_string())
+        );
+        assert_eq!(DeviceService::device_key(None, None), None);
+    }
+
+    #[test]
+    fn app_updates_keep_the_device() {
+        let key = |ua| DeviceService::device_key(None, Some(ua));
+        assert_eq!(key("Happ/1.0 (iOS 17.4)"), key("Happ/1.2.3 (iOS 17.5)"));
+        assert_eq!(
+            key("HiddifyNext/2.5.7 (android) like ClashMeta v2ray sing-box"),
+            Some("ua:hiddifynext (android) like clashmeta v2ray sing-box".to_string())
+        );
+        assert_eq!(
+            without_versions("clash-verge/v1.3.8"),
+            without_versions("clash-verge/v2.0.0")
+        );
+        assert_ne!(key("Happ/1.0"), key("v2rayNG/1.0"));
+        assert_eq!(key("1.0"), None);
+
+        // Devices recorded before versions were dropped are found again
+        assert!(same_ua_key(
+            "ua:happ/1.0 (ios 17.4)",
+            &key("Happ/1.1 (iOS 18.0)").unwrap()
+        ));
+        assert!(!same_ua_key("hwid:happ", &key("Happ/1.1").unwrap()));
+    }
+
+    #[test]
+    fn shared_credential_is_dropped_once_devices_are_issued() {
+        let subs = [
+            (1, Some("shared-1".to_string())),
+            (2, Some("shared-2".to_string())),
+        ];
+        // Sub 1: device 7 is active, device 8 was revoked; sub 2 has none
+        let devices = [(1, 7, "device-7".to_string())];
+        let with_devices = HashSet::from([1]);
+
+        let tags: Vec<String> = proxy_credentials(&subs, &devices, &with_devices)
+            .into_iter()
+            .map(|(tag, _)| tag)
+            .collect();
+        assert_eq!(tags, vec!["user_2", "user_1_d7"]);
+
+        // Revoking the last device doesn't bring the shared credential back,
+        // and a device turned away at the limit has nothing to fall back to
+        let tags: Vec<String> = proxy_credentials(&subs, &[], &with_devices)
+            .into_iter()
+            .map(|(tag, _)| tag)
+            .collect();
+        assert_eq!(tags, vec!["user_2"]);
+    }
+}
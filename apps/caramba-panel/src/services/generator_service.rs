@@ -1,13 +1,23 @@
+use crate::services::change_plan::{NodePlan, plan_node};
 use crate::services::orchestration_service::OrchestrationService;
 use crate::services::security_service::SecurityService;
 use anyhow::Result;
 use caramba_db::models::groups::InboundTemplate;
+use caramba_db::models::network::Inbound;
 use caramba_db::models::node::Node;
 use caramba_db::repositories::node_repo::NodeRepository;
 use sqlx::PgPool;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::{error, info, warn};
 
+/// Port and settings a template resolves to on one node.
+struct ResolvedInbound {
+    port: i64,
+    settings: String,
+    stream_settings: String,
+}
+
 #[derive(Clone)]
 pub struct GeneratorService {
     pool: PgPool,
@@ -32,7 +42,19 @@ impl GeneratorService {
     }
 
     /// Syncs inbounds for all nodes in a specific group based on active templates.
+    ///
+    /// Refused while a staged rollout is running for the group, since the
+    /// rollout owns which nodes get the change and when.
     pub async fn sync_group_inbounds(&self, group_id: i64) -> Result<()> {
+        if let Some(rollout_id) =
+            crate::services::rollout_service::active_rollout_for_group(&self.pool, group_id).await?
+        {
+            return Err(anyhow::anyhow!(
+                "Staged rollout #{} is in progress for this group",
+                rollout_id
+            ));
+        }
+
         let node_repo = NodeRepository::new(self.pool.clone());
 
         // 1. Get templates for this group through repository compatibility query.
@@ -44,13 +66,7 @@ impl GeneratorService {
         }
 
         // 2. Get nodes in this group through repository parser.
-        let node_ids = node_repo.get_group_nodes(group_id).await?;
-        let mut nodes: Vec<Node> = Vec::new();
-        for node_id in node_ids {
-            if let Some(node) = node_repo.get_node_by_id(node_id).await? {
-                nodes.push(node);
-            }
-        }
+        let nodes = self.group_nodes(group_id).await?;
 
         info!(
             "🔄 Syncing {} templates to {} nodes in group {}",
@@ -60,18 +76,7 @@ impl GeneratorService {
         );
 
         for node in &nodes {
-            for template in &templates {
-                match self.ensure_inbound_exists(node, template).await {
-                    Ok(_) => info!(
-                        "✅ Inbound for template {} synced to node {} ({})",
-                        template.id, node.id, node.name
-                    ),
-                    Err(e) => error!(
-                        "❌ Failed to sync template {} to node {}: {}",
-                        template.id, node.id, e
-                    ),
-                }
-            }
+            self.sync_node_templates(node, &templates).await;
             // Notify node to update config
             let _ = self
                 .pubsub
@@ -82,6 +87,150 @@ impl GeneratorService {
         Ok(())
     }
 
+    async fn group_nodes(&self, group_id: i64) -> Result<Vec<Node>> {
+        let node_repo = NodeRepository::new(self.pool.clone());
+        let mut nodes = Vec::new();
+        for node_id in node_repo.get_group_nodes(group_id).await? {
+            if let Some(node) = node_repo.get_node_by_id(node_id).await? {
+                nodes.push(node);
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// Dry run of `sync_group_inbounds`: what each node would end up with.
+    pub async fn plan_group_sync(&self, group_id: i64) -> Result<Vec<NodePlan>> {
+        let node_repo = NodeRepository::new(self.pool.clone());
+        let templates = node_repo.get_templates_for_group(group_id).await?;
+
+        let mut plans = Vec::new();
+        for node in self.group_nodes(group_id).await? {
+            let before = node_repo.get_inbounds_by_node(node.id).await?;
+            let mut after = before.clone();
+            let mut next_id = -1;
+
+            for template in &templates {
+                let tag = format!("tpl_{}", template.id);
+                let existing = after.iter_mut().find(|i| i.tag == tag);
+                let resolved = match self
+                    .resolve_template_inbound(
+                        &node,
+                        template,
+                        existing.as_ref().map(|i| i.listen_port),
+                    )
+                    .await
+                {
+                    Ok(resolved) => resolved,
+                    Err(e) => {
+                        warn!("Plan: template {} on node {}: {}", template.id, node.id, e);
+                        continue;
+                    }
+                };
+
+                match existing {
+                    Some(inbound) => {
+                        inbound.protocol = template.protocol.clone();
+                        inbound.settings = resolved.settings;
+                        inbound.stream_settings = resolved.stream_settings;
+                        inbound.remark = Some(template.name.clone());
+                        inbound.listen_port = resolved.port;
+                        inbound.enable = true;
+                    }
+                    None => {
+                        after.push(Inbound {
+                            id: next_id,
+                            node_id: node.id,
+                            tag,
+                            protocol: template.protocol.clone(),
+                            listen_port: resolved.port,
+                            listen_ip: "::".to_string(),
+                            settings: resolved.settings,
+                            stream_settings: resolved.stream_settings,
+                            remark: Some(template.name.clone()),
+                            enable: true,
+                            renew_interval_mins: 0,
+                            port_range_start: template.port_range_start,
+                            port_range_end: template.port_range_end,
+                            last_rotated_at: None,
+                            created_at: None,
+                        });
+                        next_id -= 1;
+                    }
+                }
+            }
+
+            plans.push(
+                plan_node(
+                    &self.orchestration_service,
+                    &node,
+                    before,
+                    after,
+                    &HashMap::new(),
+                )
+                .await,
+            );
+        }
+
+        Ok(plans)
+    }
+
+    /// Dry run of `rotate_group_inbounds`: new ports, SNIs and UUIDs per node.
+    pub async fn plan_group_rotation(&self, group_id: i64) -> Result<Vec<NodePlan>> {
+        let node_repo = NodeRepository::new(self.pool.clone());
+
+        let mut plans = Vec::new();
+        for node in self.group_nodes(group_id).await? {
+            let before = node_repo.get_inbounds_by_node(node.id).await?;
+            let mut after = before.clone();
+
+            for inbound in after.iter_mut().filter(|i| i.tag.starts_with("tpl_")) {
+                match self.resolve_rotation(inbound, &node).await {
+                    Ok(resolved) => {
+                        inbound.listen_port = resolved.port;
+                        inbound.settings = resolved.settings;
+                        inbound.stream_settings = resolved.stream_settings;
+                    }
+                    Err(e) => warn!("Plan: rotating inbound {}: {}", inbound.id, e),
+                }
+            }
+
+            plans.push(
+                plan_node(
+                    &self.orchestration_service,
+                    &node,
+                    before,
+                    after,
+                    &HashMap::new(),
+                )
+                .await,
+            );
+        }
+
+        Ok(plans)
+    }
+
+    /// Brings one node's template inbounds in line with `templates`.
+    /// Returns the number of templates that failed to apply.
+    pub async fn sync_node_templates(&self, node: &Node, templates: &[InboundTemplate]) -> usize {
+        let mut failed = 0;
+        for template in templates {
+            match self.ensure_inbound_exists(node, template).await {
+                Ok(_) => info!(
+                    "✅ Inbound for template {} synced to node {} ({})",
+                    template.id, node.id, node.name
+                ),
+                Err(e) => {
+                    failed += 1;
+                    error!(
+                        "❌ Failed to sync template {} to node {}: {}",
+                        template.id, node.id, e
+                    )
+                }
+            }
+        }
+        failed
+    }
+
     /// Ensures a node has an inbound matching the template.
     async fn ensure_inbound_exists(&self, node: &Node, template: &InboundTemplate) -> Result<()> {
         let tag = format!("tpl_{}", template.id);
@@ -93,6 +242,55 @@ impl GeneratorService {
                 .fetch_optional(&self.pool)
                 .await?;
 
+        let resolved = self
+            .resolve_template_inbound(node, template, existing_inbound.map(|(_, port)| port))
+            .await?;
+
+        if let Some((id, _)) = existing_inbound {
+            sqlx::query(
+                "UPDATE inbounds SET protocol = $1, settings = $2, stream_settings = $3, remark = $4, listen_port = $5, enable = TRUE WHERE id = $6"
+            )
+            .bind(&template.protocol)
+            .bind(&resolved.settings)
+            .bind(&resolved.stream_settings)
+            .bind(&template.name)
+            .bind(resolved.port)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO inbounds (node_id, tag, protocol, listen_port, settings, stream_settings, remark, enable)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, TRUE)
+            "#
+        )
+        .bind(node.id)
+        .bind(&tag)
+        .bind(&template.protocol)
+        .bind(resolved.port)
+        .bind(&resolved.settings)
+        .bind(&resolved.stream_settings)
+        .bind(&template.name)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Resolves a template's placeholders for one node without writing.
+    ///
+    /// `existing_port` is the port of the node's current inbound for the
+    /// template; it is kept while it stays inside the template's range.
+    async fn resolve_template_inbound(
+        &self,
+        node: &Node,
+        template: &InboundTemplate,
+        existing_port: Option<i64>,
+    ) -> Result<ResolvedInbound> {
         // 3. Resolve Placeholders
         let mut settings = template.settings_template.clone();
         let mut stream_settings = template.stream_settings_template.clone();
@@ -100,66 +298,33 @@ impl GeneratorService {
         let new_uuid = uuid::Uuid::new_v4().to_string();
         settings = settings.replace("{{uuid}}", &new_uuid);
 
-        if let Some((id, existing_port)) = existing_inbound {
+        let port = match existing_port {
             // Check compliance with current template range
-            let port_in_range = existing_port >= template.port_range_start
-                && existing_port <= template.port_range_end;
-
-            let port = if port_in_range {
+            Some(existing_port)
+                if existing_port >= template.port_range_start
+                    && existing_port <= template.port_range_end =>
+            {
                 existing_port
-            } else {
+            }
+            Some(existing_port) => {
                 info!(
-                    "Inbound {} port {} is outside template range {}-{}, updating...",
-                    tag, existing_port, template.port_range_start, template.port_range_end
+                    "Inbound tpl_{} port {} is outside template range {}-{}, updating...",
+                    template.id, existing_port, template.port_range_start, template.port_range_end
                 );
-                let new_port = if template.port_range_end > template.port_range_start {
+                if template.port_range_end > template.port_range_start {
                     self.allocate_port(node.id, template.port_range_start, template.port_range_end)
                         .await
                         .unwrap_or(template.port_range_start)
                 } else {
                     template.port_range_start
-                };
-                new_port
-            };
-
-            settings = settings.replace("{{port}}", &port.to_string());
-
-            if stream_settings.contains("{{reality_private}}") {
-                if let Some(priv_key) = &node.reality_priv {
-                    stream_settings = stream_settings.replace("{{reality_private}}", priv_key);
-                } else {
-                    warn!("Node {} missing Reality keys for template", node.id);
                 }
             }
-
-            if stream_settings.contains("{{pool_sni}}") || stream_settings.contains("{{sni}}") {
-                let best_sni = self
-                    .security_service
-                    .get_best_sni_for_node(node.id)
-                    .await
-                    .unwrap_or_else(|_| "www.google.com".to_string());
-                stream_settings = stream_settings.replace("{{pool_sni}}", &best_sni);
-                stream_settings = stream_settings.replace("{{sni}}", &best_sni);
+            None => {
+                self.allocate_port(node.id, template.port_range_start, template.port_range_end)
+                    .await?
             }
+        };
 
-            sqlx::query(
-                "UPDATE inbounds SET protocol = $1, settings = $2, stream_settings = $3, remark = $4, listen_port = $5, enable = TRUE WHERE id = $6"
-            )
-            .bind(&template.protocol)
-            .bind(&settings)
-            .bind(&stream_settings)
-            .bind(&template.name)
-            .bind(port)
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
-
-            return Ok(());
-        }
-
-        let port = self
-            .allocate_port(node.id, template.port_range_start, template.port_range_end)
-            .await?;
         settings = settings.replace("{{port}}", &port.to_string());
 
         if stream_settings.contains("{{reality_private}}") {
@@ -180,23 +345,11 @@ impl GeneratorService {
             stream_settings = stream_settings.replace("{{sni}}", &best_sni);
         }
 
-        sqlx::query(
-            r#"
-            INSERT INTO inbounds (node_id, tag, protocol, listen_port, settings, stream_settings, remark, enable)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, TRUE)
-            "#
-        )
-        .bind(node.id)
-        .bind(&tag)
-        .bind(&template.protocol)
-        .bind(port)
-        .bind(&settings)
-        .bind(&stream_settings)
-        .bind(&template.name)
-        .execute(&self.pool)
-        .await?;
-
-        Ok(())
+        Ok(ResolvedInbound {
+            port,
+            settings,
+            stream_settings,
+        })
     }
 
     async fn allocate_port(&self, node_id: i64, start: i64, end: i64) -> Result<i64> {
@@ -254,6 +407,23 @@ impl GeneratorService {
             return Err(anyhow::anyhow!("Inbound is not tied to a template"));
         }
 
+        let resolved = self.resolve_rotation(&inbound, &node).await?;
+
+        sqlx::query(
+            "UPDATE inbounds SET listen_port = $1, settings = $2, stream_settings = $3, last_rotated_at = CURRENT_TIMESTAMP WHERE id = $4"
+        )
+        .bind(resolved.port)
+        .bind(&resolved.settings)
+        .bind(&resolved.stream_settings)
+        .bind(inbound_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Resolves the fresh port, SNI and UUID a rotation would give an inbound.
+    async fn resolve_rotation(&self, inbound: &Inbound, node: &Node) -> Result<ResolvedInbound> {
         let template = if let Some(remark) = &inbound.remark {
             sqlx::query_as::<_, InboundTemplate>(
                 r#"
@@ -287,11 +457,7 @@ impl GeneratorService {
 
         let new_port = self
             .orchestration_service
-            .allocate_port(
-                inbound.node_id,
-                template.port_range_start,
-                template.port_range_end,
-            )
+            .allocate_port(node.id, template.port_range_start, template.port_range_end)
             .await?;
 
         let new_sni = self
@@ -332,17 +498,11 @@ impl GeneratorService {
             }
         }
 
-        sqlx::query(
-            "UPDATE inbounds SET listen_port = $1, settings = $2, stream_settings = $3, last_rotated_at = CURRENT_TIMESTAMP WHERE id = $4"
-        )
-        .bind(new_port)
-        .bind(settings)
-        .bind(&stream_settings)
-        .bind(inbound_id)
-        .execute(&self.pool)
-        .await?;
-
-        Ok(())
+        Ok(ResolvedInbound {
+            port: new_port,
+            settings,
+            stream_settings,
+        })
     }
 
     pub async fn rotate_group_inbounds(&self, group_id: i64) -> Result<()> {
@@ -1,6 +1,12 @@
 pub mod activity_service; // Legacy, to be replaced by logging_service
+pub mod admin_security_service;
 pub mod analytics_service;
+pub mod api_token_service;
+pub mod audit_service;
+pub mod chain_profile_service;
+pub mod config_version_service;
 pub mod connection_service;
+pub mod device_service;
 pub mod export_service; // NEW: Database and settings export/backup
 pub mod infrastructure_service;
 pub mod logging_service; // NEW
@@ -9,6 +15,7 @@ pub mod notification_service;
 pub mod orchestration_service;
 pub mod pay_service;
 pub mod pubsub_service; // NEW
+pub mod qr_service;
 pub mod redis_service; // NEW
 pub mod referral_service; // NEW
 pub mod security_service;
@@ -19,13 +26,19 @@ pub mod traffic_service; // Added Phase 3
 // Enterprise Modular Services
 pub mod billing_service;
 pub mod catalog_service;
+pub mod change_plan;
 pub mod generator_service;
 pub mod geo_service;
 pub mod org_service;
 pub mod payment;
 pub mod promo_service;
+pub mod rollout_service;
 pub mod rotation_service;
 pub mod sni_monitor;
+pub mod sub_template_service;
+pub mod sub_token_service;
 pub mod subscription_service;
 pub mod update_service;
 pub mod user_service; // NEW Phase 4
+pub mod webhook_service;
+pub mod wireguard_service;
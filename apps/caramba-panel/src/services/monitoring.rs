@@ -1,5 +1,7 @@
 use crate::AppState;
-use chrono::Utc;
+use crate::services::webhook_service::WebhookService;
+use caramba_db::models::webhook::WebhookEvent;
+use chrono::{DateTime, Utc};
 use tokio::time::{Duration, interval};
 use tracing::{error, info};
 
@@ -55,13 +57,24 @@ impl MonitoringService {
     }
 
     async fn check_node_status(&self) -> anyhow::Result<()> {
-        let rows_affected = sqlx::query("UPDATE nodes SET status = 'offline' WHERE last_seen < CURRENT_TIMESTAMP - INTERVAL '90 seconds' AND status != 'offline' AND status != 'new' AND status != 'disabled'")
-            .execute(&self.state.pool)
-            .await?
-            .rows_affected();
+        let offline: Vec<(i64, String, Option<DateTime<Utc>>)> = sqlx::query_as("UPDATE nodes SET status = 'offline' WHERE last_seen < CURRENT_TIMESTAMP - INTERVAL '90 seconds' AND status != 'offline' AND status != 'new' AND status != 'disabled' RETURNING id, name, last_seen")
+            .fetch_all(&self.state.pool)
+            .await?;
 
-        if rows_affected > 0 {
-            info!("Marked {} nodes as offline", rows_affected);
+        if !offline.is_empty() {
+            info!("Marked {} nodes as offline", offline.len());
+        }
+        for (node_id, name, last_seen) in offline {
+            WebhookService::emit(
+                &self.state.pool,
+                WebhookEvent::NodeOffline,
+                serde_json::json!({
+                    "node_id": node_id,
+                    "name": name,
+                    "last_seen": last_seen,
+                }),
+            )
+            .await;
         }
         Ok(())
     }
@@ -103,6 +116,17 @@ impl MonitoringService {
                 .execute(&self.state.pool)
                 .await?;
 
+            WebhookService::emit(
+                &self.state.pool,
+                WebhookEvent::SubscriptionExpired,
+                serde_json::json!({
+                    "subscription_id": sub_id,
+                    "user_id": user_id,
+                    "reason": "time",
+                }),
+            )
+            .await;
+
             info!(
                 "Subscription {} for user {} marked as expired",
                 sub_id, user_id
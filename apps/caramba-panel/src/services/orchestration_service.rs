@@ -3,9 +3,15 @@ use std::sync::Arc;
 use tracing::{error, info, warn};
 
 // Removed unused Subscription import
+use crate::services::change_plan::{NodePlan, plan_node};
+use crate::services::config_version_service::ConfigVersionService;
+use crate::services::device_service::{DeviceService, proxy_credentials, user_tag};
 use crate::services::store_service::StoreService;
+use crate::services::wireguard_service::WireguardService;
 use crate::singbox::{ConfigGenerator, RelayAuthMode};
+use caramba_db::models::network::Inbound;
 use caramba_db::models::node::Node;
+use std::collections::HashMap;
 
 use base64::Engine;
 use caramba_db::repositories::node_repo::NodeRepository;
@@ -20,6 +26,7 @@ pub struct OrchestrationService {
     security_service: Arc<crate::services::security_service::SecurityService>,
     store_service: Arc<StoreService>,
     pubsub_service: Arc<PubSubService>,
+    config_versions: Arc<ConfigVersionService>,
 }
 
 impl OrchestrationService {
@@ -28,6 +35,7 @@ impl OrchestrationService {
         store_service: Arc<StoreService>,
         security_service: Arc<crate::services::security_service::SecurityService>,
         pubsub_service: Arc<PubSubService>,
+        config_versions: Arc<ConfigVersionService>,
     ) -> Self {
         let node_repo = NodeRepository::new(pool.clone());
         Self {
@@ -36,6 +44,7 @@ impl OrchestrationService {
             store_service,
             security_service,
             pubsub_service,
+            config_versions,
         }
     }
 
@@ -62,6 +71,19 @@ impl OrchestrationService {
         info!("✅ PubSub: Published update signal to {}", channel);
         Ok(())
     }
+    /// Asks every node serving `plan_id` to pull its config again.
+    pub async fn notify_plan_nodes(&self, plan_id: i64) -> anyhow::Result<()> {
+        for node in self.node_repo.get_nodes_for_plan(plan_id).await? {
+            if let Err(e) = self.notify_node_update(node.id).await {
+                warn!(
+                    "Failed to notify node {} of plan {}: {}",
+                    node.id, plan_id, e
+                );
+            }
+        }
+        Ok(())
+    }
+
     /// Initializes default inbounds by applying Group Templates
     pub async fn init_default_inbounds(&self, node_id: i64) -> anyhow::Result<()> {
         info!("Initializing inbounds for node {} via templates", node_id);
@@ -209,53 +231,18 @@ impl OrchestrationService {
             let _ = self.node_repo.update_node(&effective_node).await;
         }
 
-        let sni = effective_node
-            .reality_sni
-            .as_deref()
-            .or(effective_node.domain.as_deref())
-            .unwrap_or("www.google.com");
-        let pkey = effective_node.reality_priv.clone().unwrap_or_default();
-        let pubkey = effective_node.reality_pub.clone().unwrap_or_default();
-        let sid = effective_node.short_id.clone().unwrap_or_default();
-
-        let settings = serde_json::json!({
-            "protocol": "vless",
-            "clients": [],
-            "decryption": "none"
-        })
-        .to_string();
-
-        let stream_settings = serde_json::json!({
-            "network": "tcp",
-            "security": "reality",
-            "reality_settings": {
-                "show": false,
-                "xver": 0,
-                "dest": format!("{}:443", sni),
-                "server_names": [sni],
-                "private_key": pkey,
-                "public_key": pubkey,
-                "short_ids": [sid]
-            }
-        })
-        .to_string();
-
-        let listen_port = if effective_node.vpn_port > 0 {
-            effective_node.vpn_port
-        } else {
-            443
-        };
+        let fallback = Self::minimal_default_inbound(&effective_node);
         let insert_result = sqlx::query(
             "INSERT INTO inbounds (node_id, tag, protocol, listen_port, listen_ip, settings, stream_settings, remark, enable) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, TRUE) ON CONFLICT (node_id, listen_port) DO NOTHING",
         )
         .bind(effective_node.id)
-        .bind("tpl_reality")
-        .bind("vless")
-        .bind(listen_port)
-        .bind("::")
-        .bind(&settings)
-        .bind(&stream_settings)
-        .bind(Some("Auto fallback inbound"))
+        .bind(&fallback.tag)
+        .bind(&fallback.protocol)
+        .bind(fallback.listen_port)
+        .bind(&fallback.listen_ip)
+        .bind(&fallback.settings)
+        .bind(&fallback.stream_settings)
+        .bind(&fallback.remark)
         .execute(&self.pool)
         .await;
 
@@ -269,11 +256,11 @@ impl OrchestrationService {
             let _ = sqlx::query(
                 "UPDATE inbounds SET protocol = $1, settings = $2, stream_settings = $3 WHERE node_id = $4 AND tag = $5",
             )
-            .bind("vless")
-            .bind(&settings)
-            .bind(&stream_settings)
+            .bind(&fallback.protocol)
+            .bind(&fallback.settings)
+            .bind(&fallback.stream_settings)
             .bind(effective_node.id)
-            .bind("tpl_reality")
+            .bind(&fallback.tag)
             .execute(&self.pool)
             .await;
 
@@ -281,11 +268,11 @@ impl OrchestrationService {
                 "INSERT INTO inbounds (node_id, tag, protocol, listen_port, settings, stream_settings) SELECT $1, $2, $3, $4, $5, $6 WHERE NOT EXISTS (SELECT 1 FROM inbounds WHERE node_id = $1 AND tag = $2)",
             )
             .bind(effective_node.id)
-            .bind("tpl_reality")
-            .bind("vless")
-            .bind(listen_port)
-            .bind(&settings)
-            .bind(&stream_settings)
+            .bind(&fallback.tag)
+            .bind(&fallback.protocol)
+            .bind(fallback.listen_port)
+            .bind(&fallback.settings)
+            .bind(&fallback.stream_settings)
             .execute(&self.pool)
             .await
             {
@@ -343,6 +330,64 @@ impl OrchestrationService {
         Ok(id)
     }
 
+    /// The single VLESS Reality inbound used when a node has no templates.
+    fn minimal_default_inbound(node: &Node) -> Inbound {
+        let sni = node
+            .reality_sni
+            .as_deref()
+            .or(node.domain.as_deref())
+            .unwrap_or("www.google.com");
+        let pkey = node.reality_priv.clone().unwrap_or_default();
+        let pubkey = node.reality_pub.clone().unwrap_or_default();
+        let sid = node.short_id.clone().unwrap_or_default();
+
+        let settings = serde_json::json!({
+            "protocol": "vless",
+            "clients": [],
+            "decryption": "none"
+        })
+        .to_string();
+
+        let stream_settings = serde_json::json!({
+            "network": "tcp",
+            "security": "reality",
+            "reality_settings": {
+                "show": false,
+                "xver": 0,
+                "dest": format!("{}:443", sni),
+                "server_names": [sni],
+                "private_key": pkey,
+                "public_key": pubkey,
+                "short_ids": [sid]
+            }
+        })
+        .to_string();
+
+        let listen_port = if node.vpn_port > 0 {
+            node.vpn_port
+        } else {
+            443
+        };
+
+        Inbound {
+            id: 0,
+            node_id: node.id,
+            tag: "tpl_reality".to_string(),
+            protocol: "vless".to_string(),
+            listen_port,
+            listen_ip: "::".to_string(),
+            settings,
+            stream_settings,
+            remark: Some("Auto fallback inbound".to_string()),
+            enable: true,
+            renew_interval_mins: 0,
+            port_range_start: 0,
+            port_range_end: 0,
+            last_rotated_at: None,
+            created_at: None,
+        }
+    }
+
     async fn instantiate_inbound_from_template(
         &self,
         node: &Node,
@@ -353,6 +398,30 @@ impl OrchestrationService {
             template.name, node.id
         );
 
+        let mut node = node.clone();
+        if template.protocol == "vless" && node.reality_priv.is_none() {
+            // Generate Reality Keys
+            let (priv_key, pub_key, short_id) = self.generate_reality_keys()?;
+            node.reality_priv = Some(priv_key);
+            node.reality_pub = Some(pub_key);
+            node.short_id = Some(short_id);
+            self.node_repo.update_node(&node).await?;
+        }
+
+        let inbound = self.build_inbound_from_template(&node, template).await?;
+        self.node_repo.upsert_inbound(&inbound).await?;
+        Ok(())
+    }
+
+    /// Resolves a template into the inbound it would create on `node`.
+    ///
+    /// Picks a free port and SNI but writes nothing; Reality keys are taken
+    /// from `node` as given.
+    async fn build_inbound_from_template(
+        &self,
+        node: &Node,
+        template: &caramba_db::models::groups::InboundTemplate,
+    ) -> anyhow::Result<Inbound> {
         // 0. Placeholder Replacement
         // 0.1 Allocate Port if needed (Dynamic Port Allocation)
         let mut port = template.port_range_start;
@@ -403,21 +472,9 @@ impl OrchestrationService {
             .replace("{{REALITY_SID}}", sid);
 
         if template.protocol == "vless" {
-            // Generate Reality Keys
-            let (priv_key, pub_key, short_id) = self.generate_reality_keys()?;
-
-            if node.reality_priv.is_none() {
-                let mut updated_node = node.clone();
-                updated_node.reality_priv = Some(priv_key.clone());
-                updated_node.reality_pub = Some(pub_key.clone());
-                updated_node.short_id = Some(short_id.clone());
-                self.node_repo.update_node(&updated_node).await?;
-            }
-
-            let node_updated = self.node_repo.get_node_by_id(node.id).await?.unwrap();
-            let pkey = node_updated.reality_priv.unwrap_or_default();
-            let pubkey = node_updated.reality_pub.unwrap_or_default();
-            let sid = node_updated.short_id.unwrap_or_default();
+            let pkey = node.reality_priv.clone().unwrap_or_default();
+            let pubkey = node.reality_pub.clone().unwrap_or_default();
+            let sid = node.short_id.clone().unwrap_or_default();
 
             if let Ok(mut stream_obj) =
                 serde_json::from_str::<caramba_db::models::network::StreamSettings>(&stream_json)
@@ -431,11 +488,10 @@ impl OrchestrationService {
             }
         } else if template.protocol == "naive" {
             // Inject Reality Keys for Naive
-            let node_updated = self.node_repo.get_node_by_id(node.id).await?.unwrap();
             if let (Some(pkey), Some(pubkey), Some(sid)) = (
-                node_updated.reality_priv,
-                node_updated.reality_pub,
-                node_updated.short_id,
+                node.reality_priv.clone(),
+                node.reality_pub.clone(),
+                node.short_id.clone(),
             ) {
                 if let Ok(mut stream_obj) = serde_json::from_str::<
                     caramba_db::models::network::StreamSettings,
@@ -472,7 +528,7 @@ impl OrchestrationService {
             }
         }
 
-        let inbound = caramba_db::models::network::Inbound {
+        Ok(Inbound {
             id: 0,
             node_id: node.id,
             tag: format!("tpl_{}", template.name.to_lowercase().replace(' ', "_")),
@@ -488,10 +544,7 @@ impl OrchestrationService {
             port_range_end: template.port_range_end,
             last_rotated_at: None,
             created_at: None,
-        };
-
-        self.node_repo.upsert_inbound(&inbound).await?;
-        Ok(())
+        })
     }
 
     fn generate_reality_keys(&self) -> anyhow::Result<(String, String, String)> {
@@ -616,10 +669,85 @@ impl OrchestrationService {
         self.init_default_inbounds(node_id).await
     }
 
+    /// Dry run of `reset_inbounds`: the inbounds the node's group templates
+    /// would recreate, compared with what it serves now.
+    ///
+    /// Ports are picked against the current inbounds, so a real reset may
+    /// land on different ones within the same template ranges.
+    pub async fn plan_reset_inbounds(&self, node_id: i64) -> anyhow::Result<NodePlan> {
+        let node = self
+            .node_repo
+            .get_node_by_id(node_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Node not found"))?;
+        let before = self.node_repo.get_inbounds_by_node(node_id).await?;
+
+        let mut effective_node = node.clone();
+        if effective_node.reality_priv.is_none() {
+            let (priv_key, pub_key, short_id) = self.generate_reality_keys()?;
+            effective_node.reality_priv = Some(priv_key);
+            effective_node.reality_pub = Some(pub_key);
+            effective_node.short_id = Some(short_id);
+        }
+
+        let group_ids: Vec<i64> =
+            sqlx::query_scalar("SELECT group_id FROM node_group_members WHERE node_id = $1")
+                .bind(node_id)
+                .fetch_all(&self.pool)
+                .await
+                .unwrap_or_default();
+
+        let mut after = Vec::new();
+        for gid in group_ids {
+            for template in self
+                .node_repo
+                .get_templates_for_group(gid)
+                .await
+                .unwrap_or_default()
+            {
+                match self
+                    .build_inbound_from_template(&effective_node, &template)
+                    .await
+                {
+                    Ok(inbound) => after.push(inbound),
+                    Err(e) => warn!(
+                        "Plan: template '{}' for node {}: {}",
+                        template.name, node_id, e
+                    ),
+                }
+            }
+        }
+        if after.is_empty() {
+            after.push(Self::minimal_default_inbound(&effective_node));
+        }
+
+        // Recreated rows get the node's and groups' plans plus the default plan.
+        let mut plans = self
+            .node_repo
+            .get_linked_plans(node_id, -1)
+            .await
+            .unwrap_or_default();
+        if !plans.contains(&1) {
+            plans.push(1);
+        }
+        let mut overrides = HashMap::new();
+        for (i, inbound) in after.iter_mut().enumerate() {
+            inbound.id = -(i as i64) - 1;
+            overrides.insert(inbound.id, plans.clone());
+        }
+
+        Ok(plan_node(self, &effective_node, before, after, &overrides).await)
+    }
+
     /// Generates Node Config JSON without applying it (Internal)
+    ///
+    /// Every result is recorded as a config version of the node, attributed
+    /// to `reason` and `author`.
     pub async fn generate_node_config_json(
         &self,
         node_id: i64,
+        reason: &str,
+        author: &str,
     ) -> anyhow::Result<(caramba_db::models::node::Node, serde_json::Value)> {
         info!("Step 1: Fetching node details for ID: {}", node_id);
         // 1. Fetch node details
@@ -703,6 +831,36 @@ impl OrchestrationService {
             self.node_repo.update_node(&node).await?;
         }
 
+        let config = self
+            .render_node_config(&node, inbounds, &HashMap::new())
+            .await?;
+        info!("Config generation successful for node {}", node_id);
+
+        if let Err(e) = self
+            .config_versions
+            .record(node_id, &config, reason, author)
+            .await
+        {
+            warn!(
+                "Failed to record config version for node {}: {}",
+                node_id, e
+            );
+        }
+
+        Ok((node, config))
+    }
+
+    /// Renders and validates the sing-box config for `node` serving `inbounds`.
+    ///
+    /// Reads subscriptions and relay context but writes nothing, so it can
+    /// also render planned inbounds. `link_overrides` replaces the plan
+    /// lookup for the given inbound ids (planned inbounds have no rows yet).
+    pub async fn render_node_config(
+        &self,
+        node: &Node,
+        mut inbounds: Vec<Inbound>,
+        link_overrides: &HashMap<i64, Vec<i64>>,
+    ) -> anyhow::Result<serde_json::Value> {
         // We do this for ALL inbounds to ensure no {{placeholders}} leak into the generator
         if let Some(_new_sni) = &node.reality_sni {
             // ... existing SNI logic ...
@@ -772,12 +930,15 @@ impl OrchestrationService {
             }
 
             // Find plans linked to this inbound
-            let linked_plans = self.node_repo.get_linked_plans(node.id, inbound.id).await?;
+            let linked_plans = match link_overrides.get(&inbound.id) {
+                Some(plans) => plans.clone(),
+                None => self.node_repo.get_linked_plans(node.id, inbound.id).await?,
+            };
 
             if linked_plans.is_empty() {
                 warn!(
                     "⚠️ Inbound {} has NO linked plans for node {}, users will be empty",
-                    inbound.tag, node_id
+                    inbound.tag, node.id
                 );
             }
 
@@ -797,6 +958,36 @@ impl OrchestrationService {
                 inbound.tag
             );
 
+            // Devices that imported the subscription URL get a uuid each; the
+            // subscription's own one (used by direct links) only until then.
+            // AmneziaWG peers follow the same split, each with its own address.
+            let sub_ids: Vec<i64> = active_subs.iter().map(|sub| sub.0).collect();
+            let device_service = DeviceService::new(self.pool.clone());
+            let device_credentials = device_service
+                .active_credentials(&sub_ids)
+                .await
+                .unwrap_or_default();
+            let with_devices = device_service
+                .with_devices(&sub_ids)
+                .await
+                .unwrap_or_default();
+            let shared: Vec<(i64, Option<String>)> = active_subs
+                .iter()
+                .map(|sub| (sub.0, sub.1.clone()))
+                .collect();
+            let credentials = proxy_credentials(&shared, &device_credentials, &with_devices);
+            let wireguard_peers = if inbound.protocol.eq_ignore_ascii_case("amneziawg") {
+                WireguardService::new(self.pool.clone())
+                    .active_peers(&sub_ids)
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!("Failed to load WireGuard peers: {}", e);
+                        Vec::new()
+                    })
+            } else {
+                Vec::new()
+            };
+
             use caramba_db::models::network::{Hysteria2User, InboundType, NaiveUser, VlessClient};
 
             // Parse as Value first to handle missing 'protocol' tag in legacy/broken data
@@ -818,142 +1009,100 @@ impl OrchestrationService {
                 Ok(mut settings) => {
                     match &mut settings {
                         InboundType::Vless(vless) => {
-                            for sub in &active_subs {
-                                if let (sub_id, Some(uuid), _tg_id, _username) =
-                                    (sub.0, &sub.1, sub.2, &sub.3)
-                                {
-                                    // Use user_{sub_id} to match TrafficService expectation
-                                    let auth_name = format!("user_{}", sub_id);
-
-                                    info!(
-                                        "🔑 Injecting VLESS user: {} (UUID: {})",
-                                        auth_name, uuid
-                                    );
-                                    // Parse stream settings to check for TCP + Reality/TLS
-                                    let stream_json: serde_json::Value =
-                                        serde_json::from_str(&inbound.stream_settings)
-                                            .unwrap_or(serde_json::Value::Null);
-                                    let network = stream_json
-                                        .get("network")
-                                        .and_then(|v| v.as_str())
-                                        .unwrap_or("");
-                                    let security = stream_json
-                                        .get("security")
-                                        .and_then(|v| v.as_str())
-                                        .unwrap_or("");
-
-                                    vless.clients.push(VlessClient {
-                                        id: uuid.clone(),
-                                        email: auth_name,
-                                        // Only apply flow for TCP + REALITY/TLS
-                                        flow: if network == "tcp"
-                                            && (security == "reality" || security == "tls")
-                                        {
-                                            "xtls-rprx-vision".to_string()
-                                        } else {
-                                            "".to_string()
-                                        },
-                                    });
-                                }
+                            // Parse stream settings to check for TCP + Reality/TLS
+                            let stream_json: serde_json::Value =
+                                serde_json::from_str(&inbound.stream_settings)
+                                    .unwrap_or(serde_json::Value::Null);
+                            let network = stream_json
+                                .get("network")
+                                .and_then(|v| v.as_str())
+                                .unwrap_or("");
+                            let security = stream_json
+                                .get("security")
+                                .and_then(|v| v.as_str())
+                                .unwrap_or("");
+                            // Only apply flow for TCP + REALITY/TLS
+                            let flow = if network == "tcp"
+                                && (security == "reality" || security == "tls")
+                            {
+                                "xtls-rprx-vision".to_string()
+                            } else {
+                                "".to_string()
+                            };
+
+                            for (auth_name, uuid) in &credentials {
+                                info!("🔑 Injecting VLESS user: {} (UUID: {})", auth_name, uuid);
+                                vless.clients.push(VlessClient {
+                                    id: uuid.clone(),
+                                    email: auth_name.clone(),
+                                    flow: flow.clone(),
+                                });
                             }
                         }
                         InboundType::Hysteria2(hy2) => {
-                            for sub in &active_subs {
-                                if let (sub_id, Some(uuid), _, _) = (sub.0, &sub.1, sub.2, &sub.3) {
-                                    let auth_name = format!("user_{}", sub_id);
-
-                                    info!(
-                                        "🔑 Injecting HYSTERIA user: {} (Pass: {})",
-                                        auth_name, uuid
-                                    );
-                                    hy2.users.push(Hysteria2User {
-                                        name: Some(auth_name),
-                                        password: uuid.replace("-", ""),
-                                    });
-                                }
+                            for (auth_name, uuid) in &credentials {
+                                info!("🔑 Injecting HYSTERIA user: {} (Pass: {})", auth_name, uuid);
+                                hy2.users.push(Hysteria2User {
+                                    name: Some(auth_name.clone()),
+                                    password: uuid.replace("-", ""),
+                                });
                             }
                         }
                         InboundType::AmneziaWg(awg) => {
                             use caramba_db::models::network::AmneziaWgUser;
-                            for sub in &active_subs {
-                                if let (sub_id, Some(uuid), tg_id, _) =
-                                    (sub.0, &sub.1, sub.2, &sub.3)
-                                {
-                                    let auth_name = format!("user_{}", sub_id);
-
-                                    let client_priv = self.derive_awg_key(uuid);
-                                    let client_pub = self.priv_to_pub(&client_priv);
-
-                                    info!(
-                                        "🔑 Injecting AMNEZIAWG user: {} (Public: {})",
-                                        auth_name, client_pub
-                                    );
-                                    awg.users.push(AmneziaWgUser {
-                                        name: Some(auth_name),
-                                        private_key: client_priv,
-                                        public_key: client_pub,
-                                        preshared_key: None,
-                                        client_ip: format!("10.10.0.{}", (tg_id % 250) + 2),
-                                    });
-                                }
+                            for peer in &wireguard_peers {
+                                let auth_name = user_tag(peer.subscription_id, peer.device_id);
+                                info!(
+                                    "🔑 Injecting AMNEZIAWG user: {} (Public: {})",
+                                    auth_name, peer.public_key
+                                );
+                                awg.users.push(AmneziaWgUser {
+                                    name: Some(auth_name),
+                                    private_key: peer.private_key.clone(),
+                                    public_key: peer.public_key.clone(),
+                                    preshared_key: None,
+                                    client_ip: peer.address(),
+                                });
                             }
                         }
                         InboundType::Trojan(trojan) => {
                             use caramba_db::models::network::TrojanClient;
-                            for sub in &active_subs {
-                                if let (sub_id, Some(uuid), _, _) = (sub.0, &sub.1, sub.2, &sub.3) {
-                                    let auth_name = format!("user_{}", sub_id);
-                                    trojan.clients.push(TrojanClient {
-                                        password: uuid.clone(),
-                                        email: Some(auth_name),
-                                    });
-                                }
+                            for (auth_name, uuid) in &credentials {
+                                trojan.clients.push(TrojanClient {
+                                    password: uuid.clone(),
+                                    email: Some(auth_name.clone()),
+                                });
                             }
                         }
                         InboundType::Tuic(tuic) => {
-                            for sub in &active_subs {
-                                if let (sub_id, Some(uuid), _, _) = (sub.0, &sub.1, sub.2, &sub.3) {
-                                    let auth_name = format!("user_{}", sub_id);
-
-                                    info!("🔑 Injecting TUIC user: {} (UUID: {})", auth_name, uuid);
-                                    tuic.users.push(caramba_db::models::network::TuicUser {
-                                        name: Some(auth_name),
-                                        uuid: uuid.clone(),
-                                        password: uuid.replace("-", ""),
-                                    });
-                                }
+                            for (auth_name, uuid) in &credentials {
+                                info!("🔑 Injecting TUIC user: {} (UUID: {})", auth_name, uuid);
+                                tuic.users.push(caramba_db::models::network::TuicUser {
+                                    name: Some(auth_name.clone()),
+                                    uuid: uuid.clone(),
+                                    password: uuid.replace("-", ""),
+                                });
                             }
                         }
                         InboundType::Naive(naive) => {
-                            for sub in &active_subs {
-                                if let (sub_id, Some(uuid), _, _) = (sub.0, &sub.1, sub.2, &sub.3) {
-                                    let auth_name = format!("user_{}", sub_id);
-
-                                    info!(
-                                        "🔑 Injecting NAIVE user: {} (Pass: {})",
-                                        auth_name, uuid
-                                    );
-                                    naive.users.push(NaiveUser {
-                                        username: auth_name,
-                                        password: uuid.replace("-", ""),
-                                    });
-                                }
+                            for (auth_name, uuid) in &credentials {
+                                info!("🔑 Injecting NAIVE user: {} (Pass: {})", auth_name, uuid);
+                                naive.users.push(NaiveUser {
+                                    username: auth_name.clone(),
+                                    password: uuid.replace("-", ""),
+                                });
                             }
                         }
                         InboundType::Shadowsocks(ss) => {
-                            for sub in &active_subs {
-                                if let (sub_id, Some(uuid), _, _) = (sub.0, &sub.1, sub.2, &sub.3) {
-                                    let auth_name = format!("user_{}", sub_id);
-
-                                    info!(
-                                        "🔑 Injecting SHADOWSOCKS user: {} (Pass: {})",
-                                        auth_name, uuid
-                                    );
-                                    ss.users.push(caramba_db::models::network::ShadowsocksUser {
-                                        username: auth_name,
-                                        password: uuid.replace("-", ""),
-                                    });
-                                }
+                            for (auth_name, uuid) in &credentials {
+                                info!(
+                                    "🔑 Injecting SHADOWSOCKS user: {} (Pass: {})",
+                                    auth_name, uuid
+                                );
+                                ss.users.push(caramba_db::models::network::ShadowsocksUser {
+                                    username: auth_name.clone(),
+                                    password: uuid.replace("-", ""),
+                                });
                             }
                         }
                     }
@@ -1015,7 +1164,7 @@ impl OrchestrationService {
         info!("Step 4: generating final sing-box config JSON");
         // 4. Generate Config
         let config = ConfigGenerator::generate_config(
-            &node,
+            node,
             inbounds,
             relay_target_node,
             relay_target_inbound,
@@ -1029,51 +1178,12 @@ impl OrchestrationService {
         if let Err(e) = ConfigGenerator::validate_config(&config) {
             error!(
                 "❌ Generated config for node {} FAILED VALIDATION: {}",
-                node_id, e
+                node.id, e
             );
             return Err(e);
         }
-        info!("✅ Config validation passed for node {}", node_id);
-
-        info!("Config generation successful for node {}", node_id);
-        Ok((node, serde_json::to_value(&config)?))
-    }
-
-    /// Derives a stable X25519 private key from a UUID string
-    fn derive_awg_key(&self, uuid: &str) -> String {
-        use sha2::{Digest, Sha256};
-        let mut hasher = Sha256::new();
-        hasher.update(uuid.as_bytes());
-        hasher.update(b"amneziawg-key-salt");
-        let result = hasher.finalize();
-
-        let mut key = [0u8; 32];
-        key.copy_from_slice(&result[..32]);
-
-        // Clamp the key to be a valid X25519 private key
-        key[0] &= 248;
-        key[31] &= 127;
-        key[31] |= 64;
-
-        base64::Engine::encode(&base64::prelude::BASE64_STANDARD, key)
-    }
-
-    /// Converts an X25519 private key (base64) to a public key (base64)
-    fn priv_to_pub(&self, priv_b64: &str) -> String {
-        use x25519_dalek::{PublicKey, StaticSecret};
-
-        let priv_bytes =
-            base64::Engine::decode(&base64::prelude::BASE64_STANDARD, priv_b64).unwrap_or_default();
-        if priv_bytes.len() != 32 {
-            return "".to_string();
-        }
-
-        let mut key_arr = [0u8; 32];
-        key_arr.copy_from_slice(&priv_bytes);
-
-        let secret = StaticSecret::from(key_arr);
-        let public = PublicKey::from(&secret);
+        info!("✅ Config validation passed for node {}", node.id);
 
-        base64::Engine::encode(&base64::prelude::BASE64_STANDARD, public.as_bytes())
+        Ok(serde_json::to_value(&config)?)
     }
 }
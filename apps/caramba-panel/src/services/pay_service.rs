@@ -1,9 +1,11 @@
 use crate::bot_manager::BotManager;
 use crate::services::payment::{PaymentAdapter, cryptomus::CryptomusAdapter};
 use crate::services::store_service::StoreService;
+use crate::services::webhook_service::WebhookService;
 use anyhow::Result;
 use anyhow::anyhow;
 use caramba_db::models::payment::PaymentType;
+use caramba_db::models::webhook::WebhookEvent;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
@@ -794,9 +796,18 @@ impl PayService {
         let parts: Vec<&str> = payload.split(':').collect();
         if parts.len() < 3 {
             if let Ok(user_id) = payload.parse::<i64>() {
-                return self
-                    .process_balance_topup(user_id, amount_usd, method, external_id)
-                    .await;
+                self.process_balance_topup(user_id, amount_usd, method, external_id.clone())
+                    .await?;
+                self.emit_payment_completed(
+                    user_id,
+                    "balance",
+                    None,
+                    amount_usd,
+                    method,
+                    external_id,
+                )
+                .await;
+                return Ok(());
             }
             return Err(anyhow::anyhow!("Invalid payload: {}", payload));
         }
@@ -809,14 +820,22 @@ impl PayService {
             return Err(anyhow::anyhow!("Zero User ID"));
         }
 
-        match type_code {
+        let kind = match type_code {
             "bal" => {
-                self.process_balance_topup(user_id, amount_usd, method, external_id)
-                    .await
+                self.process_balance_topup(user_id, amount_usd, method, external_id.clone())
+                    .await?;
+                "balance"
             }
             "ord" => {
-                self.process_order_purchase(user_id, target_id, amount_usd, method, external_id)
-                    .await
+                self.process_order_purchase(
+                    user_id,
+                    target_id,
+                    amount_usd,
+                    method,
+                    external_id.clone(),
+                )
+                .await?;
+                "order"
             }
             "sub" => {
                 self.process_subscription_purchase(
@@ -824,12 +843,42 @@ impl PayService {
                     target_id,
                     amount_usd,
                     method,
-                    external_id,
+                    external_id.clone(),
                 )
-                .await
+                .await?;
+                "subscription"
             }
-            _ => Err(anyhow::anyhow!("Unknown Type: {}", type_code)),
-        }
+            _ => return Err(anyhow::anyhow!("Unknown Type: {}", type_code)),
+        };
+
+        let target_id = (kind != "balance").then_some(target_id);
+        self.emit_payment_completed(user_id, kind, target_id, amount_usd, method, external_id)
+            .await;
+        Ok(())
+    }
+
+    async fn emit_payment_completed(
+        &self,
+        user_id: i64,
+        kind: &str,
+        target_id: Option<i64>,
+        amount_usd: f64,
+        method: &str,
+        external_id: Option<String>,
+    ) {
+        WebhookService::emit(
+            &self.pool,
+            WebhookEvent::PaymentCompleted,
+            serde_json::json!({
+                "user_id": user_id,
+                "kind": kind,
+                "target_id": target_id,
+                "amount_usd": amount_usd,
+                "method": method,
+                "external_id": external_id,
+            }),
+        )
+        .await;
     }
 
     async fn process_order_purchase(
@@ -176,6 +176,25 @@ impl PromoService {
         .context("Failed to list promos")
     }
 
+    pub async fn get_promo(&self, id: i64) -> Result<Option<PromoCode>> {
+        sqlx::query_as::<_, PromoCode>(
+            "SELECT id, code, type as promo_type, plan_id, balance_amount, duration_days, traffic_gb, max_uses, current_uses, expires_at, created_at, created_by_admin_id, promoter_user_id, is_active FROM promo_codes WHERE id = $1"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to fetch promo")
+    }
+
+    pub async fn delete_promo(&self, id: i64) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM promo_codes WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete promo")?;
+        Ok(res.rows_affected() > 0)
+    }
+
     pub async fn get_promo_usages(&self, promo_id: i64) -> Result<Vec<PromoCodeUsage>> {
         sqlx::query_as::<_, PromoCodeUsage>(
             "SELECT id, promo_code_id, user_id, used_at FROM promo_code_usage WHERE promo_code_id = $1 ORDER BY used_at DESC"
@@ -5,6 +5,17 @@ use std::sync::{Arc, Mutex};
 use tokio::sync::oneshot;
 use tracing::{error, info, warn};
 
+use crate::services::redis_service::SUB_CONFIG_GENERATION_KEY;
+
+/// Whether a node event means the node's inbounds or settings changed,
+/// as opposed to commands like `scan` or `restart`.
+fn is_config_update(channel: &str, message: &str) -> bool {
+    channel.starts_with("node_events:")
+        && (matches!(message, "update" | "settings_update")
+            || serde_json::from_str::<serde_json::Value>(message)
+                .is_ok_and(|v| v["update"] == true))
+}
+
 /// Service to handle Pub/Sub for Long Polling
 /// Maintains a list of local waiters (HTTP requests) and a Redis subscriber
 #[derive(Debug)]
@@ -97,6 +108,13 @@ impl PubSubService {
     /// Publish a message to a channel
     pub async fn publish(&self, channel: &str, message: &str) -> Result<()> {
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        // A node whose config changes serves different subscription configs.
+        if is_config_update(channel, message) {
+            let _: () = redis::cmd("INCR")
+                .arg(SUB_CONFIG_GENERATION_KEY)
+                .query_async(&mut conn)
+                .await?;
+        }
         let _: () = redis::cmd("PUBLISH")
             .arg(channel)
             .arg(message)
@@ -105,3 +123,18 @@ impl PubSubService {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_config_update;
+
+    #[test]
+    fn only_config_events_invalidate_subscriptions() {
+        assert!(is_config_update("node_events:3", "update"));
+        assert!(is_config_update("node_events:3", r#"{"update":true}"#));
+        assert!(is_config_update("node_events:3", "settings_update"));
+        assert!(!is_config_update("node_events:3", "restart"));
+        assert!(!is_config_update("node_events:3", "logs_ready"));
+        assert!(!is_config_update("other:3", "update"));
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use qrcode::{Color, QrCode};
+
+/// Light modules around the code, as the QR spec asks for.
+const QUIET_ZONE: usize = 4;
+
+/// Pixels per module of PNG codes. Small enough for Telegram photos, large
+/// enough for a phone camera pointed at a TV.
+pub const PNG_SCALE: usize = 8;
+
+/// `data` as an 8-bit grayscale PNG, `scale` pixels per module.
+pub fn png(data: &str, scale: usize) -> Result<Vec<u8>> {
+    let code = QrCode::new(data.as_bytes())?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let scale = scale.max(1);
+    let size = (modules + 2 * QUIET_ZONE) * scale;
+
+    let mut pixels = vec![0xffu8; size * size];
+    for (i, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let (x, y) = (i % modules + QUIET_ZONE, i / modules + QUIET_ZONE);
+        for row in y * scale..(y + 1) * scale {
+            pixels[row * size + x * scale..row * size + (x + 1) * scale].fill(0);
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+    Ok(out)
+}
+
+/// `data` as a standalone SVG document, at least `min_size` pixels wide.
+pub fn svg(data: &str, min_size: u32) -> Result<String> {
+    let code = QrCode::new(data.as_bytes())?;
+    Ok(code
+        .render::<qrcode::render::svg::Color>()
+        .min_dimensions(min_size, min_size)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{png, svg};
+
+    #[test]
+    fn png_is_square_with_quiet_zone() {
+        let bytes = png("wireguard", 2).unwrap();
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+        // IHDR width and height: a version 1 code is 21 modules plus 2 * 4.
+        assert_eq!(&bytes[16..20], &58u32.to_be_bytes());
+        assert_eq!(&bytes[20..24], &58u32.to_be_bytes());
+    }
+
+    #[test]
+    fn svg_is_a_document() {
+        let doc = svg("vless://example", 200).unwrap();
+        assert!(doc.starts_with("<?xml"));
+        assert!(doc.contains("<svg"));
+    }
+}
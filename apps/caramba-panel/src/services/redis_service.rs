@@ -2,6 +2,12 @@ use anyhow::{Context, Result};
 use redis::aio::ConnectionManager;
 use tracing::info;
 
+/// Generation counter embedded in subscription config cache keys.
+pub const SUB_CONFIG_GENERATION_KEY: &str = "sub_config_generation";
+/// Upper bound on how long a generated subscription config is served from
+/// cache, for changes that do not invalidate it explicitly.
+const SUB_CONFIG_TTL_SECS: usize = 600;
+
 #[derive(Clone)]
 pub struct RedisService {
     manager: ConnectionManager,
@@ -59,22 +65,38 @@ impl RedisService {
         Ok(())
     }
 
-    // --- Specific Caching Methods ---
+    // --- Subscription Config Cache ---
+
+    /// Cache key of a generated config. Keys embed the current generation,
+    /// so bumping it retires every cached config at once.
+    pub async fn subscription_cache_key(&self, sub_uuid: &str, variant: &str) -> Result<String> {
+        let generation = self.get(SUB_CONFIG_GENERATION_KEY).await?;
+        Ok(format!(
+            "sub_config:{}:{}:{}",
+            generation.as_deref().unwrap_or("0"),
+            sub_uuid,
+            variant
+        ))
+    }
 
-    pub async fn cache_subscription(&self, sub_uuid: &str, config: &str) -> Result<()> {
-        // Cache subscription config for 1 hour (3600s)
-        let key = format!("sub_config:{}", sub_uuid);
-        self.set(&key, config, 3600).await
+    pub async fn cache_subscription(&self, key: &str, config: &str) -> Result<()> {
+        self.set(key, config, SUB_CONFIG_TTL_SECS).await
     }
 
-    pub async fn get_cached_subscription(&self, sub_uuid: &str) -> Result<Option<String>> {
-        let key = format!("sub_config:{}", sub_uuid);
-        self.get(&key).await
+    pub async fn get_cached_subscription(&self, key: &str) -> Result<Option<String>> {
+        self.get(key).await
     }
 
-    pub async fn invalidate_subscription(&self, sub_uuid: &str) -> Result<()> {
-        let key = format!("sub_config:{}", sub_uuid);
-        self.del(&key).await
+    /// Drops every cached subscription config, e.g. after a plan's node
+    /// groups change. Node config updates do this through `PubSubService`.
+    pub async fn invalidate_subscriptions(&self) -> Result<()> {
+        let mut manager = self.manager.clone();
+        let _: () = redis::cmd("INCR")
+            .arg(SUB_CONFIG_GENERATION_KEY)
+            .query_async(&mut manager)
+            .await
+            .context("Redis INCR failed")?;
+        Ok(())
     }
 
     // --- Rate Limiting ---
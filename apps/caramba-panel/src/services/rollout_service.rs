@@ -0,0 +1,777 @@
+use crate::services::generator_service::GeneratorService;
+use crate::services::orchestration_service::OrchestrationService;
+use anyhow::{Context, Result};
+use caramba_db::models::network::Inbound;
+use caramba_db::models::node::Node;
+use caramba_db::models::rollout::{ConfigRollout, ConfigRolloutNode};
+use caramba_db::repositories::node_repo::NodeRepository;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+/// How often active rollouts are re-evaluated.
+const CHECK_INTERVAL_SECS: u64 = 30;
+/// Time a node gets after a change before its heartbeats count.
+const SETTLE_SECS: i64 = 90;
+/// A node whose last heartbeat is older than this is treated as down.
+const HEARTBEAT_STALE_SECS: i64 = 120;
+/// Connection retention is only judged on nodes that had at least this many.
+const MIN_BASELINE_CONNECTIONS: i32 = 10;
+/// Latency increases smaller than this are noise, whatever the percentage.
+const LATENCY_NOISE_MS: f64 = 50.0;
+
+const ROLLOUT_COLUMNS: &str = "r.id, r.group_id, g.name AS group_name, r.template_id, t.name AS template_name, r.status, r.stage, r.canary_count, r.window_mins, r.auto_continue, r.max_latency_increase_pct, r.min_connection_retention_pct, r.stage_started_at, r.status_reason, r.created_by, r.created_at, r.finished_at";
+const ROLLOUT_FROM: &str = "config_rollouts r JOIN node_groups g ON g.id = r.group_id LEFT JOIN inbound_templates t ON t.id = r.template_id";
+
+/// Settings chosen when a rollout is started.
+#[derive(Debug, Clone)]
+pub struct RolloutOptions {
+    pub canary_count: i32,
+    pub window_mins: i32,
+    pub auto_continue: bool,
+    pub max_latency_increase_pct: i32,
+    pub min_connection_retention_pct: i32,
+}
+
+impl RolloutOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=50).contains(&self.canary_count) {
+            return Err("Canary count must be between 1 and 50".to_string());
+        }
+        if !(5..=1440).contains(&self.window_mins) {
+            return Err("Watch window must be between 5 and 1440 minutes".to_string());
+        }
+        if !(10..=1000).contains(&self.max_latency_increase_pct) {
+            return Err("Latency increase must be between 10% and 1000%".to_string());
+        }
+        if !(0..=100).contains(&self.min_connection_retention_pct) {
+            return Err("Connection retention must be between 0% and 100%".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Heartbeat data for a node under watch.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct HealthSample {
+    pub status: String,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub active_connections: Option<i32>,
+    pub last_latency: Option<f64>,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    /// Config failure reported by the agent, and when.
+    pub config_error: Option<String>,
+    pub config_error_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Health {
+    /// Not enough data yet; the note says what is awaited.
+    Pending(String),
+    Healthy,
+    Unhealthy(String),
+}
+
+/// Judge one applied node against the heartbeat it had before the change.
+pub fn assess(
+    sample: &HealthSample,
+    row: &ConfigRolloutNode,
+    rollout: &ConfigRollout,
+    now: DateTime<Utc>,
+) -> Health {
+    let Some(applied_at) = row.applied_at else {
+        return Health::Pending("Not applied yet".to_string());
+    };
+    if now - applied_at < Duration::seconds(SETTLE_SECS) {
+        return Health::Pending("Settling".to_string());
+    }
+
+    let stale = sample
+        .last_seen
+        .is_none_or(|t| now - t > Duration::seconds(HEARTBEAT_STALE_SECS));
+    if sample.status != "active" || stale {
+        return Health::Unhealthy(format!(
+            "Node went {} after the change",
+            if stale {
+                "silent"
+            } else {
+                sample.status.as_str()
+            }
+        ));
+    }
+
+    if let (Some(error), Some(at)) = (&sample.config_error, sample.config_error_at)
+        && at >= applied_at
+    {
+        return Health::Unhealthy(format!("Agent could not apply the new config: {}", error));
+    }
+
+    if sample.last_synced_at.is_none_or(|t| t < applied_at) {
+        let window_over = rollout.window_ends_at().is_some_and(|end| now >= end);
+        return if window_over {
+            Health::Unhealthy("Node never pulled the new config".to_string())
+        } else {
+            Health::Pending("Waiting for the node to pull its config".to_string())
+        };
+    }
+
+    if let (Some(before), Some(after)) = (row.baseline_connections, sample.active_connections)
+        && before >= MIN_BASELINE_CONNECTIONS
+        && (after as i64) * 100 < (before as i64) * rollout.min_connection_retention_pct as i64
+    {
+        return Health::Unhealthy(format!(
+            "Active connections dropped from {} to {}",
+            before, after
+        ));
+    }
+
+    if let (Some(before), Some(after)) = (row.baseline_latency, sample.last_latency)
+        && before > 0.0
+        && after - before > LATENCY_NOISE_MS
+        && after > before * (1.0 + rollout.max_latency_increase_pct as f64 / 100.0)
+    {
+        return Health::Unhealthy(format!(
+            "Latency rose from {:.0} ms to {:.0} ms",
+            before, after
+        ));
+    }
+
+    Health::Healthy
+}
+
+/// Pick canaries among online nodes: nodes with enough traffic to judge
+/// come first, lightest first, so a bad change hits as few users as possible.
+pub fn pick_canaries(nodes: &[Node], count: usize) -> Vec<i64> {
+    let mut candidates: Vec<&Node> = nodes
+        .iter()
+        .filter(|n| n.is_enabled && n.status == "active")
+        .collect();
+    candidates.sort_by_key(|n| {
+        let conns = n.active_connections.unwrap_or(0);
+        (conns < MIN_BASELINE_CONNECTIONS, conns, n.id)
+    });
+    candidates.into_iter().take(count).map(|n| n.id).collect()
+}
+
+/// Id of the unfinished rollout for a group, if any.
+pub async fn active_rollout_for_group(pool: &PgPool, group_id: i64) -> Result<Option<i64>> {
+    let id = sqlx::query_scalar(
+        "SELECT id FROM config_rollouts WHERE group_id = $1 AND status = ANY($2)",
+    )
+    .bind(group_id)
+    .bind(&ConfigRollout::ACTIVE_STATUSES[..])
+    .fetch_optional(pool)
+    .await?;
+    Ok(id)
+}
+
+pub struct RolloutService {
+    pool: PgPool,
+    generator: Arc<GeneratorService>,
+    orchestration: Arc<OrchestrationService>,
+    /// Serialises the worker and admin actions so a stage is never
+    /// advanced and rolled back at the same time.
+    lock: Mutex<()>,
+}
+
+impl RolloutService {
+    pub fn new(
+        pool: PgPool,
+        generator: Arc<GeneratorService>,
+        orchestration: Arc<OrchestrationService>,
+    ) -> Self {
+        Self {
+            pool,
+            generator,
+            orchestration,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub async fn list(&self, limit: i64) -> Result<Vec<ConfigRollout>> {
+        let rows = sqlx::query_as::<_, ConfigRollout>(&format!(
+            "SELECT {} FROM {} ORDER BY r.created_at DESC LIMIT $1",
+            ROLLOUT_COLUMNS, ROLLOUT_FROM
+        ))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<ConfigRollout>> {
+        let row = sqlx::query_as::<_, ConfigRollout>(&format!(
+            "SELECT {} FROM {} WHERE r.id = $1",
+            ROLLOUT_COLUMNS, ROLLOUT_FROM
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    pub async fn nodes(&self, rollout_id: i64) -> Result<Vec<ConfigRolloutNode>> {
+        let rows = sqlx::query_as::<_, ConfigRolloutNode>(
+            "SELECT rn.id, rn.rollout_id, rn.node_id, n.name AS node_name, rn.stage, rn.state, rn.snapshot, rn.baseline_connections, rn.baseline_latency, rn.applied_at, rn.checked_at, rn.health_note FROM config_rollout_nodes rn JOIN nodes n ON n.id = rn.node_id WHERE rn.rollout_id = $1 ORDER BY rn.stage, n.name",
+        )
+        .bind(rollout_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Start a rollout of the group's templates and apply the canary stage.
+    pub async fn start_rollout(
+        &self,
+        group_id: i64,
+        template_id: Option<i64>,
+        opts: &RolloutOptions,
+        author: &str,
+    ) -> Result<i64> {
+        let _guard = self.lock.lock().await;
+
+        if let Some(id) = active_rollout_for_group(&self.pool, group_id).await? {
+            anyhow::bail!("Rollout #{} is already in progress for this group", id);
+        }
+
+        let node_repo = NodeRepository::new(self.pool.clone());
+        let mut nodes = Vec::new();
+        for node_id in node_repo.get_group_nodes(group_id).await? {
+            if let Some(node) = node_repo.get_node_by_id(node_id).await? {
+                nodes.push(node);
+            }
+        }
+        if nodes.is_empty() {
+            anyhow::bail!("Group has no nodes");
+        }
+        let canaries = pick_canaries(&nodes, opts.canary_count as usize);
+        if canaries.is_empty() {
+            anyhow::bail!("No online nodes in the group to use as canaries");
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let rollout_id: i64 = sqlx::query_scalar(
+            "INSERT INTO config_rollouts (group_id, template_id, canary_count, window_mins, auto_continue, max_latency_increase_pct, min_connection_retention_pct, created_by) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+        )
+        .bind(group_id)
+        .bind(template_id)
+        .bind(opts.canary_count)
+        .bind(opts.window_mins)
+        .bind(opts.auto_continue)
+        .bind(opts.max_latency_increase_pct)
+        .bind(opts.min_connection_retention_pct)
+        .bind(author)
+        .fetch_one(&mut *tx)
+        .await
+        .context("Failed to create rollout")?;
+
+        for node in &nodes {
+            let stage = if canaries.contains(&node.id) { 1 } else { 2 };
+            sqlx::query(
+                "INSERT INTO config_rollout_nodes (rollout_id, node_id, stage) VALUES ($1, $2, $3)",
+            )
+            .bind(rollout_id)
+            .bind(node.id)
+            .bind(stage)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        info!(
+            "🐤 Rollout #{} started for group {} with {} canaries",
+            rollout_id,
+            group_id,
+            canaries.len()
+        );
+
+        let rollout = self
+            .get(rollout_id)
+            .await?
+            .context("Rollout vanished after creation")?;
+        self.apply_stage(&rollout, 1, author).await?;
+        Ok(rollout_id)
+    }
+
+    /// Approve a rollout waiting after its canary stage.
+    pub async fn continue_rollout(&self, id: i64, author: &str) -> Result<bool> {
+        let _guard = self.lock.lock().await;
+        let Some(rollout) = self.get(id).await? else {
+            return Ok(false);
+        };
+        if rollout.status != "awaiting_approval" {
+            return Ok(false);
+        }
+        self.apply_stage(&rollout, 2, author).await?;
+        Ok(true)
+    }
+
+    /// Restore every node touched by an unfinished rollout.
+    pub async fn rollback(&self, id: i64, reason: &str, author: &str) -> Result<bool> {
+        let _guard = self.lock.lock().await;
+        let Some(rollout) = self.get(id).await? else {
+            return Ok(false);
+        };
+        if !rollout.is_active() {
+            return Ok(false);
+        }
+        self.rollback_locked(&rollout, reason, author).await?;
+        Ok(true)
+    }
+
+    pub async fn start(&self) {
+        info!("Starting rollout health watcher...");
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(CHECK_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.check_active().await {
+                error!("Rollout watcher error: {}", e);
+            }
+        }
+    }
+
+    async fn check_active(&self) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let active = sqlx::query_as::<_, ConfigRollout>(&format!(
+            "SELECT {} FROM {} WHERE r.status IN ('canary', 'rolling_out')",
+            ROLLOUT_COLUMNS, ROLLOUT_FROM
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        for rollout in active {
+            if let Err(e) = self.evaluate(&rollout).await {
+                error!("Failed to evaluate rollout #{}: {}", rollout.id, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the current stage's nodes and advance, finish or roll back.
+    async fn evaluate(&self, rollout: &ConfigRollout) -> Result<()> {
+        let now = Utc::now();
+        let rows: Vec<ConfigRolloutNode> = self
+            .nodes(rollout.id)
+            .await?
+            .into_iter()
+            .filter(|r| r.stage == rollout.stage && r.state == "applied")
+            .collect();
+
+        let mut all_healthy = true;
+        for row in &rows {
+            let sample = sqlx::query_as::<_, HealthSample>(
+                "SELECT status, last_seen, active_connections, last_latency, last_synced_at, config_error, config_error_at FROM nodes WHERE id = $1",
+            )
+            .bind(row.node_id)
+            .fetch_optional(&self.pool)
+            .await?;
+            let Some(sample) = sample else {
+                continue;
+            };
+
+            let (note, health) = match assess(&sample, row, rollout, now) {
+                Health::Healthy => ("Healthy".to_string(), Health::Healthy),
+                Health::Pending(note) => (note.clone(), Health::Pending(note)),
+                Health::Unhealthy(note) => (note.clone(), Health::Unhealthy(note)),
+            };
+
+            let state = if matches!(health, Health::Unhealthy(_)) {
+                "unhealthy"
+            } else {
+                "applied"
+            };
+            sqlx::query(
+                "UPDATE config_rollout_nodes SET state = $1, health_note = $2, checked_at = CURRENT_TIMESTAMP WHERE id = $3",
+            )
+            .bind(state)
+            .bind(&note)
+            .bind(row.id)
+            .execute(&self.pool)
+            .await?;
+
+            match health {
+                Health::Unhealthy(note) => {
+                    warn!(
+                        "🚨 Rollout #{}: node {} unhealthy: {}",
+                        rollout.id, row.node_name, note
+                    );
+                    let reason = format!("{}: {}", row.node_name, note);
+                    return self.rollback_locked(rollout, &reason, "system").await;
+                }
+                Health::Pending(_) => all_healthy = false,
+                Health::Healthy => {}
+            }
+        }
+
+        let window_over = rollout.window_ends_at().is_some_and(|end| now >= end);
+        if !window_over || !all_healthy {
+            return Ok(());
+        }
+
+        sqlx::query(
+            "UPDATE config_rollout_nodes SET state = 'healthy' WHERE rollout_id = $1 AND stage = $2 AND state = 'applied'",
+        )
+        .bind(rollout.id)
+        .bind(rollout.stage)
+        .execute(&self.pool)
+        .await?;
+
+        if rollout.stage == 1 && rollout.auto_continue {
+            info!("✅ Rollout #{} canaries healthy, continuing", rollout.id);
+            self.apply_stage(rollout, 2, "system").await
+        } else if rollout.stage == 1 {
+            info!(
+                "✅ Rollout #{} canaries healthy, awaiting approval",
+                rollout.id
+            );
+            self.set_status(rollout.id, "awaiting_approval", Some("Canaries healthy"))
+                .await
+        } else {
+            info!("✅ Rollout #{} completed", rollout.id);
+            self.set_status(rollout.id, "completed", None).await
+        }
+    }
+
+    /// Snapshot, apply and push the templates to every pending node of a stage.
+    async fn apply_stage(&self, rollout: &ConfigRollout, stage: i32, author: &str) -> Result<()> {
+        let node_repo = NodeRepository::new(self.pool.clone());
+        let templates = node_repo.get_templates_for_group(rollout.group_id).await?;
+
+        sqlx::query(
+            "UPDATE config_rollouts SET stage = $1, status = $2, stage_started_at = CURRENT_TIMESTAMP, status_reason = NULL WHERE id = $3",
+        )
+        .bind(stage)
+        .bind(if stage == 1 { "canary" } else { "rolling_out" })
+        .bind(rollout.id)
+        .execute(&self.pool)
+        .await?;
+
+        let rows: Vec<ConfigRolloutNode> = self
+            .nodes(rollout.id)
+            .await?
+            .into_iter()
+            .filter(|r| r.stage == stage && r.state == "pending")
+            .collect();
+
+        let reason = format!(
+            "Rollout #{} {}",
+            rollout.id,
+            if stage == 1 { "canary" } else { "stage 2" }
+        );
+        let mut failure = None;
+
+        for row in &rows {
+            let Some(node) = node_repo.get_node_by_id(row.node_id).await? else {
+                continue;
+            };
+
+            let snapshot: Vec<Inbound> = node_repo
+                .get_inbounds_by_node(node.id)
+                .await?
+                .into_iter()
+                .filter(|i| i.tag.starts_with("tpl_"))
+                .collect();
+
+            // Nodes that are already down cannot be judged, so they are
+            // updated but not watched.
+            let watched = node.is_enabled && node.status == "active";
+            let (state, note) = if watched {
+                ("applied", None)
+            } else {
+                (
+                    "healthy",
+                    Some("Not watched: node was offline when applied"),
+                )
+            };
+
+            sqlx::query(
+                "UPDATE config_rollout_nodes SET state = $1, health_note = $2, snapshot = $3, baseline_connections = $4, baseline_latency = $5, applied_at = CURRENT_TIMESTAMP WHERE id = $6",
+            )
+            .bind(state)
+            .bind(note)
+            .bind(serde_json::to_value(&snapshot)?)
+            .bind(node.active_connections)
+            .bind(node.last_latency)
+            .bind(row.id)
+            .execute(&self.pool)
+            .await?;
+
+            let failed = self.generator.sync_node_templates(&node, &templates).await;
+            let generated = self
+                .orchestration
+                .generate_node_config_json(node.id, &reason, author)
+                .await;
+
+            if failed > 0 || generated.is_err() {
+                let note = match generated {
+                    Err(e) => format!("Config generation failed: {}", e),
+                    Ok(_) => format!("{} template(s) failed to apply", failed),
+                };
+                sqlx::query(
+                    "UPDATE config_rollout_nodes SET state = 'unhealthy', health_note = $1 WHERE id = $2",
+                )
+                .bind(&note)
+                .bind(row.id)
+                .execute(&self.pool)
+                .await?;
+                failure = Some(format!("{}: {}", node.name, note));
+                break;
+            }
+
+            if let Err(e) = self.orchestration.notify_node_update(node.id).await {
+                warn!(
+                    "Failed to notify node {} of rollout #{}: {}",
+                    node.id, rollout.id, e
+                );
+            }
+        }
+
+        if let Some(reason) = failure {
+            return self.rollback_locked(rollout, &reason, author).await;
+        }
+        Ok(())
+    }
+
+    async fn rollback_locked(
+        &self,
+        rollout: &ConfigRollout,
+        reason: &str,
+        author: &str,
+    ) -> Result<()> {
+        let rows: Vec<ConfigRolloutNode> = self
+            .nodes(rollout.id)
+            .await?
+            .into_iter()
+            .filter(|r| r.state != "pending" && r.state != "rolled_back")
+            .collect();
+
+        let config_reason = format!("Rollout #{} rolled back", rollout.id);
+        for row in &rows {
+            if let Err(e) = self.restore_node(row).await {
+                error!(
+                    "Failed to restore node {} for rollout #{}: {}",
+                    row.node_id, rollout.id, e
+                );
+                continue;
+            }
+            if let Err(e) = self
+                .orchestration
+                .generate_node_config_json(row.node_id, &config_reason, author)
+                .await
+            {
+                error!("Failed to regenerate config of node {}: {}", row.node_id, e);
+            }
+            if let Err(e) = self.orchestration.notify_node_update(row.node_id).await {
+                warn!("Failed to notify node {} of rollback: {}", row.node_id, e);
+            }
+            sqlx::query("UPDATE config_rollout_nodes SET state = 'rolled_back' WHERE id = $1")
+                .bind(row.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        warn!("↩️ Rollout #{} rolled back: {}", rollout.id, reason);
+        self.set_status(rollout.id, "rolled_back", Some(reason))
+            .await
+    }
+
+    /// Put a node's template inbounds back the way the snapshot recorded them.
+    async fn restore_node(&self, row: &ConfigRolloutNode) -> Result<()> {
+        let snapshot: Vec<Inbound> = match &row.snapshot {
+            Some(value) => serde_json::from_value(value.clone())?,
+            None => return Ok(()),
+        };
+        let kept: Vec<i64> = snapshot.iter().map(|i| i.id).collect();
+
+        let mut tx = self.pool.begin().await?;
+        // Inbounds the rollout created did not exist before it.
+        sqlx::query(
+            "DELETE FROM inbounds WHERE node_id = $1 AND tag LIKE 'tpl\\_%' AND NOT (id = ANY($2))",
+        )
+        .bind(row.node_id)
+        .bind(&kept)
+        .execute(&mut *tx)
+        .await?;
+
+        for inbound in &snapshot {
+            sqlx::query(
+                "UPDATE inbounds SET protocol = $1, listen_port = $2, settings = $3, stream_settings = $4, remark = $5, enable = $6 WHERE id = $7 AND node_id = $8",
+            )
+            .bind(&inbound.protocol)
+            .bind(inbound.listen_port)
+            .bind(&inbound.settings)
+            .bind(&inbound.stream_settings)
+            .bind(&inbound.remark)
+            .bind(inbound.enable)
+            .bind(inbound.id)
+            .bind(row.node_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn set_status(&self, id: i64, status: &str, reason: Option<&str>) -> Result<()> {
+        let finished = matches!(status, "completed" | "rolled_back");
+        sqlx::query(
+            "UPDATE config_rollouts SET status = $1, status_reason = $2, finished_at = CASE WHEN $3 THEN CURRENT_TIMESTAMP ELSE finished_at END WHERE id = $4",
+        )
+        .bind(status)
+        .bind(reason)
+        .bind(finished)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rollout() -> ConfigRollout {
+        ConfigRollout {
+            id: 1,
+            group_id: 1,
+            group_name: "eu".to_string(),
+            template_id: Some(3),
+            template_name: None,
+            status: "canary".to_string(),
+            stage: 1,
+            canary_count: 1,
+            window_mins: 15,
+            auto_continue: false,
+            max_latency_increase_pct: 100,
+            min_connection_retention_pct: 50,
+            stage_started_at: Some(Utc::now() - Duration::minutes(5)),
+            status_reason: None,
+            created_by: "admin".to_string(),
+            created_at: Utc::now(),
+            finished_at: None,
+        }
+    }
+
+    fn row(conns: i32, latency: f64) -> ConfigRolloutNode {
+        ConfigRolloutNode {
+            id: 1,
+            rollout_id: 1,
+            node_id: 7,
+            node_name: "fra-1".to_string(),
+            stage: 1,
+            state: "applied".to_string(),
+            snapshot: None,
+            baseline_connections: Some(conns),
+            baseline_latency: Some(latency),
+            applied_at: Some(Utc::now() - Duration::minutes(5)),
+            checked_at: None,
+            health_note: None,
+        }
+    }
+
+    fn sample(conns: i32, latency: f64) -> HealthSample {
+        HealthSample {
+            status: "active".to_string(),
+            last_seen: Some(Utc::now()),
+            active_connections: Some(conns),
+            last_latency: Some(latency),
+            last_synced_at: Some(Utc::now() - Duration::minutes(4)),
+            config_error: None,
+            config_error_at: None,
+        }
+    }
+
+    #[test]
+    fn steady_node_is_healthy() {
+        let health = assess(&sample(95, 110.0), &row(100, 100.0), &rollout(), Utc::now());
+        assert_eq!(health, Health::Healthy);
+    }
+
+    #[test]
+    fn fresh_change_is_given_time_to_settle() {
+        let mut r = row(100, 100.0);
+        r.applied_at = Some(Utc::now() - Duration::seconds(10));
+        let health = assess(&sample(0, 100.0), &r, &rollout(), Utc::now());
+        assert!(matches!(health, Health::Pending(_)));
+    }
+
+    #[test]
+    fn silent_or_offline_node_is_unhealthy() {
+        let mut s = sample(100, 100.0);
+        s.last_seen = Some(Utc::now() - Duration::minutes(10));
+        assert!(matches!(
+            assess(&s, &row(100, 100.0), &rollout(), Utc::now()),
+            Health::Unhealthy(_)
+        ));
+
+        let mut s = sample(100, 100.0);
+        s.status = "offline".to_string();
+        assert!(matches!(
+            assess(&s, &row(100, 100.0), &rollout(), Utc::now()),
+            Health::Unhealthy(_)
+        ));
+    }
+
+    #[test]
+    fn connection_drop_beyond_retention_is_unhealthy() {
+        let health = assess(&sample(40, 100.0), &row(100, 100.0), &rollout(), Utc::now());
+        assert!(matches!(health, Health::Unhealthy(_)));
+        // Too little traffic before the change to judge
+        let health = assess(&sample(0, 100.0), &row(5, 100.0), &rollout(), Utc::now());
+        assert_eq!(health, Health::Healthy);
+    }
+
+    #[test]
+    fn latency_increase_needs_percentage_and_absolute_jump() {
+        let health = assess(
+            &sample(100, 260.0),
+            &row(100, 100.0),
+            &rollout(),
+            Utc::now(),
+        );
+        assert!(matches!(health, Health::Unhealthy(_)));
+        // Doubled, but only by 20 ms
+        let health = assess(&sample(100, 40.0), &row(100, 20.0), &rollout(), Utc::now());
+        assert_eq!(health, Health::Healthy);
+    }
+
+    #[test]
+    fn config_rejected_by_agent_is_unhealthy() {
+        let mut s = sample(100, 100.0);
+        s.config_error = Some("decode config: unknown field".to_string());
+        // Failure of an older config does not count against this change
+        s.config_error_at = Some(Utc::now() - Duration::hours(1));
+        assert_eq!(
+            assess(&s, &row(100, 100.0), &rollout(), Utc::now()),
+            Health::Healthy
+        );
+        s.config_error_at = Some(Utc::now() - Duration::minutes(3));
+        assert_eq!(
+            assess(&s, &row(100, 100.0), &rollout(), Utc::now()),
+            Health::Unhealthy(
+                "Agent could not apply the new config: decode config: unknown field".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn config_not_pulled_fails_only_after_window() {
+        let mut s = sample(100, 100.0);
+        s.last_synced_at = Some(Utc::now() - Duration::minutes(30));
+        let mut r = rollout();
+        assert!(matches!(
+            assess(&s, &row(100, 100.0), &r, Utc::now()),
+            Health::Pending(_)
+        ));
+        r.stage_started_at = Some(Utc::now() - Duration::minutes(20));
+        assert!(matches!(
+            assess(&s, &row(100, 100.0), &r, Utc::now()),
+            Health::Unhealthy(_)
+        ));
+    }
+}
@@ -1,5 +1,7 @@
+use crate::services::webhook_service::WebhookService;
 use anyhow::{Context, Result};
 use caramba_db::models::sni_log::SniRotationLog;
+use caramba_db::models::webhook::WebhookEvent;
 use sqlx::PgPool;
 
 #[derive(Debug, Clone)]
@@ -106,6 +108,21 @@ impl SecurityService {
         .await
         .context("Failed to log SNI rotation")?;
 
+        // Every rotation path goes through here, so this is the one place
+        // the event is emitted.
+        WebhookService::emit(
+            &self.pool,
+            WebhookEvent::SniRotated,
+            serde_json::json!({
+                "node_id": node_id,
+                "old_sni": old_sni,
+                "new_sni": new_sni,
+                "reason": reason,
+                "rotation_id": log.id,
+            }),
+        )
+        .await;
+
         Ok(log)
     }
 
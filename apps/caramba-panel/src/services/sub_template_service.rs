@@ -0,0 +1,126 @@
+use anyhow::Result;
+use caramba_db::models::sub_template::SubscriptionTemplate;
+use sqlx::PgPool;
+
+const TEMPLATE_COLUMNS: &str = "t.id, t.name, t.client_format, t.body, t.is_default, COALESCE((SELECT ARRAY_AGG(p.plan_id ORDER BY p.plan_id) FROM plan_subscription_templates p WHERE p.template_id = t.id), '{}') AS plan_ids, t.created_at, t.updated_at";
+
+/// Fields of a template as submitted from the admin form.
+pub struct TemplateInput<'a> {
+    pub name: &'a str,
+    pub client_format: &'a str,
+    pub body: &'a str,
+    pub is_default: bool,
+    pub plan_ids: &'a [i64],
+}
+
+pub struct SubTemplateService {
+    pool: PgPool,
+}
+
+impl SubTemplateService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self) -> Result<Vec<SubscriptionTemplate>> {
+        Ok(sqlx::query_as::<_, SubscriptionTemplate>(&format!(
+            "SELECT {} FROM subscription_templates t ORDER BY t.client_format, t.name",
+            TEMPLATE_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<SubscriptionTemplate>> {
+        Ok(sqlx::query_as::<_, SubscriptionTemplate>(&format!(
+            "SELECT {} FROM subscription_templates t WHERE t.id = $1",
+            TEMPLATE_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Template body to render `format` with for a plan: the plan's own
+    /// template, else the format's default, else `None` for the built-in config.
+    pub async fn body_for(&self, plan_id: i64, format: &str) -> Result<Option<String>> {
+        Ok(sqlx::query_scalar(
+            "SELECT t.body FROM subscription_templates t LEFT JOIN plan_subscription_templates p ON p.template_id = t.id AND p.plan_id = $1 WHERE t.client_format = $2 AND (p.plan_id IS NOT NULL OR t.is_default) ORDER BY (p.plan_id IS NOT NULL) DESC LIMIT 1",
+        )
+        .bind(plan_id)
+        .bind(format)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Create (`id` = None) or update a template, and reassign its plans.
+    /// A plan moved here leaves the template it had for the same format.
+    pub async fn save(&self, id: Option<i64>, input: &TemplateInput<'_>) -> Result<i64> {
+        let mut tx = self.pool.begin().await?;
+
+        if input.is_default {
+            sqlx::query(
+                "UPDATE subscription_templates SET is_default = FALSE WHERE client_format = $1 AND is_default AND id IS DISTINCT FROM $2",
+            )
+            .bind(input.client_format)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let id: i64 = match id {
+            Some(id) => sqlx::query_scalar(
+                "UPDATE subscription_templates SET name = $1, client_format = $2, body = $3, is_default = $4, updated_at = CURRENT_TIMESTAMP WHERE id = $5 RETURNING id",
+            )
+            .bind(input.name)
+            .bind(input.client_format)
+            .bind(input.body)
+            .bind(input.is_default)
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?,
+            None => sqlx::query_scalar(
+                "INSERT INTO subscription_templates (name, client_format, body, is_default) VALUES ($1, $2, $3, $4) RETURNING id",
+            )
+            .bind(input.name)
+            .bind(input.client_format)
+            .bind(input.body)
+            .bind(input.is_default)
+            .fetch_one(&mut *tx)
+            .await?,
+        };
+
+        sqlx::query("DELETE FROM plan_subscription_templates WHERE template_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        if !input.plan_ids.is_empty() {
+            sqlx::query(
+                "INSERT INTO plan_subscription_templates (plan_id, client_format, template_id) SELECT UNNEST($1::BIGINT[]), $2, $3 ON CONFLICT (plan_id, client_format) DO UPDATE SET template_id = EXCLUDED.template_id",
+            )
+            .bind(input.plan_ids)
+            .bind(input.client_format)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    pub async fn delete(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM subscription_templates WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Plans that can be assigned a template, as (id, name).
+    pub async fn plan_options(&self) -> Result<Vec<(i64, String)>> {
+        Ok(sqlx::query_as("SELECT id, name FROM plans ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?)
+    }
+}
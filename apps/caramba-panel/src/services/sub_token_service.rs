@@ -0,0 +1,156 @@
+use anyhow::Result;
+use caramba_db::models::store::Subscription;
+use caramba_db::models::sub_token::SubscriptionToken;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Tokens of the same subscription kept for the admin's history view.
+const TOKEN_HISTORY_LIMIT: i64 = 10;
+
+/// Subscription URL tokens: lookup, usage tracking and rotation.
+pub struct SubTokenService {
+    pool: PgPool,
+}
+
+impl SubTokenService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Subscription a URL token grants access to. A subscription that never
+    /// had a token is found by its `subscription_uuid`.
+    pub async fn resolve(&self, token: &str) -> Result<Option<Subscription>> {
+        Ok(sqlx::query_as::<_, Subscription>(
+            r#"
+            SELECT s.* FROM subscriptions s
+            WHERE s.id = COALESCE(
+                (SELECT t.subscription_id FROM subscription_tokens t
+                 WHERE t.token = $1 AND (t.revoked_at IS NULL OR t.revoked_at > CURRENT_TIMESTAMP)),
+                (SELECT s2.id FROM subscriptions s2
+                 WHERE s2.subscription_uuid = $1
+                   AND NOT EXISTS (SELECT 1 FROM subscription_tokens t2 WHERE t2.subscription_id = s2.id))
+            )
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Note that `token` was just used from `ip`. A legacy uuid token gets
+    /// its row here.
+    pub async fn record_use(
+        &self,
+        subscription_id: i64,
+        token: &str,
+        ip: &str,
+        user_agent: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO subscription_tokens (subscription_id, token, last_used_at, last_used_ip, last_user_agent)
+            VALUES ($1, $2, CURRENT_TIMESTAMP, $3, $4)
+            ON CONFLICT (token) DO UPDATE SET
+                last_used_at = EXCLUDED.last_used_at,
+                last_used_ip = EXCLUDED.last_used_ip,
+                last_user_agent = EXCLUDED.last_user_agent
+            "#,
+        )
+        .bind(subscription_id)
+        .bind(token)
+        .bind(ip)
+        .bind(user_agent)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Token links should be built with: the newest current token, else
+    /// the subscription's uuid.
+    pub async fn current(&self, subscription_id: i64, subscription_uuid: &str) -> String {
+        sqlx::query_scalar::<_, String>(
+            "SELECT token FROM subscription_tokens WHERE subscription_id = $1 AND revoked_at IS NULL ORDER BY id DESC LIMIT 1",
+        )
+        .bind(subscription_id)
+        .fetch_optional(&self.pool)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| subscription_uuid.to_string())
+    }
+
+    /// Replace the subscription's link with a new token. Earlier tokens keep
+    /// working for `grace`, then stop; a zero grace revokes them at once.
+    pub async fn rotate(&self, subscription_id: i64, grace: chrono::Duration) -> Result<String> {
+        let mut tx = self.pool.begin().await?;
+
+        // The uuid served links until now, so it is revoked like any token.
+        sqlx::query(
+            r#"
+            INSERT INTO subscription_tokens (subscription_id, token)
+            SELECT id, subscription_uuid FROM subscriptions WHERE id = $1
+            ON CONFLICT (token) DO NOTHING
+            "#,
+        )
+        .bind(subscription_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let cutoff = chrono::Utc::now() + grace;
+        sqlx::query(
+            "UPDATE subscription_tokens SET revoked_at = $2 WHERE subscription_id = $1 AND (revoked_at IS NULL OR revoked_at > $2)",
+        )
+        .bind(subscription_id)
+        .bind(cutoff)
+        .execute(&mut *tx)
+        .await?;
+
+        let token = Uuid::new_v4().simple().to_string();
+        sqlx::query("INSERT INTO subscription_tokens (subscription_id, token) VALUES ($1, $2)")
+            .bind(subscription_id)
+            .bind(&token)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(token)
+    }
+
+    /// Newest tokens first.
+    pub async fn list(&self, subscription_id: i64) -> Result<Vec<SubscriptionToken>> {
+        Ok(sqlx::query_as::<_, SubscriptionToken>(
+            "SELECT * FROM subscription_tokens WHERE subscription_id = $1 ORDER BY id DESC LIMIT $2",
+        )
+        .bind(subscription_id)
+        .bind(TOKEN_HISTORY_LIMIT)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Grace period for rotations users start themselves (bot, Mini App).
+    pub fn self_service_grace(hours_setting: &str) -> chrono::Duration {
+        chrono::Duration::hours(
+            hours_setting
+                .trim()
+                .parse::<i64>()
+                .unwrap_or(0)
+                .clamp(0, 168),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubTokenService;
+
+    #[test]
+    fn self_service_grace_is_bounded() {
+        assert_eq!(SubTokenService::self_service_grace("24").num_hours(), 24);
+        assert_eq!(SubTokenService::self_service_grace("").num_hours(), 0);
+        assert_eq!(SubTokenService::self_service_grace("-5").num_hours(), 0);
+        assert_eq!(
+            SubTokenService::self_service_grace("100000").num_hours(),
+            168
+        );
+    }
+}
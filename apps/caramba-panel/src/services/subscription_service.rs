@@ -1,12 +1,15 @@
 use crate::services::activity_service::ActivityService;
-use crate::singbox::subscription_generator::{NodeInfo, UserKeys};
+use crate::services::webhook_service::WebhookService;
+use crate::services::wireguard_service::WireguardService;
 use anyhow::{Context, Result};
+use caramba_config::{NodeInfo, UserKeys};
 use caramba_db::models::network::InboundType;
 use caramba_db::models::node::Node;
 use caramba_db::models::store::{
     AlertType, GiftCode, Plan, PlanDuration, RenewalResult, Subscription, SubscriptionIpTracking,
     SubscriptionWithDetails,
 };
+use caramba_db::models::webhook::WebhookEvent;
 use caramba_db::repositories::node_repo::NodeRepository;
 use chrono::{Duration, Utc};
 use sha2::{Digest, Sha256};
@@ -1047,6 +1050,7 @@ impl SubscriptionService {
         .await
         .context("Failed to expire subscriptions over traffic quota")?;
 
+        self.emit_quota_expired(&rows).await;
         Ok(rows)
     }
 
@@ -1076,9 +1080,26 @@ impl SubscriptionService {
         .await
         .context("Failed to expire candidate subscriptions over traffic quota")?;
 
+        self.emit_quota_expired(&rows).await;
         Ok(rows)
     }
 
+    async fn emit_quota_expired(&self, rows: &[ExpiredQuotaSubscription]) {
+        for row in rows {
+            WebhookService::emit(
+                &self.pool,
+                WebhookEvent::SubscriptionExpired,
+                serde_json::json!({
+                    "subscription_id": row.subscription_id,
+                    "user_id": row.user_id,
+                    "node_id": row.node_id,
+                    "reason": "traffic_quota",
+                }),
+            )
+            .await;
+        }
+    }
+
     pub async fn update_ips(&self, subscription_id: i64, ip_list: Vec<String>) -> Result<()> {
         let normalized: Vec<String> = ip_list
             .into_iter()
@@ -1173,6 +1194,17 @@ impl SubscriptionService {
             Err(e) => warn!("Failed to cleanup device leases: {}", e),
         }
 
+        // Devices unseen for a month free their slot; revoked ones count as
+        // seen while they keep trying, so they stay blocked.
+        match sqlx::query("DELETE FROM subscription_devices WHERE last_seen_at < $1")
+            .bind(Utc::now() - Duration::days(30))
+            .execute(&self.pool)
+            .await
+        {
+            Ok(r) => affected += r.rows_affected(),
+            Err(e) => warn!("Failed to prune stale subscription devices: {}", e),
+        }
+
         Ok(affected)
     }
 
@@ -1294,11 +1326,19 @@ impl SubscriptionService {
             "singbox".to_string()
         } else if ua.contains("clash") || ua.contains("stash") {
             "clash".to_string()
-        } else if ua.contains("v2ray")
-            || ua.contains("xray")
-            || ua.contains("fair")
-            || ua.contains("shadowrocket")
-        {
+        } else if ua.contains("surge") {
+            "surge".to_string()
+        } else if ua.contains("quantumult") {
+            "quanx".to_string()
+        } else if ua.contains("loon") {
+            "loon".to_string()
+        } else if ua.contains("shadowrocket") {
+            "shadowrocket".to_string()
+        } else if ua.starts_with("xray/") || ua.contains("xray-core") {
+            // Bare Xray-core (routers, scripts) wants a full JSON config;
+            // Xray-based GUI clients below import share links.
+            "xray".to_string()
+        } else if ua.contains("v2ray") || ua.contains("xray") || ua.contains("fair") {
             "v2ray".to_string()
         } else if ua.contains("mozilla") || ua.contains("chrome") || ua.contains("safari") {
             "html".to_string()
@@ -1438,10 +1478,22 @@ impl SubscriptionService {
             map
         };
 
+        let memberships: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT m.node_id, g.name FROM node_group_members m JOIN node_groups g ON g.id = m.group_id WHERE m.node_id = ANY($1) ORDER BY g.name",
+        )
+        .bind(&node_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
         let mut node_infos = Vec::new();
         for n in nodes {
             let n_inbounds = inbounds_map.get(&n.id).cloned().unwrap_or_default();
             let mut ni = NodeInfo::new(n, n_inbounds);
+            ni.groups = memberships
+                .iter()
+                .filter(|(node_id, _)| *node_id == n.id)
+                .map(|(_, name)| name.clone())
+                .collect();
 
             if let Some(r_id) = n.relay_id {
                 if let Some(r_info) = relays_map.get(&r_id) {
@@ -1491,17 +1543,17 @@ impl SubscriptionService {
             .unwrap_or(0);
 
         let hy2_password = format!("{}:{}", tg_id, user_uuid.replace("-", ""));
-        let awg_private_key = self.derive_awg_key(&user_uuid);
+        let (awg_peer, _) = WireguardService::new(self.pool.clone())
+            .peer(sub.id, None)
+            .await?;
 
         Ok(UserKeys {
             user_uuid,
             hy2_password,
-            _awg_private_key: Some(awg_private_key.clone()),
-        })
-    }
-
-    fn derive_awg_key(&self, uuid: &str) -> String {
-        Self::generate_amneziawg_key(uuid)
+            awg_private_key: None,
+            awg_address: None,
+        }
+        .with_wireguard_peer(&awg_peer))
     }
 
     pub async fn check_and_send_alerts(&self) -> Result<Vec<(i64, AlertType)>> {
@@ -1581,33 +1633,6 @@ impl SubscriptionService {
         Ok(alerts_to_send)
     }
 
-    pub fn generate_clash(
-        &self,
-        sub: &Subscription,
-        nodes: &[NodeInfo],
-        keys: &UserKeys,
-    ) -> Result<String> {
-        crate::singbox::subscription_generator::generate_clash_config(sub, nodes, keys)
-    }
-
-    pub fn generate_v2ray(
-        &self,
-        sub: &Subscription,
-        nodes: &[NodeInfo],
-        keys: &UserKeys,
-    ) -> Result<String> {
-        crate::singbox::subscription_generator::generate_v2ray_config(sub, nodes, keys)
-    }
-
-    pub fn generate_singbox(
-        &self,
-        sub: &Subscription,
-        nodes: &[NodeInfo],
-        keys: &UserKeys,
-    ) -> Result<String> {
-        crate::singbox::subscription_generator::generate_singbox_config(sub, nodes, keys)
-    }
-
     pub async fn update_subscription_node(&self, sub_id: i64, node_id: Option<i64>) -> Result<()> {
         sqlx::query("UPDATE subscriptions SET node_id = $1 WHERE id = $2")
             .bind(node_id)
@@ -1616,21 +1641,4 @@ impl SubscriptionService {
             .await?;
         Ok(())
     }
-
-    fn generate_amneziawg_key(uuid: &str) -> String {
-        use sha2::{Digest, Sha256};
-        let mut hasher = Sha256::new();
-        hasher.update(uuid.as_bytes());
-        hasher.update(b"amneziawg-key-salt");
-        let result = hasher.finalize();
-
-        let mut key = [0u8; 32];
-        key.copy_from_slice(&result[..32]);
-
-        key[0] &= 248;
-        key[31] &= 127;
-        key[31] |= 64;
-
-        base64::Engine::encode(&base64::prelude::BASE64_STANDARD, key)
-    }
 }
@@ -1,5 +1,6 @@
 use crate::AppState;
 use crate::services::analytics_service::AnalyticsService;
+use crate::services::device_service::parse_user_tag;
 use chrono::Utc;
 use std::collections::HashSet;
 use tokio::time::{Duration, interval};
@@ -63,7 +64,7 @@ impl TrafficService {
                 for (user_tag, bytes_val) in users_map {
                     if let Some(bytes) = bytes_val.as_u64() {
                         if user_tag.starts_with("user_") {
-                            if let Ok(sub_id) = user_tag[5..].parse::<i64>() {
+                            if let Some((sub_id, _)) = parse_user_tag(user_tag) {
                                 let sub_details = sqlx::query_as::<_, (i64, String, i64)>(
                                     "UPDATE subscriptions SET used_traffic = used_traffic + $1, traffic_updated_at = $2 WHERE id = $3 RETURNING user_id, COALESCE(note, ''), plan_id"
                                 )
@@ -0,0 +1,427 @@
+use anyhow::{Context, Result};
+use caramba_db::models::webhook::{WebhookDelivery, WebhookEndpoint, WebhookEvent};
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+
+/// Deliveries are given up after this many failed attempts.
+pub const MAX_ATTEMPTS: i32 = 8;
+/// Deliveries claimed per worker tick.
+const BATCH_SIZE: i64 = 50;
+/// How long a claimed delivery is hidden from other workers.
+const CLAIM_LEASE_SECS: i64 = 120;
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+/// Finished deliveries older than this are pruned from the log.
+const RETENTION_DAYS: i64 = 30;
+/// Longest response body / error kept in the delivery log.
+const MAX_ERROR_LEN: usize = 500;
+
+pub const SIGNATURE_HEADER: &str = "X-Caramba-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Caramba-Timestamp";
+pub const EVENT_HEADER: &str = "X-Caramba-Event";
+pub const DELIVERY_HEADER: &str = "X-Caramba-Delivery";
+
+const DELIVERY_COLUMNS: &str = "d.id, d.endpoint_id, e.name AS endpoint_name, d.event, d.payload, d.status, d.attempts, d.next_attempt_at, d.last_status_code, d.last_error, d.created_at, d.delivered_at";
+
+#[derive(sqlx::FromRow)]
+struct DueDelivery {
+    id: i64,
+    event: String,
+    payload: Value,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+pub struct WebhookService {
+    pool: PgPool,
+    client: reqwest::Client,
+}
+
+impl WebhookService {
+    pub fn new(pool: PgPool) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_default();
+        Self { pool, client }
+    }
+
+    /// Queue `event` for every active endpoint subscribed to it.
+    ///
+    /// Called from the services that own the events. Failures are logged and
+    /// never propagate, so a webhook problem cannot fail a payment.
+    pub async fn emit(pool: &PgPool, event: WebhookEvent, data: Value) {
+        let result = sqlx::query(
+            "INSERT INTO webhook_deliveries (endpoint_id, event, payload) SELECT id, $1, $2 FROM webhook_endpoints WHERE is_active AND $1 = ANY(events)",
+        )
+        .bind(event.as_str())
+        .bind(envelope(event, data))
+        .execute(pool)
+        .await;
+
+        if let Err(e) = result {
+            error!("Failed to queue webhook event {}: {}", event, e);
+        }
+    }
+
+    /// Queue a test event for one endpoint, whatever it subscribes to.
+    pub async fn send_test(&self, endpoint_id: i64) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO webhook_deliveries (endpoint_id, event, payload) VALUES ($1, $2, $3)",
+        )
+        .bind(endpoint_id)
+        .bind(WebhookEvent::Test.as_str())
+        .bind(envelope(
+            WebhookEvent::Test,
+            json!({ "message": "Test delivery from the Caramba panel" }),
+        ))
+        .execute(&self.pool)
+        .await
+        .context("Failed to queue test delivery")?;
+        Ok(())
+    }
+
+    // ------------------------------------------------------------------
+    // Endpoints
+    // ------------------------------------------------------------------
+
+    pub async fn list_endpoints(&self) -> Result<Vec<WebhookEndpoint>> {
+        sqlx::query_as::<_, WebhookEndpoint>("SELECT * FROM webhook_endpoints ORDER BY id ASC")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to list webhook endpoints")
+    }
+
+    pub async fn get_endpoint(&self, id: i64) -> Result<Option<WebhookEndpoint>> {
+        sqlx::query_as::<_, WebhookEndpoint>("SELECT * FROM webhook_endpoints WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to fetch webhook endpoint")
+    }
+
+    /// Create an endpoint and return its id with the generated signing secret.
+    pub async fn create_endpoint(
+        &self,
+        name: &str,
+        url: &str,
+        events: &[WebhookEvent],
+    ) -> Result<(i64, String)> {
+        let secret = generate_secret();
+        let events: Vec<&str> = events.iter().map(|e| e.as_str()).collect();
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO webhook_endpoints (name, url, secret, events) VALUES ($1, $2, $3, $4) RETURNING id",
+        )
+        .bind(name.trim())
+        .bind(url.trim())
+        .bind(&secret)
+        .bind(&events)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to create webhook endpoint")?;
+        Ok((id, secret))
+    }
+
+    pub async fn toggle_endpoint(&self, id: i64) -> Result<Option<bool>> {
+        sqlx::query_scalar(
+            "UPDATE webhook_endpoints SET is_active = NOT is_active, updated_at = CURRENT_TIMESTAMP WHERE id = $1 RETURNING is_active",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to toggle webhook endpoint")
+    }
+
+    pub async fn rotate_secret(&self, id: i64) -> Result<Option<String>> {
+        let secret = generate_secret();
+        let res = sqlx::query(
+            "UPDATE webhook_endpoints SET secret = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2",
+        )
+        .bind(&secret)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to rotate webhook secret")?;
+        Ok((res.rows_affected() > 0).then_some(secret))
+    }
+
+    pub async fn delete_endpoint(&self, id: i64) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM webhook_endpoints WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete webhook endpoint")?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    // ------------------------------------------------------------------
+    // Delivery log
+    // ------------------------------------------------------------------
+
+    pub async fn list_deliveries(
+        &self,
+        endpoint_id: Option<i64>,
+        status: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>> {
+        sqlx::query_as::<_, WebhookDelivery>(&format!(
+            "SELECT {} FROM webhook_deliveries d JOIN webhook_endpoints e ON e.id = d.endpoint_id WHERE ($1::BIGINT IS NULL OR d.endpoint_id = $1) AND ($2::TEXT IS NULL OR d.status = $2) ORDER BY d.created_at DESC, d.id DESC LIMIT $3",
+            DELIVERY_COLUMNS
+        ))
+        .bind(endpoint_id)
+        .bind(status)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list webhook deliveries")
+    }
+
+    /// Put a delivery back in the queue with a fresh attempt budget.
+    pub async fn retry_delivery(&self, id: i64) -> Result<bool> {
+        let res = sqlx::query(
+            "UPDATE webhook_deliveries SET status = 'pending', attempts = 0, next_attempt_at = CURRENT_TIMESTAMP WHERE id = $1 AND status != 'pending'",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to retry webhook delivery")?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    // ------------------------------------------------------------------
+    // Worker
+    // ------------------------------------------------------------------
+
+    pub async fn start(&self) {
+        info!("Starting webhook delivery worker...");
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+        let mut ticks: u64 = 0;
+
+        loop {
+            interval.tick().await;
+            ticks += 1;
+
+            if let Err(e) = self.process_due().await {
+                error!("Webhook worker error: {}", e);
+            }
+
+            // Roughly hourly
+            if ticks.is_multiple_of(720)
+                && let Err(e) = self.prune().await
+            {
+                error!("Webhook log pruning error: {}", e);
+            }
+        }
+    }
+
+    async fn process_due(&self) -> Result<()> {
+        // Claiming pushes next_attempt_at forward, so a crashed worker's
+        // batch is picked up again once the lease runs out.
+        let due = sqlx::query_as::<_, DueDelivery>(
+            r#"
+            WITH due AS (
+                SELECT id FROM webhook_deliveries
+                WHERE status = 'pending' AND next_attempt_at <= CURRENT_TIMESTAMP
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            ), claimed AS (
+                UPDATE webhook_deliveries d
+                SET next_attempt_at = CURRENT_TIMESTAMP + ($2 * INTERVAL '1 second')
+                FROM due WHERE d.id = due.id
+                RETURNING d.id, d.endpoint_id, d.event, d.payload, d.attempts
+            )
+            SELECT c.id, c.event, c.payload, c.attempts, e.url, e.secret
+            FROM claimed c JOIN webhook_endpoints e ON e.id = c.endpoint_id
+            "#,
+        )
+        .bind(BATCH_SIZE)
+        .bind(CLAIM_LEASE_SECS as f64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to claim webhook deliveries")?;
+
+        for delivery in due {
+            self.deliver(delivery).await;
+        }
+        Ok(())
+    }
+
+    async fn deliver(&self, delivery: DueDelivery) {
+        let body = delivery.payload.to_string();
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&delivery.secret, timestamp, &body);
+
+        let result = self
+            .client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(reqwest::header::USER_AGENT, "Caramba-Webhooks/1")
+            .header(EVENT_HEADER, &delivery.event)
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+            .body(body)
+            .send()
+            .await;
+
+        let (status_code, error) = match result {
+            Ok(resp) if resp.status().is_success() => (Some(resp.status().as_u16() as i32), None),
+            Ok(resp) => {
+                let code = resp.status().as_u16() as i32;
+                let text = resp.text().await.unwrap_or_default();
+                (Some(code), Some(format!("HTTP {}: {}", code, text)))
+            }
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        let attempts = delivery.attempts + 1;
+        let update = match error {
+            None => sqlx::query(
+                "UPDATE webhook_deliveries SET status = 'delivered', attempts = $1, last_status_code = $2, last_error = NULL, delivered_at = CURRENT_TIMESTAMP WHERE id = $3",
+            )
+            .bind(attempts)
+            .bind(status_code)
+            .bind(delivery.id),
+            Some(err) => {
+                let err = truncate(&err, MAX_ERROR_LEN);
+                if attempts >= MAX_ATTEMPTS {
+                    warn!(
+                        "Webhook delivery {} ({}) to {} failed permanently: {}",
+                        delivery.id, delivery.event, delivery.url, err
+                    );
+                }
+                let status = if attempts >= MAX_ATTEMPTS {
+                    "failed"
+                } else {
+                    "pending"
+                };
+                sqlx::query(
+                    "UPDATE webhook_deliveries SET status = $1, attempts = $2, last_status_code = $3, last_error = $4, next_attempt_at = $5 WHERE id = $6",
+                )
+                .bind(status)
+                .bind(attempts)
+                .bind(status_code)
+                .bind(err)
+                .bind(Utc::now() + retry_delay(attempts))
+                .bind(delivery.id)
+            }
+        };
+
+        if let Err(e) = update.execute(&self.pool).await {
+            error!(
+                "Failed to record webhook delivery {} result: {}",
+                delivery.id, e
+            );
+        }
+    }
+
+    async fn prune(&self) -> Result<()> {
+        let res = sqlx::query(
+            "DELETE FROM webhook_deliveries WHERE status != 'pending' AND created_at < CURRENT_TIMESTAMP - ($1 * INTERVAL '1 day')",
+        )
+        .bind(RETENTION_DAYS as f64)
+        .execute(&self.pool)
+        .await?;
+        if res.rows_affected() > 0 {
+            info!("Pruned {} old webhook deliveries", res.rows_affected());
+        }
+        Ok(())
+    }
+}
+
+/// Payload sent for every event.
+fn envelope(event: WebhookEvent, data: Value) -> Value {
+    json!({
+        "id": uuid::Uuid::new_v4().to_string(),
+        "event": event.as_str(),
+        "created_at": Utc::now(),
+        "data": data,
+    })
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`. Receivers recompute it with the
+/// endpoint secret and should reject stale timestamps to stop replays.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Exponential backoff after the `attempts`-th failure: 30s, 1m, 2m, ... capped at 6h.
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exp = attempts.clamp(1, 16) as u32 - 1;
+    Duration::seconds((30i64 << exp).min(6 * 60 * 60))
+}
+
+fn generate_secret() -> String {
+    format!("whsec_{}", hex::encode(rand::random::<[u8; 24]>()))
+}
+
+fn truncate(s: &str, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some((idx, _)) => format!("{}…", &s[..idx]),
+        None => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        let sig = sign("whsec_test", 1_700_000_000, r#"{"event":"node.offline"}"#);
+        assert_eq!(sig.len(), 64);
+        assert_eq!(
+            sig,
+            sign("whsec_test", 1_700_000_000, r#"{"event":"node.offline"}"#)
+        );
+        assert_ne!(
+            sig,
+            sign("whsec_test", 1_700_000_001, r#"{"event":"node.offline"}"#)
+        );
+        assert_ne!(
+            sig,
+            sign("whsec_other", 1_700_000_000, r#"{"event":"node.offline"}"#)
+        );
+    }
+
+    #[test]
+    fn signature_matches_known_vector() {
+        // echo -n '1.{}' | openssl dgst -sha256 -hmac key
+        assert_eq!(
+            sign("key", 1, "{}"),
+            "1ba6b8171186efc613e8bcc0cbdab2748f24984d7c5a84faa2637afa0e40d224"
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_and_caps() {
+        assert_eq!(retry_delay(1), Duration::seconds(30));
+        assert_eq!(retry_delay(2), Duration::seconds(60));
+        assert_eq!(retry_delay(4), Duration::seconds(240));
+        assert_eq!(retry_delay(MAX_ATTEMPTS + 20), Duration::hours(6));
+    }
+
+    #[test]
+    fn envelope_wraps_data() {
+        let v = envelope(WebhookEvent::SniRotated, json!({ "node_id": 3 }));
+        assert_eq!(v["event"], "sni.rotated");
+        assert_eq!(v["data"]["node_id"], 3);
+        assert!(v["id"].as_str().is_some_and(|id| id.len() == 36));
+    }
+
+    #[test]
+    fn truncates_on_char_boundary() {
+        assert_eq!(truncate("héllo", 2), "hé…");
+        assert_eq!(truncate("ok", 10), "ok");
+    }
+}
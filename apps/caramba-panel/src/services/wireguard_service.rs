@@ -0,0 +1,357 @@
+use anyhow::Result;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use caramba_db::models::network::AmneziaWgSettings;
+use caramba_db::models::wireguard::WireguardPeer;
+use caramba_db::repositories::node_repo::NodeRepository;
+use sqlx::PgPool;
+
+/// Resolvers tunnel clients are pointed at.
+const TUNNEL_DNS: &str = "1.1.1.1, 1.0.0.1";
+
+/// An AmneziaWG inbound a subscription's peers can connect to.
+pub struct WgEndpoint {
+    pub inbound_id: i64,
+    pub node_name: String,
+    pub host: String,
+    pub port: i64,
+    pub settings: AmneziaWgSettings,
+}
+
+impl WgEndpoint {
+    /// Public key clients pin for the server; derived from the private key
+    /// for inbounds saved without one.
+    pub fn server_public_key(&self) -> String {
+        if !self.settings.public_key.is_empty() {
+            return self.settings.public_key.clone();
+        }
+        public_key_of(&self.settings.private_key).unwrap_or_default()
+    }
+
+    /// Tunnel name the apps show, also the `.conf` file name: Linux caps
+    /// interface names at 15 characters of `[A-Za-z0-9_=+.-]`.
+    pub fn tunnel_name(&self) -> String {
+        let name: String = self
+            .node_name
+            .chars()
+            .filter_map(|c| match c {
+                c if c.is_ascii_alphanumeric() || "_=+.-".contains(c) => Some(c),
+                ' ' => Some('-'),
+                _ => None,
+            })
+            .take(15)
+            .collect();
+        if name.trim_matches('-').is_empty() {
+            format!("awg-{}", self.inbound_id)
+        } else {
+            name
+        }
+    }
+}
+
+/// A fresh X25519 keypair, base64 encoded as WireGuard expects.
+pub fn generate_keypair() -> (String, String) {
+    let mut key = rand::random::<[u8; 32]>();
+    key[0] &= 248;
+    key[31] &= 127;
+    key[31] |= 64;
+    let public = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(key));
+    (
+        BASE64_STANDARD.encode(key),
+        BASE64_STANDARD.encode(public.as_bytes()),
+    )
+}
+
+/// Public key of a base64 X25519 private key.
+pub fn public_key_of(private_key: &str) -> Option<String> {
+    let bytes: [u8; 32] = BASE64_STANDARD
+        .decode(private_key.trim())
+        .ok()?
+        .try_into()
+        .ok()?;
+    let public = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(bytes));
+    Some(BASE64_STANDARD.encode(public.as_bytes()))
+}
+
+/// wg-quick style config for `peer` on `endpoint`, with the Amnezia
+/// obfuscation parameters the AmneziaWG apps read from `[Interface]`.
+pub fn render_conf(peer: &WireguardPeer, endpoint: &WgEndpoint) -> String {
+    let s = &endpoint.settings;
+    let host = if endpoint.host.contains(':') {
+        format!("[{}]", endpoint.host)
+    } else {
+        endpoint.host.clone()
+    };
+    format!(
+        "[Interface]\n\
+         PrivateKey = {}\n\
+         Address = {}\n\
+         DNS = {}\n\
+         Jc = {}\n\
+         Jmin = {}\n\
+         Jmax = {}\n\
+         S1 = {}\n\
+         S2 = {}\n\
+         H1 = {}\n\
+         H2 = {}\n\
+         H3 = {}\n\
+         H4 = {}\n\
+         \n\
+         [Peer]\n\
+         PublicKey = {}\n\
+         Endpoint = {}:{}\n\
+         AllowedIPs = 0.0.0.0/0, ::/0\n\
+         PersistentKeepalive = 25\n",
+        peer.private_key,
+        peer.address(),
+        TUNNEL_DNS,
+        s.jc,
+        s.jmin,
+        s.jmax,
+        s.s1,
+        s.s2,
+        s.h1,
+        s.h2,
+        s.h3,
+        s.h4,
+        endpoint.server_public_key(),
+        host,
+        endpoint.port
+    )
+}
+
+/// WireGuard peers of subscriptions and their devices.
+pub struct WireguardService {
+    pool: PgPool,
+}
+
+impl WireguardService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Peer of the subscription (`device_id` unset) or one of its devices,
+    /// allocating keys and the lowest free address on first use. The flag
+    /// tells whether it was just created and nodes don't know it yet.
+    pub async fn peer(
+        &self,
+        subscription_id: i64,
+        device_id: Option<i64>,
+    ) -> Result<(WireguardPeer, bool)> {
+        // A concurrent request may take the same address or create the same
+        // peer; the insert then does nothing and we look again.
+        for _ in 0..3 {
+            if let Some(peer) = self.find(subscription_id, device_id).await? {
+                return Ok((peer, false));
+            }
+            let (private_key, public_key) = generate_keypair();
+            let created = sqlx::query_as::<_, WireguardPeer>(
+                r#"
+                INSERT INTO wireguard_peers (subscription_id, device_id, private_key, public_key, host)
+                SELECT $1, $2, $3, $4, c.n
+                FROM (SELECT 2 AS n UNION ALL SELECT host + 1 FROM wireguard_peers) c
+                WHERE c.n <= 65534
+                  AND NOT EXISTS (SELECT 1 FROM wireguard_peers p WHERE p.host = c.n)
+                ORDER BY c.n
+                LIMIT 1
+                ON CONFLICT DO NOTHING
+                RETURNING *
+                "#,
+            )
+            .bind(subscription_id)
+            .bind(device_id)
+            .bind(&private_key)
+            .bind(&public_key)
+            .fetch_optional(&self.pool)
+            .await?;
+            if let Some(peer) = created {
+                return Ok((peer, true));
+            }
+        }
+        self.find(subscription_id, device_id)
+            .await?
+            .map(|peer| (peer, false))
+            .ok_or_else(|| anyhow::anyhow!("WireGuard address pool exhausted"))
+    }
+
+    async fn find(
+        &self,
+        subscription_id: i64,
+        device_id: Option<i64>,
+    ) -> Result<Option<WireguardPeer>> {
+        Ok(sqlx::query_as::<_, WireguardPeer>(
+            "SELECT * FROM wireguard_peers WHERE subscription_id = $1 AND device_id IS NOT DISTINCT FROM $2",
+        )
+        .bind(subscription_id)
+        .bind(device_id)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Peers of the given subscriptions to put on the nodes: every shared
+    /// peer, created where missing, and those of unrevoked devices.
+    pub async fn active_peers(&self, subscription_ids: &[i64]) -> Result<Vec<WireguardPeer>> {
+        if subscription_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let missing: Vec<i64> = sqlx::query_scalar(
+            r#"
+            SELECT s.id FROM UNNEST($1::BIGINT[]) AS s(id)
+            WHERE NOT EXISTS (
+                SELECT 1 FROM wireguard_peers p WHERE p.subscription_id = s.id AND p.device_id IS NULL
+            )
+            "#,
+        )
+        .bind(subscription_ids)
+        .fetch_all(&self.pool)
+        .await?;
+        for subscription_id in missing {
+            self.peer(subscription_id, None).await?;
+        }
+
+        Ok(sqlx::query_as::<_, WireguardPeer>(
+            r#"
+            SELECT p.* FROM wireguard_peers p
+            LEFT JOIN subscription_devices d ON d.id = p.device_id
+            WHERE p.subscription_id = ANY($1) AND (p.device_id IS NULL OR d.revoked_at IS NULL)
+            ORDER BY p.id
+            "#,
+        )
+        .bind(subscription_ids)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// `.conf` of the subscription's shared peer for each AmneziaWG inbound
+    /// of its plan, and whether the peer was just created.
+    pub async fn shared_configs(
+        &self,
+        subscription_id: i64,
+        plan_id: i64,
+    ) -> Result<(Vec<(WgEndpoint, String)>, bool)> {
+        let endpoints = self.endpoints(plan_id).await?;
+        if endpoints.is_empty() {
+            return Ok((Vec::new(), false));
+        }
+        let (peer, created) = self.peer(subscription_id, None).await?;
+        Ok((
+            endpoints
+                .into_iter()
+                .map(|endpoint| {
+                    let conf = render_conf(&peer, &endpoint);
+                    (endpoint, conf)
+                })
+                .collect(),
+            created,
+        ))
+    }
+
+    /// AmneziaWG inbounds on the plan's nodes.
+    pub async fn endpoints(&self, plan_id: i64) -> Result<Vec<WgEndpoint>> {
+        let nodes = NodeRepository::new(self.pool.clone())
+            .get_nodes_for_plan(plan_id)
+            .await?;
+        let node_ids: Vec<i64> = nodes.iter().map(|n| n.id).collect();
+        if node_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let inbounds = sqlx::query_as::<_, (i64, i64, i64, String)>(
+            r#"
+            SELECT id, node_id, listen_port::BIGINT, COALESCE(settings, '{}')
+            FROM inbounds
+            WHERE node_id = ANY($1) AND COALESCE(enable, TRUE) AND LOWER(protocol) = 'amneziawg'
+            ORDER BY id
+            "#,
+        )
+        .bind(&node_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(inbounds
+            .into_iter()
+            .filter_map(|(inbound_id, node_id, port, settings)| {
+                let node = nodes.iter().find(|n| n.id == node_id)?;
+                let settings = serde_json::from_str::<AmneziaWgSettings>(&settings).ok()?;
+                Some(WgEndpoint {
+                    inbound_id,
+                    node_name: node.name.clone(),
+                    host: node.ip.clone(),
+                    port,
+                    settings,
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WgEndpoint, generate_keypair, public_key_of, render_conf};
+    use caramba_db::models::network::AmneziaWgSettings;
+    use caramba_db::models::wireguard::WireguardPeer;
+
+    fn endpoint(node_name: &str, host: &str) -> WgEndpoint {
+        let (private_key, _) = generate_keypair();
+        WgEndpoint {
+            inbound_id: 9,
+            node_name: node_name.to_string(),
+            host: host.to_string(),
+            port: 51820,
+            settings: AmneziaWgSettings {
+                private_key,
+                jc: 4,
+                jmin: 40,
+                jmax: 70,
+                s1: 15,
+                s2: 68,
+                h1: 11,
+                h2: 22,
+                h3: 33,
+                h4: 44,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn generated_keys_match() {
+        let (private_key, public_key) = generate_keypair();
+        assert_eq!(public_key_of(&private_key), Some(public_key));
+        assert_eq!(public_key_of("short"), None);
+    }
+
+    #[test]
+    fn conf_carries_address_and_obfuscation() {
+        let peer = WireguardPeer {
+            id: 1,
+            subscription_id: 5,
+            device_id: None,
+            private_key: "cHJpdmF0ZQ==".to_string(),
+            public_key: "cHVibGlj".to_string(),
+            host: 263,
+            created_at: chrono::Utc::now(),
+        };
+        let endpoint = endpoint("DE 1", "2001:db8::1");
+        let conf = render_conf(&peer, &endpoint);
+        assert!(conf.contains("PrivateKey = cHJpdmF0ZQ==\n"));
+        assert!(conf.contains("Address = 10.10.1.7/32\n"));
+        assert!(conf.contains("Jc = 4\nJmin = 40\nJmax = 70\nS1 = 15\nS2 = 68\n"));
+        assert!(conf.contains("H4 = 44\n"));
+        assert!(conf.contains(&format!(
+            "PublicKey = {}\n",
+            public_key_of(&endpoint.settings.private_key).unwrap()
+        )));
+        assert!(conf.contains("Endpoint = [2001:db8::1]:51820\n"));
+    }
+
+    #[test]
+    fn tunnel_names_are_interface_safe() {
+        assert_eq!(endpoint("DE 1", "h").tunnel_name(), "DE-1");
+        assert_eq!(
+            endpoint("Frankfurt Main Node", "h").tunnel_name(),
+            "Frankfurt-Main-"
+        );
+        assert_eq!(endpoint("🇩🇪", "h").tunnel_name(), "awg-9");
+    }
+}
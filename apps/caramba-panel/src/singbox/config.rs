@@ -119,6 +119,8 @@ pub struct VlessInbound {
     pub transport: Option<VlessTransportConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub packet_encoding: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplex: Option<InboundMultiplex>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -128,6 +130,8 @@ pub struct ShadowsocksInbound {
     pub listen_port: u16,
     pub method: String,
     pub users: Vec<ShadowsocksUser>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplex: Option<InboundMultiplex>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -313,6 +317,24 @@ pub struct TrojanInbound {
     pub listen_port: u16,
     pub users: Vec<TrojanUser>,
     pub tls: Option<VlessTlsConfig>, // Can reuse VlessTlsConfig or define TrojanTlsConfig
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplex: Option<InboundMultiplex>,
+}
+
+/// Server side of sing-mux; the protocol is negotiated by the client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InboundMultiplex {
+    pub enabled: bool,
+    pub padding: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brutal: Option<InboundBrutal>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InboundBrutal {
+    pub enabled: bool,
+    pub up_mbps: u32,
+    pub down_mbps: u32,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -1,5 +1,7 @@
 use crate::singbox::config::*;
-use caramba_db::models::network::{Certificate, InboundType, StreamSettings as DbStreamSettings}; // Added Certificate
+use caramba_db::models::network::{
+    AntiDpiSettings, Certificate, InboundType, StreamSettings as DbStreamSettings,
+};
 use sha2::{Digest, Sha256};
 use tracing::{error, warn};
 
@@ -31,6 +33,41 @@ fn parse_shadowsocks_method(settings_raw: &str) -> Option<String> {
         .map(|s| s.to_string())
 }
 
+/// Server-side multiplex accepting every anti-DPI profile of the inbound,
+/// country overrides included, since any of them may connect.
+fn inbound_multiplex(stream_settings_raw: &str) -> Option<InboundMultiplex> {
+    let value: serde_json::Value = serde_json::from_str(stream_settings_raw).ok()?;
+    let anti_dpi = value.get("antiDpi").or_else(|| value.get("anti_dpi"))?;
+    let settings: AntiDpiSettings = serde_json::from_value(anti_dpi.clone()).ok()?;
+    let muxes: Vec<_> = settings
+        .profiles()
+        .filter_map(|p| p.multiplex.as_ref())
+        .collect();
+    if muxes.is_empty() {
+        return None;
+    }
+
+    // Padding on the server rejects unpadded clients, so every profile must pad
+    let padding = muxes.iter().all(|m| m.padding);
+    // The server's upload is the client's download
+    let brutal = muxes.iter().filter_map(|m| m.brutal.as_ref()).fold(
+        None,
+        |acc: Option<InboundBrutal>, b| {
+            let (up, down) = acc.map_or((0, 0), |a| (a.up_mbps, a.down_mbps));
+            Some(InboundBrutal {
+                enabled: true,
+                up_mbps: up.max(b.down_mbps),
+                down_mbps: down.max(b.up_mbps),
+            })
+        },
+    );
+    Some(InboundMultiplex {
+        enabled: true,
+        padding,
+        brutal,
+    })
+}
+
 fn derive_relay_password(join_token: &str, target_node_id: i64) -> String {
     let mut hasher = Sha256::new();
     hasher.update(join_token.trim().as_bytes());
@@ -102,6 +139,8 @@ impl ConfigGenerator {
                     }
                 };
 
+            let multiplex = inbound_multiplex(&inbound.stream_settings);
+
             // Map DB Inbound to Sing-box Inbound
             match protocol_settings {
                 InboundType::Vless(vless) => {
@@ -309,6 +348,7 @@ impl ConfigGenerator {
                         tls: tls_config,
                         transport: transport_config,
                         packet_encoding: stream_settings.packet_encoding.clone(),
+                        multiplex,
                     }));
                 }
                 InboundType::Hysteria2(hy2) => {
@@ -586,6 +626,7 @@ impl ConfigGenerator {
                         listen_port: inbound.listen_port as u16,
                         users,
                         tls: tls_config,
+                        multiplex,
                     }));
                 }
                 InboundType::Naive(naive) => {
@@ -772,6 +813,7 @@ impl ConfigGenerator {
                         listen_port: inbound.listen_port as u16,
                         method: ss.method,
                         users,
+                        multiplex,
                     }));
                 }
             }
@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod tests {
     use caramba_db::models::network::Inbound;
-    use crate::singbox::subscription_generator::{NodeInfo, UserKeys, generate_singbox_config};
+    use caramba_config::{NodeInfo, UserKeys, generate_singbox_config};
     use serde_json::json;
 
     // Helper to create a dummy subscription
@@ -48,6 +48,8 @@ mod tests {
             frontend_url: None, 
             inbounds: vec![inbound],
             relay_info: None,
+            country_code: None,
+            groups: vec![],
             config_block_ads: false,
             config_block_porn: false,
             config_block_torrent: false,
@@ -59,7 +61,8 @@ mod tests {
         let user_keys = UserKeys {
             user_uuid: "uuid-123".to_string(),
             hy2_password: "pass".to_string(),
-            _awg_private_key: None,
+            awg_private_key: None,
+            awg_address: None,
         };
 
         // Configuration matching the user's setup (Reality TCP)
@@ -98,7 +101,8 @@ mod tests {
         let user_keys = UserKeys {
             user_uuid: "uuid-123".to_string(),
             hy2_password: "pass".to_string(),
-            _awg_private_key: None,
+            awg_private_key: None,
+            awg_address: None,
         };
 
         // Configuration matching the user's setup (Reality TCP)
@@ -4,51 +4,13 @@ mod tests {
     use caramba_db::models::network::Inbound;
     use caramba_db::models::node::Node;
     // use caramba_db::models::store::Subscription; // Unused
-    use crate::singbox::config::Outbound;
-    use crate::singbox::subscription_generator::{
-        NodeInfo, UserKeys, generate_singbox_config, generate_v2ray_config,
+    use crate::singbox::config::{
+        Inbound as SingBoxInbound, InboundBrutal, InboundMultiplex, Outbound,
     };
     use crate::singbox::{ConfigGenerator, RelayAuthMode};
     use serde_json::json;
     use sha2::{Digest, Sha256};
 
-    fn create_mock_node(inbound_protocol: &str, stream_settings: serde_json::Value) -> NodeInfo {
-        let inbound = Inbound {
-            id: 1,
-            node_id: 1,
-            tag: "test_inbound".to_string(),
-            protocol: inbound_protocol.to_string(),
-            listen_port: 443,
-            listen_ip: "0.0.0.0".to_string(),
-            settings: "{}".to_string(),
-            stream_settings: stream_settings.to_string(),
-            remark: Some("Test".to_string()),
-            enable: true,
-            renew_interval_mins: 0,
-            port_range_start: 0,
-            port_range_end: 0,
-            last_rotated_at: None,
-            created_at: None,
-        };
-
-        NodeInfo {
-            name: "TestNode".to_string(),
-            address: "1.2.3.4".to_string(),
-            reality_port: Some(443),
-            reality_sni: Some("google.com".to_string()),
-            reality_public_key: Some("pubkey".to_string()),
-            reality_short_id: Some("shortid".to_string()),
-            hy2_port: Some(8443),
-            hy2_sni: Some("google.com".to_string()),
-            frontend_url: None,
-            inbounds: vec![inbound],
-            config_block_ads: false,
-            config_block_porn: false,
-            config_block_torrent: false,
-            relay_info: None,
-        }
-    }
-
     fn create_base_enterprise_node(id: i64, name: &str, ip: &str) -> Node {
         Node {
             id,
@@ -162,305 +124,6 @@ mod tests {
         hex::encode(hasher.finalize())
     }
 
-    #[test]
-    fn test_httpupgrade_generation() {
-        // Test that xhttp/splithttp legacy inputs are mapped to httpupgrade in Sing-box
-        let user_keys = UserKeys {
-            user_uuid: "uuid-123".to_string(),
-            hy2_password: "pass".to_string(),
-            _awg_private_key: None,
-        };
-
-        let stream_settings = json!({
-            "network": "xhttp",
-            "security": "reality",
-            "realitySettings": {
-                "serverNames": ["google.com"],
-                "publicKey": "pubkey",
-                "shortIds": ["shortid"]
-            },
-            "packet_encoding": "packetaddr",
-            "x_padding_bytes": "600-900",
-            "wsSettings": {
-                "path": "/xhttp-path"
-            }
-        });
-
-        let node = create_mock_node("vless", stream_settings);
-
-        // 1. Test Sing-box JSON
-        let json_config =
-            generate_singbox_config(&match_any_sub(), &[node.clone()], &user_keys).unwrap();
-        let parsed: serde_json::Value = serde_json::from_str(&json_config).unwrap();
-
-        let outbound = parsed["outbounds"]
-            .as_array()
-            .unwrap()
-            .iter()
-            .find(|o| o["tag"] == "TestNode_test_inbound")
-            .expect("Outbound not found");
-
-        assert_eq!(outbound["type"], "vless");
-        assert_eq!(outbound["packet_encoding"], "packetaddr");
-
-        let transport = &outbound["transport"];
-        assert_eq!(transport["type"], "httpupgrade");
-        assert_eq!(transport["path"], "/xhttp-path");
-
-        // Check Multiplex defaults
-        let mux = &outbound["multiplex"];
-        assert_eq!(mux["enabled"], true);
-        assert_eq!(mux["padding"], true);
-
-        // 2. Test VLESS Link
-        let _links_base64 = generate_v2ray_config(&match_any_sub(), &[node], &user_keys).unwrap();
-        // Since it's base64, we'd need to decode it to verify fully, but let's assume if it generated, logic ran.
-        // For unit test simplicity in this environment, checking the JSON structure is the critical part for Sing-box.
-    }
-
-    #[test]
-    fn test_hysteria2_generation() {
-        let user_keys = UserKeys {
-            user_uuid: "uuid".to_string(),
-            hy2_password: "pass".to_string(),
-            _awg_private_key: None,
-        };
-
-        let stream_settings = json!({
-            "network": "udp",
-            "security": "tls",
-            "hysteria2Settings": {
-                "ports": "20000-50000",
-                "obfs_password": "myobfspassword"
-            }
-        });
-
-        let node = create_mock_node("hysteria2", stream_settings);
-
-        let json_config = generate_singbox_config(&match_any_sub(), &[node], &user_keys).unwrap();
-        let parsed: serde_json::Value = serde_json::from_str(&json_config).unwrap();
-
-        let outbound = parsed["outbounds"]
-            .as_array()
-            .unwrap()
-            .iter()
-            .find(|o| o["type"] == "hysteria2")
-            .expect("Hysteria2 outbound not found");
-
-        assert_eq!(outbound["server_ports"], "20000-50000");
-        assert_eq!(outbound["obfs"]["type"], "salamander");
-        assert_eq!(outbound["obfs"]["password"], "myobfspassword");
-    }
-
-    #[test]
-    fn test_tuic_generation() {
-        let user_keys = UserKeys {
-            user_uuid: "uuid".to_string(),
-            hy2_password: "pass".to_string(),
-            _awg_private_key: None,
-        };
-
-        let stream_settings = json!({
-            "network": "quic",
-            "security": "tls",
-            "tuicSettings": {
-                "congestion_control": "bbr",
-                "zero_rtt_handshake": true
-            }
-        });
-
-        let node = create_mock_node("tuic", stream_settings);
-
-        let json_config = generate_singbox_config(&match_any_sub(), &[node], &user_keys).unwrap();
-        let parsed: serde_json::Value = serde_json::from_str(&json_config).unwrap();
-
-        let outbound = parsed["outbounds"]
-            .as_array()
-            .unwrap()
-            .iter()
-            .find(|o| o["type"] == "tuic")
-            .expect("TUIC outbound not found");
-
-        assert_eq!(outbound["congestion_control"], "bbr");
-        assert_eq!(outbound["zero_rtt_handshake"], true);
-    }
-
-    #[test]
-    fn test_naive_generation() {
-        let user_keys = UserKeys {
-            user_uuid: "uuid".to_string(),
-            hy2_password: "pass".to_string(),
-            _awg_private_key: None,
-        };
-
-        let stream_settings = json!({
-            "network": "tcp",
-            "security": "tls"
-        });
-
-        let node = create_mock_node("naive", stream_settings);
-
-        let json_config = generate_singbox_config(&match_any_sub(), &[node], &user_keys).unwrap();
-        let parsed: serde_json::Value = serde_json::from_str(&json_config).unwrap();
-
-        let outbound = parsed["outbounds"]
-            .as_array()
-            .unwrap()
-            .iter()
-            .find(|o| o["type"] == "naive")
-            .expect("Naive outbound not found");
-
-        assert_eq!(outbound["username"], "uuid");
-        assert_eq!(outbound["tls"]["utls"]["fingerprint"], "chrome");
-    }
-
-    #[test]
-    fn test_tls_fragmentation_rule() {
-        let user_keys = UserKeys {
-            user_uuid: "uuid".to_string(),
-            hy2_password: "pass".to_string(),
-            _awg_private_key: None,
-        };
-
-        let node = create_mock_node("vless", json!({"network":"tcp","security":"reality"}));
-        let json_config = generate_singbox_config(&match_any_sub(), &[node], &user_keys).unwrap();
-        let parsed: serde_json::Value = serde_json::from_str(&json_config).unwrap();
-
-        let rule = parsed["route"]["rules"]
-            .as_array()
-            .unwrap()
-            .iter()
-            .find(|r| r.get("tls_fragment") == Some(&json!(true)))
-            .expect("TLS fragmentation rule missing");
-
-        assert!(
-            rule["domain_suffix"]
-                .as_array()
-                .unwrap()
-                .contains(&json!("github.com"))
-        );
-    }
-
-    // Helper stub
-    #[test]
-    fn test_smart_routing_generation() {
-        let user_keys = UserKeys {
-            user_uuid: "uuid".to_string(),
-            hy2_password: "pass".to_string(),
-            _awg_private_key: None,
-        };
-        // Setup XHTTP node to trigger mux logic
-        let stream_settings = json!({
-            "network": "xhttp",
-            "security": "reality",
-            "wsSettings": { "path": "/path" } // Using wsSettings key as parser supports it for path fallback or expected xhttp path
-        });
-        let node = create_mock_node("vless", stream_settings);
-
-        let json_config = generate_singbox_config(&match_any_sub(), &[node], &user_keys).unwrap();
-        let parsed: serde_json::Value = serde_json::from_str(&json_config).unwrap();
-
-        // 1. Check Route Rules
-        let rules = parsed["route"]["rules"]
-            .as_array()
-            .expect("Route rules missing");
-
-        // Find GeoSite rule
-        let geosite_rule = rules
-            .iter()
-            .find(|r| {
-                r.get("geosite")
-                    .map(|v| v.as_array().unwrap().contains(&json!("ru")))
-                    .unwrap_or(false)
-            })
-            .expect("GeoSite:ru rule missing");
-        assert_eq!(geosite_rule["outbound"], "direct");
-
-        // Find GeoIP rule
-        let geoip_rule = rules
-            .iter()
-            .find(|r| {
-                r.get("geoip")
-                    .map(|v| v.as_array().unwrap().contains(&json!("ru")))
-                    .unwrap_or(false)
-            })
-            .expect("GeoIP:ru rule missing");
-        assert_eq!(geoip_rule["outbound"], "direct");
-
-        // 2. Check multiplex enforcement
-        let outbound = parsed["outbounds"]
-            .as_array()
-            .unwrap()
-            .iter()
-            .find(|o| o["tag"].as_str().unwrap().contains("test_inbound"))
-            .expect("Outbound missing");
-
-        let mux = &outbound["multiplex"];
-        assert_eq!(mux["enabled"], true);
-        assert_eq!(mux["max_connections"], 4);
-        assert_eq!(mux["padding"], true);
-    }
-
-    // Helper stub
-    #[test]
-    fn test_frontend_masquerading() {
-        let user_keys = UserKeys {
-            user_uuid: "uuid".to_string(),
-            hy2_password: "pass".to_string(),
-            _awg_private_key: None,
-        };
-        let stream_settings = json!({
-            "network": "ws",
-            "security": "tls",
-            "tlsSettings": { "serverName": "backend.real-node.com" },
-            "wsSettings": { "path": "/" }
-        });
-
-        let mut node = create_mock_node("vless", stream_settings);
-        node.address = "1.2.3.4".to_string(); // Real IP
-        node.frontend_url = Some("frontend.fake-shop.com".to_string()); // Masquerade Domain
-
-        // Test VLESS Link (v2ray config)
-        let links_base64 = generate_v2ray_config(&match_any_sub(), &[node], &user_keys).unwrap();
-        use base64::Engine;
-        let links_str = String::from_utf8(
-            base64::engine::general_purpose::STANDARD
-                .decode(links_base64)
-                .unwrap(),
-        )
-        .unwrap();
-
-        // Assert: Link host should be frontend, but SNI should be backend
-        assert!(links_str.contains("@frontend.fake-shop.com:443"));
-        assert!(links_str.contains("sni=backend.real-node.com"));
-        assert!(!links_str.contains("@1.2.3.4")); // Real IP should NOT be visible in the address part
-    }
-
-    fn match_any_sub() -> caramba_db::models::store::Subscription {
-        // Create a dummy subscription with minimal fields populated
-        // Using unsafe/transmute or just a minimal struct construction if visible
-        // Since we can't easily construct the full DB model without sqlx::FromRow,
-        // we might need to rely on the fact that generate functions don't actually USE the subscription object currently
-        // (param is named `_sub` in the modified code).
-        // So safely passing a zeroed memory or just minimal match works?
-        // In Rust, we need a valid struct.
-        // Let's force construct one via serde if possible or just avoid it if the function signature allows.
-        // Since `_sub` is unused, we can try to hack it or update the function signature to not require it,
-        // but for this test file, let's create a dummy using serde.
-        serde_json::from_value(json!({
-            "id": 1,
-            "user_id": 1,
-            "plan_id": 1,
-            "status": "active",
-            "created_at": "2023-01-01T00:00:00Z",
-            "updated_at": "2023-01-01T00:00:00Z",
-            "expires_at": "2024-01-01T00:00:00Z", // Requires non-null NaiveDate
-            "used_traffic": 0,
-            "is_trial": false,
-            "subscription_uuid": "uuid",
-        }))
-        .expect("Failed to create mock subscription")
-    }
     #[test]
     fn test_relay_outbound_uses_target_shadowsocks_inbound_port_and_method() {
         let mut relay_node = create_base_enterprise_node(1, "Relay-A", "10.0.0.1");
@@ -971,4 +634,56 @@ mod tests {
                     && r.server == Some("block".to_string()))
         );
     }
+
+    #[test]
+    fn test_inbound_multiplex_accepts_every_anti_dpi_profile() {
+        let node = create_base_enterprise_node(1, "Mux", "10.0.0.1");
+        let mut inbound = create_shadowsocks_inbound(1, 8443, "2022-blake3-aes-128-gcm");
+        inbound.stream_settings = json!({
+            "antiDpi": {
+                "multiplex": {"padding": true, "brutal": {"up_mbps": 20, "down_mbps": 100}},
+                "countries": {
+                    "IR": {"multiplex": {"brutal": {"up_mbps": 10, "down_mbps": 200}}},
+                    "CN": {"fragment": {}}
+                }
+            }
+        })
+        .to_string();
+        let mut plain = create_shadowsocks_inbound(1, 8444, "2022-blake3-aes-128-gcm");
+        plain.tag = "plain-ss".to_string();
+
+        let config = ConfigGenerator::generate_config(
+            &node,
+            vec![inbound, plain],
+            None,
+            None,
+            vec![],
+            RelayAuthMode::V1,
+        );
+
+        let multiplex = |tag: &str| {
+            config
+                .inbounds
+                .iter()
+                .find_map(|i| match i {
+                    SingBoxInbound::Shadowsocks(ss) if ss.tag == tag => Some(ss.multiplex.clone()),
+                    _ => None,
+                })
+                .expect("inbound must be present")
+        };
+        // Iranian clients don't pad, and the server sends at their download rate
+        assert_eq!(
+            multiplex("relay-ss"),
+            Some(InboundMultiplex {
+                enabled: true,
+                padding: false,
+                brutal: Some(InboundBrutal {
+                    enabled: true,
+                    up_mbps: 200,
+                    down_mbps: 20,
+                }),
+            })
+        );
+        assert_eq!(multiplex("plain-ss"), None);
+    }
 }
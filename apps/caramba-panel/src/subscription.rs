@@ -1,19 +1,57 @@
 use axum::{
     extract::{Path, Query, Request, State},
-    http::{StatusCode, header},
+    http::{HeaderMap, HeaderName, StatusCode, header},
     response::{IntoResponse, Response},
 };
+use caramba_config::serve::{PreparedConfig, ProfileInfo, config_response, render_for};
+use caramba_config::{ConfigInput, TEMPLATE_FORMATS, UserKeys, clients};
+use caramba_db::models::store::Subscription;
+use caramba_db::models::sub_device::SubscriptionDevice;
+use caramba_db::models::wireguard::WireguardPeer;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use tracing::{error, warn};
 
 use crate::AppState;
+use crate::services::device_service::{DeviceAdmission, DeviceService};
+use crate::services::qr_service;
+use crate::services::wireguard_service::render_conf;
 
 #[derive(Deserialize)]
 pub struct SubParams {
-    pub client: Option<String>, // "clash" | "v2ray" | "singbox"
+    pub client: Option<String>, // raw config format, see client_output()
     pub node_id: Option<i64>,
 }
 
+#[derive(Deserialize)]
+pub struct QrParams {
+    pub format: Option<String>, // svg (default) | png
+    pub link: Option<usize>,    // index into the subscription's links; unset = the URL
+}
+
+#[derive(Deserialize)]
+pub struct WireguardParams {
+    pub format: Option<String>, // conf (default) | png | svg
+    pub device: Option<i64>,    // a device's own peer instead of the shared one
+}
+
+/// Public https base URL subscription links are built on.
+async fn public_base_url(state: &AppState, sub_domain: &str) -> String {
+    let panel_url_setting = state.settings.get_or_default("panel_url", "").await;
+    let base = if !sub_domain.is_empty() {
+        sub_domain.to_string()
+    } else if !panel_url_setting.is_empty() {
+        panel_url_setting
+    } else {
+        std::env::var("PANEL_URL").unwrap_or_else(|_| "localhost".to_string())
+    };
+    if base.starts_with("http") {
+        base
+    } else {
+        format!("https://{}", base)
+    }
+}
+
 fn parse_ip_maybe(value: &str) -> Option<std::net::IpAddr> {
     let value = value.trim();
     if value.is_empty() {
@@ -44,14 +82,18 @@ fn canonicalize_ip(ip: std::net::IpAddr) -> std::net::IpAddr {
     }
 }
 
-fn extract_client_ip(headers: &axum::http::HeaderMap) -> String {
+pub(crate) fn extract_client_ip(headers: &axum::http::HeaderMap) -> String {
     let raw = headers
         .get("cf-connecting-ip")
         .or_else(|| headers.get("x-forwarded-for"))
         .and_then(|h| h.to_str().ok())
         .and_then(|s| s.split(',').next())
         .unwrap_or("0.0.0.0");
+    normalize_ip(raw)
+}
 
+/// `raw` as a plain IP address, or `0.0.0.0` when it is none.
+pub(crate) fn normalize_ip(raw: &str) -> String {
     parse_ip_maybe(raw)
         .map(|ip| ip.to_string())
         .unwrap_or_else(|| "0.0.0.0".to_string())
@@ -86,124 +128,25 @@ pub async fn subscription_handler(
     }
 
     // 0.5 Extract IP and User-Agent for tracking
-    let user_agent = req
+    let fetcher = Fetcher::new(extract_client_ip(req.headers()), req.headers());
+    let if_none_match = req
         .headers()
-        .get(header::USER_AGENT)
+        .get(header::IF_NONE_MATCH)
         .and_then(|h| h.to_str().ok())
         .map(|s| s.to_string());
-    let client_ip = extract_client_ip(req.headers());
-
-    // 1. Rate Limit (30 req / min per UUID)
-    let rate_key = format!("rate:sub:{}", uuid);
-    match state.redis.check_rate_limit(&rate_key, 30, 60).await {
-        Ok(allowed) => {
-            if !allowed {
-                warn!("Rate limit exceeded for subscription {}", uuid);
-                return (StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded").into_response();
-            }
-        }
-        Err(e) => {
-            error!("Rate limit check failed: {}", e);
-        }
-    }
-
-    // 2. Get subscription
-    let sub = match state
-        .subscription_service
-        .get_subscription_by_uuid(&uuid)
-        .await
-    {
-        Ok(s) => s,
-        Err(_) => {
-            return (StatusCode::NOT_FOUND, "Subscription not found").into_response();
-        }
-    };
-
-    // 3. Check if active
-    if sub.status != "active" {
-        return (StatusCode::FORBIDDEN, "Subscription inactive or expired").into_response();
-    }
-
-    // 3.2 Check traffic quota immediately on subscription fetch to enforce limits in real-time.
-    match state
-        .subscription_service
-        .ensure_subscription_within_quota(sub.id)
-        .await
-    {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::FORBIDDEN,
-                "Traffic limit reached. Subscription is expired.",
-            )
-                .into_response();
-        }
-        Err(e) => {
-            error!(
-                "Failed to evaluate quota for subscription {}: {}",
-                sub.id, e
-            );
-        }
-    }
-
-    // 3.5 Enforce device limit (Phase 7)
-    let active_ips = state
-        .subscription_service
-        .get_active_ips(sub.id)
-        .await
-        .unwrap_or_default();
-    let current_ip = &client_ip;
-
-    // Check if this is a new IP or if we're already at the limit
-    let is_new_device = !active_ips.iter().any(|rec| rec.client_ip == *current_ip);
 
-    if is_new_device {
-        let device_limit = state
-            .subscription_service
-            .get_subscription_device_limit(sub.id)
-            .await
-            .unwrap_or(0);
-        if device_limit > 0 && active_ips.len() >= device_limit as usize {
-            warn!(
-                "Device limit reached for subscription {}. Limit: {}, Active: {}",
-                uuid,
-                device_limit,
-                active_ips.len()
-            );
-            return (StatusCode::FORBIDDEN, "Device limit reached").into_response();
-        }
-    }
-
-    // 4. Update access tracking
-    let _ = state
-        .subscription_service
-        .track_access(sub.id, &client_ip, user_agent.as_deref())
-        .await;
-
-    // 4.5 Prepare Usage Headers (for Hiddify/Sing-box)
-    let plan_details = match state
-        .subscription_service
-        .get_user_subscriptions(sub.user_id)
-        .await
-    {
-        Ok(subs) => subs
-            .iter()
-            .find(|s| s.sub.id == sub.id)
-            .map(|s| (s.plan_name.clone(), s.traffic_limit_gb.unwrap_or(0)))
-            .unwrap_or(("VPN Plan".to_string(), 0)),
-        Err(_) => ("VPN Plan".to_string(), 0),
+    // 1-4.5 Limits, tracking and usage headers
+    let OpenedSubscription {
+        sub,
+        link_token,
+        plan_name,
+        traffic_limit_gb,
+        user_info,
+    } = match open_subscription(&state, &uuid, &fetcher).await {
+        Ok(opened) => opened,
+        Err(rejection) => return rejection,
     };
 
-    let total_traffic_bytes = (plan_details.1 as i64) * 1024 * 1024 * 1024;
-    let used_traffic_bytes = sub.used_traffic as i64;
-    let expire_timestamp = sub.expires_at.timestamp();
-
-    // upload=0; download=used; total=limit; expire=timestamp
-    let user_info_header = format!(
-        "upload=0; download={}; total={}; expire={}",
-        used_traffic_bytes, total_traffic_bytes, expire_timestamp
-    );
-
     // ===================================================================
     // client autodetection or raw config mode
     // ===================================================================
@@ -213,7 +156,7 @@ pub async fn subscription_handler(
     if selected_client.is_none() {
         let detected = state
             .subscription_service
-            .detect_client_type(user_agent.as_deref());
+            .detect_client_type(fetcher.user_agent.as_deref());
         if detected != "html" {
             selected_client = Some(detected);
         }
@@ -221,11 +164,8 @@ pub async fn subscription_handler(
 
     // If still no client (or it's explicitly "html" detected), serve HTML
     if selected_client.is_none() {
-        // Use already fetched plan_details
-        let plan_name = plan_details;
-
         let used_gb = sub.used_traffic as f64 / 1024.0 / 1024.0 / 1024.0;
-        let limit_gb = plan_name.1;
+        let limit_gb = traffic_limit_gb;
         let traffic_pct = if limit_gb > 0 {
             ((used_gb / limit_gb as f64) * 100.0).min(100.0) as i32
         } else {
@@ -235,28 +175,16 @@ pub async fn subscription_handler(
         let duration_days = (sub.expires_at - sub.created_at).num_days();
 
         // Build base URL for config links
-        let panel_url_setting = state.settings.get_or_default("panel_url", "").await;
-        let base_url = if !sub_domain.is_empty() {
-            if sub_domain.starts_with("http") {
-                sub_domain.clone()
-            } else {
-                format!("https://{}", sub_domain)
-            }
-        } else if !panel_url_setting.is_empty() {
-            if panel_url_setting.starts_with("http") {
-                panel_url_setting.clone()
-            } else {
-                format!("https://{}", panel_url_setting)
-            }
-        } else {
-            let panel = std::env::var("PANEL_URL").unwrap_or_else(|_| "localhost".to_string());
-            if panel.starts_with("http") {
-                panel
-            } else {
-                format!("https://{}", panel)
-            }
-        };
-        let sub_url = format!("{}/sub/{}", base_url, uuid);
+        let base_url = public_base_url(&state, &sub_domain).await;
+        let sub_url = format!("{}/sub/{}", base_url, link_token);
+        // Outline fetches ssconf:// keys over https and re-reads them on
+        // every connect, so rotated Shadowsocks inbounds are followed.
+        let outline_key = format!(
+            "ssconf://{}?client=outline#CARAMBA",
+            sub_url
+                .trim_start_matches("https://")
+                .trim_start_matches("http://")
+        );
 
         let expires_display = if duration_days == 0 {
             "No expiration (Traffic Plan)".to_string()
@@ -274,6 +202,70 @@ pub async fn subscription_handler(
             format!("{:.2} GB / ∞", used_gb)
         };
 
+        // AmneziaWG apps import a file or a QR code rather than a link.
+        let wireguard_card = match state.wireguard_service.endpoints(sub.plan_id).await {
+            Ok(endpoints) if !endpoints.is_empty() => {
+                let mut card = String::from(
+                    r#"<div class="card">
+    <div class="section-label">WireGuard / AmneziaWG</div>
+    <div class="config-grid">"#,
+                );
+                for endpoint in &endpoints {
+                    let wg_url = format!("{}/wg/{}", sub_url, endpoint.inbound_id);
+                    card.push_str(&format!(
+                        r#"
+      <a href="{wg_url}" class="config-btn">
+        <span class="icon">🛡️</span>
+        <span class="label">{name}</span>
+        <span class="dl">.conf →</span>
+      </a>
+      <div class="qr-wrap"><img src="{wg_url}?format=svg" width="200" height="200" alt="WireGuard QR Code" /></div>"#,
+                        wg_url = wg_url,
+                        name = escape_html(&endpoint.node_name),
+                    ));
+                }
+                card.push_str("\n    </div>\n  </div>");
+                card
+            }
+            _ => String::new(),
+        };
+
+        // Each connection link with its QR code, opened without any script.
+        let links = state
+            .subscription_service
+            .get_subscription_links(sub.id)
+            .await
+            .unwrap_or_default();
+        let links_card = if links.is_empty() {
+            String::new()
+        } else {
+            let mut card = String::from(
+                r#"<div class="card">
+    <div class="section-label">Server Links</div>
+    <div class="config-grid">"#,
+            );
+            for (index, link) in links.iter().enumerate() {
+                card.push_str(&format!(
+                    r#"
+      <details class="link-item">
+        <summary class="config-btn">
+          <span class="icon">📷</span>
+          <span class="label">{label}</span>
+          <span class="dl">QR →</span>
+        </summary>
+        <div class="qr-wrap"><img src="{sub_url}/qr?link={index}" width="200" height="200" alt="QR Code" loading="lazy" /></div>
+        <input type="text" class="link-input" value="{link}" readonly onclick="this.select()" />
+      </details>"#,
+                    label = escape_html(&link_label(link)),
+                    sub_url = sub_url,
+                    index = index,
+                    link = escape_html(link),
+                ));
+            }
+            card.push_str("\n    </div>\n  </div>");
+            card
+        };
+
         let html = format!(
             r##"<!DOCTYPE html>
 <html lang="en">
@@ -359,6 +351,9 @@ body{{
   margin:16px 0;
   padding:16px;background:white;border-radius:12px;
 }}
+.link-item summary{{list-style:none}}
+.link-item summary::-webkit-details-marker{{display:none}}
+.link-item .link-input{{margin-bottom:4px}}
 .footer{{text-align:center;margin-top:24px;font-size:11px;color:rgba(255,255,255,0.2)}}
 </style>
 </head>
@@ -392,45 +387,70 @@ body{{
         <span class="label">V2Ray / Xray</span>
         <span class="dl">Base64 →</span>
       </a>
+      <a href="{sub_url}?client=xray" class="config-btn">
+        <span class="icon">🧩</span>
+        <span class="label">Xray-core</span>
+        <span class="dl">JSON →</span>
+      </a>
       <a href="{sub_url}?client=clash" class="config-btn">
         <span class="icon">🔥</span>
         <span class="label">Clash / Clash Meta</span>
         <span class="dl">YAML →</span>
       </a>
+      <a href="{sub_url}?client=sip008" class="config-btn">
+        <span class="icon">🛡️</span>
+        <span class="label">Shadowsocks (SIP008)</span>
+        <span class="dl">JSON →</span>
+      </a>
     </div>
   </div>
 
+  {wireguard_card}
+
   <div class="card">
     <div class="section-label">Subscription Link</div>
     <div class="qr-wrap">
-      <img src="https://api.qrserver.com/v1/create-qr-code/?size=180x180&data={sub_url_encoded}" width="180" height="180" alt="QR Code" />
+      <img src="{sub_url}/qr" width="180" height="180" alt="QR Code" />
     </div>
     <div class="copy-section">
       <input type="text" class="link-input" id="subLink" value="{sub_url}" readonly onclick="this.select()" />
-      <button class="copy-btn" id="copyBtn" onclick="copyLink()">📋 Copy Link</button>
+      <button class="copy-btn" id="copyBtn" onclick="copyLink('subLink','copyBtn')">📋 Copy Link</button>
+    </div>
+  </div>
+
+  {links_card}
+
+  <div class="card">
+    <div class="section-label">Outline Access Key</div>
+    <div class="copy-section">
+      <input type="text" class="link-input" id="outlineKey" value="{outline_key}" readonly onclick="this.select()" />
+      <button class="copy-btn" id="copyOutlineBtn" onclick="copyLink('outlineKey','copyOutlineBtn')">📋 Copy Key</button>
     </div>
   </div>
 
   <div class="footer">CARAMBA VPN Panel · Powered by Xray</div>
 </div>
 <script>
-function copyLink(){{
-  const btn=document.getElementById('copyBtn');
-  const input=document.getElementById('subLink');
+function copyLink(inputId,btnId){{
+  const btn=document.getElementById(btnId);
+  const input=document.getElementById(inputId);
+  const label=btn.textContent;
   navigator.clipboard.writeText(input.value).then(()=>{{
     btn.textContent='✓ Copied!';
     btn.classList.add('copied');
-    setTimeout(()=>{{btn.textContent='📋 Copy Link';btn.classList.remove('copied')}},2000);
+    setTimeout(()=>{{btn.textContent=label;btn.classList.remove('copied')}},2000);
   }});
 }}
 </script>
 </body>
 </html>"##,
-            plan_name = plan_name.0,
+            plan_name = plan_name,
             traffic_display = traffic_display,
             expires_display = expires_display,
             sub_url = sub_url,
-            sub_url_encoded = urlencoding::encode(&sub_url),
+            outline_key = outline_key,
+            wireguard_card = wireguard_card,
+            links_card = links_card,
             progress_bar = if limit_gb > 0 {
                 format!(
                     r#"<div class="progress"><div class="progress-fill" style="width:{}%"></div></div>"#,
@@ -446,7 +466,7 @@ function copyLink(){{
                 (header::CONTENT_TYPE, "text/html"),
                 (
                     header::HeaderName::from_static("subscription-userinfo"),
-                    user_info_header.as_str(),
+                    user_info.as_str(),
                 ),
                 (header::HeaderName::from_static("profile-title"), "CARAMBA"),
             ],
@@ -456,41 +476,458 @@ function copyLink(){{
     }
 
     // ===================================================================
-    // Raw config mode: ?client=clash|v2ray|singbox
+    // Raw config mode: ?client=clash|v2ray|singbox|xray|surge|quanx|loon|shadowrocket|sip008|outline
     // ===================================================================
 
+    // 4.7 Give the device its own credential, within the device limit.
+    let (device, device_peer) = match admit_device(&state, &sub, &fetcher).await {
+        Ok(admitted) => admitted,
+        Err(rejection) => return rejection,
+    };
+
+    // 4.8 Serve from cache. Keys cover every subscription field that shapes
+    // the config; node and inbound changes retire them via the generation.
+    let client_type = selected_client.as_deref().unwrap_or("singbox");
+    let client = fetcher.user_agent.as_deref().and_then(clients::detect);
+    let profile = profile_info(&state, user_info, &link_token, client.as_ref(), &sub_domain).await;
+    let fingerprint = hex::encode(
+        &Sha256::digest(format!(
+            "{}:{:?}:{:?}:{:?}",
+            sub.plan_id,
+            sub.node_id,
+            sub.vless_uuid,
+            device.as_ref().map(|d| &d.proxy_uuid)
+        ))[..6],
+    );
+    // Inbounds may carry anti-DPI overrides for the client's country
+    let country = client_country(&state, &fetcher.ip).await;
+    let cache_key = state
+        .redis
+        .subscription_cache_key(
+            &sub.subscription_uuid,
+            &format!(
+                "{}:{}:{}:{}:{}",
+                client_type,
+                params.node_id.unwrap_or(0),
+                fingerprint,
+                client
+                    .as_ref()
+                    .map(clients::Client::cache_key)
+                    .unwrap_or_default(),
+                country.as_deref().unwrap_or_default()
+            ),
+        )
+        .await
+        .ok();
+    if let Some(key) = &cache_key
+        && let Ok(Some(cached)) = state.redis.get_cached_subscription(key).await
+    {
+        return config_response(cached, &profile, client_type, if_none_match.as_deref())
+            .into_response();
+    }
+
     // 5. Get user keys
-    let user_keys = match state.subscription_service.get_user_keys(&sub).await {
-        Ok(k) => k,
+    let user_keys = match device_keys(&state, &sub, device.as_ref(), device_peer.as_ref()).await {
+        Ok(keys) => keys,
+        Err(rejection) => return rejection,
+    };
+
+    let mut input = match config_input(&state, sub, user_keys, params.node_id, client_type).await {
+        Ok(input) => input,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let content = match render_for(client_type, &mut input, country.as_deref(), client.as_ref()) {
+        Ok(c) => c,
         Err(e) => {
-            error!("Failed to get user keys for sub {}: {}", uuid, e);
-            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+            error!("Config generation for {} failed: {}", client_type, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Generation failed").into_response();
         }
     };
 
+    if let Some(key) = &cache_key {
+        let _ = state.redis.cache_subscription(key, &content).await;
+    }
+
+    config_response(content, &profile, client_type, if_none_match.as_deref()).into_response()
+}
+
+/// Who is fetching a subscription. The panel reads it off the request;
+/// subscription workers forward it to the internal API.
+pub(crate) struct Fetcher {
+    pub ip: String,
+    pub user_agent: Option<String>,
+    pub hwid: Option<String>,
+    pub device_model: Option<String>,
+}
+
+impl Fetcher {
+    pub(crate) fn new(ip: String, headers: &HeaderMap) -> Self {
+        let text = |name: header::HeaderName| {
+            headers
+                .get(name)
+                .and_then(|h| h.to_str().ok())
+                .map(|s| s.to_string())
+        };
+        Self {
+            ip,
+            user_agent: text(header::USER_AGENT),
+            hwid: text(HeaderName::from_static("x-hwid")),
+            device_model: text(HeaderName::from_static("x-device-model")),
+        }
+    }
+}
+
+/// A fetch of a subscription that passed its rate, quota and IP limits.
+pub(crate) struct OpenedSubscription {
+    pub sub: Subscription,
+    /// Current link token, which links handed out carry
+    pub link_token: String,
+    pub plan_name: String,
+    pub traffic_limit_gb: i32,
+    /// `Subscription-Userinfo` value
+    pub user_info: String,
+}
+
+/// Opens the subscription behind link token `token` for `fetcher`, and
+/// records the access. The error is the response turning it down.
+pub(crate) async fn open_subscription(
+    state: &AppState,
+    token: &str,
+    fetcher: &Fetcher,
+) -> Result<OpenedSubscription, Response> {
+    // 1. Rate Limit (30 req / min per UUID)
+    let rate_key = format!("rate:sub:{}", token);
+    match state.redis.check_rate_limit(&rate_key, 30, 60).await {
+        Ok(allowed) => {
+            if !allowed {
+                warn!("Rate limit exceeded for subscription {}", token);
+                return Err((StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded").into_response());
+            }
+        }
+        Err(e) => {
+            error!("Rate limit check failed: {}", e);
+        }
+    }
+
+    // 2. Get subscription (the path segment is a rotatable link token)
+    let sub = match state.sub_token_service.resolve(token).await {
+        Ok(Some(s)) => s,
+        Ok(None) => {
+            return Err((StatusCode::NOT_FOUND, "Subscription not found").into_response());
+        }
+        Err(e) => {
+            error!("Failed to resolve subscription token: {}", e);
+            return Err((StatusCode::NOT_FOUND, "Subscription not found").into_response());
+        }
+    };
+
+    // 3. Check if active
+    if sub.status != "active" {
+        return Err((StatusCode::FORBIDDEN, "Subscription inactive or expired").into_response());
+    }
+
+    // 3.2 Check traffic quota immediately on subscription fetch to enforce limits in real-time.
+    match state
+        .subscription_service
+        .ensure_subscription_within_quota(sub.id)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Traffic limit reached. Subscription is expired.",
+            )
+                .into_response());
+        }
+        Err(e) => {
+            error!(
+                "Failed to evaluate quota for subscription {}: {}",
+                sub.id, e
+            );
+        }
+    }
+
+    // 3.5 Enforce device limit (Phase 7)
+    let active_ips = state
+        .subscription_service
+        .get_active_ips(sub.id)
+        .await
+        .unwrap_or_default();
+    let current_ip = &fetcher.ip;
+
+    // Check if this is a new IP or if we're already at the limit
+    let is_new_device = !active_ips.iter().any(|rec| rec.client_ip == *current_ip);
+
+    if is_new_device {
+        let device_limit = state
+            .subscription_service
+            .get_subscription_device_limit(sub.id)
+            .await
+            .unwrap_or(0);
+        if device_limit > 0 && active_ips.len() >= device_limit as usize {
+            warn!(
+                "Device limit reached for subscription {}. Limit: {}, Active: {}",
+                token,
+                device_limit,
+                active_ips.len()
+            );
+            return Err((StatusCode::FORBIDDEN, "Device limit reached").into_response());
+        }
+    }
+
+    // 4. Update access tracking
+    let _ = state
+        .subscription_service
+        .track_access(sub.id, &fetcher.ip, fetcher.user_agent.as_deref())
+        .await;
+    if let Err(e) = state
+        .sub_token_service
+        .record_use(sub.id, token, &fetcher.ip, fetcher.user_agent.as_deref())
+        .await
+    {
+        warn!("Failed to record use of subscription token: {}", e);
+    }
+    // Links handed out below always carry the current token, also when
+    // this request came in on one that is about to expire.
+    let link_token = state
+        .sub_token_service
+        .current(sub.id, &sub.subscription_uuid)
+        .await;
+
+    // 4.5 Prepare Usage Headers (for Hiddify/Sing-box)
+    let (plan_name, traffic_limit_gb) = match state
+        .subscription_service
+        .get_user_subscriptions(sub.user_id)
+        .await
+    {
+        Ok(subs) => subs
+            .iter()
+            .find(|s| s.sub.id == sub.id)
+            .map(|s| (s.plan_name.clone(), s.traffic_limit_gb.unwrap_or(0)))
+            .unwrap_or(("VPN Plan".to_string(), 0)),
+        Err(_) => ("VPN Plan".to_string(), 0),
+    };
+
+    let total_traffic_bytes = (traffic_limit_gb as i64) * 1024 * 1024 * 1024;
+    let used_traffic_bytes = sub.used_traffic as i64;
+    let expire_timestamp = sub.expires_at.timestamp();
+
+    // upload=0; download=used; total=limit; expire=timestamp
+    let user_info = format!(
+        "upload=0; download={}; total={}; expire={}",
+        used_traffic_bytes, total_traffic_bytes, expire_timestamp
+    );
+
+    Ok(OpenedSubscription {
+        sub,
+        link_token,
+        plan_name,
+        traffic_limit_gb,
+        user_info,
+    })
+}
+
+/// The device `fetcher` is on, with its own credential and WireGuard peer
+/// when it can be told apart; refused once revoked or over the limit.
+async fn admit_device(
+    state: &AppState,
+    sub: &Subscription,
+    fetcher: &Fetcher,
+) -> Result<(Option<SubscriptionDevice>, Option<WireguardPeer>), Response> {
+    match DeviceService::device_key(fetcher.hwid.as_deref(), fetcher.user_agent.as_deref()) {
+        Some(device_key) => {
+            let device_name = fetcher.device_model.clone().unwrap_or_else(|| {
+                state
+                    .subscription_service
+                    .parse_device_name(fetcher.user_agent.as_deref().unwrap_or_default())
+            });
+            let device_limit = state
+                .subscription_service
+                .get_subscription_device_limit(sub.id)
+                .await
+                .unwrap_or(0);
+            match state
+                .device_service
+                .admit(sub.id, &device_key, &device_name, &fetcher.ip, device_limit)
+                .await
+            {
+                Ok(DeviceAdmission::Admitted { device, is_new }) => {
+                    // Its WireGuard peer is allocated before the nodes are told.
+                    let (peer, peer_is_new) =
+                        match state.wireguard_service.peer(sub.id, Some(device.id)).await {
+                            Ok((peer, created)) => (Some(peer), created),
+                            Err(e) => {
+                                warn!("WireGuard peer allocation failed for sub {}: {}", sub.id, e);
+                                (None, false)
+                            }
+                        };
+                    if is_new || peer_is_new {
+                        let orchestration = state.orchestration_service.clone();
+                        let plan_id = sub.plan_id;
+                        tokio::spawn(async move {
+                            if let Err(e) = orchestration.notify_plan_nodes(plan_id).await {
+                                warn!("Failed to push new device credential to nodes: {}", e);
+                            }
+                        });
+                    }
+                    Ok((Some(device), peer))
+                }
+                Ok(DeviceAdmission::Revoked) => Err((
+                    StatusCode::FORBIDDEN,
+                    "This device was removed from the subscription",
+                )
+                    .into_response()),
+                Ok(DeviceAdmission::LimitReached) => {
+                    Err((StatusCode::FORBIDDEN, "Device limit reached").into_response())
+                }
+                Err(e) => {
+                    // The shared credential is not deployed once devices
+                    // exist, so a config without the device's would not connect.
+                    error!("Device credential lookup failed for sub {}: {}", sub.id, e);
+                    Err((StatusCode::SERVICE_UNAVAILABLE, "Device lookup failed").into_response())
+                }
+            }
+        }
+        None => Ok((None, None)),
+    }
+}
+
+async fn profile_info(
+    state: &AppState,
+    user_info: String,
+    link_token: &str,
+    client: Option<&clients::Client>,
+    sub_domain: &str,
+) -> ProfileInfo {
+    ProfileInfo {
+        title: state.settings.get_or_default("brand_name", "CARAMBA").await,
+        user_info,
+        update_interval_hours: state
+            .settings
+            .get_or_default("sub_update_interval", "12")
+            .await,
+        support_url: match state.settings.get_or_default("support_url", "").await {
+            s if s.is_empty() || s.starts_with("http") => s,
+            s => format!("https://t.me/{}", s.trim_start_matches('@')),
+        },
+        web_page_url: format!(
+            "{}/sub/{}",
+            public_base_url(state, sub_domain).await,
+            link_token
+        ),
+        client_profile: client.map(clients::Client::summary),
+    }
+}
+
+async fn client_country(state: &AppState, ip: &str) -> Option<String> {
+    state
+        .geo_service
+        .get_location(ip)
+        .await
+        .map(|g| g.country_code)
+}
+
+/// Keys the config of `sub` is generated with: the device's own when it
+/// was admitted with one.
+async fn device_keys(
+    state: &AppState,
+    sub: &Subscription,
+    device: Option<&SubscriptionDevice>,
+    device_peer: Option<&WireguardPeer>,
+) -> Result<UserKeys, Response> {
+    match state.subscription_service.get_user_keys(sub).await {
+        Ok(k) => Ok(match (device, device_peer) {
+            (Some(device), Some(peer)) => {
+                k.for_device(&device.proxy_uuid).with_wireguard_peer(peer)
+            }
+            (Some(device), None) => k.for_device(&device.proxy_uuid),
+            _ => k,
+        }),
+        Err(e) => {
+            error!("Failed to get user keys for sub {}: {}", sub.id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response())
+        }
+    }
+}
+
+/// The config fetch of link token `token` by `fetcher`, admitted as
+/// `/sub/{token}` admits it, for a subscription worker to render and
+/// answer. `client` defaults to what the fetcher's app reads.
+pub(crate) async fn prepare_config(
+    state: &AppState,
+    token: &str,
+    fetcher: &Fetcher,
+    client: Option<String>,
+    node_id: Option<i64>,
+) -> Result<PreparedConfig, Response> {
+    let opened = open_subscription(state, token, fetcher).await?;
+    // Workers only serve configs; browsers get the panel's page.
+    let client_type = client.unwrap_or_else(|| {
+        match state
+            .subscription_service
+            .detect_client_type(fetcher.user_agent.as_deref())
+        {
+            detected if detected == "html" => "singbox".to_string(),
+            detected => detected,
+        }
+    });
+    let (device, device_peer) = admit_device(state, &opened.sub, fetcher).await?;
+    let sub_domain = state
+        .settings
+        .get_or_default("subscription_domain", "")
+        .await;
+    let detected = fetcher.user_agent.as_deref().and_then(clients::detect);
+    let profile = profile_info(
+        state,
+        opened.user_info,
+        &opened.link_token,
+        detected.as_ref(),
+        &sub_domain,
+    )
+    .await;
+    let country = client_country(state, &fetcher.ip).await;
+    let keys = device_keys(state, &opened.sub, device.as_ref(), device_peer.as_ref()).await?;
+    let input = config_input(state, opened.sub, keys, node_id, &client_type)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    Ok(PreparedConfig {
+        client_type,
+        country,
+        profile,
+        input,
+    })
+}
+
+/// What the config of `sub` is generated from: the plan's nodes, or the
+/// requested or pinned one, with their relays and the plan's template for
+/// `client_type`. Subscription workers fetch it through the internal API.
+pub(crate) async fn config_input(
+    state: &AppState,
+    sub: Subscription,
+    keys: UserKeys,
+    node_id: Option<i64>,
+    client_type: &str,
+) -> Result<ConfigInput, (StatusCode, &'static str)> {
     // Fetch and filter nodes (Refactored Phase 1.8: Use Plan Groups)
     // Fallback to all active nodes if plan bindings are temporarily missing.
     let mut nodes_raw = match state.store_service.get_user_nodes(sub.user_id).await {
         Ok(nodes) if !nodes.is_empty() => nodes,
         Ok(_) => match state.store_service.get_active_nodes().await {
             Ok(nodes) => nodes,
-            Err(_) => {
-                return (StatusCode::SERVICE_UNAVAILABLE, "No servers available").into_response();
-            }
+            Err(_) => return Err((StatusCode::SERVICE_UNAVAILABLE, "No servers available")),
         },
         Err(_) => match state.store_service.get_active_nodes().await {
             Ok(nodes) => nodes,
-            Err(_) => {
-                return (StatusCode::SERVICE_UNAVAILABLE, "No servers available").into_response();
-            }
+            Err(_) => return Err((StatusCode::SERVICE_UNAVAILABLE, "No servers available")),
         },
     };
 
     if nodes_raw.is_empty() {
-        return (StatusCode::SERVICE_UNAVAILABLE, "No servers available").into_response();
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "No servers available"));
     }
 
-    let mut filtered_nodes = if let Some(nid) = params.node_id {
+    let mut filtered_nodes = if let Some(nid) = node_id {
         nodes_raw
             .iter()
             .filter(|n| n.id == nid)
@@ -507,16 +944,16 @@ function copyLink(){{
     };
 
     // If subscription has stale pinned node (deleted/disabled), fall back to full set.
-    if filtered_nodes.is_empty() && params.node_id.is_none() {
+    if filtered_nodes.is_empty() && node_id.is_none() {
         filtered_nodes = std::mem::take(&mut nodes_raw);
     }
 
     if filtered_nodes.is_empty() {
-        return (StatusCode::NOT_FOUND, "Requested server not found").into_response();
+        return Err((StatusCode::NOT_FOUND, "Requested server not found"));
     }
 
     // Persist last explicitly selected node so UI/miniapp can show where the user last pulled config from.
-    if let Some(selected_node_id) = params.node_id {
+    if let Some(selected_node_id) = node_id {
         if filtered_nodes.iter().any(|n| n.id == selected_node_id) {
             let _ = state
                 .subscription_service
@@ -533,106 +970,260 @@ function copyLink(){{
         Ok(infos) => infos,
         Err(e) => {
             error!("Failed to generate node infos: {}", e);
-            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to process nodes").into_response();
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to process nodes"));
         }
     };
 
-    // Check Redis Cache & Generate
-    let client_type = selected_client.as_deref().unwrap_or("singbox");
-    let cache_node_id = params.node_id.unwrap_or(0);
-    let cache_key = format!("sub_config_v2:{}:{}:{}", uuid, client_type, cache_node_id);
-
-    if let Ok(Some(cached_config)) = state.redis.get(&cache_key).await {
-        let filename = match client_type {
-            "clash" => "config.yaml",
-            "v2ray" => "config.txt",
-            _ => "config.json",
-        };
-        let content_type = match client_type {
-            "clash" => "application/yaml",
-            "v2ray" => "text/plain",
-            _ => "application/json",
-        };
-        return (
-            StatusCode::OK,
+    let mut template = None;
+    if TEMPLATE_FORMATS.contains(&client_type) {
+        match state
+            .sub_template_service
+            .body_for(sub.plan_id, client_type)
+            .await
+        {
+            Ok(body) => template = body,
+            Err(e) => warn!("Failed to load subscription template: {}", e),
+        }
+    }
+
+    let chains = match state.chain_profile_service.chains_for(sub.plan_id).await {
+        Ok(chains) => chains,
+        Err(e) => {
+            warn!("Failed to load chains for plan {}: {}", sub.plan_id, e);
+            Vec::new()
+        }
+    };
+
+    Ok(ConfigInput {
+        subscription: sub,
+        nodes: node_infos,
+        keys,
+        template,
+        chains,
+    })
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Display name of a connection link: its decoded `#fragment`, else its
+/// scheme.
+fn link_label(link: &str) -> String {
+    match link.rsplit_once('#') {
+        Some((_, label)) if !label.is_empty() => urlencoding::decode(label)
+            .map(|l| l.into_owned())
+            .unwrap_or_else(|_| label.to_string()),
+        _ => link.split("://").next().unwrap_or(link).to_uppercase(),
+    }
+}
+
+/// `.conf` file, or its QR code, for one AmneziaWG inbound of the plan.
+pub async fn wireguard_config_handler(
+    Path((uuid, inbound_id)): Path<(String, i64)>,
+    Query(params): Query<WireguardParams>,
+    State(state): State<AppState>,
+) -> Response {
+    let rate_key = format!("rate:wg:{}", uuid);
+    if let Ok(false) = state.redis.check_rate_limit(&rate_key, 30, 60).await {
+        return (StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded").into_response();
+    }
+
+    let sub = match state.sub_token_service.resolve(&uuid).await {
+        Ok(Some(s)) if s.status == "active" => s,
+        Ok(Some(_)) => {
+            return (StatusCode::FORBIDDEN, "Subscription inactive or expired").into_response();
+        }
+        _ => return (StatusCode::NOT_FOUND, "Subscription not found").into_response(),
+    };
+
+    if let Some(device_id) = params.device {
+        let devices = state.device_service.list(sub.id).await.unwrap_or_default();
+        if !devices.iter().any(|d| d.id == device_id && !d.is_revoked()) {
+            return (StatusCode::NOT_FOUND, "Device not found").into_response();
+        }
+    }
+
+    let endpoint = match state.wireguard_service.endpoints(sub.plan_id).await {
+        Ok(endpoints) => endpoints.into_iter().find(|e| e.inbound_id == inbound_id),
+        Err(e) => {
+            error!("Failed to load WireGuard endpoints: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+        }
+    };
+    let Some(endpoint) = endpoint else {
+        return (StatusCode::NOT_FOUND, "WireGuard server not found").into_response();
+    };
+
+    let peer = match state.wireguard_service.peer(sub.id, params.device).await {
+        Ok((peer, created)) => {
+            if created {
+                let orchestration = state.orchestration_service.clone();
+                let plan_id = sub.plan_id;
+                tokio::spawn(async move {
+                    if let Err(e) = orchestration.notify_plan_nodes(plan_id).await {
+                        warn!("Failed to push new WireGuard peer to nodes: {}", e);
+                    }
+                });
+            }
+            peer
+        }
+        Err(e) => {
+            error!("WireGuard peer allocation failed for sub {}: {}", sub.id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+        }
+    };
+
+    let conf = render_conf(&peer, &endpoint);
+    match params.format.as_deref().unwrap_or("conf") {
+        format @ ("png" | "svg") => qr_response(&conf, format),
+        _ => (
             [
-                (header::CONTENT_TYPE, content_type),
                 (
-                    header::CONTENT_DISPOSITION,
-                    format!("inline; filename={}", filename).as_str(),
+                    header::CONTENT_TYPE,
+                    "text/plain; charset=utf-8".to_string(),
                 ),
                 (
-                    header::HeaderName::from_static("subscription-userinfo"),
-                    user_info_header.as_str(),
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.conf\"", endpoint.tunnel_name()),
                 ),
-                (header::HeaderName::from_static("profile-title"), "CARAMBA"),
             ],
-            cached_config,
+            conf,
         )
-            .into_response();
+            .into_response(),
     }
+}
 
-    let (content, content_type, filename): (String, &'static str, &'static str) = match client_type
-    {
-        "clash" => {
-            match state
-                .subscription_service
-                .generate_clash(&sub, &node_infos, &user_keys)
-            {
-                Ok(c) => (c, "application/yaml", "config.yaml"),
-                Err(e) => {
-                    error!("Clash gen failed: {}", e);
-                    return (StatusCode::INTERNAL_SERVER_ERROR, "Generation failed")
-                        .into_response();
-                }
-            }
+/// QR code of the subscription URL, or of its `link`-th connection link.
+/// Only the subscription's own data is rendered, never arbitrary text.
+pub async fn qr_handler(
+    Path(uuid): Path<String>,
+    Query(params): Query<QrParams>,
+    State(state): State<AppState>,
+) -> Response {
+    let rate_key = format!("rate:qr:{}", uuid);
+    if let Ok(false) = state.redis.check_rate_limit(&rate_key, 60, 60).await {
+        return (StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded").into_response();
+    }
+
+    let sub = match state.sub_token_service.resolve(&uuid).await {
+        Ok(Some(s)) => s,
+        _ => return (StatusCode::NOT_FOUND, "Subscription not found").into_response(),
+    };
+
+    match qr_payload(&state, &sub, params.link).await {
+        Some(data) => qr_response(&data, params.format.as_deref().unwrap_or("svg")),
+        None => (StatusCode::NOT_FOUND, "Link not found").into_response(),
+    }
+}
+
+/// What a subscription's QR codes encode: its URL with the current link
+/// token, or its `link`-th connection link.
+pub(crate) async fn qr_payload(
+    state: &AppState,
+    sub: &caramba_db::models::store::Subscription,
+    link: Option<usize>,
+) -> Option<String> {
+    match link {
+        Some(index) => state
+            .subscription_service
+            .get_subscription_links(sub.id)
+            .await
+            .ok()?
+            .into_iter()
+            .nth(index),
+        None => {
+            let sub_domain = state
+                .settings
+                .get_or_default("subscription_domain", "")
+                .await;
+            Some(format!(
+                "{}/sub/{}",
+                public_base_url(state, &sub_domain).await,
+                state
+                    .sub_token_service
+                    .current(sub.id, &sub.subscription_uuid)
+                    .await
+            ))
         }
-        "v2ray" => {
-            match state
-                .subscription_service
-                .generate_v2ray(&sub, &node_infos, &user_keys)
-            {
-                Ok(c) => (c, "text/plain", "config.txt"),
-                Err(e) => {
-                    error!("V2Ray gen failed: {}", e);
-                    return (StatusCode::INTERNAL_SERVER_ERROR, "Generation failed")
-                        .into_response();
-                }
-            }
+    }
+}
+
+/// `data` as a PNG or (for any other format) SVG QR code.
+pub(crate) fn qr_response(data: &str, format: &str) -> Response {
+    let rendered = if format == "png" {
+        qr_service::png(data, qr_service::PNG_SCALE).map(|png| ("image/png", png))
+    } else {
+        qr_service::svg(data, 240).map(|svg| ("image/svg+xml", svg.into_bytes()))
+    };
+    match rendered {
+        Ok((content_type, body)) => (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CACHE_CONTROL, "private, max-age=300"),
+            ],
+            body,
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to render QR code: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "QR rendering failed").into_response()
         }
-        _ => {
-            match state
-                .subscription_service
-                .generate_singbox(&sub, &node_infos, &user_keys)
-            {
-                Ok(c) => (c, "application/json", "config.json"),
-                Err(e) => {
-                    error!("Singbox gen failed: {}", e);
-                    return (StatusCode::INTERNAL_SERVER_ERROR, "Generation failed")
-                        .into_response();
-                }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_the_shared_corpus_like_the_workers() {
+        use caramba_config::corpus;
+
+        for (case, json) in corpus::cases() {
+            for format in corpus::FORMATS {
+                // What `subscription_handler` renders and answers once
+                // `config_input` is built; workers answer from the same.
+                let mut input: ConfigInput = serde_json::from_str(&json).unwrap();
+                let content = render_for(format, &mut input, None, None).unwrap();
+                let response = config_response(content, &corpus::profile(), format, None);
+                assert_eq!(
+                    corpus::header_list(response.headers()),
+                    corpus::expected_headers(&case, format),
+                    "{} {}",
+                    case,
+                    format
+                );
+                assert_eq!(
+                    response.into_body(),
+                    corpus::expected(&case, format),
+                    "{} {}",
+                    case,
+                    format
+                );
             }
         }
-    };
-
-    // Cache
-    let _ = state.redis.set(&cache_key, &content, 60).await; // 1 min cache
+    }
 
-    (
-        StatusCode::OK,
-        [
-            (header::CONTENT_TYPE, content_type),
-            (
-                header::CONTENT_DISPOSITION,
-                format!("inline; filename={}", filename).as_str(),
+    #[test]
+    fn link_labels_come_from_fragments() {
+        assert_eq!(
+            link_label(
+                "vless://id@host:443?security=reality#%F0%9F%87%A9%F0%9F%87%AA%20DE%20VLESS"
             ),
-            (
-                header::HeaderName::from_static("subscription-userinfo"),
-                user_info_header.as_str(),
-            ),
-            (header::HeaderName::from_static("profile-title"), "CARAMBA"),
-        ],
-        content,
-    )
-        .into_response()
+            "🇩🇪 DE VLESS"
+        );
+        assert_eq!(link_label("ss://abc@host:8388"), "SS");
+    }
+
+    #[test]
+    fn qr_responses_match_format() {
+        let png = qr_response("vless://id@host:443", "png");
+        assert_eq!(png.headers()["content-type"], "image/png");
+        let svg = qr_response("vless://id@host:443", "svg");
+        assert_eq!(svg.headers()["content-type"], "image/svg+xml");
+    }
 }
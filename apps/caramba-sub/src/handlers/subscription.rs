@@ -1,16 +1,19 @@
-use crate::singbox_generator::ConfigGenerator;
+use crate::panel_client::Rejected;
 use crate::AppState;
 use axum::{
     extract::{Path, Query, State},
     http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
+use caramba_config::clients;
+use caramba_config::serve::{config_response, render_for, PreparedConfig};
 use serde::Deserialize;
 use tracing::{error, info};
 
 #[derive(Deserialize)]
 pub struct SubParams {
-    pub client: Option<String>, // "clash" | "v2ray" | "singbox"
+    pub client: Option<String>, // raw config format, see caramba_config::client_output()
+    pub node_id: Option<i64>,
 }
 
 pub async fn subscription_handler(
@@ -34,88 +37,67 @@ pub async fn subscription_handler(
         uuid, params.client, client_ip, country_code
     );
 
-    // 2. Get Subscription
-    let sub = match state.panel_client.get_subscription(&uuid).await {
-        Ok(s) => s,
+    // 2. Have the panel admit the fetch: limits, device credential, profile
+    let prepared = match state
+        .panel_client
+        .get_config_input(
+            &uuid,
+            params.client.as_deref(),
+            params.node_id,
+            &client_ip,
+            &headers,
+        )
+        .await
+    {
+        Ok(prepared) => prepared,
         Err(e) => {
-            error!("Failed to fetch subscription: {}", e);
-            return (StatusCode::NOT_FOUND, "Subscription not found").into_response();
+            error!("Failed to fetch config input: {}", e);
+            if let Some(rejected) = e.downcast_ref::<Rejected>() {
+                let status = StatusCode::from_u16(rejected.status).unwrap_or(StatusCode::FORBIDDEN);
+                return (status, rejected.message.clone()).into_response();
+            }
+            return (StatusCode::SERVICE_UNAVAILABLE, "Subscription unavailable").into_response();
         }
     };
 
-    if sub.status != "active" {
-        return (StatusCode::FORBIDDEN, "Subscription inactive").into_response();
-    }
-
-    // 3. Get Nodes (InternalNode structure)
-    let nodes = match state.panel_client.get_active_nodes().await {
-        Ok(n) => n,
-        Err(e) => {
-            error!("Failed to fetch nodes: {}", e);
-            return (StatusCode::SERVICE_UNAVAILABLE, "No nodes available").into_response();
-        }
-    };
+    // 3. Generate Config and answer, exactly as the panel would
+    let client = headers
+        .get(header::USER_AGENT)
+        .and_then(|ua| ua.to_str().ok())
+        .and_then(clients::detect);
+    let if_none_match = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|h| h.to_str().ok());
+    respond(prepared, client.as_ref(), if_none_match)
+}
 
-    // 4. Get User Keys
-    let user_keys = match state.panel_client.get_user_keys(sub.user_id).await {
-        Ok(k) => k,
+/// The answer to a fetch the panel admitted as `prepared`.
+fn respond(
+    mut prepared: PreparedConfig,
+    client: Option<&clients::Client>,
+    if_none_match: Option<&str>,
+) -> Response {
+    match render_for(
+        &prepared.client_type,
+        &mut prepared.input,
+        prepared.country.as_deref(),
+        client,
+    ) {
+        Ok(content) => config_response(
+            content,
+            &prepared.profile,
+            &prepared.client_type,
+            if_none_match,
+        )
+        .into_response(),
         Err(e) => {
-            error!("Failed to fetch user keys: {}", e);
-            return (StatusCode::INTERNAL_SERVER_ERROR, "Key error").into_response();
+            error!(
+                "Config generation for {} failed: {}",
+                prepared.client_type, e
+            );
+            (StatusCode::INTERNAL_SERVER_ERROR, "Generation failed").into_response()
         }
-    };
-
-    // 5. Generate Config
-    let client_type = params.client.as_deref().unwrap_or("singbox");
-
-    let (content, content_type, filename) = match client_type {
-        "clash" => {
-            // Legacy Clash Gen
-            let simple_nodes: Vec<&crate::panel_client::Node> =
-                nodes.iter().map(|n| &n.node).collect();
-            match generate_clash_config(&simple_nodes, &user_keys) {
-                Ok(c) => (c, "application/yaml", "config.yaml"),
-                Err(e) => {
-                    return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
-                }
-            }
-        }
-        "v2ray" => {
-            // Legacy V2Ray Gen
-            let simple_nodes: Vec<&crate::panel_client::Node> =
-                nodes.iter().map(|n| &n.node).collect();
-            match generate_v2ray_config(&simple_nodes, &user_keys) {
-                Ok(c) => (c, "text/plain", "config.txt"),
-                Err(e) => {
-                    return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
-                }
-            }
-        }
-        _ => {
-            // Smart Sing-box Gen
-            // Pass the FULL nodes (with inbounds) and the detected region
-            let config_json = ConfigGenerator::generate(nodes, &user_keys, country_code);
-            let json_str = serde_json::to_string_pretty(&config_json).unwrap_or_default();
-            (json_str, "application/json", "config.json")
-        }
-    };
-
-    // 6. Return with proper headers
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, content_type)
-        .header(
-            header::CONTENT_DISPOSITION,
-            format!("attachment; filename=\"{}\"", filename),
-        )
-        .header("X-Profile-Update-Interval", "24")
-        .header(
-            "Subscription-Userinfo",
-            format!("upload=0; download={}; total=0; expire=0", sub.used_traffic),
-        )
-        .body(content)
-        .unwrap()
-        .into_response()
+    }
 }
 
 fn get_client_ip(headers: &HeaderMap) -> Option<String> {
@@ -132,87 +114,46 @@ fn get_client_ip(headers: &HeaderMap) -> Option<String> {
     None
 }
 
-fn is_placeholder_sni(sni: &str) -> bool {
-    let sni = sni.trim().to_ascii_lowercase();
-    sni.is_empty() || sni == "www.google.com" || sni == "google.com" || sni == "drive.google.com"
-}
-
-fn resolve_node_sni(node: &crate::panel_client::Node) -> String {
-    node.reality_sni
-        .as_ref()
-        .or(node.domain.as_ref())
-        .filter(|s| !is_placeholder_sni(s))
-        .cloned()
-        .unwrap_or_else(|| node.ip.clone())
-}
-
-// Config generators (simplified versions from main panel)
-fn generate_clash_config(
-    nodes: &[&crate::panel_client::Node],
-    keys: &crate::panel_client::UserKeys,
-) -> anyhow::Result<String> {
-    use serde_json::json;
-
-    let mut proxies = Vec::new();
-
-    for node in nodes {
-        let node_sni = resolve_node_sni(node);
-        proxies.push(json!({
-            "name": format!("{} VLESS", node.name),
-            "type": "vless",
-            "server": node.ip,
-            "port": node.vpn_port,
-            "uuid": keys.user_uuid,
-            "network": "tcp",
-            "tls": true,
-            "servername": node_sni,
-            "reality-opts": {
-                "public-key": node.reality_pub.as_ref().unwrap_or(&"".to_string()),
-                "short-id": node.short_id.as_ref().unwrap_or(&"".to_string())
-            },
-            "client-fingerprint": "chrome"
-        }));
-    }
-
-    let proxy_names: Vec<String> = proxies
-        .iter()
-        .map(|p| p["name"].as_str().unwrap().to_string())
-        .collect();
-
-    let config = json!({
-        "proxies": proxies,
-        "proxy-groups": [{
-            "name": "CARAMBA",
-            "type": "select",
-            "proxies": proxy_names
-        }],
-        "rules": ["MATCH,CARAMBA"]
-    });
-
-    Ok(serde_yaml::to_string(&config)?)
-}
-
-fn generate_v2ray_config(
-    nodes: &[&crate::panel_client::Node],
-    keys: &crate::panel_client::UserKeys,
-) -> anyhow::Result<String> {
-    let mut links = Vec::new();
-
-    for node in nodes {
-        let node_sni = resolve_node_sni(node);
-        let vless_link = format!(
-            "vless://{}@{}:{}?encryption=none&flow=xtls-rprx-vision&security=reality&sni={}&fp=chrome&pbk={}&sid={}&type=tcp#{}",
-            keys.user_uuid,
-            node.ip,
-            node.vpn_port,
-            node_sni,
-            node.reality_pub.as_ref().unwrap_or(&"".to_string()),
-            node.short_id.as_ref().unwrap_or(&"".to_string()),
-            urlencoding::encode(&format!("{} VLESS", node.name))
-        );
-        links.push(vless_link);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use caramba_config::{corpus, ConfigInput};
+
+    #[tokio::test]
+    async fn answers_the_shared_corpus_like_the_panel() {
+        for (case, json) in corpus::cases() {
+            for format in corpus::FORMATS {
+                // Sent as the internal endpoint sends it, decoded as
+                // `PanelClient::get_config_input` decodes it.
+                let input: ConfigInput = serde_json::from_str(&json).unwrap();
+                let sent = serde_json::to_string(&PreparedConfig {
+                    client_type: format.to_string(),
+                    country: None,
+                    profile: corpus::profile(),
+                    input,
+                })
+                .unwrap();
+                let response = respond(serde_json::from_str(&sent).unwrap(), None, None);
+
+                assert_eq!(response.status(), StatusCode::OK);
+                assert_eq!(
+                    corpus::header_list(response.headers()),
+                    corpus::expected_headers(&case, format),
+                    "{} {}",
+                    case,
+                    format
+                );
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    String::from_utf8(body.to_vec()).unwrap(),
+                    corpus::expected(&case, format),
+                    "{} {}",
+                    case,
+                    format
+                );
+            }
+        }
     }
-
-    use base64::Engine;
-    Ok(base64::engine::general_purpose::STANDARD.encode(links.join("\n")))
 }
@@ -1,4 +1,6 @@
 use anyhow::Result;
+use caramba_config::serve::{PreparedConfig, CLIENT_IP_HEADER};
+use reqwest::header::HeaderMap;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
@@ -18,46 +20,53 @@ impl PanelClient {
         }
     }
 
-    pub async fn get_subscription(&self, uuid: &str) -> Result<Subscription> {
-        let url = format!("{}/api/internal/subscriptions/{}", self.base_url, uuid);
-
-        let response = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.auth_token)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        Ok(response.json().await?)
-    }
-
-    pub async fn get_active_nodes(&self) -> Result<Vec<InternalNode>> {
-        let url = format!("{}/api/internal/nodes/active", self.base_url);
-
-        let response = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.auth_token)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        Ok(response.json().await?)
-    }
-
-    pub async fn get_user_keys(&self, user_id: i64) -> Result<UserKeys> {
-        let url = format!("{}/api/internal/users/{}/keys", self.base_url, user_id);
+    /// The config fetch of link token `uuid` by the client at `client_ip`
+    /// sending `headers`, as the panel admits it: in `client` format, or
+    /// what the client's app reads, of `node_id` or the plan's nodes.
+    pub async fn get_config_input(
+        &self,
+        uuid: &str,
+        client: Option<&str>,
+        node_id: Option<i64>,
+        client_ip: &str,
+        headers: &HeaderMap,
+    ) -> Result<PreparedConfig> {
+        let mut query = Vec::new();
+        if let Some(client) = client {
+            query.push(format!("client={}", urlencoding::encode(client)));
+        }
+        if let Some(node_id) = node_id {
+            query.push(format!("node_id={}", node_id));
+        }
+        let url = format!(
+            "{}/api/internal/subscriptions/{}/config?{}",
+            self.base_url,
+            uuid,
+            query.join("&")
+        );
 
-        let response = self
+        let mut request = self
             .client
             .get(&url)
             .bearer_auth(&self.auth_token)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        Ok(response.json().await?)
+            .header(CLIENT_IP_HEADER, client_ip);
+        // What the panel tells devices and apps apart by
+        for name in ["user-agent", "x-hwid", "x-device-model"] {
+            if let Some(value) = headers.get(name) {
+                request = request.header(name, value);
+            }
+        }
+        let response = request.send().await?;
+
+        let status = response.status();
+        if status.is_client_error() {
+            return Err(Rejected {
+                status: status.as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            }
+            .into());
+        }
+        Ok(response.error_for_status()?.json().await?)
     }
 
     pub async fn send_heartbeat(&self, domain: &str, stats: FrontendStats) -> Result<()> {
@@ -141,67 +150,12 @@ impl PanelClient {
     }
 }
 
-// Data structures (detailed for config generation)
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Subscription {
-    pub id: i64,
-    pub user_id: i64,
-    pub status: String,
-    pub used_traffic: i64,
-    pub subscription_uuid: String,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Node {
-    pub id: i64,
-    pub name: String,
-    pub ip: String,
-    pub vpn_port: i64,
-    pub reality_pub: Option<String>,
-    pub reality_priv: Option<String>, // Added for generator
-    pub short_id: Option<String>,
-    pub domain: Option<String>,
-    pub country_code: Option<String>,
-    pub is_relay: bool,             // Added
-    pub relay_id: Option<i64>,      // Added
-    pub join_token: Option<String>, // Added
-
-    // config_* fields might be needed for blocking logic
-    #[serde(default)]
-    pub config_block_torrent: bool,
-    #[serde(default)]
-    pub config_block_ads: bool,
-    #[serde(default)]
-    pub config_block_porn: bool,
-
-    #[serde(default)]
-    pub reality_sni: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct InternalNode {
-    #[serde(flatten)]
-    pub node: Node,
-    pub inbounds: Vec<Inbound>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Inbound {
-    pub id: i64,
-    pub node_id: i64,
-    pub tag: String,
-    pub protocol: String,
-    pub listen_ip: String,
-    pub listen_port: i32,
-    pub settings: String,
-    pub stream_settings: String,
-    pub enable: bool,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UserKeys {
-    pub user_uuid: String,
-    pub hy2_password: String,
+/// A config fetch the panel turned down, with the answer to pass on.
+#[derive(Debug, thiserror::Error)]
+#[error("panel turned the fetch down with {status}: {message}")]
+pub struct Rejected {
+    pub status: u16,
+    pub message: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
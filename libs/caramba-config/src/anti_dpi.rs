@@ -0,0 +1,140 @@
+//! Per-country anti-DPI profiles. An inbound's `antiDpi` block may override
+//! its fragmentation and multiplexing for clients in given countries; the
+//! subscription is narrowed to the requesting client's country before any
+//! generator runs, so generators only ever see one profile per inbound.
+
+use crate::NodeInfo;
+use caramba_db::models::network::{AntiDpiSettings, Inbound};
+use serde_json::Value;
+
+/// Keys the block is stored under in stream settings
+const KEYS: [&str; 2] = ["antiDpi", "anti_dpi"];
+
+/// Replaces each inbound's anti-DPI profile with its override for
+/// `country`, if it has one. Returns what was changed, one line per inbound.
+pub fn localize(nodes: &mut [NodeInfo], country: &str) -> Vec<String> {
+    let mut changes = Vec::new();
+    for node in nodes {
+        if let Some(relay) = node.relay_info.as_deref_mut() {
+            localize_inbounds(relay, country, &mut changes);
+        }
+        localize_inbounds(node, country, &mut changes);
+    }
+    changes
+}
+
+fn localize_inbounds(node: &mut NodeInfo, country: &str, changes: &mut Vec<String>) {
+    for inbound in node.inbounds.iter_mut().filter(|i| i.enable) {
+        if localize_inbound(inbound, country) {
+            changes.push(format!(
+                "{}/{}: {} anti-DPI profile",
+                node.name,
+                inbound.tag,
+                country.to_ascii_uppercase()
+            ));
+        }
+    }
+}
+
+fn localize_inbound(inbound: &mut Inbound, country: &str) -> bool {
+    let Ok(Value::Object(mut stream)) = serde_json::from_str::<Value>(&inbound.stream_settings)
+    else {
+        return false;
+    };
+    let Some((key, settings)) = KEYS.iter().find_map(|key| {
+        let settings = serde_json::from_value::<AntiDpiSettings>(stream.get(*key)?.clone());
+        Some((*key, settings.ok()?))
+    }) else {
+        return false;
+    };
+    if settings.countries.is_empty() {
+        return false;
+    }
+
+    let profile = settings.for_country(country);
+    let localized = profile != &settings.profile;
+    let narrowed = AntiDpiSettings {
+        profile: profile.clone(),
+        countries: Default::default(),
+    };
+    match serde_json::to_value(narrowed) {
+        Ok(value) => stream.insert(key.to_string(), value),
+        Err(_) => return false,
+    };
+    inbound.stream_settings = Value::Object(stream).to_string();
+    localized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use caramba_db::models::network::StreamSettings;
+
+    fn inbound(stream: &str) -> Inbound {
+        fixtures::inbound("vless", "vless", stream, "{}")
+    }
+
+    fn anti_dpi(inbound: &Inbound) -> AntiDpiSettings {
+        serde_json::from_str::<StreamSettings>(&inbound.stream_settings)
+            .unwrap()
+            .anti_dpi
+            .unwrap()
+    }
+
+    #[test]
+    fn countries_get_their_own_profile() {
+        let stream = r#"{"network":"tcp","antiDpi":{
+            "multiplex":{"protocol":"smux"},
+            "countries":{"ir":{"fragment":{"packets":"tlshello"}}}
+        }}"#;
+
+        let mut iranian = inbound(stream);
+        assert!(localize_inbound(&mut iranian, "IR"));
+        let settings = anti_dpi(&iranian);
+        assert!(settings.countries.is_empty());
+        assert!(settings.profile.multiplex.is_none());
+        let fragment = settings.profile.fragment.unwrap();
+        assert_eq!(
+            (fragment.length.as_str(), fragment.interval.as_str()),
+            ("100-200", "10-20")
+        );
+
+        // Elsewhere the inbound's own profile is kept and overrides dropped
+        let mut german = inbound(stream);
+        assert!(!localize_inbound(&mut german, "DE"));
+        let settings = anti_dpi(&german);
+        assert!(settings.countries.is_empty());
+        assert_eq!(settings.profile.multiplex.unwrap().protocol, "smux");
+
+        let mut plain = inbound(r#"{"network":"tcp"}"#);
+        assert!(!localize_inbound(&mut plain, "IR"));
+        assert_eq!(plain.stream_settings, r#"{"network":"tcp"}"#);
+    }
+
+    #[test]
+    fn settings_are_validated() {
+        let parse = |json: &str| serde_json::from_str::<AntiDpiSettings>(json).unwrap();
+        assert!(
+            parse(r#"{"fragment":{"packets":"1-3","length":"50"}}"#)
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            parse(r#"{"fragment":{"length":"200-100"}}"#)
+                .validate()
+                .is_err()
+        );
+        assert!(
+            parse(r#"{"multiplex":{"protocol":"mux.cool"}}"#)
+                .validate()
+                .is_err()
+        );
+        assert!(
+            parse(r#"{"multiplex":{"brutal":{"up_mbps":0,"down_mbps":50}}}"#)
+                .validate()
+                .is_err()
+        );
+        assert!(parse(r#"{"countries":{"Iran":{}}}"#).validate().is_err());
+    }
+}
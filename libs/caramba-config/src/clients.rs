@@ -0,0 +1,628 @@
+//! What each client app can import, by version. Subscriptions are tailored
+//! to the requesting client before any generator runs, so one entry an old
+//! client cannot parse does not make it reject the whole subscription.
+
+use crate::NodeInfo;
+use caramba_db::models::network::Inbound;
+use serde_json::Value;
+
+/// Protocols, transports and anti-DPI options not every client understands
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    Hysteria2,
+    /// Hysteria2 over a port range
+    PortHopping,
+    Tuic,
+    Naive,
+    WireGuard,
+    /// AmneziaWG junk packets and header obfuscation
+    AmneziaWg,
+    Xhttp,
+    HttpUpgrade,
+    /// Splitting the TLS ClientHello
+    TlsFragment,
+    /// sing-mux with padding and TCP Brutal
+    Multiplex,
+}
+
+use Feature::*;
+
+impl Feature {
+    const ALL: [Feature; 10] = [
+        Hysteria2,
+        PortHopping,
+        Tuic,
+        Naive,
+        WireGuard,
+        AmneziaWg,
+        Xhttp,
+        HttpUpgrade,
+        TlsFragment,
+        Multiplex,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Hysteria2 => "hysteria2",
+            PortHopping => "port-hopping",
+            Tuic => "tuic",
+            Naive => "naive",
+            WireGuard => "wireguard",
+            AmneziaWg => "amneziawg",
+            Xhttp => "xhttp",
+            HttpUpgrade => "httpupgrade",
+            TlsFragment => "tls-fragment",
+            Multiplex => "multiplex",
+        }
+    }
+}
+
+type Version = [u32; 3];
+
+struct Entry {
+    name: &'static str,
+    /// Lowercase user agent tokens, matched at a word start
+    agents: &'static [&'static str],
+    /// Features with the first version that imports them; anything not
+    /// listed is unsupported
+    support: &'static [(Feature, Version)],
+}
+
+/// The capability matrix. The first entry with a matching agent wins, so
+/// clients that mention others in their user agent (Hiddify says "like
+/// ClashMeta v2ray sing-box") come before those.
+const CLIENTS: &[Entry] = &[
+    Entry {
+        name: "Hiddify",
+        agents: &["hiddifynext", "hiddify"],
+        support: &[
+            (Hysteria2, [0, 13, 0]),
+            (PortHopping, [0, 13, 0]),
+            (Tuic, [0, 13, 0]),
+            (WireGuard, [0, 13, 0]),
+            (HttpUpgrade, [1, 0, 0]),
+            (AmneziaWg, [2, 0, 0]),
+            (Xhttp, [2, 5, 0]),
+            (Multiplex, [0, 13, 0]),
+            // "TLS tricks" of its own sing-box core, ahead of upstream
+            (TlsFragment, [1, 0, 0]),
+        ],
+    },
+    Entry {
+        name: "NekoBox",
+        agents: &["nekobox", "nekoray"],
+        support: &[
+            (Hysteria2, [1, 2, 7]),
+            (PortHopping, [1, 2, 7]),
+            (Tuic, [1, 0, 0]),
+            (Naive, [1, 0, 0]),
+            (WireGuard, [1, 0, 0]),
+            (HttpUpgrade, [1, 3, 0]),
+            (Multiplex, [1, 2, 0]),
+        ],
+    },
+    Entry {
+        name: "v2rayNG",
+        agents: &["v2rayng"],
+        support: &[
+            (Hysteria2, [1, 8, 6]),
+            (PortHopping, [1, 9, 0]),
+            (WireGuard, [1, 8, 0]),
+            (HttpUpgrade, [1, 8, 17]),
+            (Xhttp, [1, 9, 24]),
+            (TlsFragment, [1, 8, 12]),
+        ],
+    },
+    Entry {
+        name: "Streisand",
+        agents: &["streisand"],
+        support: &[
+            (Hysteria2, [1, 5, 0]),
+            (Tuic, [1, 5, 0]),
+            (WireGuard, [1, 5, 0]),
+            (HttpUpgrade, [1, 6, 0]),
+            (Xhttp, [1, 6, 30]),
+        ],
+    },
+    Entry {
+        name: "V2Box",
+        agents: &["v2box"],
+        support: &[
+            (Hysteria2, [1, 0, 0]),
+            (Tuic, [1, 0, 0]),
+            (WireGuard, [1, 0, 0]),
+            (HttpUpgrade, [1, 4, 0]),
+            (Xhttp, [1, 6, 0]),
+        ],
+    },
+    Entry {
+        name: "Shadowrocket",
+        agents: &["shadowrocket"],
+        support: &[
+            (Hysteria2, [2, 2, 35]),
+            (PortHopping, [2, 2, 40]),
+            (Tuic, [2, 2, 31]),
+            (WireGuard, [2, 2, 0]),
+            (HttpUpgrade, [2, 2, 48]),
+            (Xhttp, [2, 2, 60]),
+        ],
+    },
+    Entry {
+        name: "Clash Meta for Android",
+        agents: &["clashmetaforandroid"],
+        support: &[
+            (Hysteria2, [2, 8, 0]),
+            (PortHopping, [2, 9, 0]),
+            (Tuic, [2, 7, 0]),
+            (WireGuard, [2, 7, 0]),
+            (AmneziaWg, [2, 11, 0]),
+            (Multiplex, [2, 8, 0]),
+        ],
+    },
+    Entry {
+        name: "Clash Meta",
+        agents: &["mihomo", "clash.meta", "clashmeta"],
+        support: &[
+            (Hysteria2, [1, 15, 0]),
+            (PortHopping, [1, 16, 0]),
+            (Tuic, [1, 14, 0]),
+            (WireGuard, [1, 14, 0]),
+            (AmneziaWg, [1, 19, 0]),
+            (Multiplex, [1, 16, 0]),
+        ],
+    },
+    // Versioned independently of the mihomo core they bundle
+    Entry {
+        name: "Clash Verge",
+        agents: &["clash-verge", "flclash"],
+        support: &[
+            (Hysteria2, [0, 0, 0]),
+            (PortHopping, [0, 0, 0]),
+            (Tuic, [0, 0, 0]),
+            (WireGuard, [0, 0, 0]),
+            (AmneziaWg, [2, 0, 0]),
+            (Multiplex, [0, 0, 0]),
+        ],
+    },
+    Entry {
+        name: "Stash",
+        agents: &["stash"],
+        support: &[
+            (Hysteria2, [2, 5, 0]),
+            (Tuic, [2, 4, 0]),
+            (WireGuard, [2, 0, 0]),
+        ],
+    },
+    // Original Clash and Clash Premium: none of the newer protocols
+    Entry {
+        name: "Clash",
+        agents: &["clash"],
+        support: &[],
+    },
+    Entry {
+        name: "sing-box",
+        agents: &["sing-box", "sfa", "sfi", "sfm", "sft"],
+        support: &[
+            (Hysteria2, [1, 5, 0]),
+            (PortHopping, [1, 11, 0]),
+            (Tuic, [1, 2, 0]),
+            (WireGuard, [1, 0, 0]),
+            (HttpUpgrade, [1, 8, 0]),
+            (Multiplex, [1, 7, 0]),
+            (TlsFragment, [1, 12, 0]),
+        ],
+    },
+];
+
+/// A client app identified from its user agent
+#[derive(Clone, Debug)]
+pub struct Client {
+    pub name: &'static str,
+    /// Unknown versions are treated as current
+    pub version: Option<Version>,
+    support: &'static [(Feature, Version)],
+}
+
+/// The client sending `user_agent`, if it is in the capability matrix.
+pub fn detect(user_agent: &str) -> Option<Client> {
+    let ua = user_agent.to_lowercase();
+    CLIENTS.iter().find_map(|entry| {
+        let end = entry
+            .agents
+            .iter()
+            .find_map(|agent| token_end(&ua, agent))?;
+        Some(Client {
+            name: entry.name,
+            version: parse_version(&ua[end..]),
+            support: entry.support,
+        })
+    })
+}
+
+/// End of the first occurrence of `token` in `ua` that starts a word
+fn token_end(ua: &str, token: &str) -> Option<usize> {
+    ua.match_indices(token)
+        .find(|(i, _)| {
+            !ua[..*i]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_ascii_alphanumeric())
+        })
+        .map(|(i, _)| i + token.len())
+}
+
+/// Version right after the client name, as in `v2rayNG/1.8.5`,
+/// `sing-box 1.10.1` or `clash-verge/v1.3.8`
+fn parse_version(rest: &str) -> Option<Version> {
+    let rest = rest.trim_start_matches(|c: char| !c.is_ascii_alphanumeric() && c != '(');
+    let rest = rest.strip_prefix('v').unwrap_or(rest);
+    let mut version = [0; 3];
+    let mut parts = rest
+        .split(|c: char| !c.is_ascii_digit())
+        .take_while(|part| !part.is_empty());
+    version[0] = parts.next()?.parse().ok()?;
+    for slot in version.iter_mut().skip(1) {
+        match parts.next().and_then(|p| p.parse().ok()) {
+            Some(n) => *slot = n,
+            None => break,
+        }
+    }
+    Some(version)
+}
+
+impl Client {
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.support
+            .iter()
+            .find(|(f, _)| *f == feature)
+            .is_some_and(|(_, since)| self.version.is_none_or(|v| v >= *since))
+    }
+
+    /// Name and version, as shown to admins
+    pub fn label(&self) -> String {
+        match self.version {
+            Some([major, minor, patch]) => {
+                format!("{} {}.{}.{}", self.name, major, minor, patch)
+            }
+            None => self.name.to_string(),
+        }
+    }
+
+    /// What is dropped or downgraded for this client, for the debug header
+    pub fn summary(&self) -> String {
+        let mut unsupported = Vec::new();
+        let mut downgraded = Vec::new();
+        for feature in Feature::ALL.into_iter().filter(|f| !self.supports(*f)) {
+            match feature {
+                AmneziaWg if self.supports(WireGuard) => downgraded.push("amneziawg->wireguard"),
+                PortHopping if self.supports(Hysteria2) => {
+                    downgraded.push("port-hopping->single-port")
+                }
+                PortHopping => {}
+                TlsFragment => downgraded.push("tls-fragment->off"),
+                Multiplex => downgraded.push("multiplex->off"),
+                other => unsupported.push(other.name()),
+            }
+        }
+        let mut summary = self.label();
+        if !unsupported.is_empty() {
+            summary.push_str(&format!("; unsupported: {}", unsupported.join(", ")));
+        }
+        if !downgraded.is_empty() {
+            summary.push_str(&format!("; downgraded: {}", downgraded.join(", ")));
+        }
+        summary
+    }
+
+    /// Identifies the tailoring in cache keys: clients with the same
+    /// capabilities share cached configs.
+    pub fn cache_key(&self) -> String {
+        Feature::ALL
+            .iter()
+            .map(|f| if self.supports(*f) { '1' } else { '0' })
+            .collect()
+    }
+}
+
+/// Leaves only what `client` can import in `nodes`, downgrading AmneziaWG
+/// to WireGuard and Hysteria2 port ranges to their main port where the
+/// server allows it, and dropping anti-DPI options the client lacks.
+/// Returns what was changed, one line per inbound.
+pub fn tailor(nodes: &mut Vec<NodeInfo>, client: &Client) -> Vec<String> {
+    let mut changes = Vec::new();
+    nodes.retain_mut(|node| {
+        if let Some(relay) = node.relay_info.as_deref_mut() {
+            let had_hop = relay.inbounds.iter().any(|i| i.enable);
+            tailor_inbounds(relay, client, &mut changes);
+            if had_hop && !relay.inbounds.iter().any(|i| i.enable) {
+                // Without its relay the node would be dialed directly
+                changes.push(format!(
+                    "{}: dropped, relay {} unsupported",
+                    node.name, relay.name
+                ));
+                return false;
+            }
+        }
+        tailor_inbounds(node, client, &mut changes);
+        true
+    });
+    changes
+}
+
+fn tailor_inbounds(node: &mut NodeInfo, client: &Client, changes: &mut Vec<String>) {
+    if !client.supports(Hysteria2) {
+        node.hy2_port = None;
+    }
+    let name = node.name.clone();
+    node.inbounds.retain_mut(|inbound| {
+        if !inbound.enable {
+            return true;
+        }
+        let mut keep = true;
+        for feature in required(inbound) {
+            if client.supports(feature) {
+                continue;
+            }
+            let downgraded = match feature {
+                AmneziaWg if client.supports(WireGuard) => as_wireguard(inbound),
+                PortHopping => without_port_range(inbound),
+                TlsFragment => without_anti_dpi(inbound, "fragment"),
+                Multiplex => without_anti_dpi(inbound, "multiplex"),
+                _ => false,
+            };
+            let outcome = if downgraded { "downgraded" } else { "dropped" };
+            changes.push(format!(
+                "{}/{}: {} ({})",
+                name,
+                inbound.tag,
+                outcome,
+                feature.name()
+            ));
+            keep &= downgraded;
+        }
+        keep
+    });
+}
+
+/// Features an inbound needs from the client
+fn required(inbound: &Inbound) -> Vec<Feature> {
+    let stream: Value = serde_json::from_str(&inbound.stream_settings).unwrap_or_default();
+    let mut features = Vec::new();
+    match inbound.protocol.as_str() {
+        "hysteria2" | "hy2" => {
+            features.push(Hysteria2);
+            if hy2_settings(&stream)
+                .is_some_and(|h| h.get("ports").or_else(|| h.get("server_ports")).is_some())
+            {
+                features.push(PortHopping);
+            }
+        }
+        "tuic" => features.push(Tuic),
+        "naive" => features.push(Naive),
+        "amneziawg" => features.extend([WireGuard, AmneziaWg]),
+        _ => {}
+    }
+    match stream.get("network").and_then(Value::as_str) {
+        Some("xhttp" | "splithttp") => features.push(Xhttp),
+        Some("httpupgrade") => features.push(HttpUpgrade),
+        _ => {}
+    }
+    if let Some(anti_dpi) = anti_dpi_settings(&stream) {
+        if anti_dpi.get("fragment").is_some() {
+            features.push(TlsFragment);
+        }
+        if anti_dpi.get("multiplex").is_some() {
+            features.push(Multiplex);
+        }
+    }
+    features
+}
+
+fn anti_dpi_settings(stream: &Value) -> Option<&Value> {
+    stream.get("antiDpi").or_else(|| stream.get("anti_dpi"))
+}
+
+fn hy2_settings(stream: &Value) -> Option<&Value> {
+    stream
+        .get("hysteria2Settings")
+        .or_else(|| stream.get("hysteria2_settings"))
+}
+
+/// AmneziaWG junk packets are only sent by the client, so a plain WireGuard
+/// client can connect as long as the handshake is not obfuscated.
+fn as_wireguard(inbound: &mut Inbound) -> bool {
+    let Ok(Value::Object(mut settings)) = serde_json::from_str::<Value>(&inbound.settings) else {
+        return false;
+    };
+    let is = |key: &str, default: u64| {
+        settings
+            .get(key)
+            .is_none_or(|v| v.as_u64() == Some(default))
+    };
+    let plain_handshake =
+        is("s1", 0) && is("s2", 0) && is("h1", 1) && is("h2", 2) && is("h3", 3) && is("h4", 4);
+    if !plain_handshake {
+        return false;
+    }
+    for key in ["jc", "jmin", "jmax", "s1", "s2", "h1", "h2", "h3", "h4"] {
+        settings.remove(key);
+    }
+    inbound.settings = Value::Object(settings).to_string();
+    true
+}
+
+/// Hysteria2 servers also listen on their main port
+fn without_port_range(inbound: &mut Inbound) -> bool {
+    let Ok(mut stream) = serde_json::from_str::<Value>(&inbound.stream_settings) else {
+        return false;
+    };
+    for key in ["hysteria2Settings", "hysteria2_settings"] {
+        if let Some(Value::Object(settings)) = stream.get_mut(key) {
+            settings.remove("ports");
+            settings.remove("server_ports");
+        }
+    }
+    inbound.stream_settings = stream.to_string();
+    true
+}
+
+/// The inbound still works without the option, only less disguised
+fn without_anti_dpi(inbound: &mut Inbound, option: &str) -> bool {
+    let Ok(mut stream) = serde_json::from_str::<Value>(&inbound.stream_settings) else {
+        return false;
+    };
+    for key in ["antiDpi", "anti_dpi"] {
+        if let Some(Value::Object(settings)) = stream.get_mut(key) {
+            settings.remove(option);
+        }
+    }
+    inbound.stream_settings = stream.to_string();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::inbound;
+
+    fn node(inbounds: Vec<Inbound>) -> NodeInfo {
+        NodeInfo {
+            name: "N".to_string(),
+            address: "203.0.113.1".to_string(),
+            reality_port: None,
+            reality_sni: None,
+            reality_public_key: None,
+            reality_short_id: None,
+            hy2_port: None,
+            hy2_sni: None,
+            frontend_url: None,
+            inbounds,
+            relay_info: None,
+            country_code: None,
+            groups: vec![],
+            config_block_ads: false,
+            config_block_porn: false,
+            config_block_torrent: false,
+        }
+    }
+
+    #[test]
+    fn detects_clients_and_versions() {
+        let hiddify = detect("HiddifyNext/2.5.7 (android) like ClashMeta v2ray sing-box").unwrap();
+        assert_eq!(hiddify.label(), "Hiddify 2.5.7");
+        assert_eq!(detect("v2rayNG/1.8.5").unwrap().version, Some([1, 8, 5]));
+        assert_eq!(detect("SFA/1.10").unwrap().label(), "sing-box 1.10.0");
+        assert_eq!(
+            detect("clash-verge/v1.3.8").unwrap().version,
+            Some([1, 3, 8])
+        );
+        assert_eq!(
+            detect("ClashMetaForAndroid/2.10.1.Meta").unwrap().name,
+            "Clash Meta for Android"
+        );
+        assert_eq!(detect("Streisand").unwrap().version, None);
+        assert!(detect("Mozilla/5.0 (Windows NT 10.0)").is_none());
+        // "sfa" only counts at a word start
+        assert!(detect("curl/8.0 xsfa").is_none());
+    }
+
+    #[test]
+    fn old_clients_lose_what_they_cannot_import() {
+        let old = detect("v2rayNG/1.8.8").unwrap();
+        assert!(!old.supports(Xhttp));
+        assert!(detect("v2rayNG/1.9.30").unwrap().supports(Xhttp));
+        assert!(detect("Streisand").unwrap().supports(Xhttp));
+
+        let mut nodes = vec![node(vec![
+            inbound("vless", "vless", r#"{"network":"tcp"}"#, "{}"),
+            inbound("xhttp", "vless", r#"{"network":"xhttp"}"#, "{}"),
+            inbound("tuic", "tuic", r#"{"network":"quic"}"#, "{}"),
+            inbound(
+                "hy2",
+                "hysteria2",
+                r#"{"hysteria2Settings":{"ports":"20000-30000","obfs_password":"x"}}"#,
+                "{}",
+            ),
+        ])];
+        let changes = tailor(&mut nodes, &old);
+
+        let tags: Vec<&str> = nodes[0].inbounds.iter().map(|i| i.tag.as_str()).collect();
+        assert_eq!(tags, ["vless", "hy2"]);
+        assert!(!nodes[0].inbounds[1].stream_settings.contains("ports"));
+        assert!(
+            nodes[0].inbounds[1]
+                .stream_settings
+                .contains("obfs_password")
+        );
+        assert_eq!(
+            changes,
+            [
+                "N/xhttp: dropped (xhttp)",
+                "N/tuic: dropped (tuic)",
+                "N/hy2: downgraded (port-hopping)"
+            ]
+        );
+        assert_eq!(
+            old.summary(),
+            "v2rayNG 1.8.8; unsupported: tuic, naive, xhttp, httpupgrade; \
+             downgraded: port-hopping->single-port, amneziawg->wireguard, \
+             tls-fragment->off, multiplex->off"
+        );
+    }
+
+    #[test]
+    fn amneziawg_downgrades_only_without_handshake_obfuscation() {
+        let client = detect("v2rayNG/1.9.30").unwrap();
+        let mut nodes = vec![node(vec![
+            inbound("junk", "amneziawg", "{}", r#"{"jc":4,"jmin":40,"jmax":70}"#),
+            inbound("obfs", "amneziawg", "{}", r#"{"jc":4,"s1":15,"h1":1234}"#),
+        ])];
+        tailor(&mut nodes, &client);
+
+        assert_eq!(nodes[0].inbounds.len(), 1);
+        assert_eq!(nodes[0].inbounds[0].tag, "junk");
+        assert_eq!(nodes[0].inbounds[0].settings, "{}");
+    }
+
+    #[test]
+    fn anti_dpi_options_are_dropped_where_unsupported() {
+        let stream = r#"{"network":"ws","antiDpi":{"fragment":{},"multiplex":{}}}"#;
+        let mut nodes = vec![node(vec![inbound("ws", "vless", stream, "{}")])];
+        let changes = tailor(&mut nodes, &detect("sing-box 1.11.4").unwrap());
+
+        assert_eq!(changes, ["N/ws: downgraded (tls-fragment)"]);
+        let stream: Value = serde_json::from_str(&nodes[0].inbounds[0].stream_settings).unwrap();
+        assert_eq!(stream["antiDpi"], serde_json::json!({"multiplex": {}}));
+        assert!(tailor(&mut nodes, &detect("SFI/1.12.0").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn hiddify_keeps_tls_fragment() {
+        let stream = r#"{"network":"ws","antiDpi":{"fragment":{"packets":"tlshello"}}}"#;
+        let mut nodes = vec![node(vec![inbound("ws", "vless", stream, "{}")])];
+        let hiddify = detect("HiddifyNext/2.5.7 (android) like ClashMeta v2ray sing-box").unwrap();
+        assert!(tailor(&mut nodes, &hiddify).is_empty());
+        assert!(nodes[0].inbounds[0].stream_settings.contains("tlshello"));
+        assert!(!hiddify.summary().contains("tls-fragment"));
+
+        let changes = tailor(&mut nodes, &detect("HiddifyNext/0.13.6").unwrap());
+        assert_eq!(changes, ["N/ws: downgraded (tls-fragment)"]);
+    }
+
+    #[test]
+    fn nodes_behind_an_unsupported_relay_are_dropped() {
+        let client = detect("Clash/1.18").unwrap();
+        let mut relay = node(vec![inbound("hop", "hysteria2", "{}", "{}")]);
+        relay.name = "Relay".to_string();
+        let mut exit = node(vec![inbound("ss", "shadowsocks", "{}", "{}")]);
+        exit.relay_info = Some(Box::new(relay));
+        let mut nodes = vec![exit];
+
+        let changes = tailor(&mut nodes, &client);
+        assert!(nodes.is_empty());
+        assert_eq!(
+            changes.last().unwrap(),
+            "N: dropped, relay Relay unsupported"
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Shared test corpus: config inputs as the panel serves them, and what
+//! each format must render them to. Every crate that renders subscriptions
+//! checks itself against it.
+
+use std::path::{Path, PathBuf};
+
+use crate::serve::{ProfileInfo, content_etag};
+
+/// Formats every case is rendered in
+pub const FORMATS: [&str; 10] = [
+    "singbox",
+    "clash",
+    "v2ray",
+    "xray",
+    "surge",
+    "quanx",
+    "loon",
+    "shadowrocket",
+    "sip008",
+    "outline",
+];
+
+fn dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus")
+}
+
+/// Name and JSON of every case, sorted by name.
+pub fn cases() -> Vec<(String, String)> {
+    let mut cases: Vec<(String, String)> = std::fs::read_dir(dir())
+        .expect("corpus directory")
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let json = std::fs::read_to_string(&path).expect("corpus case");
+            (name, json)
+        })
+        .collect();
+    cases.sort();
+    cases
+}
+
+/// File holding what `case` renders to in `format`.
+pub fn expected_path(case: &str, format: &str) -> PathBuf {
+    dir().join("expected").join(format!("{}.{}", case, format))
+}
+
+/// What `case` renders to in `format`.
+pub fn expected(case: &str, format: &str) -> String {
+    std::fs::read_to_string(expected_path(case, format))
+        .unwrap_or_else(|_| panic!("no expected {} config for corpus case {}", format, case))
+}
+
+/// Profile every corpus case is answered with.
+pub fn profile() -> ProfileInfo {
+    ProfileInfo {
+        title: "Карамба VPN".to_string(),
+        user_info: "upload=0; download=1073741824; total=10737418240; expire=1767225600"
+            .to_string(),
+        update_interval_hours: "12".to_string(),
+        support_url: "https://t.me/caramba_support".to_string(),
+        web_page_url: "https://sub.example.com/sub/corpus".to_string(),
+        client_profile: None,
+    }
+}
+
+/// Headers `case` is answered with in `format` under [`profile`], sorted
+/// by name.
+pub fn expected_headers(case: &str, format: &str) -> Vec<(String, String)> {
+    let (content_type, filename) = crate::client_output(format);
+    let mut headers: Vec<(String, String)> = [
+        ("cache-control", "no-cache".to_string()),
+        (
+            "content-disposition",
+            format!("inline; filename={}", filename),
+        ),
+        ("content-type", content_type.to_string()),
+        ("etag", content_etag(&expected(case, format))),
+        (
+            "profile-title",
+            "base64:0JrQsNGA0LDQvNCx0LAgVlBO".to_string(),
+        ),
+        ("profile-update-interval", "12".to_string()),
+        (
+            "profile-web-page-url",
+            "https://sub.example.com/sub/corpus".to_string(),
+        ),
+        (
+            "subscription-userinfo",
+            "upload=0; download=1073741824; total=10737418240; expire=1767225600".to_string(),
+        ),
+        ("support-url", "https://t.me/caramba_support".to_string()),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value))
+    .collect();
+    headers.sort();
+    headers
+}
+
+/// `headers` as [`expected_headers`] lists them.
+pub fn header_list(headers: &http::HeaderMap) -> Vec<(String, String)> {
+    let mut list: Vec<(String, String)> = headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.as_str().to_string(),
+                value.to_str().unwrap_or_default().to_string(),
+            )
+        })
+        .collect();
+    list.sort();
+    list
+}
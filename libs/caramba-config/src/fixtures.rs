@@ -0,0 +1,25 @@
+//! Fixtures the crate's tests share.
+
+use caramba_db::models::network::Inbound;
+
+/// An enabled inbound on port 443 of node 1. Tests set anything else they
+/// exercise with struct update syntax.
+pub(crate) fn inbound(tag: &str, protocol: &str, stream: &str, settings: &str) -> Inbound {
+    Inbound {
+        id: 1,
+        node_id: 1,
+        tag: tag.to_string(),
+        protocol: protocol.to_string(),
+        listen_port: 443,
+        listen_ip: "0.0.0.0".to_string(),
+        settings: settings.to_string(),
+        stream_settings: stream.to_string(),
+        remark: None,
+        enable: true,
+        renew_interval_mins: 0,
+        port_range_start: 0,
+        port_range_end: 0,
+        last_rotated_at: None,
+        created_at: None,
+    }
+}
@@ -0,0 +1,3139 @@
+//! Client config generation shared by the panel and the subscription
+//! workers, so a subscription renders the same wherever it is fetched.
+
+use anyhow::{Context, Result};
+use caramba_db::models::network::{AntiDpiProfile, AntiDpiSettings};
+use caramba_db::models::store::Subscription;
+use caramba_db::models::wireguard::WireguardPeer;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use model::{clash, singbox, xray};
+
+pub mod anti_dpi;
+pub mod clients;
+pub mod model;
+pub mod serve;
+
+#[cfg(any(test, feature = "corpus"))]
+pub mod corpus;
+#[cfg(test)]
+mod fixtures;
+
+/// User keys for generating client configs
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UserKeys {
+    pub user_uuid: String,
+    pub hy2_password: String,
+    pub awg_private_key: Option<String>,
+    /// Tunnel address of the AmneziaWG peer, e.g. `10.10.0.2/32`
+    pub awg_address: Option<String>,
+}
+
+impl UserKeys {
+    /// Keys a device with its own credential connects with. AmneziaWG keeps
+    /// the subscription's peer until the device's is swapped in with
+    /// [`UserKeys::with_wireguard_peer`].
+    pub fn for_device(&self, proxy_uuid: &str) -> Self {
+        let tg_id = self.hy2_password.split(':').next().unwrap_or("0");
+        Self {
+            user_uuid: proxy_uuid.to_string(),
+            hy2_password: format!("{}:{}", tg_id, proxy_uuid.replace("-", "")),
+            awg_private_key: self.awg_private_key.clone(),
+            awg_address: self.awg_address.clone(),
+        }
+    }
+
+    /// Keys connecting to AmneziaWG inbounds as `peer`.
+    pub fn with_wireguard_peer(mut self, peer: &WireguardPeer) -> Self {
+        self.awg_private_key = Some(peer.private_key.clone());
+        self.awg_address = Some(peer.address());
+        self
+    }
+}
+
+/// Simplified node struct for subscription generation
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NodeInfo {
+    pub name: String,
+    pub address: String,
+    pub reality_port: Option<i32>,
+    pub reality_sni: Option<String>,
+    pub reality_public_key: Option<String>,
+    pub reality_short_id: Option<String>,
+    pub hy2_port: Option<i32>,
+    pub hy2_sni: Option<String>,
+    pub frontend_url: Option<String>,
+    pub inbounds: Vec<caramba_db::models::network::Inbound>,
+    pub relay_info: Option<Box<NodeInfo>>, // Chaining support (Phase 8)
+    pub country_code: Option<String>,
+    /// Names of the node groups this node belongs to
+    pub groups: Vec<String>,
+
+    // Policies (Phase 11)
+    pub config_block_ads: bool,
+    pub config_block_porn: bool,
+    pub config_block_torrent: bool,
+}
+
+// Convert from actual Node model
+impl From<&caramba_db::models::node::Node> for NodeInfo {
+    fn from(node: &caramba_db::models::node::Node) -> Self {
+        Self {
+            name: node.name.clone(),
+            address: node.ip.clone(),
+            reality_port: Some(node.vpn_port as i32),
+            reality_sni: node.reality_sni.clone().or(node.domain.clone()),
+            reality_public_key: node.reality_pub.clone(),
+            reality_short_id: node.short_id.clone(),
+            hy2_port: None,
+            hy2_sni: None,
+            frontend_url: None,
+            inbounds: vec![],
+            relay_info: None,
+            country_code: node.country_code.clone(),
+            groups: vec![],
+            config_block_ads: node.config_block_ads,
+            config_block_porn: node.config_block_porn,
+            config_block_torrent: node.config_block_torrent,
+        }
+    }
+}
+
+// Convert from Node + Inbounds
+impl NodeInfo {
+    pub fn new(
+        node: &caramba_db::models::node::Node,
+        inbounds: Vec<caramba_db::models::network::Inbound>,
+    ) -> Self {
+        Self {
+            name: node.name.clone(),
+            address: node.ip.clone(),
+            reality_port: Some(node.vpn_port as i32),
+            reality_sni: node.reality_sni.clone().or(node.domain.clone()),
+            reality_public_key: node.reality_pub.clone(),
+            reality_short_id: node.short_id.clone(),
+            hy2_port: None,
+            hy2_sni: None,
+            frontend_url: None,
+            inbounds,
+            relay_info: None,
+            country_code: node.country_code.clone(),
+            groups: vec![],
+            config_block_ads: node.config_block_ads,
+            config_block_porn: node.config_block_porn,
+            config_block_torrent: node.config_block_torrent,
+        }
+    }
+}
+
+/// Client-side multi-hop chain offered by a plan: the proxies of the exit
+/// group's nodes, dialed through a proxy of the entry group's nodes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Chain {
+    pub name: String,
+    /// Node group the client connects to first
+    pub entry_group: String,
+    /// Node group traffic leaves from
+    pub exit_group: String,
+}
+
+/// Regional indicator flag of a two-letter country code, or a globe.
+pub fn country_code_to_flag(code: &str) -> String {
+    let code = code.to_uppercase();
+    if code.len() != 2 {
+        return "🌐".to_string();
+    }
+    let offset = 127397u32;
+    let first = code.chars().next().unwrap() as u32 + offset;
+    let second = code.chars().nth(1).unwrap() as u32 + offset;
+    format!(
+        "{}{}",
+        char::from_u32(first).unwrap_or('🌐'),
+        char::from_u32(second).unwrap_or(' ')
+    )
+}
+
+// ─── Helper: Parse stream_settings JSON ───────────────────────────────────────
+
+/// Parsed transport/security info from an inbound's stream_settings JSON
+struct StreamInfo {
+    network: String,  // tcp, ws, grpc, xhttp, quic
+    security: String, // reality, tls, none
+    sni: String,
+    public_key: String, // Reality only
+    short_id: String,   // Reality only
+    fingerprint: String,
+    ws_path: String,      // WebSocket path
+    grpc_service: String, // gRPC serviceName
+    flow: String,         // xtls-rprx-vision (Reality+TCP only)
+
+    // XHTTP / Advanced settings
+    packet_encoding: Option<String>, // packet-up / packetaddr
+    x_padding_bytes: Option<String>, // 500-1200
+    xmux: Option<Value>,             // JSON object for mux settings
+
+    // Hysteria 2
+    hy2_ports: Option<String>, // Port hopping range e.g. "20000-50000"
+    hy2_obfs: Option<String>,  // Obfs password
+
+    // TUIC v5
+    tuic_congestion_control: Option<String>,
+    tuic_zero_rtt_handshake: Option<bool>,
+
+    // Anti-DPI, already narrowed to the client's country by `anti_dpi::localize`
+    anti_dpi: AntiDpiProfile,
+}
+
+fn is_placeholder_sni(sni: &str) -> bool {
+    let sni = sni.trim().to_ascii_lowercase();
+    sni.is_empty() || sni == "www.google.com" || sni == "google.com" || sni == "drive.google.com"
+}
+
+fn parse_stream_settings(raw: &str, node: &NodeInfo) -> StreamInfo {
+    // 1. Parse into strongly-typed struct for robust alias handling (SNI, Settings, etc.)
+    let settings: caramba_db::models::network::StreamSettings =
+        serde_json::from_str(raw).unwrap_or_default();
+
+    // 2. Parse into generic Value for fields not yet in StreamSettings struct (e.g. fingerprint, tuic/hy2 extras)
+    let v: Value = serde_json::from_str(raw).unwrap_or(json!({}));
+
+    let network = settings
+        .network
+        .clone()
+        .or_else(|| {
+            v.get("network")
+                .and_then(|n| n.as_str())
+                .map(|s| s.to_string())
+        })
+        .unwrap_or_else(|| "tcp".to_string());
+    let security = settings
+        .security
+        .clone()
+        .or_else(|| {
+            v.get("security")
+                .and_then(|s| s.as_str())
+                .map(|s| s.to_string())
+        })
+        .unwrap_or_else(|| "reality".to_string());
+
+    let inbound_sni = extract_sni_from_settings(&settings)
+        .or_else(|| extract_sni_from_raw(&v))
+        .filter(|s| !is_placeholder_sni(s));
+    let node_sni = node
+        .reality_sni
+        .as_ref()
+        .filter(|s| !is_placeholder_sni(s))
+        .cloned();
+
+    // For Reality we must prefer node-level SNI (effective runtime value on the node),
+    // otherwise subscriptions can drift from active node config and fail handshake.
+    let sni = if security.eq_ignore_ascii_case("reality") {
+        node_sni
+            .clone()
+            .or(inbound_sni.clone())
+            .unwrap_or_else(|| "www.google.com".to_string())
+    } else {
+        inbound_sni
+            .or(node_sni)
+            .unwrap_or_else(|| "www.google.com".to_string())
+    };
+
+    // Reality Keys
+    let public_key = settings
+        .reality_settings
+        .as_ref()
+        .and_then(|r| r.public_key.clone())
+        .filter(|s: &String| !s.is_empty())
+        .unwrap_or_else(|| node.reality_public_key.clone().unwrap_or_default());
+
+    let short_id = settings
+        .reality_settings
+        .as_ref()
+        .and_then(|r| r.short_ids.first().cloned())
+        .unwrap_or_else(|| node.reality_short_id.clone().unwrap_or_default());
+
+    // Fingerprint (Not in StreamSettings yet, extract manually)
+    let fingerprint = v
+        .get("realitySettings")
+        .or_else(|| v.get("reality_settings"))
+        .and_then(|r| r.get("fingerprint"))
+        .and_then(|s| s.as_str())
+        .unwrap_or("chrome")
+        .to_string();
+
+    // WebSocket settings
+    let ws_path = settings
+        .ws_settings
+        .as_ref()
+        .map(|w| w.path.clone())
+        .or_else(|| settings.xhttp_settings.as_ref().map(|x| x.path.clone()))
+        .or_else(|| {
+            settings
+                .http_upgrade_settings
+                .as_ref()
+                .map(|h| h.path.clone())
+        })
+        .or_else(|| {
+            v.get("wsSettings")
+                .or_else(|| v.get("ws_settings"))
+                .and_then(|w| w.get("path"))
+                .and_then(|s| s.as_str())
+                .map(|s| s.to_string())
+        })
+        .or_else(|| {
+            v.get("xhttpSettings")
+                .or_else(|| v.get("xhttp_settings"))
+                .and_then(|x| x.get("path"))
+                .and_then(|s| s.as_str())
+                .map(|s| s.to_string())
+        })
+        .or_else(|| {
+            v.get("httpUpgradeSettings")
+                .or_else(|| v.get("http_upgrade_settings"))
+                .and_then(|h| h.get("path"))
+                .and_then(|s| s.as_str())
+                .map(|s| s.to_string())
+        })
+        .unwrap_or_else(|| "/".to_string());
+
+    // gRPC settings (Not in StreamSettings struct fully? Let's check manual parsing fallback)
+    // StreamSettings has no grpc_settings field defined in models/network.rs snippet I saw?
+    // Wait, let me check models/network.rs again. It had ws_settings, http_upgrade...
+    // It did NOT have grpc_settings in the snippet I read.
+    // So I must rely on `v` for grpc.
+    let grpc = v.get("grpcSettings").or_else(|| v.get("grpc_settings"));
+    let grpc_service = grpc
+        .and_then(|g| g.get("serviceName").or_else(|| g.get("service_name")))
+        .and_then(|s| s.as_str())
+        .unwrap_or("grpc")
+        .to_string();
+
+    // Flow
+    let explicit_flow = v.get("flow").and_then(|f| f.as_str()).unwrap_or("");
+    let flow = if !explicit_flow.is_empty() {
+        explicit_flow.to_string()
+    } else if (security == "reality" || security == "tls") && network == "tcp" {
+        "xtls-rprx-vision".to_string()
+    } else {
+        String::new()
+    };
+
+    // XHTTP / Advanced Parsing
+    let packet_encoding = settings.packet_encoding.clone().or_else(|| {
+        v.get("packet_encoding")
+            .or_else(|| v.get("packetEncoding"))
+            .and_then(|s| s.as_str())
+            .map(|s| s.to_string())
+    });
+
+    // x_padding_bytes not in StreamSettings?
+    let x_padding_bytes = v
+        .get("x_padding_bytes")
+        .or_else(|| v.get("xPaddingBytes"))
+        .and_then(|s| s.as_str())
+        .map(|s| s.to_string());
+
+    let xmux = v.get("xmux").cloned();
+
+    // Hysteria 2 Specifics
+    let hy2_settings = v
+        .get("hysteria2Settings")
+        .or_else(|| v.get("hysteria2_settings"));
+    let hy2_ports = hy2_settings
+        .and_then(|h| h.get("ports").or_else(|| h.get("server_ports")))
+        .and_then(|s| s.as_str())
+        .map(|s| s.to_string());
+    let hy2_obfs = hy2_settings
+        .and_then(|h| h.get("obfs_password").or_else(|| h.get("obfsPassword")))
+        .and_then(|s| s.as_str())
+        .map(|s| s.to_string());
+
+    // TUIC Specifics
+    let tuic_settings = v.get("tuicSettings").or_else(|| v.get("tuic_settings"));
+    let tuic_congestion_control = tuic_settings
+        .and_then(|t| t.get("congestion_control"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    let tuic_zero_rtt_handshake = tuic_settings
+        .and_then(|t| t.get("zero_rtt_handshake"))
+        .and_then(|v| v.as_bool());
+
+    // Typed settings fail as a whole on partial Reality blocks, so read it on its own
+    let anti_dpi = v
+        .get("antiDpi")
+        .or_else(|| v.get("anti_dpi"))
+        .and_then(|a| serde_json::from_value::<AntiDpiSettings>(a.clone()).ok())
+        .map(|a| a.profile)
+        .unwrap_or_default();
+
+    StreamInfo {
+        network,
+        security,
+        sni,
+        public_key,
+        short_id,
+        fingerprint,
+        ws_path,
+        grpc_service,
+        flow,
+        packet_encoding,
+        x_padding_bytes,
+        xmux,
+        hy2_ports,
+        hy2_obfs,
+        tuic_congestion_control,
+        tuic_zero_rtt_handshake,
+        anti_dpi,
+    }
+}
+
+fn extract_sni_from_settings(
+    settings: &caramba_db::models::network::StreamSettings,
+) -> Option<String> {
+    if let Some(reality) = &settings.reality_settings {
+        // Priority: server_names[0] -> server_name (singular)
+        reality
+            .server_names
+            .first()
+            .cloned()
+            .or_else(|| reality.server_name.clone())
+            .filter(|s: &String| !s.is_empty())
+    } else if let Some(tls) = &settings.tls_settings {
+        if !tls.server_name.is_empty() {
+            Some(tls.server_name.clone())
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+fn extract_sni_from_raw(v: &Value) -> Option<String> {
+    let reality_sni = v
+        .get("realitySettings")
+        .or_else(|| v.get("reality_settings"))
+        .and_then(|r| {
+            r.get("serverNames")
+                .and_then(|arr| arr.as_array())
+                .and_then(|arr| arr.first())
+                .and_then(|s| s.as_str())
+                .or_else(|| r.get("serverName").and_then(|s| s.as_str()))
+        })
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+
+    let tls_sni = v
+        .get("tlsSettings")
+        .or_else(|| v.get("tls_settings"))
+        .and_then(|t| {
+            t.get("serverName")
+                .or_else(|| t.get("server_name"))
+                .and_then(|s| s.as_str())
+        })
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+
+    reality_sni.or(tls_sni)
+}
+
+// ─── Helper: Parse Shadowsocks method from settings JSON ──────────────────────
+
+fn parse_ss_method(settings_raw: &str) -> String {
+    let v: Value = serde_json::from_str(settings_raw).unwrap_or(json!({}));
+    v.get("method")
+        .and_then(|s| s.as_str())
+        .unwrap_or("2022-blake3-aes-128-gcm")
+        .to_string()
+}
+
+fn parse_ss_password(settings_raw: &str, user_uuid: &str) -> String {
+    let v: Value = serde_json::from_str(settings_raw).unwrap_or(json!({}));
+
+    // 1. Try to find in `users` list matching UUID
+    if let Some(users) = v.get("users").and_then(|u| u.as_array()) {
+        for user in users {
+            // Check username/name against UUID
+            if let Some(username) = user
+                .get("username")
+                .or(user.get("name"))
+                .and_then(|u| u.as_str())
+                && username == user_uuid
+            {
+                return user
+                    .get("password")
+                    .and_then(|p| p.as_str())
+                    .unwrap_or("")
+                    .to_string();
+            }
+        }
+        // Fallback: if list has 1 item and we didn't match (maybe single user mode but ID mismatch?), use it.
+        if users.len() == 1 {
+            return users[0]
+                .get("password")
+                .and_then(|p| p.as_str())
+                .unwrap_or("")
+                .to_string();
+        }
+    }
+
+    // 2. Fallback to top-level password
+    v.get("password")
+        .and_then(|s| s.as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+// ─── Helper: Unique names and validated proxies ───────────────────────────────
+
+/// `name`, or `name 2`, `name 3`... when it is already taken
+fn unique_name(taken: &mut HashSet<String>, name: String) -> String {
+    let mut candidate = name.clone();
+    let mut n = 1;
+    while taken.contains(&candidate) {
+        n += 1;
+        candidate = format!("{} {}", name, n);
+    }
+    taken.insert(candidate.clone());
+    candidate
+}
+
+/// Keeps a generated proxy only if it validates; one broken inbound must not
+/// take the whole config down with it.
+fn checked<T>(kind: &str, name: &str, proxy: T, validate: impl Fn(&T) -> Result<()>) -> Option<T> {
+    match validate(&proxy) {
+        Ok(()) => Some(proxy),
+        Err(e) => {
+            tracing::warn!("Skipping invalid {} {}: {:#}", kind, name, e);
+            None
+        }
+    }
+}
+
+// ─── Helper: Client-side chains ───────────────────────────────────────────────
+
+/// Proxy names of the entry nodes of `chain`, per node, and of its exit
+/// nodes. A node in both groups only serves as an entry.
+fn chain_members<'a>(
+    chain: &Chain,
+    node_names: &'a [(&NodeInfo, Vec<String>)],
+) -> (Vec<&'a [String]>, Vec<&'a String>) {
+    let mut entries = Vec::new();
+    let mut exits = Vec::new();
+    for (node, names) in node_names {
+        if node.groups.contains(&chain.entry_group) {
+            entries.push(names.as_slice());
+        } else if node.groups.contains(&chain.exit_group) {
+            exits.extend(names);
+        }
+    }
+    entries.retain(|names| !names.is_empty());
+    (entries, exits)
+}
+
+/// A chain as sing-box and Clash render it: a url-test group of the entry
+/// proxies, copies of the exit proxies dialed through that group, and a
+/// url-test group of the copies to select the chain by.
+struct ChainLayout {
+    name: String,
+    entry: String,
+    entries: Vec<String>,
+    /// Exit proxy and the name of its chained copy
+    exits: Vec<(String, String)>,
+}
+
+fn chain_layouts(
+    chains: &[Chain],
+    node_names: &[(&NodeInfo, Vec<String>)],
+    taken: &mut HashSet<String>,
+) -> Vec<ChainLayout> {
+    let mut layouts = Vec::new();
+    for chain in chains {
+        let (entries, exits) = chain_members(chain, node_names);
+        if entries.is_empty() || exits.is_empty() {
+            tracing::debug!("Chain {} has no usable entry or exit proxies", chain.name);
+            continue;
+        }
+        layouts.push(ChainLayout {
+            name: unique_name(taken, chain.name.clone()),
+            entry: unique_name(taken, format!("{} entry", chain.name)),
+            entries: entries.concat(),
+            exits: exits
+                .into_iter()
+                .map(|exit| {
+                    let copy = unique_name(taken, format!("{}: {}", chain.name, exit));
+                    (exit.clone(), copy)
+                })
+                .collect(),
+        });
+    }
+    layouts
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// V2Ray Link Generation (base64 encoded links)
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Generate V2Ray base64 config (multi-protocol link format)
+pub fn generate_v2ray_config(
+    _sub: &Subscription,
+    nodes: &[NodeInfo],
+    user_keys: &UserKeys,
+) -> Result<String> {
+    let mut links = Vec::new();
+
+    for node in nodes {
+        if !node.inbounds.is_empty() {
+            for inbound in &node.inbounds {
+                if !inbound.enable {
+                    continue;
+                }
+                let si = parse_stream_settings(&inbound.stream_settings, node);
+                let label_raw = format!(
+                    "{} - {}",
+                    node.name,
+                    inbound.remark.as_deref().unwrap_or("Auto")
+                );
+                let label = urlencoding::encode(&label_raw);
+
+                match inbound.protocol.as_str() {
+                    "vless" => {
+                        let mut params = vec![
+                            format!("encryption=none"),
+                            format!("type={}", si.network),
+                            format!("security={}", si.security),
+                            format!("sni={}", si.sni),
+                            format!("fp={}", si.fingerprint),
+                        ];
+                        if !si.flow.is_empty() {
+                            params.push(format!("flow={}", si.flow));
+                        }
+                        if si.security == "reality" {
+                            params.push(format!("pbk={}", si.public_key));
+                            params.push(format!("sid={}", si.short_id));
+                        }
+
+                        // XHTTP & Mux
+                        if let Some(pe) = &si.packet_encoding {
+                            params.push(format!("packetEncoding={}", pe));
+                        }
+
+                        // Randomize padding if not set but recommended (500-1200)
+                        // Note: For VLESS links, usually 'xPaddingBytes' isn't standard in all clients,
+                        // but widely supported in Xray/Sing-box via query params if using XHTTP
+                        if let Some(pad) = &si.x_padding_bytes {
+                            params.push(format!("xPaddingBytes={}", pad));
+                        } else if si.network == "xhttp" || si.network == "httpupgrade" {
+                            // Default randomization
+                            use rand::Rng;
+                            let mut rng = rand::rng();
+                            let pad_len = rng.random_range(500..=1200);
+                            params.push(format!("xPaddingBytes={}", pad_len));
+                        }
+
+                        match si.network.as_str() {
+                            "ws" => {
+                                params.push(format!("path={}", urlencoding::encode(&si.ws_path)))
+                            }
+                            "grpc" => params.push(format!("serviceName={}", si.grpc_service)),
+                            "xhttp" | "httpupgrade" => {
+                                params.push(format!("path={}", urlencoding::encode(&si.ws_path)));
+                                params.push("mode=auto".to_string());
+                            }
+                            _ => {}
+                        }
+                        // Frontend Masquerading Logic
+                        let host = node.frontend_url.as_deref().unwrap_or(&node.address);
+                        // If masquerading, we MUST use the real SNI in the header/TLS config
+                        // which is already handled by `si.sni` (streams settings)
+                        // But the connection address (host/ip) in the link should be the frontend.
+
+                        links.push(format!(
+                            "vless://{}@{}:{}?{}#{}",
+                            user_keys.user_uuid,
+                            host,
+                            inbound.listen_port, // Use host (frontend or node IP)
+                            params.join("&"),
+                            label
+                        ));
+                    }
+                    "vmess" => {
+                        // VMess uses JSON-base64 link format
+                        let mut vmess_obj = json!({
+                            "v": "2",
+                            "ps": format!("{} - {}", node.name, inbound.remark.as_deref().unwrap_or("Auto")),
+                            "add": node.frontend_url.as_deref().unwrap_or(&node.address), // Masquerading
+                            "port": inbound.listen_port.to_string(),
+                            "id": user_keys.user_uuid,
+                            "aid": "0",
+                            "scy": "auto",
+                            "net": si.network,
+                            "type": "none",
+                            "tls": if si.security == "tls" { "tls" } else { "" },
+                            "sni": si.sni,
+                            "fp": si.fingerprint,
+                        });
+                        if si.network == "ws" {
+                            vmess_obj["path"] = json!(si.ws_path);
+                            vmess_obj["host"] = json!(si.sni);
+                        }
+                        if si.network == "grpc" {
+                            vmess_obj["path"] = json!(si.grpc_service);
+                        }
+                        use base64::Engine;
+                        let encoded = base64::engine::general_purpose::STANDARD
+                            .encode(serde_json::to_string(&vmess_obj)?);
+                        links.push(format!("vmess://{}", encoded));
+                    }
+                    "trojan" => {
+                        let mut params = vec![
+                            format!("type={}", si.network),
+                            format!("security={}", si.security),
+                            format!("sni={}", si.sni),
+                            format!("fp={}", si.fingerprint),
+                        ];
+                        if si.security == "reality" {
+                            params.push(format!("pbk={}", si.public_key));
+                            params.push(format!("sid={}", si.short_id));
+                        }
+                        match si.network.as_str() {
+                            "ws" => {
+                                params.push(format!("path={}", urlencoding::encode(&si.ws_path)))
+                            }
+                            "grpc" => params.push(format!("serviceName={}", si.grpc_service)),
+                            _ => {}
+                        }
+                        // Trojan uses user_uuid as password
+                        let host = node.frontend_url.as_deref().unwrap_or(&node.address);
+                        links.push(format!(
+                            "trojan://{}@{}:{}?{}#{}",
+                            user_keys.user_uuid,
+                            host,
+                            inbound.listen_port, // Masquerading
+                            params.join("&"),
+                            label
+                        ));
+                    }
+                    "shadowsocks" | "ss" => {
+                        let method = parse_ss_method(&inbound.settings);
+                        // Phase 46: Use per-user password (consistent with orchestration_service)
+                        let password = parse_ss_password(&inbound.settings, &user_keys.user_uuid);
+
+                        let host = node.frontend_url.as_deref().unwrap_or(&node.address);
+
+                        // ss://base64(method:password)@host:port#tag
+                        use base64::Engine;
+                        let userinfo = base64::engine::general_purpose::URL_SAFE_NO_PAD
+                            .encode(format!("{}:{}", method, password));
+                        links.push(format!(
+                            "ss://{}@{}:{}#{}",
+                            userinfo, host, inbound.listen_port, label
+                        ));
+                    }
+                    "hysteria2" | "hy2" => {
+                        let mut params = vec![format!("sni={}", si.sni), format!("insecure=1")];
+                        if let Some(ports) = &si.hy2_ports {
+                            params.push(format!("mport={}", ports));
+                        }
+                        if let Some(obfs) = &si.hy2_obfs {
+                            params.push("obfs=salamander".to_string());
+                            params.push(format!("obfs-password={}", obfs));
+                        }
+
+                        let host = node.frontend_url.as_deref().unwrap_or(&node.address);
+                        links.push(format!(
+                            "hysteria2://{}@{}:{}?{}#{}",
+                            user_keys.hy2_password,
+                            host,
+                            inbound.listen_port, // Masquerading
+                            params.join("&"),
+                            label
+                        ));
+                    }
+                    "amneziawg" => {
+                        let local_address = user_keys.awg_address.clone().unwrap_or_default();
+
+                        // wireguard://private_key@server:port?public_key=...&preshared_key=...#label
+                        // Note: Some clients use 'address' param for local address
+                        let mut params = vec![
+                            format!("public_key={}", si.public_key),
+                            format!("address={}", urlencoding::encode(&local_address)),
+                        ];
+
+                        // Add AmneziaWG obfuscation as non-standard params (supported by some clients/converted by users)
+                        if let Ok(awg_obj) =
+                            serde_json::from_str::<serde_json::Value>(&inbound.settings)
+                        {
+                            for field in ["jc", "jmin", "jmax", "s1", "s2", "h1", "h2", "h3", "h4"]
+                            {
+                                if let Some(v) = awg_obj.get(field) {
+                                    params.push(format!("{}={}", field, v));
+                                }
+                            }
+                        }
+
+                        links.push(format!(
+                            "wireguard://{}@{}:{}?{}#{}",
+                            user_keys.awg_private_key.clone().unwrap_or_default(),
+                            node.address,
+                            inbound.listen_port,
+                            params.join("&"),
+                            label
+                        ));
+                    }
+                    "naive" => {
+                        let host = node.frontend_url.as_deref().unwrap_or(&node.address);
+                        links.push(format!(
+                            "naive+https://{}:{}@{}:{}?sni={}#{}",
+                            user_keys.user_uuid,
+                            user_keys.hy2_password,
+                            host,
+                            inbound.listen_port,
+                            si.sni,
+                            label
+                        ));
+                    }
+                    "tuic" => {
+                        let host = node.frontend_url.as_deref().unwrap_or(&node.address);
+                        let params = [
+                            format!("sni={}", si.sni),
+                            format!(
+                                "congestion_control={}",
+                                si.tuic_congestion_control.as_deref().unwrap_or("bbr")
+                            ),
+                            "alpn=h3".to_string(),
+                        ];
+                        links.push(format!(
+                            "tuic://{}:{}@{}:{}?{}#{}",
+                            user_keys.user_uuid,
+                            user_keys.hy2_password,
+                            host,
+                            inbound.listen_port,
+                            params.join("&"),
+                            label
+                        ));
+                    }
+                    _ => {
+                        // Unknown protocol, skip
+                    }
+                }
+            }
+        }
+        // Legacy fallback: VLESS Reality
+        else if let Some(port) = node.reality_port {
+            let host = node.frontend_url.as_deref().unwrap_or(&node.address);
+            let vless_link = format!(
+                "vless://{}@{}:{}?encryption=none&flow=xtls-rprx-vision&security=reality&sni={}&fp=chrome&pbk={}&sid={}&type=tcp#{}",
+                user_keys.user_uuid,
+                host,
+                port,
+                node.reality_sni
+                    .as_ref()
+                    .unwrap_or(&"www.google.com".to_string()),
+                node.reality_public_key.as_ref().unwrap_or(&"".to_string()),
+                node.reality_short_id.as_ref().unwrap_or(&"".to_string()),
+                urlencoding::encode(&format!("{} VLESS", node.name))
+            );
+            links.push(vless_link);
+            // Legacy Hysteria2
+            if let Some(port) = node.hy2_port {
+                links.push(format!(
+                    "hysteria2://{}@{}:{}?sni={}&insecure=1#{}",
+                    user_keys.hy2_password,
+                    host,
+                    port,
+                    node.hy2_sni.as_deref().unwrap_or(host),
+                    urlencoding::encode(&format!("{} HY2", node.name))
+                ));
+            }
+        }
+    }
+
+    use base64::Engine;
+    Ok(base64::engine::general_purpose::STANDARD.encode(links.join("\n")))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Clash YAML Config Generation
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Names of the built-in Clash groups, which proxies must not shadow
+const CLASH_RESERVED_NAMES: [&str; 4] = ["CARAMBA", "Auto", "Fallback", "Load Balance"];
+
+fn clash_ws_opts(si: &StreamInfo) -> Option<clash::WsOpts> {
+    (si.network == "ws").then(|| clash::WsOpts {
+        path: si.ws_path.clone(),
+        headers: BTreeMap::from([("Host".to_string(), si.sni.clone())]),
+    })
+}
+
+fn clash_grpc_opts(si: &StreamInfo) -> Option<clash::GrpcOpts> {
+    (si.network == "grpc").then(|| clash::GrpcOpts {
+        grpc_service_name: si.grpc_service.clone(),
+    })
+}
+
+fn clash_reality_opts(si: &StreamInfo) -> Option<clash::RealityOpts> {
+    (si.security == "reality").then(|| clash::RealityOpts {
+        public_key: si.public_key.clone(),
+        short_id: si.short_id.clone(),
+    })
+}
+
+/// sing-mux from the inbound's anti-DPI profile
+fn clash_smux(si: &StreamInfo) -> Option<clash::Smux> {
+    let mux = si.anti_dpi.multiplex.as_ref()?;
+    Some(clash::Smux {
+        enabled: true,
+        protocol: mux.protocol.clone(),
+        max_connections: mux.max_connections,
+        padding: mux.padding,
+        brutal_opts: mux.brutal.as_ref().map(|b| clash::BrutalOpts {
+            enabled: true,
+            up: b.up_mbps,
+            down: b.down_mbps,
+        }),
+    })
+}
+
+/// Clash proxy for one inbound
+fn clash_proxy(
+    node: &NodeInfo,
+    inbound: &caramba_db::models::network::Inbound,
+    name: String,
+    user_keys: &UserKeys,
+) -> Option<clash::Proxy> {
+    let si = parse_stream_settings(&inbound.stream_settings, node);
+    let server = node.address.clone();
+    let port = inbound.listen_port;
+    let tls = matches!(si.security.as_str(), "reality" | "tls");
+
+    let proxy = match inbound.protocol.as_str() {
+        "vless" => clash::Proxy::Vless(clash::Vless {
+            name,
+            server,
+            port,
+            uuid: user_keys.user_uuid.clone(),
+            network: si.network.clone(),
+            client_fingerprint: si.fingerprint.clone(),
+            flow: (!si.flow.is_empty()).then(|| si.flow.clone()),
+            tls: tls.then_some(true),
+            servername: tls.then(|| si.sni.clone()),
+            reality_opts: clash_reality_opts(&si),
+            ws_opts: clash_ws_opts(&si),
+            grpc_opts: clash_grpc_opts(&si),
+            // Vision cannot be multiplexed
+            smux: clash_smux(&si).filter(|_| si.flow.is_empty()),
+            dialer_proxy: None,
+        }),
+        "vmess" => {
+            let tls = si.security == "tls";
+            clash::Proxy::Vmess(clash::Vmess {
+                name,
+                server,
+                port,
+                uuid: user_keys.user_uuid.clone(),
+                alter_id: 0,
+                cipher: "auto".to_string(),
+                network: si.network.clone(),
+                tls: tls.then_some(true),
+                servername: tls.then(|| si.sni.clone()),
+                ws_opts: clash_ws_opts(&si),
+                grpc_opts: clash_grpc_opts(&si),
+                smux: clash_smux(&si),
+                dialer_proxy: None,
+            })
+        }
+        "trojan" => {
+            let reality_opts = clash_reality_opts(&si);
+            clash::Proxy::Trojan(clash::Trojan {
+                name,
+                server,
+                port,
+                password: user_keys.user_uuid.clone(),
+                sni: si.sni.clone(),
+                network: matches!(si.network.as_str(), "ws" | "grpc").then(|| si.network.clone()),
+                client_fingerprint: reality_opts.as_ref().map(|_| si.fingerprint.clone()),
+                reality_opts,
+                ws_opts: clash_ws_opts(&si),
+                grpc_opts: clash_grpc_opts(&si),
+                smux: clash_smux(&si),
+                dialer_proxy: None,
+            })
+        }
+        "shadowsocks" | "ss" => clash::Proxy::Ss(clash::Shadowsocks {
+            name,
+            server,
+            port,
+            cipher: parse_ss_method(&inbound.settings),
+            password: parse_ss_password(&inbound.settings, &user_keys.user_uuid),
+            smux: clash_smux(&si),
+            dialer_proxy: None,
+        }),
+        "hysteria2" | "hy2" => clash::Proxy::Hysteria2(clash::Hysteria2 {
+            name,
+            server,
+            port,
+            password: user_keys.hy2_password.clone(),
+            sni: si.sni.clone(),
+            skip_cert_verify: true,
+            dialer_proxy: None,
+        }),
+        "amneziawg" => {
+            // Clash Meta amnezia-wg opts
+            let amnezia_wg = serde_json::from_str::<Value>(&inbound.settings)
+                .ok()
+                .map(|awg| {
+                    ["jc", "jmin", "jmax", "s1", "s2", "h1", "h2", "h3", "h4"]
+                        .into_iter()
+                        .filter_map(|field| Some((field.to_string(), awg.get(field)?.clone())))
+                        .collect::<BTreeMap<_, _>>()
+                })
+                .filter(|opts| !opts.is_empty());
+            clash::Proxy::WireGuard(clash::WireGuard {
+                name,
+                server,
+                port,
+                ip: user_keys.awg_address.clone().unwrap_or_default(),
+                private_key: user_keys.awg_private_key.clone().unwrap_or_default(),
+                public_key: si.public_key.clone(),
+                udp: true,
+                mtu: 1280,
+                amnezia_wg,
+                dialer_proxy: None,
+            })
+        }
+        _ => return None,
+    };
+    Some(proxy)
+}
+
+/// Proxies of a node without inbounds, from its legacy Reality and
+/// Hysteria2 ports
+fn clash_legacy_proxies(node: &NodeInfo, user_keys: &UserKeys) -> Vec<clash::Proxy> {
+    let mut proxies = Vec::new();
+    if node.inbounds.is_empty()
+        && let Some(port) = node.reality_port
+    {
+        proxies.push(clash::Proxy::Vless(clash::Vless {
+            name: format!("{} VLESS", node.name),
+            server: node.address.clone(),
+            port: port.into(),
+            uuid: user_keys.user_uuid.clone(),
+            network: "tcp".to_string(),
+            client_fingerprint: "chrome".to_string(),
+            flow: None,
+            tls: Some(true),
+            servername: Some(
+                node.reality_sni
+                    .clone()
+                    .unwrap_or_else(|| "www.google.com".to_string()),
+            ),
+            reality_opts: Some(clash::RealityOpts {
+                public_key: node.reality_public_key.clone().unwrap_or_default(),
+                short_id: node.reality_short_id.clone().unwrap_or_default(),
+            }),
+            ws_opts: None,
+            grpc_opts: None,
+            smux: None,
+            dialer_proxy: None,
+        }));
+    }
+    if let Some(port) = node.hy2_port {
+        proxies.push(clash::Proxy::Hysteria2(clash::Hysteria2 {
+            name: format!("{} HY2", node.name),
+            server: node.address.clone(),
+            port: port.into(),
+            password: user_keys.hy2_password.clone(),
+            sni: node.hy2_sni.clone().unwrap_or_else(|| node.address.clone()),
+            skip_cert_verify: true,
+            dialer_proxy: None,
+        }));
+    }
+    proxies
+}
+
+/// Generated Clash proxies with what groups are built from
+struct ClashProxies<'a> {
+    proxies: Vec<clash::Proxy>,
+    /// Proxy names of each node
+    node_proxies: Vec<(&'a NodeInfo, Vec<String>)>,
+    /// The (entry, selectable) url-test groups of each chain
+    chain_groups: Vec<(clash::ProxyGroup, clash::ProxyGroup)>,
+}
+
+/// Clash proxies for every enabled inbound and the chained copies of
+/// `chains`. Proxies that do not validate are left out and names are made
+/// unique.
+fn clash_proxies<'a>(
+    nodes: &'a [NodeInfo],
+    chains: &[Chain],
+    user_keys: &UserKeys,
+) -> ClashProxies<'a> {
+    let mut proxies = Vec::new();
+    // Proxy names per node, for region and node group selection
+    let mut node_proxies: Vec<(&NodeInfo, Vec<String>)> = Vec::new();
+    let mut taken: HashSet<String> = CLASH_RESERVED_NAMES
+        .into_iter()
+        .chain(clash::BUILT_IN)
+        .map(String::from)
+        .collect();
+
+    for node in nodes {
+        let generated = node
+            .inbounds
+            .iter()
+            .filter(|i| i.enable)
+            .filter_map(|inbound| {
+                let name = format!(
+                    "{} - {}",
+                    node.name,
+                    inbound.remark.as_deref().unwrap_or("Auto")
+                );
+                clash_proxy(node, inbound, name, user_keys)
+            })
+            .chain(clash_legacy_proxies(node, user_keys));
+
+        let mut names = Vec::new();
+        for proxy in generated {
+            let name = proxy.name().to_string();
+            let Some(mut proxy) = checked("proxy", &name, proxy, clash::Proxy::validate) else {
+                continue;
+            };
+            let name = unique_name(&mut taken, name);
+            proxy.set_name(name.clone());
+            names.push(name);
+            proxies.push(proxy);
+        }
+        node_proxies.push((node, names));
+    }
+
+    let mut chain_groups = Vec::new();
+    for layout in chain_layouts(chains, &node_proxies, &mut taken) {
+        let mut copies = Vec::new();
+        for (exit, copy) in &layout.exits {
+            let Some(mut proxy) = proxies.iter().find(|p| p.name() == exit).cloned() else {
+                continue;
+            };
+            proxy.set_name(copy.clone());
+            proxy.set_dialer_proxy(layout.entry.clone());
+            proxies.push(proxy);
+            copies.push(copy.clone());
+        }
+        chain_groups.push((
+            clash::ProxyGroup::auto(&layout.entry, "url-test", &layout.entries, CLASH_TEST_URL),
+            clash::ProxyGroup::auto(&layout.name, "url-test", &copies, CLASH_TEST_URL),
+        ));
+    }
+
+    ClashProxies {
+        proxies,
+        node_proxies,
+        chain_groups,
+    }
+}
+
+/// Generate Clash YAML config (multi-protocol)
+pub fn generate_clash_config(
+    _sub: &Subscription,
+    nodes: &[NodeInfo],
+    chains: &[Chain],
+    user_keys: &UserKeys,
+) -> Result<String> {
+    let ClashProxies {
+        proxies,
+        node_proxies,
+        chain_groups,
+    } = clash_proxies(nodes, chains, user_keys);
+
+    let config = clash::Config {
+        proxies,
+        proxy_groups: clash_proxy_groups(&node_proxies, chain_groups),
+        rule_providers: clash_rule_providers(nodes),
+        rules: clash_rules(nodes),
+    };
+    config
+        .validate()
+        .context("Generated Clash config is invalid")?;
+
+    Ok(serde_yaml::to_string(&config)?)
+}
+
+const CLASH_TEST_URL: &str = "http://www.gstatic.com/generate_204";
+const CLASH_RULESET_BASE: &str =
+    "https://raw.githubusercontent.com/MetaCubeX/meta-rules-dat/meta/geo/geosite";
+
+/// Clash Meta proxy groups: a main selector, global url-test, fallback and
+/// load-balance groups, the groups of each chain, then a url-test group per
+/// region and per node group.
+fn clash_proxy_groups(
+    node_proxies: &[(&NodeInfo, Vec<String>)],
+    chain_groups: Vec<(clash::ProxyGroup, clash::ProxyGroup)>,
+) -> Vec<clash::ProxyGroup> {
+    let all: Vec<String> = node_proxies
+        .iter()
+        .flat_map(|(_, names)| names.iter().cloned())
+        .collect();
+
+    let mut regions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut node_groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (node, names) in node_proxies {
+        if names.is_empty() {
+            continue;
+        }
+        if let Some(code) = node.country_code.as_deref().filter(|c| c.len() == 2) {
+            let label = format!("{} {}", country_code_to_flag(code), code.to_uppercase());
+            regions
+                .entry(label)
+                .or_default()
+                .extend(names.iter().cloned());
+        }
+        for group in &node.groups {
+            node_groups
+                .entry(group.clone())
+                .or_default()
+                .extend(names.iter().cloned());
+        }
+    }
+
+    let mut selectable = vec![
+        "Auto".to_string(),
+        "Fallback".to_string(),
+        "Load Balance".to_string(),
+    ];
+    let mut extra_groups = Vec::new();
+    let mut chain_entries = Vec::new();
+    for (entry, chain) in chain_groups {
+        selectable.push(chain.name.clone());
+        chain_entries.push(entry.name.clone());
+        extra_groups.extend([entry, chain]);
+    }
+    for (name, proxies) in regions.into_iter().chain(node_groups) {
+        // A node group named like a region, chain, proxy or built-in group is skipped
+        if selectable.contains(&name)
+            || chain_entries.contains(&name)
+            || all.contains(&name)
+            || CLASH_RESERVED_NAMES.contains(&name.as_str())
+            || clash::BUILT_IN.contains(&name.as_str())
+        {
+            continue;
+        }
+        extra_groups.push(clash::ProxyGroup::auto(
+            &name,
+            "url-test",
+            &proxies,
+            CLASH_TEST_URL,
+        ));
+        selectable.push(name);
+    }
+
+    let mut main = selectable;
+    main.extend(all.iter().cloned());
+    main.push("DIRECT".to_string());
+
+    let mut groups = vec![
+        clash::ProxyGroup {
+            name: "CARAMBA".to_string(),
+            kind: "select".to_string(),
+            proxies: main,
+            url: None,
+            interval: None,
+            tolerance: None,
+            strategy: None,
+        },
+        clash::ProxyGroup::auto("Auto", "url-test", &all, CLASH_TEST_URL),
+        clash::ProxyGroup::auto("Fallback", "fallback", &all, CLASH_TEST_URL),
+        clash::ProxyGroup::auto("Load Balance", "load-balance", &all, CLASH_TEST_URL),
+    ];
+    groups.extend(extra_groups);
+    groups
+}
+
+/// Blocking policies of the nodes as (rule provider name, geosite list)
+fn clash_block_lists(nodes: &[NodeInfo]) -> Vec<(&'static str, &'static str)> {
+    let mut lists = Vec::new();
+    if nodes.iter().any(|n| n.config_block_ads) {
+        lists.push(("block-ads", "category-ads-all"));
+    }
+    if nodes.iter().any(|n| n.config_block_porn) {
+        lists.push(("block-porn", "category-porn"));
+    }
+    if nodes.iter().any(|n| n.config_block_torrent) {
+        lists.push(("block-torrent", "category-public-tracker"));
+    }
+    lists
+}
+
+fn clash_rule_providers(nodes: &[NodeInfo]) -> BTreeMap<String, clash::RuleProvider> {
+    clash_block_lists(nodes)
+        .into_iter()
+        .map(|(name, geosite)| {
+            let provider = clash::RuleProvider {
+                kind: "http".to_string(),
+                behavior: "domain".to_string(),
+                format: "mrs".to_string(),
+                url: format!("{}/{}.mrs", CLASH_RULESET_BASE, geosite),
+                path: format!("./ruleset/{}.mrs", geosite),
+                interval: 86400,
+            };
+            (name.to_string(), provider)
+        })
+        .collect()
+}
+
+fn clash_rules(nodes: &[NodeInfo]) -> Vec<String> {
+    let mut rules: Vec<String> = clash_block_lists(nodes)
+        .into_iter()
+        .map(|(name, _)| format!("RULE-SET,{},REJECT", name))
+        .collect();
+    rules.push("GEOIP,private,DIRECT,no-resolve".to_string());
+    rules.push("MATCH,CARAMBA".to_string());
+    rules
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Sing-box JSON Config Generation
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Tags of the sing-box groups and built-in outbounds
+const SINGBOX_RESERVED_TAGS: [&str; 5] = ["proxy", "auto", "direct", "block", "dns-out"];
+
+fn singbox_tls(si: &StreamInfo) -> singbox::Tls {
+    let utls = singbox::Utls {
+        enabled: true,
+        fingerprint: si.fingerprint.clone(),
+    };
+    let reality = (si.security == "reality").then(|| singbox::Reality {
+        enabled: true,
+        public_key: si.public_key.clone(),
+        short_id: si.short_id.clone(),
+    });
+    // sing-box sizes fragments itself; `tlshello` asks for TLS records as in Xray
+    let fragment = si.anti_dpi.fragment.as_ref();
+    let records = fragment.is_some_and(|f| f.packets == "tlshello");
+    singbox::Tls {
+        enabled: true,
+        server_name: si.sni.clone(),
+        utls: Some(utls),
+        reality,
+        fragment: (fragment.is_some() && !records).then_some(true),
+        record_fragment: records.then_some(true),
+        ..Default::default()
+    }
+}
+
+/// TLS for the QUIC based protocols, which skip certificate checks
+fn singbox_quic_tls(si: &StreamInfo) -> singbox::Tls {
+    singbox::Tls {
+        enabled: true,
+        server_name: si.sni.clone(),
+        insecure: Some(true),
+        alpn: Some(vec!["h3".to_string()]),
+        ..Default::default()
+    }
+}
+
+fn singbox_transport(si: &StreamInfo) -> Option<singbox::Transport> {
+    match si.network.as_str() {
+        "ws" => Some(singbox::Transport::Ws {
+            path: si.ws_path.clone(),
+            headers: BTreeMap::from([("Host".to_string(), si.sni.clone())]),
+        }),
+        "grpc" => Some(singbox::Transport::Grpc {
+            service_name: si.grpc_service.clone(),
+        }),
+        "xhttp" | "splithttp" | "httpupgrade" => Some(singbox::Transport::HttpUpgrade {
+            path: si.ws_path.clone(),
+            host: (!si.sni.is_empty()).then(|| vec![si.sni.clone()]),
+        }),
+        _ => None,
+    }
+}
+
+/// The inbound's xmux if it is a sing-box multiplex block, smux otherwise
+fn singbox_multiplex(si: &StreamInfo) -> singbox::Multiplex {
+    si.xmux
+        .clone()
+        .and_then(|xmux| serde_json::from_value(xmux).ok())
+        .unwrap_or(singbox::Multiplex {
+            enabled: true,
+            protocol: Some("smux".to_string()),
+            max_connections: Some(4),
+            min_streams: Some(4),
+            max_streams: None,
+            padding: Some(true),
+            brutal: None,
+        })
+}
+
+/// sing-mux from the inbound's anti-DPI profile
+fn singbox_anti_dpi_multiplex(si: &StreamInfo) -> Option<singbox::Multiplex> {
+    let mux = si.anti_dpi.multiplex.as_ref()?;
+    Some(singbox::Multiplex {
+        enabled: true,
+        protocol: Some(mux.protocol.clone()),
+        max_connections: mux.max_connections,
+        min_streams: None,
+        max_streams: None,
+        padding: mux.padding.then_some(true),
+        brutal: mux.brutal.as_ref().map(|b| singbox::Brutal {
+            enabled: true,
+            up_mbps: b.up_mbps,
+            down_mbps: b.down_mbps,
+        }),
+    })
+}
+
+/// Relay hop in front of `relay`'s nodes, if it has a usable inbound
+fn singbox_relay_outbound(
+    relay: &NodeInfo,
+    tag: String,
+    user_keys: &UserKeys,
+) -> Option<singbox::Outbound> {
+    let ri = relay.inbounds.iter().find(|i| i.enable)?;
+    let r_si = parse_stream_settings(&ri.stream_settings, relay);
+
+    match ri.protocol.as_str() {
+        "shadowsocks" | "ss" => Some(singbox::Outbound::Shadowsocks(singbox::Shadowsocks {
+            tag,
+            server: relay.address.clone(),
+            server_port: ri.listen_port,
+            method: parse_ss_method(&ri.settings),
+            password: parse_ss_password(&ri.settings, &user_keys.user_uuid),
+            multiplex: singbox_anti_dpi_multiplex(&r_si),
+            detour: None,
+        })),
+        "hysteria2" | "hy2" => Some(singbox::Outbound::Hysteria2(singbox::Hysteria2 {
+            tag,
+            server: relay.address.clone(),
+            server_port: ri.listen_port,
+            server_ports: None,
+            password: user_keys.hy2_password.clone(),
+            tls: singbox_quic_tls(&r_si),
+            obfs: None,
+            detour: None,
+        })),
+        _ => None,
+    }
+}
+
+/// Sing-box outbound for one inbound, without its relay detour
+fn singbox_proxy_outbound(
+    node: &NodeInfo,
+    inbound: &caramba_db::models::network::Inbound,
+    tag: String,
+    user_keys: &UserKeys,
+) -> Option<singbox::Outbound> {
+    let si = parse_stream_settings(&inbound.stream_settings, node);
+    let server = node
+        .frontend_url
+        .clone()
+        .unwrap_or_else(|| node.address.clone());
+    let server_port = inbound.listen_port;
+
+    let outbound = match inbound.protocol.as_str() {
+        "vless" => {
+            let tls = matches!(si.security.as_str(), "reality" | "tls").then(|| singbox_tls(&si));
+            let transport = singbox_transport(&si);
+            let upgrade = matches!(transport, Some(singbox::Transport::HttpUpgrade { .. }));
+            singbox::Outbound::Vless(singbox::Vless {
+                tag,
+                server,
+                server_port,
+                uuid: user_keys.user_uuid.clone(),
+                flow: si.flow.clone(),
+                tls,
+                transport,
+                packet_encoding: upgrade.then(|| {
+                    si.packet_encoding
+                        .clone()
+                        .unwrap_or_else(|| "xudp".to_string())
+                }),
+                // Vision cannot be multiplexed
+                multiplex: singbox_anti_dpi_multiplex(&si)
+                    .filter(|_| si.flow.is_empty())
+                    .or_else(|| upgrade.then(|| singbox_multiplex(&si))),
+                detour: None,
+            })
+        }
+        "hysteria2" | "hy2" => singbox::Outbound::Hysteria2(singbox::Hysteria2 {
+            tag,
+            server,
+            server_port,
+            server_ports: si.hy2_ports.clone(),
+            password: user_keys.hy2_password.clone(),
+            tls: singbox_quic_tls(&si),
+            obfs: si.hy2_obfs.as_ref().map(|password| singbox::Obfs {
+                kind: "salamander".to_string(),
+                password: password.clone(),
+            }),
+            detour: None,
+        }),
+        "tuic" => singbox::Outbound::Tuic(singbox::Tuic {
+            tag,
+            server,
+            server_port,
+            uuid: user_keys.user_uuid.clone(),
+            password: user_keys.hy2_password.clone(),
+            congestion_control: si
+                .tuic_congestion_control
+                .clone()
+                .unwrap_or_else(|| "bbr".to_string()),
+            zero_rtt_handshake: si.tuic_zero_rtt_handshake.unwrap_or(false),
+            tls: singbox_quic_tls(&si),
+            detour: None,
+        }),
+        "trojan" => {
+            let mut tls = singbox_tls(&si);
+            if si.security != "reality" {
+                tls.utls = None;
+            }
+            singbox::Outbound::Trojan(singbox::Trojan {
+                tag,
+                server,
+                server_port,
+                password: user_keys.user_uuid.clone(),
+                tls,
+                transport: singbox_transport(&si).filter(|t| {
+                    matches!(
+                        t,
+                        singbox::Transport::Ws { .. } | singbox::Transport::Grpc { .. }
+                    )
+                }),
+                multiplex: singbox_anti_dpi_multiplex(&si),
+                detour: None,
+            })
+        }
+        "shadowsocks" | "ss" => singbox::Outbound::Shadowsocks(singbox::Shadowsocks {
+            tag,
+            server,
+            server_port,
+            method: parse_ss_method(&inbound.settings),
+            password: parse_ss_password(&inbound.settings, &user_keys.user_uuid),
+            multiplex: singbox_anti_dpi_multiplex(&si),
+            detour: None,
+        }),
+        "naive" => {
+            // Naive brings its own TLS stack
+            let mut tls = singbox::Tls {
+                fragment: None,
+                record_fragment: None,
+                ..singbox_tls(&si)
+            };
+            tls.reality = None;
+            tls.alpn = Some(vec!["h2".to_string(), "http/1.1".to_string()]);
+            singbox::Outbound::Naive(singbox::Naive {
+                tag,
+                server,
+                server_port,
+                username: user_keys.user_uuid.clone(),
+                password: user_keys.hy2_password.clone(),
+                tls,
+                detour: None,
+            })
+        }
+        "amneziawg" => {
+            let reserved = serde_json::from_str::<Value>(&inbound.settings)
+                .ok()
+                .filter(|awg| awg.get("jc").is_some())
+                .map(|awg| {
+                    ["jc", "jmin", "jmax"]
+                        .iter()
+                        .map(|field| awg[field].as_u64().unwrap_or(0))
+                        .collect()
+                });
+            singbox::Outbound::WireGuard(singbox::WireGuard {
+                tag,
+                server,
+                server_port,
+                local_address: vec![user_keys.awg_address.clone().unwrap_or_default()],
+                private_key: user_keys.awg_private_key.clone().unwrap_or_default(),
+                peer_public_key: si.public_key.clone(),
+                mtu: 1280,
+                reserved,
+                detour: None,
+            })
+        }
+        _ => return None,
+    };
+    Some(outbound)
+}
+
+fn singbox_url_test(tag: &str, outbounds: Vec<String>) -> singbox::Outbound {
+    singbox::Outbound::UrlTest(singbox::UrlTest {
+        tag: tag.to_string(),
+        outbounds,
+        url: "https://www.gstatic.com/generate_204".to_string(),
+        interval: "3m".to_string(),
+        tolerance: 50,
+    })
+}
+
+/// Proxy and relay outbounds for every enabled inbound and the groups and
+/// chained copies of `chains`, plus the tags of the proxies (relays are only
+/// used as detours) and of the chains to select. Outbounds that do not
+/// validate are left out.
+fn singbox_proxy_outbounds(
+    nodes: &[NodeInfo],
+    chains: &[Chain],
+    user_keys: &UserKeys,
+) -> (Vec<singbox::Outbound>, Vec<String>, Vec<String>) {
+    let mut outbounds = vec![];
+    let mut outbound_tags = vec![];
+    let mut node_tags: Vec<(&NodeInfo, Vec<String>)> = vec![];
+    let mut taken: HashSet<String> = SINGBOX_RESERVED_TAGS.map(String::from).into();
+    let mut generated_relays: HashMap<String, String> = HashMap::new();
+
+    for node in nodes {
+        let mut tags = vec![];
+        for inbound in node.inbounds.iter().filter(|i| i.enable) {
+            // ─── Relay Chaining Support ──────────────────────────────────────
+            let mut detour_tag: Option<String> = None;
+            if let Some(relay) = &node.relay_info {
+                let relay_key = format!("relay_{}", relay.address);
+                if let Some(existing_tag) = generated_relays.get(&relay_key) {
+                    detour_tag = Some(existing_tag.clone());
+                } else {
+                    let r_tag = unique_name(&mut taken, format!("relay_{}", relay.name));
+                    if let Some(r_ob) = singbox_relay_outbound(relay, r_tag.clone(), user_keys)
+                        .and_then(|o| checked("relay", &r_tag, o, singbox::Outbound::validate))
+                    {
+                        outbounds.push(r_ob);
+                        generated_relays.insert(relay_key, r_tag.clone());
+                        detour_tag = Some(r_tag);
+                    }
+                }
+            }
+            // ─────────────────────────────────────────────────────────────────
+
+            let tag = format!("{}_{}", node.name, inbound.tag);
+            let Some(mut outbound) = singbox_proxy_outbound(node, inbound, tag.clone(), user_keys)
+            else {
+                continue;
+            };
+            if let Some(detour) = detour_tag {
+                outbound.set_detour(detour);
+            }
+            let Some(mut outbound) =
+                checked("outbound", &tag, outbound, singbox::Outbound::validate)
+            else {
+                continue;
+            };
+
+            let tag = unique_name(&mut taken, tag);
+            outbound.set_tag(tag.clone());
+            tags.push(tag.clone());
+            outbound_tags.push(tag);
+            outbounds.push(outbound);
+        }
+        node_tags.push((node, tags));
+    }
+
+    let mut chain_tags = vec![];
+    for layout in chain_layouts(chains, &node_tags, &mut taken) {
+        let mut copies = vec![];
+        for (exit, copy) in &layout.exits {
+            let Some(mut outbound) = outbounds.iter().find(|o| o.tag() == exit).cloned() else {
+                continue;
+            };
+            outbound.set_tag(copy.clone());
+            outbound.set_detour(layout.entry.clone());
+            copies.push(outbound);
+        }
+        let copy_tags = copies.iter().map(|o| o.tag().to_string()).collect();
+        outbounds.push(singbox_url_test(&layout.entry, layout.entries));
+        outbounds.push(singbox_url_test(&layout.name, copy_tags));
+        outbounds.extend(copies);
+        chain_tags.push(layout.name);
+    }
+
+    (outbounds, outbound_tags, chain_tags)
+}
+
+/// Generate Sing-box JSON config (multi-protocol) with smart routing
+pub fn generate_singbox_config(
+    _sub: &Subscription,
+    nodes: &[NodeInfo],
+    chains: &[Chain],
+    user_keys: &UserKeys,
+) -> Result<String> {
+    let (outbounds, outbound_tags, chain_tags) = singbox_proxy_outbounds(nodes, chains, user_keys);
+
+    if outbound_tags.is_empty() {
+        return Ok(json!({}).to_string());
+    }
+
+    let named = |tag: &str| singbox::Named {
+        tag: tag.to_string(),
+    };
+
+    // 1. Proxy selector (main group) defaulting to the URLTest group, then
+    // DIRECT, BLOCK and DNS, then the generated proxies and chains
+    let mut final_outbounds = vec![
+        singbox::Outbound::Selector(singbox::Selector {
+            tag: "proxy".to_string(),
+            outbounds: std::iter::once("auto".to_string())
+                .chain(chain_tags)
+                .chain(outbound_tags.iter().cloned())
+                .collect(),
+            default: Some("auto".to_string()),
+        }),
+        singbox_url_test("auto", outbound_tags),
+        singbox::Outbound::Direct(named("direct")),
+        singbox::Outbound::Block(named("block")),
+        singbox::Outbound::Dns(named("dns-out")),
+    ];
+    final_outbounds.extend(outbounds);
+
+    // Aggregated Policies
+    let block_ads = nodes.iter().any(|n| n.config_block_ads);
+    let block_porn = nodes.iter().any(|n| n.config_block_porn);
+    // let block_torrent = nodes.iter().any(|n| n.config_block_torrent); // Client-side P2P blocking is harder to enforce reliably via geosite alone, but we can try.
+
+    // 2. DNS Configuration
+    let dns_rule = |server: &str| singbox::DnsRule {
+        server: server.to_string(),
+        ..Default::default()
+    };
+    let mut dns_rules = vec![
+        singbox::DnsRule {
+            outbound: Some(vec!["any".to_string()]),
+            ..dns_rule("local")
+        },
+        singbox::DnsRule {
+            clash_mode: Some("direct".to_string()),
+            ..dns_rule("local")
+        },
+        singbox::DnsRule {
+            clash_mode: Some("global".to_string()),
+            ..dns_rule("google")
+        },
+        singbox::DnsRule {
+            geosite: Some("cn".to_string()),
+            ..dns_rule("local")
+        },
+    ];
+    // Route DNS through proxy to avoid leaks/poisoning
+    let mut dns_servers = vec![
+        singbox::DnsServer {
+            tag: "google".to_string(),
+            address: "8.8.8.8".to_string(),
+            detour: Some("proxy".to_string()),
+        },
+        singbox::DnsServer {
+            tag: "local".to_string(),
+            address: "local".to_string(),
+            detour: Some("direct".to_string()),
+        },
+    ];
+    for (enabled, geosite) in [
+        (block_ads, "category-ads-all"),
+        (block_porn, "category-porn"),
+    ] {
+        if enabled {
+            dns_rules.push(singbox::DnsRule {
+                geosite: Some(geosite.to_string()),
+                ..dns_rule("block")
+            });
+        }
+    }
+    if block_ads || block_porn {
+        dns_servers.push(singbox::DnsServer {
+            tag: "block".to_string(),
+            address: "rcode://success".to_string(),
+            detour: None,
+        });
+    }
+
+    // 3. Route Rules
+    let route_rule = |outbound: &str| singbox::RouteRule {
+        outbound: outbound.to_string(),
+        ..Default::default()
+    };
+    let list = |items: &[&str]| Some(items.iter().map(|s| s.to_string()).collect());
+    let mut route_rules = vec![singbox::RouteRule {
+        protocol: Some("dns".to_string()),
+        ..route_rule("dns-out")
+    }];
+    for (enabled, geosite) in [
+        (block_ads, "category-ads-all"),
+        (block_porn, "category-porn"),
+    ] {
+        if enabled {
+            route_rules.push(singbox::RouteRule {
+                geosite: list(&[geosite]),
+                ..route_rule("block")
+            });
+        }
+    }
+    route_rules.extend([
+        singbox::RouteRule {
+            geosite: list(&["cn", "private"]),
+            ..route_rule("direct")
+        },
+        singbox::RouteRule {
+            geoip: list(&["cn", "private"]),
+            ..route_rule("direct")
+        },
+        // Add RU/UA specifics if needed, for now standard
+        singbox::RouteRule {
+            geosite: list(&["ru"]),
+            ..route_rule("direct")
+        },
+        singbox::RouteRule {
+            geoip: list(&["ru"]),
+            ..route_rule("direct")
+        },
+        singbox::RouteRule {
+            network: Some("tcp".to_string()),
+            domain_suffix: list(&["github.com", "githubusercontent.com", "githubassets.com"]),
+            tls_fragment: Some(true),
+            ..route_rule("proxy")
+        },
+    ]);
+
+    // 4. Final Assembly, with a mixed port for the client
+    let config = singbox::Config {
+        log: singbox::Log {
+            level: "info".to_string(),
+            timestamp: true,
+        },
+        dns: singbox::Dns {
+            servers: dns_servers,
+            rules: dns_rules,
+            final_server: "google".to_string(),
+            strategy: "ipv4_only".to_string(), // Safer for most
+        },
+        inbounds: vec![singbox::Inbound::Mixed(singbox::MixedInbound {
+            tag: "mixed-in".to_string(),
+            listen: "127.0.0.1".to_string(),
+            listen_port: 2080,
+            sniff: true,
+            sniff_override_destination: true,
+        })],
+        outbounds: final_outbounds,
+        route: singbox::Route {
+            auto_detect_interface: true,
+            final_outbound: "proxy".to_string(),
+            rules: route_rules,
+        },
+        experimental: singbox::Experimental {
+            cache_file: singbox::CacheFile {
+                enabled: true,
+                store_fakeip: true,
+            },
+            clash_api: singbox::ClashApi {
+                external_controller: "127.0.0.1:9090".to_string(),
+                external_ui: "ui".to_string(),
+                external_ui_download_url:
+                    "https://github.com/MetaCubeX/Yacd-meta/archive/gh-pages.zip".to_string(),
+                external_ui_download_detour: "proxy".to_string(),
+                default_mode: "rule".to_string(),
+            },
+        },
+    };
+    config
+        .validate()
+        .context("Generated sing-box config is invalid")?;
+
+    Ok(serde_json::to_string_pretty(&config)?)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Xray JSON Config Generation
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Xray `streamSettings` for an outbound built from the inbound's transport
+fn xray_stream_settings(si: &StreamInfo) -> xray::StreamSettings {
+    let network = match si.network.as_str() {
+        "splithttp" => "xhttp",
+        other => other,
+    };
+    let mut stream = xray::StreamSettings {
+        network: Some(network.to_string()),
+        ..Default::default()
+    };
+
+    match si.security.as_str() {
+        "reality" => {
+            stream.security = Some("reality".to_string());
+            stream.reality_settings = Some(xray::RealitySettings {
+                server_name: si.sni.clone(),
+                public_key: si.public_key.clone(),
+                short_id: si.short_id.clone(),
+                fingerprint: si.fingerprint.clone(),
+            });
+        }
+        "tls" => {
+            stream.security = Some("tls".to_string());
+            stream.tls_settings = Some(xray_tls_settings(si));
+        }
+        _ => stream.security = Some("none".to_string()),
+    }
+
+    let path = || xray::PathSettings {
+        path: si.ws_path.clone(),
+        host: si.sni.clone(),
+    };
+    match network {
+        "ws" => stream.ws_settings = Some(path()),
+        "grpc" => {
+            stream.grpc_settings = Some(xray::GrpcSettings {
+                service_name: si.grpc_service.clone(),
+            })
+        }
+        "httpupgrade" => stream.httpupgrade_settings = Some(path()),
+        "xhttp" => {
+            let mut extra = serde_json::Map::new();
+            if let Some(padding) = &si.x_padding_bytes {
+                extra.insert("xPaddingBytes".to_string(), json!(padding));
+            }
+            if let Some(xmux) = &si.xmux {
+                extra.insert("xmux".to_string(), xmux.clone());
+            }
+            stream.xhttp_settings = Some(xray::XhttpSettings {
+                path: si.ws_path.clone(),
+                host: si.sni.clone(),
+                mode: "auto".to_string(),
+                extra: (!extra.is_empty()).then_some(Value::Object(extra)),
+            });
+        }
+        _ => {}
+    }
+
+    stream
+}
+
+fn xray_tls_settings(si: &StreamInfo) -> xray::TlsSettings {
+    xray::TlsSettings {
+        server_name: si.sni.clone(),
+        fingerprint: si.fingerprint.clone(),
+    }
+}
+
+/// Xray shadowsocks outbound, used both for proxies and relay hops
+fn xray_shadowsocks_outbound(
+    tag: &str,
+    host: &str,
+    port: i64,
+    settings_raw: &str,
+    user_uuid: &str,
+) -> xray::Outbound {
+    xray::Outbound {
+        tag: tag.to_string(),
+        protocol: xray::Protocol::Shadowsocks(xray::Servers {
+            servers: vec![xray::Server {
+                address: host.to_string(),
+                port,
+                method: Some(parse_ss_method(settings_raw)),
+                password: parse_ss_password(settings_raw, user_uuid),
+            }],
+        }),
+        stream_settings: None,
+    }
+}
+
+/// Xray outbound for one inbound, without its relay dialer
+fn xray_proxy_outbound(
+    node: &NodeInfo,
+    inbound: &caramba_db::models::network::Inbound,
+    tag: &str,
+    user_keys: &UserKeys,
+) -> Option<xray::Outbound> {
+    let si = parse_stream_settings(&inbound.stream_settings, node);
+    let endpoint_host = node.frontend_url.as_deref().unwrap_or(&node.address);
+    let vnext = |user: xray::User| xray::Vnext {
+        vnext: vec![xray::VnextServer {
+            address: endpoint_host.to_string(),
+            port: inbound.listen_port,
+            users: vec![user],
+        }],
+    };
+
+    let (protocol, stream) = match inbound.protocol.as_str() {
+        "vless" => (
+            xray::Protocol::Vless(vnext(xray::User {
+                id: user_keys.user_uuid.clone(),
+                encryption: Some("none".to_string()),
+                flow: Some(si.flow.clone()),
+                security: None,
+            })),
+            xray_stream_settings(&si),
+        ),
+        "vmess" => (
+            xray::Protocol::Vmess(vnext(xray::User {
+                id: user_keys.user_uuid.clone(),
+                encryption: None,
+                flow: None,
+                security: Some("auto".to_string()),
+            })),
+            xray_stream_settings(&si),
+        ),
+        "trojan" => {
+            let mut stream = xray_stream_settings(&si);
+            if stream.security.as_deref() == Some("none") {
+                // Trojan is always TLS-wrapped
+                stream.security = Some("tls".to_string());
+                stream.tls_settings = Some(xray_tls_settings(&si));
+            }
+            let server = xray::Server {
+                address: endpoint_host.to_string(),
+                port: inbound.listen_port,
+                method: None,
+                password: user_keys.user_uuid.clone(),
+            };
+            (
+                xray::Protocol::Trojan(xray::Servers {
+                    servers: vec![server],
+                }),
+                stream,
+            )
+        }
+        "shadowsocks" | "ss" => {
+            return Some(xray_shadowsocks_outbound(
+                tag,
+                endpoint_host,
+                inbound.listen_port,
+                &inbound.settings,
+                &user_keys.user_uuid,
+            ));
+        }
+        _ => return None,
+    };
+
+    Some(xray::Outbound {
+        tag: tag.to_string(),
+        protocol,
+        stream_settings: Some(stream),
+    })
+}
+
+/// The inbound's anti-DPI fragmentation, if `outbound` shakes hands over TLS
+fn xray_fragment(
+    node: &NodeInfo,
+    inbound: &caramba_db::models::network::Inbound,
+    outbound: &xray::Outbound,
+) -> Option<xray::Fragment> {
+    let security = outbound.stream_settings.as_ref()?.security.as_deref();
+    if !matches!(security, Some("tls" | "reality")) {
+        return None;
+    }
+    let fragment = parse_stream_settings(&inbound.stream_settings, node)
+        .anti_dpi
+        .fragment?;
+    Some(xray::Fragment {
+        packets: fragment.packets,
+        length: fragment.length,
+        interval: fragment.interval,
+    })
+}
+
+/// Generate a complete Xray-core JSON config (outbounds, routing, DNS).
+///
+/// Only protocols Xray-core can dial are emitted; Hysteria2, TUIC, Naive and
+/// AmneziaWG inbounds are skipped, as are outbounds that do not validate.
+/// Xray has no selector, so each chain becomes a balancer of its own.
+pub fn generate_xray_config(
+    _sub: &Subscription,
+    nodes: &[NodeInfo],
+    chains: &[Chain],
+    user_keys: &UserKeys,
+) -> Result<String> {
+    let mut outbounds = vec![];
+    let mut outbound_tags = vec![];
+    let mut node_tags: Vec<(&NodeInfo, Vec<String>)> = vec![];
+    let mut taken: HashSet<String> = ["direct", "block"].map(String::from).into();
+    let mut generated_relays: HashMap<String, String> = HashMap::new();
+    // Freedom outbounds splitting the ClientHello, one per distinct setting
+    let mut fragments: Vec<xray::Outbound> = vec![];
+
+    // 1. Generate Proxy Outbounds
+    for node in nodes {
+        let mut tags = vec![];
+        for inbound in node.inbounds.iter().filter(|i| i.enable) {
+            // ─── Relay Chaining Support (sockopt.dialerProxy) ────────────────
+            let mut dialer_proxy: Option<String> = None;
+            if let Some(relay) = &node.relay_info {
+                let relay_key = format!("relay_{}", relay.address);
+                if let Some(existing_tag) = generated_relays.get(&relay_key) {
+                    dialer_proxy = Some(existing_tag.clone());
+                } else if let Some(ri) = relay
+                    .inbounds
+                    .iter()
+                    .find(|i| i.enable && matches!(i.protocol.as_str(), "shadowsocks" | "ss"))
+                {
+                    let r_tag = unique_name(&mut taken, format!("relay_{}", relay.name));
+                    let r_ob = xray_shadowsocks_outbound(
+                        &r_tag,
+                        &relay.address,
+                        ri.listen_port,
+                        &ri.settings,
+                        &user_keys.user_uuid,
+                    );
+                    if let Some(r_ob) = checked("relay", &r_tag, r_ob, xray::Outbound::validate) {
+                        outbounds.push(r_ob);
+                        generated_relays.insert(relay_key, r_tag.clone());
+                        dialer_proxy = Some(r_tag);
+                    }
+                }
+            }
+            // ─────────────────────────────────────────────────────────────────
+
+            let tag = format!("{}_{}", node.name, inbound.tag);
+            let Some(mut outbound) = xray_proxy_outbound(node, inbound, &tag, user_keys) else {
+                continue;
+            };
+            // A relayed proxy's handshake is already inside the relay tunnel
+            let dialer_proxy = dialer_proxy.or_else(|| {
+                let fragment = xray_fragment(node, inbound, &outbound)?;
+                let existing = fragments.iter().find(|f| {
+                    matches!(&f.protocol, xray::Protocol::Freedom(freedom) if freedom.fragment.as_ref() == Some(&fragment))
+                });
+                if let Some(existing) = existing {
+                    return Some(existing.tag.clone());
+                }
+                let tag = unique_name(&mut taken, "fragment".to_string());
+                fragments.push(xray::Outbound {
+                    tag: tag.clone(),
+                    protocol: xray::Protocol::Freedom(xray::Freedom {
+                        fragment: Some(fragment),
+                    }),
+                    stream_settings: None,
+                });
+                Some(tag)
+            });
+            if let Some(dialer_proxy) = dialer_proxy {
+                outbound.stream_settings.get_or_insert_default().sockopt =
+                    Some(xray::Sockopt { dialer_proxy });
+            }
+            let Some(mut outbound) = checked("outbound", &tag, outbound, xray::Outbound::validate)
+            else {
+                continue;
+            };
+
+            outbound.tag = unique_name(&mut taken, tag);
+            tags.push(outbound.tag.clone());
+            outbound_tags.push(outbound.tag.clone());
+            outbounds.push(outbound);
+        }
+        node_tags.push((node, tags));
+    }
+
+    if outbound_tags.is_empty() {
+        return Ok(json!({}).to_string());
+    }
+
+    // dialerProxy takes an outbound, not a balancer: every exit proxy is
+    // chained through the first proxy of each entry node
+    let mut balancers = vec![xray::Balancer {
+        tag: "auto".to_string(),
+        selector: outbound_tags.clone(),
+        strategy: xray::BalancerStrategy {
+            kind: "leastPing".to_string(),
+        },
+    }];
+    let mut observed = outbound_tags.clone();
+    let mut balancer_tags: HashSet<String> = ["auto"].map(String::from).into();
+    for chain in chains {
+        let (entries, exits) = chain_members(chain, &node_tags);
+        let mut chained = vec![];
+        for entry in entries.iter().map(|names| &names[0]) {
+            for exit in &exits {
+                let Some(mut outbound) = outbounds.iter().find(|o| &o.tag == *exit).cloned() else {
+                    continue;
+                };
+                outbound.tag = unique_name(
+                    &mut taken,
+                    format!("{}: {} via {}", chain.name, exit, entry),
+                );
+                outbound.stream_settings.get_or_insert_default().sockopt = Some(xray::Sockopt {
+                    dialer_proxy: entry.clone(),
+                });
+                chained.push(outbound);
+            }
+        }
+        if chained.is_empty() {
+            continue;
+        }
+        let selector: Vec<String> = chained.iter().map(|o| o.tag.clone()).collect();
+        observed.extend(selector.iter().cloned());
+        balancers.push(xray::Balancer {
+            tag: unique_name(&mut balancer_tags, chain.name.clone()),
+            selector,
+            strategy: xray::BalancerStrategy {
+                kind: "leastPing".to_string(),
+            },
+        });
+        outbounds.extend(chained);
+    }
+
+    // 2. Proxies first, so Xray falls back to a proxy; then DIRECT and BLOCK
+    let mut final_outbounds = outbounds;
+    final_outbounds.extend(fragments);
+    final_outbounds.push(xray::Outbound {
+        tag: "direct".to_string(),
+        protocol: xray::Protocol::Freedom(xray::Freedom::default()),
+        stream_settings: None,
+    });
+    final_outbounds.push(xray::Outbound {
+        tag: "block".to_string(),
+        protocol: xray::Protocol::Blackhole(xray::Blackhole {
+            response: xray::BlackholeResponse {
+                kind: "http".to_string(),
+            },
+        }),
+        stream_settings: None,
+    });
+
+    // Aggregated Policies
+    let block_ads = nodes.iter().any(|n| n.config_block_ads);
+    let block_porn = nodes.iter().any(|n| n.config_block_porn);
+    let block_torrent = nodes.iter().any(|n| n.config_block_torrent);
+
+    // 3. DNS Configuration
+    let dns_config = xray::Dns {
+        servers: vec![
+            xray::DnsServer::Address("8.8.8.8".to_string()),
+            xray::DnsServer::Server {
+                address: "localhost".to_string(),
+                domains: ["geosite:cn", "geosite:ru", "geosite:private"]
+                    .map(String::from)
+                    .into(),
+                skip_fallback: true,
+            },
+        ],
+        query_strategy: "UseIPv4".to_string(),
+    };
+
+    // 4. Routing Rules
+    let list = |items: &[&str]| Some(items.iter().map(|s| s.to_string()).collect());
+    let to = |outbound: &str| xray::Rule {
+        outbound_tag: Some(outbound.to_string()),
+        ..xray::Rule::field()
+    };
+    let mut rules = vec![];
+    if block_ads {
+        rules.push(xray::Rule {
+            domain: list(&["geosite:category-ads-all"]),
+            ..to("block")
+        });
+    }
+    if block_porn {
+        rules.push(xray::Rule {
+            domain: list(&["geosite:category-porn"]),
+            ..to("block")
+        });
+    }
+    if block_torrent {
+        rules.push(xray::Rule {
+            protocol: list(&["bittorrent"]),
+            ..to("block")
+        });
+    }
+    rules.extend([
+        xray::Rule {
+            domain: list(&["geosite:private", "geosite:cn", "geosite:ru"]),
+            ..to("direct")
+        },
+        xray::Rule {
+            ip: list(&["geoip:private", "geoip:cn", "geoip:ru"]),
+            ..to("direct")
+        },
+        xray::Rule {
+            network: Some("tcp,udp".to_string()),
+            balancer_tag: Some("auto".to_string()),
+            ..xray::Rule::field()
+        },
+    ]);
+
+    // 5. Inbounds (SOCKS + HTTP for Client); sniffing feeds the policy rules
+    let sniffing = xray::Sniffing {
+        enabled: true,
+        dest_override: ["http", "tls", "quic"].map(String::from).into(),
+        route_only: true,
+    };
+    let inbounds_config = vec![
+        xray::Inbound {
+            tag: "socks-in".to_string(),
+            protocol: "socks".to_string(),
+            listen: "127.0.0.1".to_string(),
+            port: 10808,
+            settings: Some(json!({ "udp": true })),
+            sniffing: sniffing.clone(),
+        },
+        xray::Inbound {
+            tag: "http-in".to_string(),
+            protocol: "http".to_string(),
+            listen: "127.0.0.1".to_string(),
+            port: 10809,
+            settings: None,
+            sniffing,
+        },
+    ];
+
+    // 6. Final Assembly
+    let config = xray::Config {
+        log: xray::Log {
+            loglevel: "warning".to_string(),
+        },
+        dns: dns_config,
+        inbounds: inbounds_config,
+        outbounds: final_outbounds,
+        routing: xray::Routing {
+            domain_strategy: "IPIfNonMatch".to_string(),
+            rules,
+            balancers,
+        },
+        observatory: xray::Observatory {
+            subject_selector: observed,
+            probe_url: "https://www.gstatic.com/generate_204".to_string(),
+            probe_interval: "3m".to_string(),
+        },
+    };
+    config
+        .validate()
+        .context("Generated Xray config is invalid")?;
+
+    Ok(serde_json::to_string_pretty(&config)?)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// iOS Client Configs (Surge, Quantumult X, Loon, Shadowrocket)
+// ═══════════════════════════════════════════════════════════════════════════════
+
+const IOS_TEST_URL: &str = "http://www.gstatic.com/generate_204";
+const IOS_SKIP_PROXY: &str =
+    "127.0.0.1, 192.168.0.0/16, 10.0.0.0/8, 172.16.0.0/12, 100.64.0.0/10, localhost, *.local";
+
+/// Proxy name safe for the comma/equals separated iOS config formats
+fn ios_proxy_name(node: &NodeInfo, inbound: &caramba_db::models::network::Inbound) -> String {
+    format!(
+        "{} - {}",
+        node.name,
+        inbound.remark.as_deref().unwrap_or(&inbound.tag)
+    )
+    .replace([',', '=', '"'], " ")
+}
+
+/// An enabled inbound prepared for one proxy line
+struct IosProxy<'a> {
+    name: String,
+    si: StreamInfo,
+    inbound: &'a caramba_db::models::network::Inbound,
+    host: &'a str,
+}
+
+fn ios_proxies(nodes: &[NodeInfo]) -> Vec<IosProxy<'_>> {
+    let mut out = Vec::new();
+    for node in nodes {
+        let host = node.frontend_url.as_deref().unwrap_or(&node.address);
+        for inbound in node.inbounds.iter().filter(|i| i.enable) {
+            out.push(IosProxy {
+                name: ios_proxy_name(node, inbound),
+                si: parse_stream_settings(&inbound.stream_settings, node),
+                inbound,
+                host,
+            });
+        }
+    }
+    out
+}
+
+/// Surge `.conf` profile. Surge has no VLESS, and no Reality or gRPC/XHTTP
+/// transports, so those inbounds are skipped.
+pub fn generate_surge_config(
+    _sub: &Subscription,
+    nodes: &[NodeInfo],
+    user_keys: &UserKeys,
+) -> Result<String> {
+    let mut names = Vec::new();
+    let mut proxies = Vec::new();
+
+    for IosProxy {
+        name,
+        si,
+        inbound,
+        host,
+    } in ios_proxies(nodes)
+    {
+        let port = inbound.listen_port;
+        let ws = |params: &mut Vec<String>| {
+            if si.network == "ws" {
+                params.push("ws=true".to_string());
+                params.push(format!("ws-path={}", si.ws_path));
+                params.push(format!("ws-headers=Host:{}", si.sni));
+            }
+        };
+        let transport_ok = matches!(si.network.as_str(), "tcp" | "ws");
+
+        let line = match inbound.protocol.as_str() {
+            "shadowsocks" | "ss" => format!(
+                "{} = ss, {}, {}, encrypt-method={}, password={}, udp-relay=true",
+                name,
+                host,
+                port,
+                parse_ss_method(&inbound.settings),
+                parse_ss_password(&inbound.settings, &user_keys.user_uuid)
+            ),
+            "vmess" if transport_ok && si.security != "reality" => {
+                let mut params = vec![
+                    format!("username={}", user_keys.user_uuid),
+                    "vmess-aead=true".to_string(),
+                ];
+                ws(&mut params);
+                if si.security == "tls" {
+                    params.push("tls=true".to_string());
+                    params.push(format!("sni={}", si.sni));
+                }
+                format!(
+                    "{} = vmess, {}, {}, {}",
+                    name,
+                    host,
+                    port,
+                    params.join(", ")
+                )
+            }
+            "trojan" if transport_ok && si.security != "reality" => {
+                let mut params = vec![
+                    format!("password={}", user_keys.user_uuid),
+                    format!("sni={}", si.sni),
+                ];
+                ws(&mut params);
+                format!(
+                    "{} = trojan, {}, {}, {}",
+                    name,
+                    host,
+                    port,
+                    params.join(", ")
+                )
+            }
+            "hysteria2" | "hy2" => {
+                let mut params = vec![
+                    format!("password={}", user_keys.hy2_password),
+                    format!("sni={}", si.sni),
+                    "skip-cert-verify=true".to_string(),
+                ];
+                if let Some(ports) = &si.hy2_ports {
+                    params.push(format!("port-hopping={}", ports.replace(',', ";")));
+                }
+                if let Some(obfs) = &si.hy2_obfs {
+                    params.push(format!("salamander-password={}", obfs));
+                }
+                format!(
+                    "{} = hysteria2, {}, {}, {}",
+                    name,
+                    host,
+                    port,
+                    params.join(", ")
+                )
+            }
+            "tuic" => format!(
+                "{} = tuic-v5, {}, {}, uuid={}, password={}, sni={}, alpn=h3, skip-cert-verify=true",
+                name, host, port, user_keys.user_uuid, user_keys.hy2_password, si.sni
+            ),
+            _ => continue,
+        };
+        names.push(name);
+        proxies.push(line);
+    }
+
+    if names.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut conf = vec![
+        "[General]".to_string(),
+        "loglevel = notify".to_string(),
+        format!("skip-proxy = {}", IOS_SKIP_PROXY),
+        "dns-server = system, 8.8.8.8, 1.1.1.1".to_string(),
+        format!("proxy-test-url = {}", IOS_TEST_URL),
+        String::new(),
+        "[Proxy]".to_string(),
+        "DIRECT = direct".to_string(),
+    ];
+    conf.extend(proxies);
+    conf.extend([
+        String::new(),
+        "[Proxy Group]".to_string(),
+        format!("Proxy = select, Auto, {}", names.join(", ")),
+        format!(
+            "Auto = url-test, {}, url={}, interval=600, tolerance=50",
+            names.join(", "),
+            IOS_TEST_URL
+        ),
+        String::new(),
+        "[Rule]".to_string(),
+        "GEOIP,CN,DIRECT".to_string(),
+        "GEOIP,RU,DIRECT".to_string(),
+        "FINAL,Proxy,dns-failed".to_string(),
+    ]);
+    Ok(conf.join("\n") + "\n")
+}
+
+/// Quantumult X profile. Quantumult X has no Hysteria2/TUIC and only
+/// plain TCP or WebSocket transports, so other inbounds are skipped.
+pub fn generate_quanx_config(
+    _sub: &Subscription,
+    nodes: &[NodeInfo],
+    user_keys: &UserKeys,
+) -> Result<String> {
+    let mut names = Vec::new();
+    let mut servers = Vec::new();
+
+    for IosProxy {
+        name,
+        si,
+        inbound,
+        host,
+    } in ios_proxies(nodes)
+    {
+        if !matches!(si.network.as_str(), "tcp" | "ws") {
+            continue;
+        }
+        let endpoint = format!("{}:{}", host, inbound.listen_port);
+        let tls = si.security == "tls" || si.security == "reality";
+        // Transport/TLS options shared by VMess, Trojan and VLESS
+        let mut obfs = Vec::new();
+        if si.network == "ws" {
+            obfs.push(format!("obfs={}", if tls { "wss" } else { "ws" }));
+            obfs.push(format!("obfs-host={}", si.sni));
+            obfs.push(format!("obfs-uri={}", si.ws_path));
+        } else if tls {
+            obfs.push("obfs=over-tls".to_string());
+            obfs.push(format!("obfs-host={}", si.sni));
+        }
+
+        let line = match inbound.protocol.as_str() {
+            "shadowsocks" | "ss" => format!(
+                "shadowsocks={}, method={}, password={}, udp-relay=true",
+                endpoint,
+                parse_ss_method(&inbound.settings),
+                parse_ss_password(&inbound.settings, &user_keys.user_uuid)
+            ),
+            "vmess" if si.security != "reality" => {
+                let mut params = vec![
+                    endpoint,
+                    "method=chacha20-ietf-poly1305".to_string(),
+                    format!("password={}", user_keys.user_uuid),
+                    "aead=true".to_string(),
+                ];
+                params.extend(obfs);
+                format!("vmess={}", params.join(", "))
+            }
+            "trojan" if si.security != "reality" => {
+                let mut params = vec![endpoint, format!("password={}", user_keys.user_uuid)];
+                if si.network == "ws" {
+                    params.extend(obfs);
+                } else {
+                    params.push("over-tls=true".to_string());
+                    params.push(format!("tls-host={}", si.sni));
+                }
+                format!("trojan={}", params.join(", "))
+            }
+            "vless" => {
+                let mut params = vec![
+                    endpoint,
+                    "method=none".to_string(),
+                    format!("password={}", user_keys.user_uuid),
+                ];
+                params.extend(obfs);
+                if si.security == "reality" {
+                    params.push(format!("reality-base64-pubkey={}", si.public_key));
+                    params.push(format!("reality-hex-shortid={}", si.short_id));
+                }
+                if !si.flow.is_empty() {
+                    params.push(format!("vless-flow={}", si.flow));
+                }
+                format!("vless={}", params.join(", "))
+            }
+            _ => continue,
+        };
+        servers.push(format!("{}, tag={}", line, name));
+        names.push(name);
+    }
+
+    if names.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut conf = vec![
+        "[general]".to_string(),
+        format!("server_check_url={}", IOS_TEST_URL),
+        String::new(),
+        "[dns]".to_string(),
+        "server=8.8.8.8".to_string(),
+        "server=1.1.1.1".to_string(),
+        String::new(),
+        "[policy]".to_string(),
+        format!("static=Proxy, Auto, {}", names.join(", ")),
+        format!(
+            "url-latency-benchmark=Auto, {}, check-interval=600, tolerance=50",
+            names.join(", ")
+        ),
+        String::new(),
+        "[server_local]".to_string(),
+    ];
+    conf.extend(servers);
+    conf.extend([
+        String::new(),
+        "[filter_local]".to_string(),
+        "geoip, cn, direct".to_string(),
+        "geoip, ru, direct".to_string(),
+        "final, Proxy".to_string(),
+    ]);
+    Ok(conf.join("\n") + "\n")
+}
+
+/// Loon profile. Loon has no TUIC and only TCP or WebSocket transports,
+/// so other inbounds are skipped.
+pub fn generate_loon_config(
+    _sub: &Subscription,
+    nodes: &[NodeInfo],
+    user_keys: &UserKeys,
+) -> Result<String> {
+    let mut names = Vec::new();
+    let mut proxies = Vec::new();
+
+    for IosProxy {
+        name,
+        si,
+        inbound,
+        host,
+    } in ios_proxies(nodes)
+    {
+        let port = inbound.listen_port;
+        let transport_ok = matches!(si.network.as_str(), "tcp" | "ws");
+        let mut transport = vec![format!("transport={}", si.network)];
+        if si.network == "ws" {
+            transport.push(format!("path={}", si.ws_path));
+            transport.push(format!("host={}", si.sni));
+        }
+
+        let line = match inbound.protocol.as_str() {
+            "shadowsocks" | "ss" => format!(
+                "{} = Shadowsocks,{},{},{},\"{}\",udp=true",
+                name,
+                host,
+                port,
+                parse_ss_method(&inbound.settings),
+                parse_ss_password(&inbound.settings, &user_keys.user_uuid)
+            ),
+            "vmess" if transport_ok && si.security != "reality" => {
+                let mut params = transport;
+                if si.security == "tls" {
+                    params.push("over-tls=true".to_string());
+                    params.push(format!("sni={}", si.sni));
+                }
+                format!(
+                    "{} = vmess,{},{},auto,\"{}\",{}",
+                    name,
+                    host,
+                    port,
+                    user_keys.user_uuid,
+                    params.join(",")
+                )
+            }
+            "trojan" if transport_ok && si.security != "reality" => {
+                let mut params = transport;
+                params.push(format!("sni={}", si.sni));
+                format!(
+                    "{} = trojan,{},{},\"{}\",{}",
+                    name,
+                    host,
+                    port,
+                    user_keys.user_uuid,
+                    params.join(",")
+                )
+            }
+            "vless" if transport_ok => {
+                let mut params = transport;
+                if !si.flow.is_empty() {
+                    params.push(format!("flow={}", si.flow));
+                }
+                if si.security == "reality" {
+                    params.push(format!("public-key=\"{}\"", si.public_key));
+                    params.push(format!("short-id={}", si.short_id));
+                }
+                if si.security == "reality" || si.security == "tls" {
+                    params.push("over-tls=true".to_string());
+                    params.push(format!("sni={}", si.sni));
+                }
+                format!(
+                    "{} = VLESS,{},{},\"{}\",{}",
+                    name,
+                    host,
+                    port,
+                    user_keys.user_uuid,
+                    params.join(",")
+                )
+            }
+            "hysteria2" | "hy2" => {
+                let mut params = vec![
+                    format!("sni={}", si.sni),
+                    "skip-cert-verify=true".to_string(),
+                ];
+                if let Some(obfs) = &si.hy2_obfs {
+                    params.push(format!("salamander-password={}", obfs));
+                }
+                format!(
+                    "{} = Hysteria2,{},{},\"{}\",{}",
+                    name,
+                    host,
+                    port,
+                    user_keys.hy2_password,
+                    params.join(",")
+                )
+            }
+            _ => continue,
+        };
+        names.push(name);
+        proxies.push(line);
+    }
+
+    if names.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut conf = vec![
+        "[General]".to_string(),
+        format!("skip-proxy = {}", IOS_SKIP_PROXY),
+        "dns-server = system,8.8.8.8,1.1.1.1".to_string(),
+        String::new(),
+        "[Proxy]".to_string(),
+    ];
+    conf.extend(proxies);
+    conf.extend([
+        String::new(),
+        "[Proxy Group]".to_string(),
+        format!("Proxy = select,Auto,{}", names.join(",")),
+        format!(
+            "Auto = url-test,{},url={},interval=600",
+            names.join(","),
+            IOS_TEST_URL
+        ),
+        String::new(),
+        "[Rule]".to_string(),
+        "GEOIP,CN,DIRECT".to_string(),
+        "GEOIP,RU,DIRECT".to_string(),
+        "FINAL,Proxy".to_string(),
+    ]);
+    Ok(conf.join("\n") + "\n")
+}
+
+/// Shadowrocket subscription: base64 share links like the V2Ray output,
+/// limited to the protocols Shadowrocket imports.
+pub fn generate_shadowrocket_config(
+    sub: &Subscription,
+    nodes: &[NodeInfo],
+    user_keys: &UserKeys,
+) -> Result<String> {
+    let supported: Vec<NodeInfo> = nodes
+        .iter()
+        .filter_map(|node| {
+            let mut node = node.clone();
+            if node.inbounds.is_empty() {
+                // Legacy VLESS/HY2 links only
+                return Some(node);
+            }
+            node.inbounds.retain(|i| {
+                matches!(
+                    i.protocol.as_str(),
+                    "vless"
+                        | "vmess"
+                        | "trojan"
+                        | "shadowsocks"
+                        | "ss"
+                        | "hysteria2"
+                        | "hy2"
+                        | "tuic"
+                )
+            });
+            // Emptied nodes would otherwise fall back to legacy links
+            (!node.inbounds.is_empty()).then_some(node)
+        })
+        .collect();
+    generate_v2ray_config(sub, &supported, user_keys)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Shadowsocks Online Config (SIP008) and Outline Dynamic Access Keys
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Every enabled Shadowsocks inbound as a SIP008 server object. Values are
+/// read from the current inbound, so rotated ports and passwords are
+/// picked up on the client's next fetch.
+fn shadowsocks_servers(sub: &Subscription, nodes: &[NodeInfo], user_keys: &UserKeys) -> Vec<Value> {
+    use sha2::{Digest, Sha256};
+
+    let mut servers = Vec::new();
+    for node in nodes {
+        let host = node.frontend_url.as_deref().unwrap_or(&node.address);
+        for inbound in node.inbounds.iter().filter(|i| i.enable) {
+            if !matches!(inbound.protocol.as_str(), "shadowsocks" | "ss") {
+                continue;
+            }
+            // SIP008 ids must stay the same across fetches; derive one from
+            // the subscription, node and inbound tag.
+            let digest = Sha256::digest(
+                format!("{}:{}:{}", sub.subscription_uuid, node.name, inbound.tag).as_bytes(),
+            );
+            let mut id = [0u8; 16];
+            id.copy_from_slice(&digest[..16]);
+
+            servers.push(json!({
+                "id": uuid::Uuid::from_bytes(id).to_string(),
+                "remarks": format!(
+                    "{} - {}",
+                    node.name,
+                    inbound.remark.as_deref().unwrap_or(&inbound.tag)
+                ),
+                "server": host,
+                "server_port": inbound.listen_port,
+                "password": parse_ss_password(&inbound.settings, &user_keys.user_uuid),
+                "method": parse_ss_method(&inbound.settings),
+            }));
+        }
+    }
+    servers
+}
+
+/// Generate a SIP008 online config listing every Shadowsocks server
+pub fn generate_sip008_config(
+    sub: &Subscription,
+    nodes: &[NodeInfo],
+    user_keys: &UserKeys,
+) -> Result<String> {
+    let config = json!({
+        "version": 1,
+        "servers": shadowsocks_servers(sub, nodes, user_keys),
+        "bytes_used": sub.used_traffic.max(0),
+    });
+    Ok(serde_json::to_string_pretty(&config)?)
+}
+
+/// Generate the document behind an Outline `ssconf://` dynamic access key.
+///
+/// Outline follows a single server, so the first Shadowsocks server is
+/// used; `?node_id=` on the key URL picks a specific node. Without any
+/// Shadowsocks server an Outline error object is returned, which the
+/// client shows to the user.
+pub fn generate_outline_config(
+    sub: &Subscription,
+    nodes: &[NodeInfo],
+    user_keys: &UserKeys,
+) -> Result<String> {
+    let config = match shadowsocks_servers(sub, nodes, user_keys)
+        .into_iter()
+        .next()
+    {
+        Some(server) => json!({
+            "server": server["server"],
+            "server_port": server["server_port"],
+            "password": server["password"],
+            "method": server["method"],
+        }),
+        None => json!({
+            "error": { "message": "No Shadowsocks server is available for this subscription" }
+        }),
+    };
+    Ok(serde_json::to_string(&config)?)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Admin Subscription Templates
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Client formats that can be rendered from an admin template
+pub const TEMPLATE_FORMATS: [&str; 2] = ["singbox", "clash"];
+
+/// Variables a template of `format` may reference as `"{{name}}"`; the
+/// first one is required so the template actually carries the proxies.
+pub fn template_variables(format: &str) -> &'static [&'static str] {
+    match format {
+        "singbox" => &["outbounds", "outbound_tags", "chain_tags"],
+        "clash" => &[
+            "proxies",
+            "proxy_names",
+            "chain_names",
+            "proxy_groups",
+            "rule_providers",
+            "rules",
+        ],
+        _ => &[],
+    }
+}
+
+/// Name of the variable if `s` is a `{{name}}` placeholder
+fn placeholder(s: &str) -> Option<&str> {
+    s.trim()
+        .strip_prefix("{{")?
+        .strip_suffix("}}")
+        .map(str::trim)
+}
+
+/// Replaces placeholders in `value`. A placeholder that is an array element
+/// and stands for an array is spliced in, so `["direct", "{{outbound_tags}}"]`
+/// lists the generated tags after `direct`.
+fn substitute(value: &mut Value, vars: &serde_json::Map<String, Value>) -> Result<()> {
+    let lookup = |name: &str| {
+        vars.get(name)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown template variable {{{{{}}}}}", name))
+    };
+
+    match value {
+        Value::String(s) => {
+            if let Some(name) = placeholder(s) {
+                *value = lookup(name)?;
+            }
+        }
+        Value::Array(items) => {
+            let mut out = Vec::with_capacity(items.len());
+            for mut item in std::mem::take(items) {
+                if let Some(name) = item.as_str().and_then(placeholder) {
+                    match lookup(name)? {
+                        Value::Array(spliced) => out.extend(spliced),
+                        other => out.push(other),
+                    }
+                } else {
+                    substitute(&mut item, vars)?;
+                    out.push(item);
+                }
+            }
+            *items = out;
+        }
+        Value::Object(map) => {
+            for item in map.values_mut() {
+                substitute(item, vars)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn collect_placeholders(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::String(s) => out.extend(placeholder(s).map(str::to_string)),
+        Value::Array(items) => items.iter().for_each(|v| collect_placeholders(v, out)),
+        Value::Object(map) => map.values().for_each(|v| collect_placeholders(v, out)),
+        _ => {}
+    }
+}
+
+fn parse_template(format: &str, body: &str) -> Result<Value> {
+    let value: Value = match format {
+        "singbox" => serde_json::from_str(body)
+            .map_err(|e| anyhow::anyhow!("Template is not valid JSON: {}", e))?,
+        "clash" => serde_yaml::from_str(body)
+            .map_err(|e| anyhow::anyhow!("Template is not valid YAML: {}", e))?,
+        other => anyhow::bail!("Templates are not supported for {}", other),
+    };
+    if !value.is_object() {
+        anyhow::bail!("Template must be a JSON/YAML object at the top level");
+    }
+    Ok(value)
+}
+
+/// The spliced proxies are valid on their own, but the template around them
+/// may still reuse one of their tags.
+fn check_rendered(format: &str, config: &Value) -> Result<()> {
+    let names = |key: &str, field: &'static str| {
+        config
+            .get(key)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(move |item| item.get(field).and_then(Value::as_str))
+    };
+    match format {
+        "singbox" => model::unique("outbound tag", names("outbounds", "tag"))?,
+        _ => model::unique(
+            "proxy or group name",
+            names("proxies", "name").chain(names("proxy-groups", "name")),
+        )?,
+    };
+    Ok(())
+}
+
+/// Render an admin template of `format` with the generated proxies.
+pub fn render_template(
+    format: &str,
+    body: &str,
+    nodes: &[NodeInfo],
+    chains: &[Chain],
+    user_keys: &UserKeys,
+) -> Result<String> {
+    let mut config = parse_template(format, body)?;
+    let mut vars = serde_json::Map::new();
+
+    match format {
+        "singbox" => {
+            let (outbounds, tags, chain_tags) = singbox_proxy_outbounds(nodes, chains, user_keys);
+            vars.insert("outbounds".to_string(), json!(outbounds));
+            vars.insert("outbound_tags".to_string(), json!(tags));
+            vars.insert("chain_tags".to_string(), json!(chain_tags));
+        }
+        _ => {
+            let ClashProxies {
+                proxies,
+                node_proxies,
+                chain_groups,
+            } = clash_proxies(nodes, chains, user_keys);
+            let names: Vec<&String> = node_proxies.iter().flat_map(|(_, n)| n).collect();
+            let chain_names: Vec<&String> = chain_groups.iter().map(|(_, c)| &c.name).collect();
+            vars.insert("proxy_names".to_string(), json!(names));
+            vars.insert("chain_names".to_string(), json!(chain_names));
+            vars.insert("proxies".to_string(), json!(proxies));
+            vars.insert(
+                "proxy_groups".to_string(),
+                json!(clash_proxy_groups(&node_proxies, chain_groups)),
+            );
+            vars.insert(
+                "rule_providers".to_string(),
+                json!(clash_rule_providers(nodes)),
+            );
+            vars.insert("rules".to_string(), json!(clash_rules(nodes)));
+        }
+    }
+
+    substitute(&mut config, &vars)?;
+    check_rendered(format, &config)?;
+    match format {
+        "singbox" => Ok(serde_json::to_string_pretty(&config)?),
+        _ => Ok(serde_yaml::to_string(&config)?),
+    }
+}
+
+/// Check a template before it is saved: it must parse, reference the
+/// format's required variable, use no unknown variables and render.
+pub fn validate_template(format: &str, body: &str) -> Result<()> {
+    let mut used = Vec::new();
+    collect_placeholders(&parse_template(format, body)?, &mut used);
+    let known = template_variables(format);
+    if let Some(unknown) = used.iter().find(|v| !known.contains(&v.as_str())) {
+        anyhow::bail!(
+            "Unknown template variable {{{{{}}}}}; available: {}",
+            unknown,
+            known.join(", ")
+        );
+    }
+    if !used.iter().any(|v| v == known[0]) {
+        anyhow::bail!("Template must include \"{{{{{}}}}}\"", known[0]);
+    }
+
+    let sample = NodeInfo {
+        name: "Sample".to_string(),
+        address: "203.0.113.1".to_string(),
+        reality_port: Some(443),
+        reality_sni: Some("www.microsoft.com".to_string()),
+        reality_public_key: Some("sample".to_string()),
+        reality_short_id: Some("ab".to_string()),
+        hy2_port: None,
+        hy2_sni: None,
+        frontend_url: None,
+        inbounds: vec![],
+        relay_info: None,
+        country_code: Some("NL".to_string()),
+        groups: vec![],
+        config_block_ads: true,
+        config_block_porn: true,
+        config_block_torrent: true,
+    };
+    let keys = UserKeys {
+        user_uuid: "00000000-0000-0000-0000-000000000000".to_string(),
+        hy2_password: "sample".to_string(),
+        awg_private_key: None,
+        awg_address: None,
+    };
+    let chain = Chain {
+        name: "Sample chain".to_string(),
+        entry_group: "Entry".to_string(),
+        exit_group: "Exit".to_string(),
+    };
+    render_template(format, body, &[sample], &[chain], &keys)?;
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Format Dispatch
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Everything a subscription's config is generated from. The panel serves
+/// it to subscription workers, which then render what the panel would.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ConfigInput {
+    pub subscription: Subscription,
+    pub nodes: Vec<NodeInfo>,
+    pub keys: UserKeys,
+    /// Admin template of the requested format, if the plan has one
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Client-side chains the plan offers
+    #[serde(default)]
+    pub chains: Vec<Chain>,
+}
+
+/// Content type and download filename of each raw config format
+pub fn client_output(format: &str) -> (&'static str, &'static str) {
+    match format {
+        "clash" => ("application/yaml", "config.yaml"),
+        "v2ray" => ("text/plain", "config.txt"),
+        "xray" => ("application/json", "xray.json"),
+        "surge" => ("text/plain", "surge.conf"),
+        "quanx" => ("text/plain", "quantumult.conf"),
+        "loon" => ("text/plain", "loon.conf"),
+        "shadowrocket" => ("text/plain", "shadowrocket.txt"),
+        "sip008" => ("application/json", "sip008.json"),
+        "outline" => ("application/json", "outline.json"),
+        _ => ("application/json", "config.json"),
+    }
+}
+
+/// Built-in config of `format`; unknown formats get sing-box. Only sing-box,
+/// Clash and Xray render `chains`.
+pub fn generate(
+    format: &str,
+    sub: &Subscription,
+    nodes: &[NodeInfo],
+    chains: &[Chain],
+    user_keys: &UserKeys,
+) -> Result<String> {
+    match format {
+        "clash" => generate_clash_config(sub, nodes, chains, user_keys),
+        "v2ray" => generate_v2ray_config(sub, nodes, user_keys),
+        "xray" => generate_xray_config(sub, nodes, chains, user_keys),
+        "surge" => generate_surge_config(sub, nodes, user_keys),
+        "quanx" => generate_quanx_config(sub, nodes, user_keys),
+        "loon" => generate_loon_config(sub, nodes, user_keys),
+        "shadowrocket" => generate_shadowrocket_config(sub, nodes, user_keys),
+        "sip008" => generate_sip008_config(sub, nodes, user_keys),
+        "outline" => generate_outline_config(sub, nodes, user_keys),
+        _ => generate_singbox_config(sub, nodes, chains, user_keys),
+    }
+}
+
+/// Config of `format` for `input`: the admin template when there is one
+/// and it renders, the built-in layout otherwise.
+pub fn render(format: &str, input: &ConfigInput) -> Result<String> {
+    if let Some(body) = &input.template
+        && TEMPLATE_FORMATS.contains(&format)
+    {
+        match render_template(format, body, &input.nodes, &input.chains, &input.keys) {
+            Ok(content) => return Ok(content),
+            Err(e) => tracing::warn!("Subscription template for {} failed: {}", format, e),
+        }
+    }
+    generate(
+        format,
+        &input.subscription,
+        &input.nodes,
+        &input.chains,
+        &input.keys,
+    )
+}
+
+#[cfg(test)]
+mod tests;
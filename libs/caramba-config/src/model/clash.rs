@@ -0,0 +1,411 @@
+//! Clash Meta (mihomo) config.
+
+use super::{check_endpoint, check_path, check_present, resolves, unique};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Policies every Clash core knows without a proxy or group of that name
+pub const BUILT_IN: [&str; 4] = ["DIRECT", "REJECT", "REJECT-DROP", "PASS"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct Config {
+    pub proxies: Vec<Proxy>,
+    pub proxy_groups: Vec<ProxyGroup>,
+    pub rule_providers: BTreeMap<String, RuleProvider>,
+    pub rules: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Proxy {
+    Vless(Vless),
+    Vmess(Vmess),
+    Trojan(Trojan),
+    Ss(Shadowsocks),
+    Hysteria2(Hysteria2),
+    #[serde(rename = "wireguard")]
+    WireGuard(WireGuard),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct Vless {
+    pub name: String,
+    pub server: String,
+    pub port: i64,
+    pub uuid: String,
+    pub network: String,
+    pub client_fingerprint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flow: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub servername: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reality_opts: Option<RealityOpts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ws_opts: Option<WsOpts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grpc_opts: Option<GrpcOpts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smux: Option<Smux>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dialer_proxy: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct Vmess {
+    pub name: String,
+    pub server: String,
+    pub port: i64,
+    pub uuid: String,
+    #[serde(rename = "alterId")]
+    pub alter_id: u32,
+    pub cipher: String,
+    pub network: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub servername: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ws_opts: Option<WsOpts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grpc_opts: Option<GrpcOpts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smux: Option<Smux>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dialer_proxy: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct Trojan {
+    pub name: String,
+    pub server: String,
+    pub port: i64,
+    pub password: String,
+    pub sni: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_fingerprint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reality_opts: Option<RealityOpts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ws_opts: Option<WsOpts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grpc_opts: Option<GrpcOpts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smux: Option<Smux>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dialer_proxy: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Shadowsocks {
+    pub name: String,
+    pub server: String,
+    pub port: i64,
+    pub cipher: String,
+    pub password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smux: Option<Smux>,
+    #[serde(rename = "dialer-proxy", skip_serializing_if = "Option::is_none")]
+    pub dialer_proxy: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct Hysteria2 {
+    pub name: String,
+    pub server: String,
+    pub port: i64,
+    pub password: String,
+    pub sni: String,
+    pub skip_cert_verify: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dialer_proxy: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct WireGuard {
+    pub name: String,
+    pub server: String,
+    pub port: i64,
+    pub ip: String,
+    pub private_key: String,
+    pub public_key: String,
+    pub udp: bool,
+    pub mtu: u32,
+    /// Junk packet and header parameters, as configured on the inbound
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amnezia_wg: Option<BTreeMap<String, Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dialer_proxy: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct RealityOpts {
+    pub public_key: String,
+    pub short_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WsOpts {
+    pub path: String,
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct GrpcOpts {
+    pub grpc_service_name: String,
+}
+
+/// sing-mux multiplexing
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct Smux {
+    pub enabled: bool,
+    pub protocol: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<u32>,
+    pub padding: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brutal_opts: Option<BrutalOpts>,
+}
+
+/// TCP Brutal bandwidth in Mbps
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BrutalOpts {
+    pub enabled: bool,
+    pub up: u32,
+    pub down: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProxyGroup {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub proxies: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RuleProvider {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub behavior: String,
+    pub format: String,
+    pub url: String,
+    pub path: String,
+    pub interval: u32,
+}
+
+impl Proxy {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Vless(p) => &p.name,
+            Self::Vmess(p) => &p.name,
+            Self::Trojan(p) => &p.name,
+            Self::Ss(p) => &p.name,
+            Self::Hysteria2(p) => &p.name,
+            Self::WireGuard(p) => &p.name,
+        }
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        match self {
+            Self::Vless(p) => p.name = name,
+            Self::Vmess(p) => p.name = name,
+            Self::Trojan(p) => p.name = name,
+            Self::Ss(p) => p.name = name,
+            Self::Hysteria2(p) => p.name = name,
+            Self::WireGuard(p) => p.name = name,
+        }
+    }
+
+    fn dialer_proxy(&self) -> Option<&str> {
+        match self {
+            Self::Vless(p) => p.dialer_proxy.as_deref(),
+            Self::Vmess(p) => p.dialer_proxy.as_deref(),
+            Self::Trojan(p) => p.dialer_proxy.as_deref(),
+            Self::Ss(p) => p.dialer_proxy.as_deref(),
+            Self::Hysteria2(p) => p.dialer_proxy.as_deref(),
+            Self::WireGuard(p) => p.dialer_proxy.as_deref(),
+        }
+    }
+
+    /// Sets the proxy or group this one is dialed through
+    pub fn set_dialer_proxy(&mut self, name: String) {
+        let dialer_proxy = match self {
+            Self::Vless(p) => &mut p.dialer_proxy,
+            Self::Vmess(p) => &mut p.dialer_proxy,
+            Self::Trojan(p) => &mut p.dialer_proxy,
+            Self::Ss(p) => &mut p.dialer_proxy,
+            Self::Hysteria2(p) => &mut p.dialer_proxy,
+            Self::WireGuard(p) => &mut p.dialer_proxy,
+        };
+        *dialer_proxy = Some(name);
+    }
+
+    /// Checks a proxy on its own: endpoint, credentials, TLS and transport.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::Vless(p) => {
+                check_endpoint(&p.server, p.port)?;
+                check_present("uuid", &p.uuid)?;
+                let tls = p.tls == Some(true);
+                if tls {
+                    check_present("servername", p.servername.as_deref().unwrap_or_default())?;
+                }
+                if let Some(reality) = &p.reality_opts {
+                    if !tls {
+                        bail!("reality-opts without tls");
+                    }
+                    check_present("reality public-key", &reality.public_key)?;
+                }
+                if let Some(flow) = &p.flow
+                    && (!tls || p.network != "tcp")
+                {
+                    bail!("flow {} needs TLS over raw TCP", flow);
+                }
+                if let Some(flow) = &p.flow
+                    && p.smux.as_ref().is_some_and(|m| m.enabled)
+                {
+                    bail!("flow {} cannot be multiplexed", flow);
+                }
+                check_transport(&p.network, p.ws_opts.as_ref(), p.grpc_opts.as_ref())
+            }
+            Self::Vmess(p) => {
+                check_endpoint(&p.server, p.port)?;
+                check_present("uuid", &p.uuid)?;
+                if p.tls == Some(true) {
+                    check_present("servername", p.servername.as_deref().unwrap_or_default())?;
+                }
+                check_transport(&p.network, p.ws_opts.as_ref(), p.grpc_opts.as_ref())
+            }
+            Self::Trojan(p) => {
+                check_endpoint(&p.server, p.port)?;
+                check_present("password", &p.password)?;
+                check_present("sni", &p.sni)?;
+                if let Some(reality) = &p.reality_opts {
+                    check_present("reality public-key", &reality.public_key)?;
+                }
+                let network = p.network.as_deref().unwrap_or("tcp");
+                check_transport(network, p.ws_opts.as_ref(), p.grpc_opts.as_ref())
+            }
+            Self::Ss(p) => {
+                check_endpoint(&p.server, p.port)?;
+                check_present("cipher", &p.cipher)?;
+                check_present("password", &p.password)
+            }
+            Self::Hysteria2(p) => {
+                check_endpoint(&p.server, p.port)?;
+                check_present("password", &p.password)?;
+                check_present("sni", &p.sni)
+            }
+            Self::WireGuard(p) => {
+                check_endpoint(&p.server, p.port)?;
+                check_present("ip", &p.ip)?;
+                check_present("private-key", &p.private_key)?;
+                check_present("public-key", &p.public_key)
+            }
+        }
+    }
+}
+
+/// The options block a transport needs must be there and be usable.
+fn check_transport(network: &str, ws: Option<&WsOpts>, grpc: Option<&GrpcOpts>) -> Result<()> {
+    match network {
+        "tcp" => Ok(()),
+        "ws" => check_path(&ws.context("ws without ws-opts")?.path),
+        "grpc" => check_present(
+            "grpc-service-name",
+            &grpc.context("grpc without grpc-opts")?.grpc_service_name,
+        ),
+        other => bail!("unsupported network {}", other),
+    }
+}
+
+impl ProxyGroup {
+    /// A group that health-checks its members
+    pub fn auto(name: &str, kind: &str, proxies: &[String], url: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            proxies: proxies.to_vec(),
+            url: Some(url.to_string()),
+            interval: Some(300),
+            tolerance: (kind == "url-test").then_some(50),
+            strategy: (kind == "load-balance").then(|| "consistent-hashing".to_string()),
+        }
+    }
+}
+
+impl Config {
+    /// Checks the whole document: every proxy is valid, proxy and group
+    /// names are unique and groups, dialer proxies and rules only refer to
+    /// known policies.
+    pub fn validate(&self) -> Result<()> {
+        for proxy in &self.proxies {
+            proxy
+                .validate()
+                .with_context(|| format!("proxy {:?}", proxy.name()))?;
+        }
+        // Proxies and groups share one namespace
+        let mut policies = unique(
+            "proxy or group name",
+            self.proxies
+                .iter()
+                .map(Proxy::name)
+                .chain(self.proxy_groups.iter().map(|g| g.name.as_str())),
+        )?;
+        policies.extend(BUILT_IN);
+
+        for group in &self.proxy_groups {
+            let from = format!("group {:?}", group.name);
+            for member in &group.proxies {
+                resolves(&from, member, &policies)?;
+            }
+        }
+        for proxy in &self.proxies {
+            if let Some(dialer) = proxy.dialer_proxy() {
+                let from = format!("dialer-proxy of {:?}", proxy.name());
+                resolves(&from, dialer, &policies)?;
+            }
+        }
+
+        let providers = self.rule_providers.keys().map(String::as_str).collect();
+        for rule in &self.rules {
+            let parts: Vec<&str> = rule.split(',').map(str::trim).collect();
+            let from = format!("rule {:?}", rule);
+            let target = match parts.as_slice() {
+                ["MATCH", target] => target,
+                ["RULE-SET", set, target, ..] => {
+                    resolves(&from, set, &providers)?;
+                    target
+                }
+                [_, _, target, ..] => target,
+                _ => bail!("malformed {}", from),
+            };
+            resolves(&from, target, &policies)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+//! Typed client configs. The generators build these rather than free-form
+//! JSON, and every document is validated before it is served.
+
+pub mod clash;
+pub mod singbox;
+pub mod xray;
+
+use anyhow::{Result, bail};
+use std::collections::HashSet;
+
+/// Names of `kind`, failing on an empty or repeated one.
+pub(crate) fn unique<'a>(
+    kind: &str,
+    names: impl IntoIterator<Item = &'a str>,
+) -> Result<HashSet<&'a str>> {
+    let mut seen = HashSet::new();
+    for name in names {
+        if name.is_empty() {
+            bail!("{} without a name", kind);
+        }
+        if !seen.insert(name) {
+            bail!("duplicate {} {:?}", kind, name);
+        }
+    }
+    Ok(seen)
+}
+
+/// Fails unless `name`, referenced by `from`, is one of `known`.
+fn resolves(from: &str, name: &str, known: &HashSet<&str>) -> Result<()> {
+    if !known.contains(name) {
+        bail!("{} refers to unknown {:?}", from, name);
+    }
+    Ok(())
+}
+
+fn check_endpoint(server: &str, port: i64) -> Result<()> {
+    if server.trim().is_empty() {
+        bail!("no server address");
+    }
+    if !(1..=65535).contains(&port) {
+        bail!("port {} out of range", port);
+    }
+    Ok(())
+}
+
+fn check_path(path: &str) -> Result<()> {
+    if !path.starts_with('/') {
+        bail!("transport path {:?} is not absolute", path);
+    }
+    Ok(())
+}
+
+fn check_present(field: &str, value: &str) -> Result<()> {
+    if value.trim().is_empty() {
+        bail!("{} is empty", field);
+    }
+    Ok(())
+}
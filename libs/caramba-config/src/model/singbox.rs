@@ -0,0 +1,546 @@
+//! sing-box client config.
+
+use super::{check_endpoint, check_path, check_present, resolves, unique};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Config {
+    pub log: Log,
+    pub dns: Dns,
+    pub inbounds: Vec<Inbound>,
+    pub outbounds: Vec<Outbound>,
+    pub route: Route,
+    pub experimental: Experimental,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Log {
+    pub level: String,
+    pub timestamp: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Dns {
+    pub servers: Vec<DnsServer>,
+    pub rules: Vec<DnsRule>,
+    #[serde(rename = "final")]
+    pub final_server: String,
+    pub strategy: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DnsServer {
+    pub tag: String,
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detour: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DnsRule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outbound: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clash_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geosite: Option<String>,
+    pub server: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Inbound {
+    Mixed(MixedInbound),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MixedInbound {
+    pub tag: String,
+    pub listen: String,
+    pub listen_port: u16,
+    pub sniff: bool,
+    pub sniff_override_destination: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Route {
+    pub auto_detect_interface: bool,
+    #[serde(rename = "final")]
+    pub final_outbound: String,
+    pub rules: Vec<RouteRule>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RouteRule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geosite: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geoip: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain_suffix: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_fragment: Option<bool>,
+    pub outbound: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Experimental {
+    pub cache_file: CacheFile,
+    pub clash_api: ClashApi,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheFile {
+    pub enabled: bool,
+    pub store_fakeip: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClashApi {
+    pub external_controller: String,
+    pub external_ui: String,
+    pub external_ui_download_url: String,
+    pub external_ui_download_detour: String,
+    pub default_mode: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Outbound {
+    Selector(Selector),
+    #[serde(rename = "urltest")]
+    UrlTest(UrlTest),
+    Direct(Named),
+    Block(Named),
+    Dns(Named),
+    Vless(Vless),
+    Hysteria2(Hysteria2),
+    Tuic(Tuic),
+    Trojan(Trojan),
+    Shadowsocks(Shadowsocks),
+    Naive(Naive),
+    #[serde(rename = "wireguard")]
+    WireGuard(WireGuard),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Named {
+    pub tag: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Selector {
+    pub tag: String,
+    pub outbounds: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UrlTest {
+    pub tag: String,
+    pub outbounds: Vec<String>,
+    pub url: String,
+    pub interval: String,
+    pub tolerance: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Vless {
+    pub tag: String,
+    pub server: String,
+    pub server_port: i64,
+    pub uuid: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub flow: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<Tls>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transport: Option<Transport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub packet_encoding: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplex: Option<Multiplex>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detour: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Hysteria2 {
+    pub tag: String,
+    pub server: String,
+    pub server_port: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_ports: Option<String>,
+    pub password: String,
+    pub tls: Tls,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfs: Option<Obfs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detour: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Obfs {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Tuic {
+    pub tag: String,
+    pub server: String,
+    pub server_port: i64,
+    pub uuid: String,
+    pub password: String,
+    pub congestion_control: String,
+    pub zero_rtt_handshake: bool,
+    pub tls: Tls,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detour: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Trojan {
+    pub tag: String,
+    pub server: String,
+    pub server_port: i64,
+    pub password: String,
+    pub tls: Tls,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transport: Option<Transport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplex: Option<Multiplex>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detour: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Shadowsocks {
+    pub tag: String,
+    pub server: String,
+    pub server_port: i64,
+    pub method: String,
+    pub password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplex: Option<Multiplex>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detour: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Naive {
+    pub tag: String,
+    pub server: String,
+    pub server_port: i64,
+    pub username: String,
+    pub password: String,
+    pub tls: Tls,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detour: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WireGuard {
+    pub tag: String,
+    pub server: String,
+    pub server_port: i64,
+    pub local_address: Vec<String>,
+    pub private_key: String,
+    pub peer_public_key: String,
+    pub mtu: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reserved: Option<Vec<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detour: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Tls {
+    pub enabled: bool,
+    pub server_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insecure: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpn: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utls: Option<Utls>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reality: Option<Reality>,
+    /// Split the ClientHello into TCP segments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fragment: Option<bool>,
+    /// Split the ClientHello into TLS records
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_fragment: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Utls {
+    pub enabled: bool,
+    pub fingerprint: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Reality {
+    pub enabled: bool,
+    pub public_key: String,
+    pub short_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Transport {
+    Ws {
+        path: String,
+        headers: BTreeMap<String, String>,
+    },
+    Grpc {
+        service_name: String,
+    },
+    #[serde(rename = "httpupgrade")]
+    HttpUpgrade {
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        host: Option<Vec<String>>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Multiplex {
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_streams: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_streams: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub padding: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brutal: Option<Brutal>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Brutal {
+    pub enabled: bool,
+    pub up_mbps: u32,
+    pub down_mbps: u32,
+}
+
+impl Outbound {
+    pub fn tag(&self) -> &str {
+        match self {
+            Self::Selector(o) => &o.tag,
+            Self::UrlTest(o) => &o.tag,
+            Self::Direct(o) | Self::Block(o) | Self::Dns(o) => &o.tag,
+            Self::Vless(o) => &o.tag,
+            Self::Hysteria2(o) => &o.tag,
+            Self::Tuic(o) => &o.tag,
+            Self::Trojan(o) => &o.tag,
+            Self::Shadowsocks(o) => &o.tag,
+            Self::Naive(o) => &o.tag,
+            Self::WireGuard(o) => &o.tag,
+        }
+    }
+
+    pub fn set_tag(&mut self, tag: String) {
+        match self {
+            Self::Selector(o) => o.tag = tag,
+            Self::UrlTest(o) => o.tag = tag,
+            Self::Direct(o) | Self::Block(o) | Self::Dns(o) => o.tag = tag,
+            Self::Vless(o) => o.tag = tag,
+            Self::Hysteria2(o) => o.tag = tag,
+            Self::Tuic(o) => o.tag = tag,
+            Self::Trojan(o) => o.tag = tag,
+            Self::Shadowsocks(o) => o.tag = tag,
+            Self::Naive(o) => o.tag = tag,
+            Self::WireGuard(o) => o.tag = tag,
+        }
+    }
+
+    fn detour(&self) -> Option<&str> {
+        match self {
+            Self::Vless(o) => o.detour.as_deref(),
+            Self::Hysteria2(o) => o.detour.as_deref(),
+            Self::Tuic(o) => o.detour.as_deref(),
+            Self::Trojan(o) => o.detour.as_deref(),
+            Self::Shadowsocks(o) => o.detour.as_deref(),
+            Self::Naive(o) => o.detour.as_deref(),
+            Self::WireGuard(o) => o.detour.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Sets the outbound dialing through; groups and built-ins have none.
+    pub fn set_detour(&mut self, tag: String) {
+        let detour = match self {
+            Self::Vless(o) => &mut o.detour,
+            Self::Hysteria2(o) => &mut o.detour,
+            Self::Tuic(o) => &mut o.detour,
+            Self::Trojan(o) => &mut o.detour,
+            Self::Shadowsocks(o) => &mut o.detour,
+            Self::Naive(o) => &mut o.detour,
+            Self::WireGuard(o) => &mut o.detour,
+            _ => return,
+        };
+        *detour = Some(tag);
+    }
+
+    /// Checks a proxy on its own: endpoint, credentials, TLS and transport.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::Selector(_) | Self::UrlTest(_) | Self::Direct(_) | Self::Block(_) => Ok(()),
+            Self::Dns(_) => Ok(()),
+            Self::Vless(o) => {
+                check_endpoint(&o.server, o.server_port)?;
+                check_present("uuid", &o.uuid)?;
+                if let Some(tls) = &o.tls {
+                    tls.validate()?;
+                }
+                if !o.flow.is_empty() && (o.tls.is_none() || o.transport.is_some()) {
+                    bail!("flow {} needs TLS over raw TCP", o.flow);
+                }
+                if !o.flow.is_empty() && o.multiplex.as_ref().is_some_and(|m| m.enabled) {
+                    bail!("flow {} cannot be multiplexed", o.flow);
+                }
+                o.transport.as_ref().map_or(Ok(()), Transport::validate)
+            }
+            Self::Hysteria2(o) => {
+                check_endpoint(&o.server, o.server_port)?;
+                check_present("password", &o.password)?;
+                o.tls.require()
+            }
+            Self::Tuic(o) => {
+                check_endpoint(&o.server, o.server_port)?;
+                check_present("uuid", &o.uuid)?;
+                o.tls.require()
+            }
+            Self::Trojan(o) => {
+                check_endpoint(&o.server, o.server_port)?;
+                check_present("password", &o.password)?;
+                o.tls.require()?;
+                o.transport.as_ref().map_or(Ok(()), Transport::validate)
+            }
+            Self::Shadowsocks(o) => {
+                check_endpoint(&o.server, o.server_port)?;
+                check_present("method", &o.method)?;
+                check_present("password", &o.password)
+            }
+            Self::Naive(o) => {
+                check_endpoint(&o.server, o.server_port)?;
+                check_present("username", &o.username)?;
+                o.tls.require()
+            }
+            Self::WireGuard(o) => {
+                check_endpoint(&o.server, o.server_port)?;
+                check_present("private_key", &o.private_key)?;
+                check_present("peer_public_key", &o.peer_public_key)?;
+                if o.local_address.iter().all(|a| a.trim().is_empty()) {
+                    bail!("no local address");
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Tls {
+    fn validate(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        check_present("TLS server_name", &self.server_name)?;
+        if let Some(reality) = &self.reality {
+            check_present("Reality public_key", &reality.public_key)?;
+            if self.utls.is_none() {
+                bail!("Reality without uTLS");
+            }
+        }
+        Ok(())
+    }
+
+    /// For protocols that only run over TLS.
+    fn require(&self) -> Result<()> {
+        if !self.enabled {
+            bail!("TLS is required");
+        }
+        self.validate()
+    }
+}
+
+impl Transport {
+    fn validate(&self) -> Result<()> {
+        match self {
+            Self::Ws { path, .. } | Self::HttpUpgrade { path, .. } => check_path(path),
+            Self::Grpc { service_name } => check_present("gRPC service_name", service_name),
+        }
+    }
+}
+
+impl Config {
+    /// Checks the whole document: every outbound is valid, tags are unique
+    /// and every reference resolves to an outbound or DNS server.
+    pub fn validate(&self) -> Result<()> {
+        for outbound in &self.outbounds {
+            outbound
+                .validate()
+                .with_context(|| format!("outbound {:?}", outbound.tag()))?;
+        }
+        let tags = unique("outbound tag", self.outbounds.iter().map(Outbound::tag))?;
+        let servers = unique(
+            "DNS server tag",
+            self.dns.servers.iter().map(|s| s.tag.as_str()),
+        )?;
+
+        for outbound in &self.outbounds {
+            let from = format!("outbound {:?}", outbound.tag());
+            match outbound {
+                Outbound::Selector(Selector {
+                    outbounds, default, ..
+                }) => {
+                    for member in outbounds.iter().chain(default) {
+                        resolves(&from, member, &tags)?;
+                    }
+                }
+                Outbound::UrlTest(UrlTest { outbounds, .. }) => {
+                    for member in outbounds {
+                        resolves(&from, member, &tags)?;
+                    }
+                }
+                _ => {}
+            }
+            if let Some(detour) = outbound.detour() {
+                resolves(&from, detour, &tags)?;
+            }
+        }
+
+        for server in &self.dns.servers {
+            if let Some(detour) = &server.detour {
+                resolves("DNS server", detour, &tags)?;
+            }
+        }
+        for rule in &self.dns.rules {
+            resolves("DNS rule", &rule.server, &servers)?;
+        }
+        resolves("DNS final", &self.dns.final_server, &servers)?;
+        for rule in &self.route.rules {
+            resolves("route rule", &rule.outbound, &tags)?;
+        }
+        resolves("route final", &self.route.final_outbound, &tags)?;
+        resolves(
+            "clash_api",
+            &self.experimental.clash_api.external_ui_download_detour,
+            &tags,
+        )
+    }
+}
@@ -0,0 +1,394 @@
+//! Xray-core client config.
+
+use super::{check_endpoint, check_path, check_present, resolves, unique};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Config {
+    pub log: Log,
+    pub dns: Dns,
+    pub inbounds: Vec<Inbound>,
+    pub outbounds: Vec<Outbound>,
+    pub routing: Routing,
+    pub observatory: Observatory,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Log {
+    pub loglevel: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Dns {
+    pub servers: Vec<DnsServer>,
+    pub query_strategy: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum DnsServer {
+    Address(String),
+    Server {
+        address: String,
+        domains: Vec<String>,
+        #[serde(rename = "skipFallback")]
+        skip_fallback: bool,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Inbound {
+    pub tag: String,
+    pub protocol: String,
+    pub listen: String,
+    pub port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings: Option<Value>,
+    pub sniffing: Sniffing,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Sniffing {
+    pub enabled: bool,
+    pub dest_override: Vec<String>,
+    pub route_only: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Outbound {
+    pub tag: String,
+    #[serde(flatten)]
+    pub protocol: Protocol,
+    #[serde(rename = "streamSettings", skip_serializing_if = "Option::is_none")]
+    pub stream_settings: Option<StreamSettings>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "protocol", content = "settings", rename_all = "lowercase")]
+pub enum Protocol {
+    Vless(Vnext),
+    Vmess(Vnext),
+    Trojan(Servers),
+    Shadowsocks(Servers),
+    Freedom(Freedom),
+    Blackhole(Blackhole),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Vnext {
+    pub vnext: Vec<VnextServer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VnextServer {
+    pub address: String,
+    pub port: i64,
+    pub users: Vec<User>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct User {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<String>,
+    /// Always set for VLESS, empty unless the transport allows a flow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flow: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Servers {
+    pub servers: Vec<Server>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Server {
+    pub address: String,
+    pub port: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Freedom {
+    /// Splits the TLS ClientHello of outbounds dialed through this one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fragment: Option<Fragment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Fragment {
+    pub packets: String,
+    pub length: String,
+    pub interval: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Blackhole {
+    pub response: BlackholeResponse,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlackholeResponse {
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reality_settings: Option<RealitySettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_settings: Option<TlsSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ws_settings: Option<PathSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grpc_settings: Option<GrpcSettings>,
+    #[serde(
+        rename = "httpupgradeSettings",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub httpupgrade_settings: Option<PathSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xhttp_settings: Option<XhttpSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sockopt: Option<Sockopt>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RealitySettings {
+    pub server_name: String,
+    pub public_key: String,
+    pub short_id: String,
+    pub fingerprint: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsSettings {
+    pub server_name: String,
+    pub fingerprint: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PathSettings {
+    pub path: String,
+    pub host: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GrpcSettings {
+    pub service_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct XhttpSettings {
+    pub path: String,
+    pub host: String,
+    pub mode: String,
+    /// Padding and xmux, passed through as configured on the inbound
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Sockopt {
+    pub dialer_proxy: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Routing {
+    pub domain_strategy: String,
+    pub rules: Vec<Rule>,
+    pub balancers: Vec<Balancer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outbound_tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balancer_tag: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Balancer {
+    pub tag: String,
+    pub selector: Vec<String>,
+    pub strategy: BalancerStrategy,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BalancerStrategy {
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Observatory {
+    pub subject_selector: Vec<String>,
+    #[serde(rename = "probeURL")]
+    pub probe_url: String,
+    pub probe_interval: String,
+}
+
+impl Rule {
+    pub fn field() -> Self {
+        Self {
+            kind: "field".to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Outbound {
+    /// Checks an outbound on its own: endpoint, credentials, TLS and transport.
+    pub fn validate(&self) -> Result<()> {
+        let stream = self.stream_settings.clone().unwrap_or_default();
+        let security = stream.security.as_deref().unwrap_or("none");
+        match &self.protocol {
+            Protocol::Vless(Vnext { vnext }) | Protocol::Vmess(Vnext { vnext }) => {
+                let server = vnext.first().context("no vnext server")?;
+                check_endpoint(&server.address, server.port)?;
+                let user = server.users.first().context("no user")?;
+                check_present("user id", &user.id)?;
+                if let Some(flow) = user.flow.as_deref().filter(|f| !f.is_empty())
+                    && (security == "none" || stream.network.as_deref() != Some("tcp"))
+                {
+                    bail!("flow {} needs TLS over raw TCP", flow);
+                }
+            }
+            Protocol::Trojan(Servers { servers }) | Protocol::Shadowsocks(Servers { servers }) => {
+                let server = servers.first().context("no server")?;
+                check_endpoint(&server.address, server.port)?;
+                check_present("password", &server.password)?;
+                if matches!(self.protocol, Protocol::Trojan(_)) && security == "none" {
+                    bail!("trojan without TLS");
+                }
+            }
+            Protocol::Freedom(_) | Protocol::Blackhole(_) => {}
+        }
+
+        match security {
+            "reality" => {
+                let reality = stream
+                    .reality_settings
+                    .as_ref()
+                    .context("reality without realitySettings")?;
+                check_present("Reality serverName", &reality.server_name)?;
+                check_present("Reality publicKey", &reality.public_key)?;
+            }
+            "tls" => check_present(
+                "TLS serverName",
+                &stream
+                    .tls_settings
+                    .as_ref()
+                    .context("tls without tlsSettings")?
+                    .server_name,
+            )?,
+            "none" => {}
+            other => bail!("unsupported security {}", other),
+        }
+
+        match stream.network.as_deref() {
+            None | Some("tcp") => Ok(()),
+            Some("ws") => check_path(&stream.ws_settings.context("ws without wsSettings")?.path),
+            Some("grpc") => check_present(
+                "gRPC serviceName",
+                &stream
+                    .grpc_settings
+                    .context("grpc without grpcSettings")?
+                    .service_name,
+            ),
+            Some("httpupgrade") => check_path(
+                &stream
+                    .httpupgrade_settings
+                    .context("httpupgrade without httpupgradeSettings")?
+                    .path,
+            ),
+            Some("xhttp") => check_path(
+                &stream
+                    .xhttp_settings
+                    .context("xhttp without xhttpSettings")?
+                    .path,
+            ),
+            Some(other) => bail!("unsupported network {}", other),
+        }
+    }
+}
+
+impl Config {
+    /// Checks the whole document: every outbound is valid, tags are unique
+    /// and routing, balancers and dialer proxies refer to known outbounds.
+    pub fn validate(&self) -> Result<()> {
+        for outbound in &self.outbounds {
+            outbound
+                .validate()
+                .with_context(|| format!("outbound {:?}", outbound.tag))?;
+        }
+        let tags = unique(
+            "outbound tag",
+            self.outbounds.iter().map(|o| o.tag.as_str()),
+        )?;
+        let balancers = unique(
+            "balancer tag",
+            self.routing.balancers.iter().map(|b| b.tag.as_str()),
+        )?;
+        unique("inbound tag", self.inbounds.iter().map(|i| i.tag.as_str()))?;
+
+        for outbound in &self.outbounds {
+            if let Some(sockopt) = outbound
+                .stream_settings
+                .as_ref()
+                .and_then(|s| s.sockopt.as_ref())
+            {
+                let from = format!("dialerProxy of {:?}", outbound.tag);
+                resolves(&from, &sockopt.dialer_proxy, &tags)?;
+            }
+        }
+        for balancer in &self.routing.balancers {
+            for selector in &balancer.selector {
+                resolves("balancer", selector, &tags)?;
+            }
+        }
+        for rule in &self.routing.rules {
+            match (&rule.outbound_tag, &rule.balancer_tag) {
+                (Some(tag), None) => resolves("routing rule", tag, &tags)?,
+                (None, Some(tag)) => resolves("routing rule", tag, &balancers)?,
+                _ => bail!("routing rule needs exactly one of outboundTag and balancerTag"),
+            }
+        }
+        for subject in &self.observatory.subject_selector {
+            resolves("observatory", subject, &tags)?;
+        }
+        Ok(())
+    }
+}
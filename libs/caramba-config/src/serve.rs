@@ -0,0 +1,216 @@
+//! Answering a config fetch: the profile headers clients read usage and
+//! refresh hints from, and `If-None-Match` revalidation. The panel and the
+//! subscription workers both answer through here.
+
+use anyhow::Result;
+use base64::Engine;
+use http::{HeaderMap, HeaderName, HeaderValue, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::{ConfigInput, anti_dpi, client_output, clients};
+
+/// Header subscription workers pass the fetching client's address to the
+/// internal API in, apart from the proxy headers describing the worker.
+pub const CLIENT_IP_HEADER: &str = "x-subscriber-ip";
+
+/// Profile metadata sent with every config response, and with 304s so
+/// clients still see fresh usage.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProfileInfo {
+    pub title: String,
+    /// `upload=0; download=<used>; total=<limit>; expire=<unix time>`
+    pub user_info: String,
+    pub update_interval_hours: String,
+    pub support_url: String,
+    pub web_page_url: String,
+    /// What the config was tailored to, when the client was recognized
+    pub client_profile: Option<String>,
+}
+
+/// A config fetch the panel admitted: what to generate, in which format,
+/// and the profile to answer with. Subscription workers get it from the
+/// internal API.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PreparedConfig {
+    pub client_type: String,
+    /// Country of the fetching client, for anti-DPI overrides
+    pub country: Option<String>,
+    pub profile: ProfileInfo,
+    pub input: ConfigInput,
+}
+
+/// Config of `client_type` for `input`, with the anti-DPI overrides of
+/// `country` and tailored to `client` when it was recognized.
+pub fn render_for(
+    client_type: &str,
+    input: &mut ConfigInput,
+    country: Option<&str>,
+    client: Option<&clients::Client>,
+) -> Result<String> {
+    if let Some(country) = country {
+        for change in anti_dpi::localize(&mut input.nodes, country) {
+            debug!("Localizing for {}: {}", country, change);
+        }
+    }
+    if let Some(client) = client {
+        for change in clients::tailor(&mut input.nodes, client) {
+            debug!("Tailoring for {}: {}", client.label(), change);
+        }
+    }
+    crate::render(client_type, input)
+}
+
+/// Header value for free text: as is when it is plain ASCII, otherwise in
+/// the `base64:` form Clash, Hiddify and v2rayN decode.
+pub fn header_text(text: &str) -> String {
+    if text.bytes().all(|b| (0x20..0x7f).contains(&b)) {
+        text.to_string()
+    } else {
+        format!(
+            "base64:{}",
+            base64::engine::general_purpose::STANDARD.encode(text)
+        )
+    }
+}
+
+/// Strong validator of a generated config.
+pub fn content_etag(content: &str) -> String {
+    format!("\"{}\"", hex::encode(&Sha256::digest(content)[..16]))
+}
+
+/// Whether an `If-None-Match` header lists `etag` (weak comparison).
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+pub fn profile_headers(info: &ProfileInfo, client_type: &str, etag: &str) -> HeaderMap {
+    let (content_type, filename) = client_output(client_type);
+    let mut headers = HeaderMap::new();
+    let mut put = |name: &'static str, value: &str| {
+        if let Ok(value) = HeaderValue::from_str(value) {
+            headers.insert(HeaderName::from_static(name), value);
+        }
+    };
+
+    put("content-type", content_type);
+    put(
+        "content-disposition",
+        &format!("inline; filename={}", filename),
+    );
+    put("subscription-userinfo", &info.user_info);
+    put("profile-title", &header_text(&info.title));
+    put("profile-update-interval", &info.update_interval_hours);
+    if !info.support_url.is_empty() {
+        put("support-url", &info.support_url);
+    }
+    put("profile-web-page-url", &info.web_page_url);
+    if let Some(client_profile) = &info.client_profile {
+        put("x-client-profile", &header_text(client_profile));
+    }
+    put("etag", etag);
+    // Clients may keep the config but must revalidate it on every refresh.
+    put("cache-control", "no-cache");
+    headers
+}
+
+/// The config with its profile headers, or an empty 304 when the client
+/// already has this exact config.
+pub fn config_response(
+    content: String,
+    profile: &ProfileInfo,
+    client_type: &str,
+    if_none_match: Option<&str>,
+) -> Response<String> {
+    let etag = content_etag(&content);
+    let headers = profile_headers(profile, client_type, &etag);
+    let (status, body) = if if_none_match.is_some_and(|inm| etag_matches(inm, &etag)) {
+        (StatusCode::NOT_MODIFIED, String::new())
+    } else {
+        (StatusCode::OK, content)
+    };
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile() -> ProfileInfo {
+        ProfileInfo {
+            title: "Карамба VPN".to_string(),
+            user_info: "upload=0; download=1; total=2; expire=3".to_string(),
+            update_interval_hours: "12".to_string(),
+            support_url: String::new(),
+            web_page_url: "https://sub.example.com/sub/abc".to_string(),
+            client_profile: None,
+        }
+    }
+
+    #[test]
+    fn etag_follows_content_and_matches_weakly() {
+        let etag = content_etag("{}");
+        assert_eq!(etag, content_etag("{}"));
+        assert_ne!(etag, content_etag("{ }"));
+        assert!(etag_matches(&etag, &etag));
+        assert!(etag_matches(&format!("\"other\", W/{}", etag), &etag));
+        assert!(etag_matches("*", &etag));
+        assert!(!etag_matches("\"other\"", &etag));
+    }
+
+    #[test]
+    fn not_modified_keeps_profile_headers() {
+        let content = "proxies: []".to_string();
+        let etag = content_etag(&content);
+
+        let fresh = config_response(content.clone(), &profile(), "clash", None);
+        assert_eq!(fresh.status(), StatusCode::OK);
+        assert_eq!(fresh.headers()["etag"], etag.as_str());
+        assert_eq!(fresh.headers()["profile-update-interval"], "12");
+        assert!(fresh.headers().get("support-url").is_none());
+
+        let cached = config_response(content, &profile(), "clash", Some(&etag));
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        assert!(cached.body().is_empty());
+        assert_eq!(
+            cached.headers()["subscription-userinfo"],
+            "upload=0; download=1; total=2; expire=3"
+        );
+    }
+
+    #[test]
+    fn client_profile_is_explained_in_a_header() {
+        let content = "proxies: []".to_string();
+        assert!(
+            config_response(content.clone(), &profile(), "clash", None)
+                .headers()
+                .get("x-client-profile")
+                .is_none()
+        );
+
+        let profile = ProfileInfo {
+            client_profile: clients::detect("v2rayNG/1.8.8").map(|c| c.summary()),
+            ..profile()
+        };
+        let response = config_response(content, &profile, "v2ray", None);
+        let header = response.headers()["x-client-profile"].to_str().unwrap();
+        assert!(header.starts_with("v2rayNG 1.8.8; unsupported: tuic"));
+    }
+
+    #[test]
+    fn non_ascii_titles_are_base64() {
+        assert_eq!(header_text("CARAMBA"), "CARAMBA");
+        let encoded = header_text("Карамба VPN");
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(encoded.strip_prefix("base64:").unwrap())
+            .unwrap();
+        assert_eq!(String::from_utf8(decoded).unwrap(), "Карамба VPN");
+    }
+}
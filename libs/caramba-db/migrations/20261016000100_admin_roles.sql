@@ -0,0 +1,7 @@
+-- Role-based access control for panel admins.
+-- Existing admins keep full access; new admins default to read-only.
+ALTER TABLE admins ADD COLUMN IF NOT EXISTS role TEXT NOT NULL DEFAULT 'superadmin';
+ALTER TABLE admins ALTER COLUMN role SET DEFAULT 'read_only';
+ALTER TABLE admins ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS idx_admins_role ON admins(role);
@@ -0,0 +1,19 @@
+-- Every sing-box config generated for a node, so changes can be diffed and
+-- a node can be pinned to a known-good version.
+CREATE TABLE IF NOT EXISTS node_config_versions (
+    id BIGSERIAL PRIMARY KEY,
+    node_id BIGINT NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    -- md5 of the exact config text served to the agent.
+    config_hash TEXT NOT NULL,
+    -- Stored as text, not JSONB, so the served bytes and hash are preserved.
+    config TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    author TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (node_id, version)
+);
+
+-- When set, agents are served this version instead of a freshly generated config.
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS pinned_config_version_id BIGINT
+    REFERENCES node_config_versions(id) ON DELETE SET NULL;
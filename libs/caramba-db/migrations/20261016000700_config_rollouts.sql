@@ -0,0 +1,42 @@
+-- Staged rollouts of template changes across a node group: a canary stage is
+-- applied and watched before the rest of the group, with automatic rollback.
+-- status: canary | awaiting_approval | rolling_out | completed | rolled_back
+CREATE TABLE IF NOT EXISTS config_rollouts (
+    id BIGSERIAL PRIMARY KEY,
+    group_id BIGINT NOT NULL REFERENCES node_groups(id) ON DELETE CASCADE,
+    template_id BIGINT REFERENCES inbound_templates(id) ON DELETE SET NULL,
+    status TEXT NOT NULL DEFAULT 'canary',
+    -- 1 = canary nodes, 2 = the rest of the group.
+    stage INTEGER NOT NULL DEFAULT 1,
+    canary_count INTEGER NOT NULL,
+    window_mins INTEGER NOT NULL,
+    auto_continue BOOLEAN NOT NULL DEFAULT FALSE,
+    max_latency_increase_pct INTEGER NOT NULL DEFAULT 100,
+    min_connection_retention_pct INTEGER NOT NULL DEFAULT 50,
+    stage_started_at TIMESTAMPTZ,
+    status_reason TEXT,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMPTZ
+);
+
+-- At most one unfinished rollout per group.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_config_rollouts_active_group
+    ON config_rollouts(group_id) WHERE status IN ('canary', 'awaiting_approval', 'rolling_out');
+
+-- state: pending | applied | healthy | unhealthy | rolled_back
+CREATE TABLE IF NOT EXISTS config_rollout_nodes (
+    id BIGSERIAL PRIMARY KEY,
+    rollout_id BIGINT NOT NULL REFERENCES config_rollouts(id) ON DELETE CASCADE,
+    node_id BIGINT NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+    stage INTEGER NOT NULL,
+    state TEXT NOT NULL DEFAULT 'pending',
+    -- The node's template inbounds before the change, restored on rollback.
+    snapshot JSONB,
+    baseline_connections INTEGER,
+    baseline_latency DOUBLE PRECISION,
+    applied_at TIMESTAMPTZ,
+    checked_at TIMESTAMPTZ,
+    health_note TEXT,
+    UNIQUE (rollout_id, node_id)
+);
@@ -0,0 +1,8 @@
+-- Last config an agent refused or rolled back, as reported in its heartbeat.
+-- Cleared once the agent applies a config successfully.
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS config_error TEXT;
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS config_error_stage TEXT;
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS config_error_hash TEXT;
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS config_error_rolled_back BOOLEAN NOT NULL DEFAULT FALSE;
+-- When the failure was first reported.
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS config_error_at TIMESTAMPTZ;
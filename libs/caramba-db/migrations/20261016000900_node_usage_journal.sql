@@ -0,0 +1,4 @@
+-- Agents replay per-user usage batches until acknowledged. The panel keeps
+-- the highest batch applied per agent journal so replays are not counted twice.
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS usage_journal_id TEXT;
+ALTER TABLE nodes ADD COLUMN IF NOT EXISTS usage_acked_seq BIGINT NOT NULL DEFAULT 0;
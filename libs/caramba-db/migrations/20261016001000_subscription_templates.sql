@@ -0,0 +1,25 @@
+-- Admin-managed base configs for generated client subscriptions.
+-- client_format: singbox | clash. The body is JSON (sing-box) or YAML (Clash)
+-- with "{{variable}}" placeholders for the generated outbounds/proxies.
+CREATE TABLE IF NOT EXISTS subscription_templates (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    client_format TEXT NOT NULL,
+    body TEXT NOT NULL,
+    -- Used for plans without a template of their own.
+    is_default BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- At most one default template per client format.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_subscription_templates_default
+    ON subscription_templates(client_format) WHERE is_default;
+
+-- A plan uses at most one template per client format.
+CREATE TABLE IF NOT EXISTS plan_subscription_templates (
+    plan_id BIGINT NOT NULL REFERENCES plans(id) ON DELETE CASCADE,
+    client_format TEXT NOT NULL,
+    template_id BIGINT NOT NULL REFERENCES subscription_templates(id) ON DELETE CASCADE,
+    PRIMARY KEY (plan_id, client_format)
+);
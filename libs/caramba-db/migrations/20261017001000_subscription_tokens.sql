@@ -0,0 +1,19 @@
+-- Access tokens for subscription URLs (/sub/{token}), so a leaked link can
+-- be rotated without recreating the subscription. A subscription without
+-- any token row is still served by its subscription_uuid; the uuid becomes
+-- a token row on first use or rotation.
+CREATE TABLE IF NOT EXISTS subscription_tokens (
+    id BIGSERIAL PRIMARY KEY,
+    subscription_id BIGINT NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
+    token TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- When the token stops working; set on rotation, later than the
+    -- rotation itself while a grace period runs.
+    revoked_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ,
+    last_used_ip TEXT,
+    last_user_agent TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_subscription_tokens_subscription
+    ON subscription_tokens(subscription_id);
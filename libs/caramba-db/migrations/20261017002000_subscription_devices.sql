@@ -0,0 +1,22 @@
+-- Devices that imported a subscription, each with its own proxy credential
+-- (user_{subscription_id}_d{id} on the nodes), so a device limit caps
+-- credentials rather than IPs and one device can be revoked alone. Leases
+-- only live while a device is online; a device row stays until revoked or
+-- unseen long enough to be pruned.
+CREATE TABLE IF NOT EXISTS subscription_devices (
+    id BIGSERIAL PRIMARY KEY,
+    subscription_id BIGINT NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
+    -- Client-reported hardware id when sent (x-hwid), else its user agent.
+    device_key TEXT NOT NULL,
+    device_name TEXT,
+    proxy_uuid TEXT NOT NULL UNIQUE,
+    last_ip TEXT,
+    used_traffic BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    revoked_at TIMESTAMPTZ,
+    UNIQUE(subscription_id, device_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_subscription_devices_subscription
+    ON subscription_devices(subscription_id);
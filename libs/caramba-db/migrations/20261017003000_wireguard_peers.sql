@@ -0,0 +1,18 @@
+-- WireGuard/AmneziaWG keypairs and tunnel addresses: one peer for the
+-- subscription's shared credential and one per device with its own.
+-- `host` numbers the peer inside 10.10.0.0/16 (the server is .0.1), so
+-- addresses never collide and are reused once a peer is deleted.
+CREATE TABLE IF NOT EXISTS wireguard_peers (
+    id BIGSERIAL PRIMARY KEY,
+    subscription_id BIGINT NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
+    device_id BIGINT REFERENCES subscription_devices(id) ON DELETE CASCADE,
+    private_key TEXT NOT NULL,
+    public_key TEXT NOT NULL,
+    host INTEGER NOT NULL UNIQUE CHECK (host BETWEEN 2 AND 65534),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_wireguard_peers_shared
+    ON wireguard_peers(subscription_id) WHERE device_id IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_wireguard_peers_device
+    ON wireguard_peers(device_id) WHERE device_id IS NOT NULL;
@@ -0,0 +1,17 @@
+-- Client-side multi-hop chains: subscriptions of the assigned plans get
+-- the exit group's proxies dialed through the entry group's.
+CREATE TABLE IF NOT EXISTS chain_profiles (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    entry_group_id BIGINT NOT NULL REFERENCES node_groups(id) ON DELETE CASCADE,
+    exit_group_id BIGINT NOT NULL REFERENCES node_groups(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (entry_group_id <> exit_group_id)
+);
+
+CREATE TABLE IF NOT EXISTS plan_chain_profiles (
+    plan_id BIGINT NOT NULL REFERENCES plans(id) ON DELETE CASCADE,
+    chain_id BIGINT NOT NULL REFERENCES chain_profiles(id) ON DELETE CASCADE,
+    PRIMARY KEY (plan_id, chain_id)
+);
@@ -0,0 +1,158 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Admin {
+    pub id: i64,
+    pub username: String,
+    pub role: String, // see AdminRole
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl Admin {
+    pub fn role(&self) -> AdminRole {
+        AdminRole::parse(&self.role)
+    }
+}
+
+/// Panel admin roles. Stored as snake_case text in `admins.role`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminRole {
+    Superadmin,
+    Operator,
+    Support,
+    Finance,
+    ReadOnly,
+}
+
+/// Coarse-grained permissions checked per admin route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Dashboard,
+    NodesRead,
+    NodesWrite,
+    UsersRead,
+    UsersWrite,
+    Broadcast,
+    FinanceRead,
+    FinanceWrite,
+    LogsRead,
+    AuditRead,
+    SettingsManage,
+    AdminsManage,
+}
+
+impl AdminRole {
+    pub const ALL: [AdminRole; 5] = [
+        AdminRole::Superadmin,
+        AdminRole::Operator,
+        AdminRole::Support,
+        AdminRole::Finance,
+        AdminRole::ReadOnly,
+    ];
+
+    /// Unknown values fall back to the least privileged role.
+    pub fn parse(s: &str) -> Self {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "superadmin" => AdminRole::Superadmin,
+            "operator" => AdminRole::Operator,
+            "support" => AdminRole::Support,
+            "finance" => AdminRole::Finance,
+            _ => AdminRole::ReadOnly,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdminRole::Superadmin => "superadmin",
+            AdminRole::Operator => "operator",
+            AdminRole::Support => "support",
+            AdminRole::Finance => "finance",
+            AdminRole::ReadOnly => "read_only",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AdminRole::Superadmin => "Superadmin",
+            AdminRole::Operator => "Operator",
+            AdminRole::Support => "Support",
+            AdminRole::Finance => "Finance",
+            AdminRole::ReadOnly => "Read-only",
+        }
+    }
+
+    pub fn allows(&self, permission: Permission) -> bool {
+        use Permission::*;
+        match self {
+            AdminRole::Superadmin => true,
+            AdminRole::Operator => matches!(
+                permission,
+                Dashboard | NodesRead | NodesWrite | UsersRead | LogsRead
+            ),
+            AdminRole::Support => matches!(
+                permission,
+                Dashboard | NodesRead | UsersRead | UsersWrite | LogsRead
+            ),
+            AdminRole::Finance => matches!(
+                permission,
+                Dashboard | UsersRead | FinanceRead | FinanceWrite | LogsRead
+            ),
+            AdminRole::ReadOnly => matches!(
+                permission,
+                Dashboard | NodesRead | UsersRead | FinanceRead | LogsRead
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for AdminRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::Dashboard => "dashboard",
+            Permission::NodesRead => "nodes:read",
+            Permission::NodesWrite => "nodes:write",
+            Permission::UsersRead => "users:read",
+            Permission::UsersWrite => "users:write",
+            Permission::Broadcast => "users:broadcast",
+            Permission::FinanceRead => "finance:read",
+            Permission::FinanceWrite => "finance:write",
+            Permission::LogsRead => "logs:read",
+            Permission::AuditRead => "audit:read",
+            Permission::SettingsManage => "settings:manage",
+            Permission::AdminsManage => "admins:manage",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AdminSession {
+    pub id: i64,
+    pub admin_id: i64,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Login-relevant security state of an admin account.
+#[derive(Debug, Clone, FromRow)]
+pub struct AdminCredentials {
+    pub id: i64,
+    pub username: String,
+    pub password_hash: String,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub failed_login_attempts: i32,
+    pub locked_until: Option<DateTime<Utc>>,
+}
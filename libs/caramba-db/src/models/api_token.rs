@@ -0,0 +1,139 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::admin::Permission;
+
+/// A bearer token for the JSON admin API, owned by an admin.
+///
+/// Requests made with it act as the owning admin, limited to `scopes` and to
+/// whatever the admin's current role still allows.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AdminApiToken {
+    pub id: i64,
+    pub admin_id: i64,
+    pub admin_username: String,
+    pub name: String,
+    /// First characters of the token, shown in the UI to tell tokens apart.
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub last_used_ip: Option<String>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl AdminApiToken {
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|exp| exp > Utc::now())
+    }
+
+    pub fn has_scope(&self, scope: ApiScope) -> bool {
+        self.scopes.iter().any(|s| s == scope.as_str())
+    }
+}
+
+/// Scopes that can be granted to an API token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiScope {
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:write")]
+    UsersWrite,
+    #[serde(rename = "billing:write")]
+    BillingWrite,
+    #[serde(rename = "subs:read")]
+    SubsRead,
+    #[serde(rename = "subs:write")]
+    SubsWrite,
+    #[serde(rename = "plans:read")]
+    PlansRead,
+    #[serde(rename = "nodes:read")]
+    NodesRead,
+    #[serde(rename = "nodes:admin")]
+    NodesAdmin,
+    #[serde(rename = "inbounds:read")]
+    InboundsRead,
+    #[serde(rename = "inbounds:write")]
+    InboundsWrite,
+    #[serde(rename = "promo:read")]
+    PromoRead,
+    #[serde(rename = "promo:write")]
+    PromoWrite,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 12] = [
+        ApiScope::UsersRead,
+        ApiScope::UsersWrite,
+        ApiScope::BillingWrite,
+        ApiScope::SubsRead,
+        ApiScope::SubsWrite,
+        ApiScope::PlansRead,
+        ApiScope::NodesRead,
+        ApiScope::NodesAdmin,
+        ApiScope::InboundsRead,
+        ApiScope::InboundsWrite,
+        ApiScope::PromoRead,
+        ApiScope::PromoWrite,
+    ];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s.trim())
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::UsersRead => "users:read",
+            ApiScope::UsersWrite => "users:write",
+            ApiScope::BillingWrite => "billing:write",
+            ApiScope::SubsRead => "subs:read",
+            ApiScope::SubsWrite => "subs:write",
+            ApiScope::PlansRead => "plans:read",
+            ApiScope::NodesRead => "nodes:read",
+            ApiScope::NodesAdmin => "nodes:admin",
+            ApiScope::InboundsRead => "inbounds:read",
+            ApiScope::InboundsWrite => "inbounds:write",
+            ApiScope::PromoRead => "promo:read",
+            ApiScope::PromoWrite => "promo:write",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ApiScope::UsersRead => "List and view users",
+            ApiScope::UsersWrite => "Ban and unban users",
+            ApiScope::BillingWrite => "Set user balances",
+            ApiScope::SubsRead => "View subscriptions",
+            ApiScope::SubsWrite => "Create, extend and delete subscriptions",
+            ApiScope::PlansRead => "List and view plans",
+            ApiScope::NodesRead => "List and view nodes",
+            ApiScope::NodesAdmin => "Sync and enable/disable nodes",
+            ApiScope::InboundsRead => "View node inbounds",
+            ApiScope::InboundsWrite => "Enable/disable inbounds",
+            ApiScope::PromoRead => "List promo codes",
+            ApiScope::PromoWrite => "Create and delete promo codes",
+        }
+    }
+
+    /// The panel permission a role needs to grant or use this scope.
+    pub fn permission(&self) -> Permission {
+        match self {
+            ApiScope::UsersRead | ApiScope::SubsRead => Permission::UsersRead,
+            ApiScope::UsersWrite | ApiScope::SubsWrite => Permission::UsersWrite,
+            ApiScope::BillingWrite | ApiScope::PromoWrite => Permission::FinanceWrite,
+            ApiScope::PlansRead | ApiScope::PromoRead => Permission::FinanceRead,
+            ApiScope::NodesRead | ApiScope::InboundsRead => Permission::NodesRead,
+            ApiScope::NodesAdmin | ApiScope::InboundsWrite => Permission::NodesWrite,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
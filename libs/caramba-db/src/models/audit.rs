@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// One row of the admin audit trail.
+///
+/// `changes` holds only the fields that differ, as
+/// `{"field": {"before": .., "after": ..}}`. Creates have `before = null`,
+/// deletes have `after = null`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AuditLogEntry {
+    pub id: i64,
+    pub admin_id: Option<i64>,
+    pub admin_username: String,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: Option<String>,
+    pub changes: Option<serde_json::Value>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditLogEntry {
+    pub fn changes_pretty(&self) -> String {
+        self.changes
+            .as_ref()
+            .and_then(|c| serde_json::to_string_pretty(c).ok())
+            .unwrap_or_default()
+    }
+}
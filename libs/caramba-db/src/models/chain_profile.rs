@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Admin-defined client-side chain: the exit group's proxies dialed through
+/// the entry group's, offered to the assigned plans.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ChainProfile {
+    pub id: i64,
+    pub name: String,
+    pub entry_group_id: i64,
+    pub exit_group_id: i64,
+    /// Plans offering this chain.
+    pub plan_ids: Vec<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
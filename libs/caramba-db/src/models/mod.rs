@@ -1,12 +1,23 @@
 pub mod activity;
+pub mod admin;
 pub mod api_key;
+pub mod api_token;
+pub mod audit;
+pub mod chain_profile;
 pub mod frontend;
 pub mod groups;
 pub mod network;
 pub mod node;
+pub mod node_config;
 pub mod orgs;
 pub mod payment;
 pub mod promo;
+pub mod rollout;
 pub mod sni;
 pub mod sni_log;
 pub mod store;
+pub mod sub_device;
+pub mod sub_template;
+pub mod sub_token;
+pub mod webhook;
+pub mod wireguard;
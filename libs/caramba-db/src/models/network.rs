@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Inbound {
@@ -209,6 +210,136 @@ pub struct StreamSettings {
     pub xhttp_settings: Option<XhttpSettings>,
     #[serde(alias = "packetEncoding", default)]
     pub packet_encoding: Option<String>,
+    #[serde(alias = "antiDpi", default)]
+    pub anti_dpi: Option<AntiDpiSettings>,
+}
+
+/// Client-side anti-DPI tuning of an inbound. Clients in one of `countries`
+/// get that country's profile instead of the inbound's own.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct AntiDpiSettings {
+    #[serde(flatten)]
+    pub profile: AntiDpiProfile,
+    /// Profiles by ISO code of the client's country
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub countries: BTreeMap<String, AntiDpiProfile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct AntiDpiProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fragment: Option<TlsFragment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multiplex: Option<Multiplex>,
+}
+
+/// Splitting of the TLS ClientHello
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TlsFragment {
+    /// `tlshello` to split it into TLS records, or a range of TCP segments
+    /// such as `1-3`
+    #[serde(default = "default_fragment_packets")]
+    pub packets: String,
+    /// Bytes per fragment, e.g. `100-200`
+    #[serde(default = "default_fragment_length")]
+    pub length: String,
+    /// Milliseconds between fragments, e.g. `10-20`
+    #[serde(default = "default_fragment_interval")]
+    pub interval: String,
+}
+
+fn default_fragment_packets() -> String {
+    "tlshello".to_string()
+}
+fn default_fragment_length() -> String {
+    "100-200".to_string()
+}
+fn default_fragment_interval() -> String {
+    "10-20".to_string()
+}
+
+/// sing-mux multiplexing, accepted by the node on VLESS, Trojan and
+/// Shadowsocks inbounds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Multiplex {
+    /// `h2mux`, `smux` or `yamux`
+    #[serde(default = "default_mux_protocol")]
+    pub protocol: String,
+    #[serde(alias = "maxConnections", default)]
+    pub max_connections: Option<u32>,
+    #[serde(default)]
+    pub padding: bool,
+    /// TCP Brutal congestion control at the client's bandwidth
+    #[serde(default)]
+    pub brutal: Option<Brutal>,
+}
+
+fn default_mux_protocol() -> String {
+    "h2mux".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Brutal {
+    #[serde(alias = "upMbps")]
+    pub up_mbps: u32,
+    #[serde(alias = "downMbps")]
+    pub down_mbps: u32,
+}
+
+impl AntiDpiSettings {
+    /// The profile for clients in `country`
+    pub fn for_country(&self, country: &str) -> &AntiDpiProfile {
+        self.countries
+            .iter()
+            .find(|(code, _)| code.eq_ignore_ascii_case(country))
+            .map_or(&self.profile, |(_, profile)| profile)
+    }
+
+    /// The inbound's own profile and every country's
+    pub fn profiles(&self) -> impl Iterator<Item = &AntiDpiProfile> {
+        std::iter::once(&self.profile).chain(self.countries.values())
+    }
+
+    /// Checks what serde cannot: country codes, ranges and protocols.
+    pub fn validate(&self) -> Result<(), String> {
+        for code in self.countries.keys() {
+            if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(format!("{:?} is not a two-letter country code", code));
+            }
+        }
+        for profile in self.profiles() {
+            if let Some(fragment) = &profile.fragment {
+                if fragment.packets != "tlshello" {
+                    check_range("fragment packets", &fragment.packets)?;
+                }
+                check_range("fragment length", &fragment.length)?;
+                check_range("fragment interval", &fragment.interval)?;
+            }
+            if let Some(mux) = &profile.multiplex {
+                if !matches!(mux.protocol.as_str(), "h2mux" | "smux" | "yamux") {
+                    return Err(format!("unknown multiplex protocol {}", mux.protocol));
+                }
+                if mux
+                    .brutal
+                    .as_ref()
+                    .is_some_and(|b| b.up_mbps == 0 || b.down_mbps == 0)
+                {
+                    return Err("brutal needs both up_mbps and down_mbps".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `N` or `N-M` with N <= M
+fn check_range(what: &str, value: &str) -> Result<(), String> {
+    let bounds: Vec<Option<u32>> = value.split('-').map(|n| n.trim().parse().ok()).collect();
+    match bounds[..] {
+        [Some(_)] => Ok(()),
+        [Some(min), Some(max)] if min <= max => Ok(()),
+        _ => Err(format!("{} {:?} is not a range like 10-20", what, value)),
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A stored sing-box config generated for a node.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct NodeConfigVersion {
+    pub id: i64,
+    pub node_id: i64,
+    /// Per-node sequence number, starting at 1.
+    pub version: i32,
+    pub config_hash: String,
+    /// Exact config text served to the agent.
+    pub config: String,
+    /// What triggered generation, e.g. "Inbound updated" or "Agent pull".
+    pub reason: String,
+    /// Admin username, or "system" for background generation.
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl NodeConfigVersion {
+    pub fn config_pretty(&self) -> String {
+        serde_json::from_str::<serde_json::Value>(&self.config)
+            .and_then(|v| serde_json::to_string_pretty(&v))
+            .unwrap_or_else(|_| self.config.clone())
+    }
+}
+
+/// A version without its config body, for history listings.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct NodeConfigVersionSummary {
+    pub id: i64,
+    pub node_id: i64,
+    pub version: i32,
+    pub config_hash: String,
+    pub reason: String,
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+    /// Size of the config text in bytes.
+    pub size: i32,
+}
+
+impl NodeConfigVersionSummary {
+    pub fn short_hash(&self) -> &str {
+        &self.config_hash[..self.config_hash.len().min(8)]
+    }
+}
+
+/// A config the node's agent refused or rolled back, from its heartbeat.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct NodeConfigFailure {
+    /// "validate", "restart" or "health".
+    pub stage: String,
+    pub reason: String,
+    pub config_hash: String,
+    /// Whether the agent restored its last known-good config.
+    pub rolled_back: bool,
+    pub reported_at: DateTime<Utc>,
+    /// Stored version with the rejected hash, 0 if none matches.
+    pub version: i32,
+}
@@ -0,0 +1,63 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A staged rollout of template changes across a node group.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ConfigRollout {
+    pub id: i64,
+    pub group_id: i64,
+    #[sqlx(default)]
+    pub group_name: String,
+    pub template_id: Option<i64>,
+    #[sqlx(default)]
+    pub template_name: Option<String>,
+    /// canary | awaiting_approval | rolling_out | completed | rolled_back | cancelled
+    pub status: String,
+    /// 1 = canary nodes, 2 = the rest of the group.
+    pub stage: i32,
+    pub canary_count: i32,
+    pub window_mins: i32,
+    pub auto_continue: bool,
+    pub max_latency_increase_pct: i32,
+    pub min_connection_retention_pct: i32,
+    pub stage_started_at: Option<DateTime<Utc>>,
+    pub status_reason: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl ConfigRollout {
+    pub const ACTIVE_STATUSES: [&'static str; 3] = ["canary", "awaiting_approval", "rolling_out"];
+
+    pub fn is_active(&self) -> bool {
+        Self::ACTIVE_STATUSES.contains(&self.status.as_str())
+    }
+
+    /// End of the health watch window for the current stage.
+    pub fn window_ends_at(&self) -> Option<DateTime<Utc>> {
+        self.stage_started_at
+            .map(|t| t + Duration::minutes(self.window_mins as i64))
+    }
+}
+
+/// A node's part in a rollout.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ConfigRolloutNode {
+    pub id: i64,
+    pub rollout_id: i64,
+    pub node_id: i64,
+    #[sqlx(default)]
+    pub node_name: String,
+    pub stage: i32,
+    /// pending | applied | healthy | unhealthy | rolled_back
+    pub state: String,
+    /// The node's template inbounds before the change, restored on rollback.
+    pub snapshot: Option<serde_json::Value>,
+    pub baseline_connections: Option<i32>,
+    pub baseline_latency: Option<f64>,
+    pub applied_at: Option<DateTime<Utc>>,
+    pub checked_at: Option<DateTime<Utc>>,
+    pub health_note: Option<String>,
+}
@@ -0,0 +1,31 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A device that imported a subscription, with its own proxy credential.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SubscriptionDevice {
+    pub id: i64,
+    pub subscription_id: i64,
+    pub device_key: String,
+    pub device_name: Option<String>,
+    pub proxy_uuid: String,
+    pub last_ip: Option<String>,
+    pub used_traffic: i64,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl SubscriptionDevice {
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.device_name
+            .as_deref()
+            .filter(|name| !name.is_empty())
+            .unwrap_or("Unknown Device")
+    }
+}
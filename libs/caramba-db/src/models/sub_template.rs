@@ -0,0 +1,18 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Admin-managed base config for one client format, rendered with the
+/// generated proxies of each subscription.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SubscriptionTemplate {
+    pub id: i64,
+    pub name: String,
+    pub client_format: String, // singbox | clash
+    pub body: String,
+    pub is_default: bool,
+    /// Plans assigned to this template.
+    pub plan_ids: Vec<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
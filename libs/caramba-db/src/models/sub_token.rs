@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A token a subscription URL can be fetched with.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SubscriptionToken {
+    pub id: i64,
+    pub subscription_id: i64,
+    pub token: String,
+    pub created_at: DateTime<Utc>,
+    /// When the token stops (or stopped) working, `None` while current.
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub last_used_ip: Option<String>,
+    pub last_user_agent: Option<String>,
+}
+
+impl SubscriptionToken {
+    pub fn is_current(&self) -> bool {
+        self.revoked_at.is_none()
+    }
+
+    /// Still accepted after rotation, until its grace period ends.
+    pub fn in_grace(&self) -> bool {
+        self.revoked_at.is_some_and(|at| at > Utc::now())
+    }
+
+    /// Enough of the token to tell tokens apart without exposing it.
+    pub fn masked(&self) -> String {
+        let head: String = self.token.chars().take(8).collect();
+        format!("{}…", head)
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookEndpoint {
+    pub id: i64,
+    pub name: String,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub events: Vec<String>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WebhookEndpoint {
+    pub fn subscribes_to(&self, event: WebhookEvent) -> bool {
+        self.events.iter().any(|e| e == event.as_str())
+    }
+}
+
+/// One queued or attempted delivery of an event to an endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub endpoint_id: i64,
+    pub endpoint_name: String,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status: String, // pending | delivered | failed
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl WebhookDelivery {
+    pub fn payload_pretty(&self) -> String {
+        serde_json::to_string_pretty(&self.payload).unwrap_or_default()
+    }
+}
+
+/// Events an endpoint can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "payment.completed")]
+    PaymentCompleted,
+    #[serde(rename = "subscription.expired")]
+    SubscriptionExpired,
+    #[serde(rename = "node.offline")]
+    NodeOffline,
+    #[serde(rename = "sni.rotated")]
+    SniRotated,
+    /// Sent on demand from the panel, regardless of subscriptions.
+    #[serde(rename = "webhook.test")]
+    Test,
+}
+
+impl WebhookEvent {
+    /// Events that endpoints can subscribe to.
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::PaymentCompleted,
+        WebhookEvent::SubscriptionExpired,
+        WebhookEvent::NodeOffline,
+        WebhookEvent::SniRotated,
+    ];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == s.trim())
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::PaymentCompleted => "payment.completed",
+            WebhookEvent::SubscriptionExpired => "subscription.expired",
+            WebhookEvent::NodeOffline => "node.offline",
+            WebhookEvent::SniRotated => "sni.rotated",
+            WebhookEvent::Test => "webhook.test",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            WebhookEvent::PaymentCompleted => {
+                "A top-up, order or subscription payment was credited"
+            }
+            WebhookEvent::SubscriptionExpired => "A subscription ran out of time or traffic",
+            WebhookEvent::NodeOffline => "A node stopped sending heartbeats",
+            WebhookEvent::SniRotated => "A node's Reality SNI was rotated",
+            WebhookEvent::Test => "Test delivery from the panel",
+        }
+    }
+}
+
+impl std::fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,23 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A WireGuard keypair and tunnel address, for a subscription's shared
+/// credential (`device_id` unset) or one of its devices.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WireguardPeer {
+    pub id: i64,
+    pub subscription_id: i64,
+    pub device_id: Option<i64>,
+    pub private_key: String,
+    pub public_key: String,
+    pub host: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WireguardPeer {
+    /// Tunnel address of the peer, e.g. `10.10.1.7/32` for host 263.
+    pub fn address(&self) -> String {
+        format!("10.10.{}.{}/32", self.host >> 8, self.host & 0xff)
+    }
+}
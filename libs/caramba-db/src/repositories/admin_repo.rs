@@ -0,0 +1,78 @@
+use crate::models::admin::{Admin, AdminRole};
+use anyhow::{Context, Result};
+use sqlx::PgPool;
+
+#[derive(Clone, Debug)]
+pub struct AdminRepository {
+    pool: PgPool,
+}
+
+impl AdminRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_by_username(&self, username: &str) -> Result<Option<Admin>> {
+        sqlx::query_as::<_, Admin>(
+            "SELECT id, username, role, created_at FROM admins WHERE username = $1",
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to fetch admin")
+    }
+
+    pub async fn get_all(&self) -> Result<Vec<Admin>> {
+        sqlx::query_as::<_, Admin>(
+            "SELECT id, username, role, created_at FROM admins ORDER BY id ASC",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to fetch admins")
+    }
+
+    pub async fn create(
+        &self,
+        username: &str,
+        password_hash: &str,
+        role: AdminRole,
+    ) -> Result<i64> {
+        let id = sqlx::query_scalar(
+            "INSERT INTO admins (username, password_hash, role) VALUES ($1, $2, $3) RETURNING id",
+        )
+        .bind(username)
+        .bind(password_hash)
+        .bind(role.as_str())
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to create admin")?;
+        Ok(id)
+    }
+
+    pub async fn update_role(&self, id: i64, role: AdminRole) -> Result<()> {
+        sqlx::query("UPDATE admins SET role = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2")
+            .bind(role.as_str())
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update admin role")?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM admins WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete admin")?;
+        Ok(())
+    }
+
+    /// Used to make sure the panel never loses its last superadmin.
+    pub async fn count_superadmins(&self) -> Result<i64> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM admins WHERE role = 'superadmin'")
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to count superadmins")
+    }
+}
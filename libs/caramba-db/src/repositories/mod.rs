@@ -1,3 +1,4 @@
+pub mod admin_repo;
 pub mod api_key_repo;
 pub mod node_repo;
 pub mod org_repo;
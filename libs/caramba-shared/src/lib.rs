@@ -31,8 +31,42 @@ pub mod api {
         pub speed_mbps: Option<i32>,
         pub active_connections: Option<u32>, // Added for Telemetry (Phase 3)
         /// Per-user traffic usage. Key is User Tag (e.g. "user_123"), value is bytes used.
+        /// Sent by agents without a usage journal; superseded by `usage_report`.
         pub user_usage: Option<std::collections::HashMap<String, u64>>,
+        /// Unacknowledged usage batches, replayed until the panel acknowledges them.
+        #[serde(default)]
+        pub usage_report: Option<UsageReport>,
         pub discovered_snis: Option<Vec<DiscoveredSni>>,
+        /// Set while the agent keeps serving its last known-good config
+        /// because the newest one from the panel failed to apply.
+        #[serde(default)]
+        pub config_failure: Option<ConfigFailure>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct UsageReport {
+        /// Sequence numbers are unique within one journal.
+        pub journal_id: String,
+        /// Oldest first.
+        pub batches: Vec<UsageBatch>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct UsageBatch {
+        pub seq: u64,
+        /// Bytes per user tag (e.g. "user_123").
+        pub usage: std::collections::HashMap<String, u64>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ConfigFailure {
+        /// Hash of the rejected config, as served by the panel.
+        pub config_hash: String,
+        /// "validate", "restart" or "health".
+        pub stage: String,
+        pub reason: String,
+        /// Whether the previous config was restored and is running.
+        pub rolled_back: bool,
     }
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -48,6 +82,10 @@ pub mod api {
         pub success: bool,
         pub action: AgentAction,
         pub latest_version: Option<String>,
+        /// Highest usage batch applied from the reported journal; the agent
+        /// may drop batches up to and including it.
+        #[serde(default)]
+        pub usage_acked_seq: Option<u64>,
     }
 
     #[derive(Debug, Serialize, Deserialize, PartialEq)]